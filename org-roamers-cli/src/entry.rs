@@ -1,4 +1,5 @@
 use std::fs;
+use std::path::Path;
 
 use anyhow::Result;
 use org_roamers::{
@@ -54,3 +55,68 @@ pub fn dump_db(_state: ServerState) -> anyhow::Result<()> {
     // The previous implementation used rusqlite's backup feature which is not available in sqlx
     anyhow::bail!("Database dump functionality is not yet implemented for sqlx")
 }
+
+/// Write the vault index to `dest`, or to stdout if no destination is given.
+/// Log output also goes to stdout, so pass a file when piping the index.
+pub async fn export_index(state: &ServerState, dest: Option<String>) -> Result<()> {
+    let index = org_roamers::export_index(state).await?;
+    match dest {
+        Some(dest) => {
+            fs::write(&dest, index)?;
+            info!("Wrote index to {dest}");
+        }
+        None => println!("{index}"),
+    }
+    Ok(())
+}
+
+/// Write the flashcards of the vault to `dest` for Anki's text import, or
+/// to stdout if no destination is given.
+pub fn export_anki(state: &ServerState, dest: Option<String>) -> Result<()> {
+    let cards = org_roamers::export_anki(state)?;
+    match dest {
+        Some(dest) => {
+            fs::write(&dest, cards)?;
+            info!("Wrote flashcards to {dest}");
+        }
+        None => print!("{cards}"),
+    }
+    Ok(())
+}
+
+/// Render the vault as a static HTML site into the directory `dest`.
+pub async fn export_static(state: &ServerState, dest: &str) -> Result<()> {
+    let pages = org_roamers::export_static(state, Path::new(dest)).await?;
+    info!("Wrote {pages} pages to {dest}");
+    Ok(())
+}
+
+/// Merge the node `remove` into `keep` and move the file of `remove` to the
+/// trash.
+pub async fn merge(state: &ServerState, keep: &str, remove: &str) -> Result<()> {
+    for file in org_roamers::merge_nodes(state, keep, remove).await? {
+        info!("Changed {file}");
+    }
+    Ok(())
+}
+
+/// Print the bound addresses as a single JSON line to stdout once the server
+/// listens, so tools starting the server with port `0` can find it.
+pub fn report_listening(state: &ServerState) {
+    let mut listening = state.listening.subscribe();
+    tokio::spawn(async move {
+        if listening.changed().await.is_err() {
+            return;
+        }
+        let addresses: Vec<String> = listening
+            .borrow()
+            .iter()
+            .map(|addr| addr.to_string())
+            .collect();
+        let port = listening.borrow().first().map(|addr| addr.port());
+        println!(
+            "{}",
+            serde_json::json!({ "listening": addresses, "port": port })
+        );
+    });
+}
@@ -5,8 +5,18 @@ use org_roamers::start;
 mod conf;
 mod entry;
 
-#[tokio::main]
-async fn main() -> ExitCode {
+fn main() -> ExitCode {
+    // Only possible while no other thread runs
+    org_roamers::init_local_offset();
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to build the tokio runtime")
+        .block_on(run())
+}
+
+async fn run() -> ExitCode {
     tracing_subscriber::fmt()
         .with_file(true)
         .with_ansi(true)
@@ -32,8 +42,11 @@ async fn main() -> ExitCode {
                         return ExitCode::FAILURE;
                     }
                 };
-                start(state).await.unwrap();
-                tracing::info!("Starting CLI...");
+                entry::report_listening(&state);
+                if let Err(err) = start(state).await {
+                    tracing::error!("{err}");
+                    return ExitCode::FAILURE;
+                }
                 tracing::info!("Successfully shut down runtime.");
             }
             "--dump-db" => {
@@ -49,6 +62,66 @@ async fn main() -> ExitCode {
                     return ExitCode::FAILURE;
                 }
             }
+            "--export-index" => {
+                let state = match entry::init_state().await {
+                    Ok(state) => state,
+                    Err(err) => {
+                        tracing::error!("{err}");
+                        return ExitCode::FAILURE;
+                    }
+                };
+                if let Err(err) = entry::export_index(&state, args.next()).await {
+                    tracing::error!("{err}");
+                    return ExitCode::FAILURE;
+                }
+            }
+            "--export-anki" => {
+                let state = match entry::init_state().await {
+                    Ok(state) => state,
+                    Err(err) => {
+                        tracing::error!("{err}");
+                        return ExitCode::FAILURE;
+                    }
+                };
+                if let Err(err) = entry::export_anki(&state, args.next()) {
+                    tracing::error!("{err}");
+                    return ExitCode::FAILURE;
+                }
+            }
+            "--export-static" => {
+                let Some(dest) = args.next() else {
+                    eprintln!("Usage: --export-static <DIR>");
+                    return ExitCode::FAILURE;
+                };
+                let state = match entry::init_state().await {
+                    Ok(state) => state,
+                    Err(err) => {
+                        tracing::error!("{err}");
+                        return ExitCode::FAILURE;
+                    }
+                };
+                if let Err(err) = entry::export_static(&state, &dest).await {
+                    tracing::error!("{err}");
+                    return ExitCode::FAILURE;
+                }
+            }
+            "--merge" => {
+                let (Some(keep), Some(remove)) = (args.next(), args.next()) else {
+                    eprintln!("Usage: --merge <KEEP_ID> <REMOVE_ID>");
+                    return ExitCode::FAILURE;
+                };
+                let state = match entry::init_state().await {
+                    Ok(state) => state,
+                    Err(err) => {
+                        tracing::error!("{err}");
+                        return ExitCode::FAILURE;
+                    }
+                };
+                if let Err(err) = entry::merge(&state, &keep, &remove).await {
+                    tracing::error!("{err}");
+                    return ExitCode::FAILURE;
+                }
+            }
             "--get-config" => {
                 entry::print_config();
             }
@@ -58,7 +131,9 @@ async fn main() -> ExitCode {
             }
         }
     } else {
-        eprintln!("No command provided. Use --server, --get-config or --dump-db");
+        eprintln!(
+            "No command provided. Use --server, --get-config, --dump-db, --export-index, --export-anki, --export-static or --merge"
+        );
         return ExitCode::FAILURE;
     }
 
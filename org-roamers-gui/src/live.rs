@@ -0,0 +1,160 @@
+use std::{
+    collections::VecDeque,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use org_roamers::{
+    client_api::{self, ApiClient, ApiError, ConnectionInfo, GraphUpdate, Topic, WebSocketMessage},
+    config::Config,
+};
+
+/// Graph updates kept for display.
+const RECENT_UPDATES: usize = 16;
+/// How often the list of clients is refreshed.
+const CONNECTIONS_INTERVAL: Duration = Duration::from_secs(2);
+const RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+/// What the GUI shows about the running server, filled by [`watch`].
+#[derive(Default)]
+pub struct LiveStatus {
+    /// Why the server could not be reached, `None` while connected.
+    pub error: Option<String>,
+    /// Websocket and server-sent events clients, including the connection of
+    /// the GUI itself.
+    pub connections: Vec<ConnectionInfo>,
+    /// `(job, done, total)` of the running reindex.
+    pub reindex: Option<(u64, usize, usize)>,
+    /// Discrepancies found by the last reindex.
+    pub last_reindex: Option<usize>,
+    /// Files indexed since the GUI connected.
+    pub files_changed: usize,
+    /// Summaries of the latest graph updates, newest first.
+    pub updates: VecDeque<(Instant, String)>,
+}
+
+pub type SharedStatus = Arc<Mutex<LiveStatus>>;
+
+/// `(username, password)` the GUI logs in with if authentication is
+/// enabled: the first admin of the config, as only admins may list the
+/// clients, or else the first user.
+pub fn credentials(config: &Config) -> Option<(String, String)> {
+    let auth = config.authentication.as_ref().filter(|auth| auth.enabled)?;
+    let user = auth
+        .users
+        .iter()
+        .find(|user| auth.admins.contains(&user.username))
+        .or(auth.users.first())?;
+    Some((user.username.clone(), user.password.clone()))
+}
+
+/// Follow the server listening on `addr` through its websocket and keep
+/// `status` up to date until the runtime shuts down. The GUI logs in with
+/// `credentials` first, see [`credentials`]. `repaint` wakes the GUI on
+/// every change.
+pub async fn watch(
+    addr: SocketAddr,
+    credentials: Option<(String, String)>,
+    status: SharedStatus,
+    repaint: egui::Context,
+) {
+    let host = match addr.ip().is_unspecified() {
+        true => format!("localhost:{}", addr.port()),
+        false => addr.to_string(),
+    };
+    let client = match ApiClient::new(&format!("http://{host}")) {
+        Ok(client) => client,
+        Err(err) => {
+            status.lock().unwrap().error = Some(err.to_string());
+            return;
+        }
+    };
+
+    loop {
+        if let Err(err) = follow(&client, credentials.as_ref(), &status, &repaint).await {
+            status.lock().unwrap().error = Some(err.to_string());
+            repaint.request_repaint();
+        }
+        tokio::time::sleep(RETRY_INTERVAL).await;
+    }
+}
+
+async fn follow(
+    client: &ApiClient,
+    credentials: Option<&(String, String)>,
+    status: &SharedStatus,
+    repaint: &egui::Context,
+) -> client_api::Result<()> {
+    if let Some((username, password)) = credentials {
+        client.login(username, password).await?;
+    }
+    let mut ws = client.websocket().await?;
+    ws.subscribe(vec![Topic::Graph, Topic::Status, Topic::Reindex])
+        .await?;
+    status.lock().unwrap().error = None;
+
+    let mut refresh = tokio::time::interval(CONNECTIONS_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = refresh.tick() => {
+                // Without admin rights the clients stay unknown, the rest of
+                // the status is still followed.
+                let connections = client.connections().await;
+                let mut status = status.lock().unwrap();
+                match connections {
+                    Ok(response) => {
+                        status.connections = response.connections;
+                        status.error = None;
+                    }
+                    Err(err) => status.error = Some(format!("Failed to list clients: {err}")),
+                }
+            }
+            message = ws.next() => {
+                let Some(message) = message? else {
+                    return Err(ApiError::Closed);
+                };
+                apply(&mut status.lock().unwrap(), message);
+            }
+        }
+        repaint.request_repaint();
+    }
+}
+
+fn apply(status: &mut LiveStatus, message: WebSocketMessage) {
+    match message {
+        WebSocketMessage::StatusUpdate { files_changed } => status.files_changed += files_changed,
+        WebSocketMessage::GraphUpdate(update) => {
+            status
+                .updates
+                .push_front((Instant::now(), summary(&update)));
+            status.updates.truncate(RECENT_UPDATES);
+        }
+        WebSocketMessage::ReindexProgress {
+            job_id,
+            done,
+            total,
+        } => status.reindex = Some((job_id, done, total)),
+        WebSocketMessage::Reindexed { discrepancies, .. } => {
+            status.reindex = None;
+            status.last_reindex = Some(discrepancies);
+        }
+        _ => {}
+    }
+}
+
+fn summary(update: &GraphUpdate) -> String {
+    let parts = [
+        (update.new_nodes.len(), "new nodes"),
+        (update.updated_nodes.len(), "changed nodes"),
+        (update.removed_nodes.len(), "removed nodes"),
+        (update.new_links.len(), "new links"),
+        (update.removed_links.len(), "removed links"),
+    ];
+    let parts: Vec<String> = parts
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, what)| format!("{count} {what}"))
+        .collect();
+    parts.join(", ")
+}
@@ -1,21 +1,29 @@
 #![windows_subsystem = "windows"]
 
-use std::{env, path::PathBuf, process::Command};
+use std::{env, path::PathBuf, process::Command, time::Duration};
 
 use eframe;
 use egui::{Button, IconData};
+use live::LiveStatus;
 use logger::LogBuffer;
+use org_roamers::client_api::ConnectionKind;
 use rfd::FileDialog;
 use settings::Settings;
 use start::ServerHandle;
 
+mod live;
 mod logger;
 mod settings;
 mod start;
 
 const LOG_ENTRIES: usize = 64;
+/// How often a stopping server is checked on.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 fn main() {
+    // Only possible while no other thread runs
+    org_roamers::init_local_offset();
+
     let log_buffer = LogBuffer::new();
 
     let subscriber = tracing_subscriber::fmt()
@@ -75,6 +83,8 @@ struct OrgRoamersGUI {
     settings: Settings,
     logs: LogBuffer<LOG_ENTRIES>,
     handle: Option<ServerHandle>,
+    /// Server that was asked to stop and is still shutting down
+    stopping: Option<ServerHandle>,
 }
 
 impl OrgRoamersGUI {
@@ -88,6 +98,7 @@ impl OrgRoamersGUI {
                 }
             },
             handle: None,
+            stopping: None,
             logs,
         }
     }
@@ -104,8 +115,58 @@ impl OrgRoamersGUI {
     }
 
     pub fn url_with_protocol(&self) -> anyhow::Result<String> {
-        let port: usize = self.settings.port.parse()?;
-        Ok(format!("http://{}:{}", self.settings.ip_addr, port))
+        let port = match self.handle.as_ref().and_then(ServerHandle::port) {
+            Some(port) => port,
+            None => self.settings.port.parse()?,
+        };
+        let host = org_roamers::config::host_port(&self.settings.ip_addr, port);
+        Ok(format!("http://{host}"))
+    }
+}
+
+fn show_status(ui: &mut egui::Ui, status: &LiveStatus) {
+    if let Some(err) = &status.error {
+        ui.colored_label(ui.visuals().error_fg_color, format!("Not connected: {err}"));
+    }
+
+    ui.label(format!("Clients: {}", status.connections.len()));
+    for connection in &status.connections {
+        let who = connection
+            .user
+            .as_deref()
+            .or(connection.user_agent.as_deref())
+            .unwrap_or("anonymous");
+        let kind = match connection.kind {
+            ConnectionKind::WebSocket => "websocket",
+            ConnectionKind::Sse => "events",
+        };
+        let mut line = format!("  #{} {kind}: {who}", connection.id);
+        if let Some(id) = &connection.working_id {
+            line.push_str(&format!(", visiting {}", id.id()));
+        }
+        ui.label(line);
+    }
+
+    match (status.reindex, status.last_reindex) {
+        (Some((job, done, total)), _) => {
+            let progress = match total {
+                0 => 0.,
+                total => done as f32 / total as f32,
+            };
+            ui.add(
+                egui::ProgressBar::new(progress)
+                    .text(format!("Reindex #{job}: {done}/{total} files")),
+            );
+        }
+        (None, Some(discrepancies)) => {
+            ui.label(format!("Last reindex found {discrepancies} discrepancies"));
+        }
+        (None, None) => {}
+    }
+
+    ui.label(format!("Files changed: {}", status.files_changed));
+    for (at, update) in &status.updates {
+        ui.label(format!("  {}s ago: {update}", at.elapsed().as_secs()));
     }
 }
 
@@ -138,25 +199,42 @@ impl eframe::App for OrgRoamersGUI {
 
             ui.checkbox(&mut self.settings.fs_watcher, "Enable file system watcher");
 
+            if let Some(port) = self.handle.as_ref().and_then(ServerHandle::port) {
+                ui.label(format!("Listening on port {port}"));
+            }
+
             ui.separator();
 
-            let button_label = if self.handle.is_some() {
-                "Stop Server"
-            } else {
-                "Start Server"
+            if self.stopping.as_mut().is_some_and(ServerHandle::is_stopped) {
+                self.stopping = None;
+            } else if self.stopping.is_some() {
+                ctx.request_repaint_after(STOP_POLL_INTERVAL);
+            }
+
+            let button_label = match (&self.handle, &self.stopping) {
+                (Some(_), _) => "Stop Server",
+                (None, Some(_)) => "Stopping Server...",
+                (None, None) => "Start Server",
             };
 
             let button_width = ui.available_width();
+            let button = Button::new(button_label);
             if ui
-                .add_sized([button_width, 1.], Button::new(button_label))
+                .add_enabled_ui(self.stopping.is_none(), |ui| {
+                    ui.add_sized([button_width, 1.], button)
+                })
+                .inner
                 .clicked()
             {
-                match &mut self.handle {
-                    Some(handle) => {
-                        handle.abort();
-                        self.handle = None;
+                match self.handle.take() {
+                    Some(mut handle) => {
+                        handle.stop();
+                        self.stopping = Some(handle);
+                    }
+                    None => {
+                        let handle = start::start(self, ctx.clone());
+                        self.handle = Some(handle);
                     }
-                    None => self.handle = Some(start::start(&self)),
                 }
             }
             if ui
@@ -168,6 +246,11 @@ impl eframe::App for OrgRoamersGUI {
                     .status();
             }
 
+            if let Some(handle) = &self.handle {
+                ui.separator();
+                show_status(ui, &handle.status.lock().unwrap());
+            }
+
             ui.separator();
 
             egui::ScrollArea::vertical()
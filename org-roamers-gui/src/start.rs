@@ -1,21 +1,57 @@
-use std::{fs, path::PathBuf, thread};
+use std::{fs, net::SocketAddr, path::PathBuf, thread};
 
 use org_roamers::{ServerState, config::Config};
-use tokio::runtime::Runtime;
+use tokio::{
+    runtime::Runtime,
+    sync::{oneshot, watch},
+};
 
-use crate::{OrgRoamersGUI, settings::Settings};
+use crate::{
+    OrgRoamersGUI,
+    live::{self, SharedStatus},
+    settings::Settings,
+};
 
 pub struct ServerHandle {
     handle: Option<thread::JoinHandle<anyhow::Result<()>>>,
+    listening: watch::Receiver<Vec<SocketAddr>>,
+    stop: Option<oneshot::Sender<()>>,
+    /// Clients, indexing and graph updates of the server
+    pub status: SharedStatus,
 }
 
 impl ServerHandle {
-    pub fn abort(&mut self) {
+    /// Port the server listens on, once it is bound.
+    pub fn port(&self) -> Option<u16> {
+        self.listening.borrow().first().map(|addr| addr.port())
+    }
+
+    /// Ask the server to stop. It closes its connections and the database in
+    /// the background, see [`ServerHandle::is_stopped`].
+    pub fn stop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+    }
+
+    /// Whether the server thread ended, so the server can be started again.
+    /// Never blocks; the outcome is logged once the thread ended.
+    pub fn is_stopped(&mut self) -> bool {
+        if self
+            .handle
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished())
+        {
+            return false;
+        }
         if let Some(handle) = self.handle.take() {
-            // We can't gracefully abort a thread, so we'll need to implement
-            // proper shutdown signaling in the future
-            drop(handle);
+            match handle.join() {
+                Ok(Err(err)) => tracing::error!("Server stopped with error: {err}"),
+                Err(_) => tracing::error!("Server thread panicked"),
+                Ok(Ok(())) => {}
+            }
         }
+        true
     }
 }
 
@@ -39,20 +75,39 @@ fn server_conf_path() -> PathBuf {
     }
 }
 
-pub fn start(ctx: &OrgRoamersGUI) -> ServerHandle {
+pub fn start(ctx: &OrgRoamersGUI, repaint: egui::Context) -> ServerHandle {
     let settings = ctx.settings.clone();
+    let (listening_tx, listening) = watch::channel(vec![]);
+    let (stop, stopped) = oneshot::channel();
+    let status = SharedStatus::default();
 
+    let live_status = status.clone();
+    let mut live_listening = listening.clone();
     let handle = thread::spawn(move || {
+        let config = load_config(settings)?;
+        let credentials = live::credentials(&config);
         let rt = Runtime::new().unwrap();
-        rt.block_on(async move { start_server(settings).await })
+        // Follow the server once it listens. The task ends with the runtime.
+        rt.spawn(async move {
+            let addr = match live_listening.wait_for(|addrs| !addrs.is_empty()).await {
+                Ok(addrs) => addrs[0],
+                Err(_) => return,
+            };
+            live::watch(addr, credentials, live_status, repaint).await;
+        });
+        rt.block_on(async move { start_server(config, listening_tx, stopped).await })
     });
 
     ServerHandle {
         handle: Some(handle),
+        listening,
+        stop: Some(stop),
+        status,
     }
 }
 
-pub async fn start_server(ctx: Settings) -> anyhow::Result<()> {
+/// The server config with the settings of the GUI applied.
+fn load_config(ctx: Settings) -> anyhow::Result<Config> {
     let mut server_configuration = match fs::read_to_string(server_conf_path()) {
         Ok(content) => serde_json::from_str(content.as_str()).unwrap(),
         Err(err) => {
@@ -62,12 +117,28 @@ pub async fn start_server(ctx: Settings) -> anyhow::Result<()> {
     };
 
     server_configuration.fs_watcher = ctx.fs_watcher;
-    server_configuration.http_server_config.host = ctx.ip_addr;
+    server_configuration.http_server_config.host = ctx.ip_addr.into();
     server_configuration.http_server_config.port = ctx.port.parse()?;
+    Ok(server_configuration)
+}
 
+pub async fn start_server(
+    server_configuration: Config,
+    listening_tx: watch::Sender<Vec<SocketAddr>>,
+    stopped: oneshot::Receiver<()>,
+) -> anyhow::Result<()> {
     let state = ServerState::new(server_configuration).await?;
 
-    org_roamers::start(state).await.unwrap();
+    let mut listening = state.listening.subscribe();
+    tokio::spawn(async move {
+        while listening.changed().await.is_ok() {
+            listening_tx.send_replace(listening.borrow().clone());
+        }
+    });
 
-    Ok(())
+    // Stop when the GUI asks for it or drops the handle
+    org_roamers::start_with_shutdown(state, async move {
+        let _ = stopped.await;
+    })
+    .await
 }
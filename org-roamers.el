@@ -110,6 +110,84 @@
 	 (lambda (&key data &allow-other-keys)
 	   (message "Successfully informed server.")))))))
 
+(defvar org-roamers--follow-seq nil
+  "Sequence number of the last node selection received from the server.")
+
+(defun org-roamers--follow-url ()
+  (if org-roamers--follow-seq
+      (format "%s/emacs/follow?since=%s" org-roamers-url org-roamers--follow-seq)
+    (format "%s/emacs/follow" org-roamers-url)))
+
+(defun org-roamers--poll-selection ()
+  "Wait for the next node selected in the web UI and visit it."
+  (when org-roamers-follow-browser-mode
+    (request
+      (org-roamers--follow-url)
+      :type "GET"
+      :timeout 60
+      ;; The server responds with an empty body if nothing was selected.
+      :parser (lambda () (unless (eobp) (json-read)))
+      :success
+      (cl-function
+       (lambda (&key data &allow-other-keys)
+	 (when data
+	   (setq org-roamers--follow-seq (alist-get 'seq data))
+	   (let ((node (org-roam-node-from-id (alist-get 'id data))))
+	     (when node
+	       ;; Prevent `org-roamers-follow' from reporting the node back.
+	       (setq org-roamers--last-id (org-roam-node-id node))
+	       (org-roam-node-visit node))))
+	 (org-roamers--poll-selection)))
+      :error
+      (cl-function
+       (lambda (&rest _)
+	 (run-at-time 5 nil #'org-roamers--poll-selection))))))
+
+(define-minor-mode org-roamers-follow-browser-mode
+  "Visit nodes in Emacs that are selected in the web UI."
+  :group 'org-roamers
+  :global t
+  (when org-roamers-follow-browser-mode
+    (setq org-roamers--follow-seq nil)
+    (org-roamers--poll-selection)))
+
+(defvar org-roamers-vault-changed-hook nil
+  "Hook run when the server reports a change of the vault.")
+
+(defvar org-roamers--status-seq nil
+  "Last change sequence number reported by the server.")
+
+(defun org-roamers--wait-status ()
+  "Wait for the next change of the vault and run `org-roamers-vault-changed-hook'."
+  (when org-roamers-watch-mode
+    (request
+      (if org-roamers--status-seq
+	  (format "%s/status/wait?since=%s" org-roamers-url org-roamers--status-seq)
+	(format "%s/status" org-roamers-url))
+      :type "GET"
+      :timeout 60
+      :parser (lambda () (unless (eobp) (json-read)))
+      :success
+      (cl-function
+       (lambda (&key data &allow-other-keys)
+	 (when data
+	   (when org-roamers--status-seq
+	     (run-hooks 'org-roamers-vault-changed-hook))
+	   (setq org-roamers--status-seq (alist-get 'seq data)))
+	 (org-roamers--wait-status)))
+      :error
+      (cl-function
+       (lambda (&rest _)
+	 (run-at-time 5 nil #'org-roamers--wait-status))))))
+
+(define-minor-mode org-roamers-watch-mode
+  "Run `org-roamers-vault-changed-hook' when the vault changes on the server."
+  :group 'org-roamers
+  :global t
+  (when org-roamers-watch-mode
+    (setq org-roamers--status-seq nil)
+    (org-roamers--wait-status)))
+
 (define-minor-mode org-roamers-mode
   "Enable org-roamers enhances in current buffer."
   :group 'org-roamers
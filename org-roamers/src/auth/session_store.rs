@@ -1,6 +1,16 @@
-use sqlx::SqlitePool;
+use std::path::Path;
+
+use sqlx::{sqlite::SqliteConnectOptions, SqlitePool};
 use tower_sessions_sqlx_store::SqliteStore;
 
+/// Open the SQLite file at `path` for sessions, creating it if missing.
+pub async fn open_session_db(path: &Path) -> anyhow::Result<SqlitePool> {
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true);
+    Ok(SqlitePool::connect_with(options).await?)
+}
+
 /// Initialize session store using the shared SqlitePool
 /// The session store will create its own table (tower_sessions) in the same database
 pub async fn create_session_store(pool: SqlitePool) -> anyhow::Result<SqliteStore> {
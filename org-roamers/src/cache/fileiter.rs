@@ -1,22 +1,78 @@
 use std::{
+    collections::HashSet,
     ffi::OsStr,
-    fs::{self, ReadDir},
+    fs::{self, DirEntry, Metadata, ReadDir},
     io,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
 };
 
+/// Whether `path`, relative to a root, is hidden or lies in a hidden
+/// directory like `.templates/` or `.git/`. Hidden files are not indexed.
+pub fn is_hidden(path: &Path) -> bool {
+    path.components().any(|component| match component {
+        Component::Normal(name) => name.to_string_lossy().starts_with('.'),
+        _ => false,
+    })
+}
+
+/// Iterator over the org files below a directory, skipping hidden files and
+/// directories, see [`is_hidden`]. Symlinks, and junctions
+/// on Windows, are followed if enabled. Every directory is entered at most
+/// once, so links pointing back up the tree or to an already visited
+/// directory do not lead to cycles or duplicate files.
 pub struct FileIter {
     pending_dirs: Vec<ReadDir>,
+    follow_symlinks: bool,
+    /// Canonical paths of all directories entered so far.
+    visited: HashSet<PathBuf>,
 }
 
 impl FileIter {
-    pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    pub fn new<P: AsRef<Path>>(path: P, follow_symlinks: bool) -> io::Result<Self> {
+        let path = path.as_ref();
         let mut this = Self {
             pending_dirs: Vec::new(),
+            follow_symlinks,
+            visited: HashSet::new(),
         };
         this.pending_dirs.push(fs::read_dir(path)?);
+        if follow_symlinks {
+            this.visited.insert(path.canonicalize()?);
+        }
         Ok(this)
     }
+
+    /// Metadata of `entry`, with symlinks resolved if they are followed.
+    /// `None` skips the entry, e.g. a dangling link.
+    fn metadata(&self, entry: &DirEntry) -> Option<io::Result<Metadata>> {
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(err) => return Some(Err(err)),
+        };
+        if !self.follow_symlinks || !metadata.is_symlink() {
+            return Some(Ok(metadata));
+        }
+        match fs::metadata(entry.path()) {
+            Ok(metadata) => Some(Ok(metadata)),
+            Err(err) => {
+                tracing::warn!("Skipping broken link {}: {err}", entry.path().display());
+                None
+            }
+        }
+    }
+
+    /// Whether the directory at `path` was not entered before.
+    fn first_visit(&mut self, path: &Path) -> io::Result<bool> {
+        if !self.follow_symlinks {
+            return Ok(true);
+        }
+        let canonical = path.canonicalize()?;
+        if self.visited.insert(canonical) {
+            return Ok(true);
+        }
+        tracing::debug!("Skipping already visited directory {}", path.display());
+        Ok(false)
+    }
 }
 
 impl Iterator for FileIter {
@@ -34,13 +90,22 @@ impl Iterator for FileIter {
                     Ok(entry) => entry,
                     Err(e) => return Some(Err(e)),
                 };
+                if is_hidden(Path::new(&entry.file_name())) {
+                    continue;
+                }
 
-                let metadata = match entry.metadata() {
-                    Ok(metadata) => metadata,
-                    Err(err) => return Some(Err(err)),
+                let metadata = match self.metadata(&entry) {
+                    Some(Ok(metadata)) => metadata,
+                    Some(Err(err)) => return Some(Err(err)),
+                    None => continue,
                 };
 
                 if metadata.is_dir() {
+                    match self.first_visit(&entry.path()) {
+                        Ok(true) => {}
+                        Ok(false) => continue,
+                        Err(e) => return Some(Err(e)),
+                    }
                     match fs::read_dir(entry.path()) {
                         Ok(read_dir) => self.pending_dirs.push(read_dir),
                         Err(e) => return Some(Err(e)),
@@ -57,3 +122,64 @@ impl Iterator for FileIter {
         }
     }
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn files(iter: FileIter, root: &Path) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = iter
+            .map(|path| path.unwrap().strip_prefix(root).unwrap().to_path_buf())
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_follow_symlinks() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        let synced = temp_dir.path().join("synced");
+        fs::create_dir_all(root.join("dir")).unwrap();
+        fs::create_dir_all(&synced).unwrap();
+        fs::write(root.join("a.org"), "").unwrap();
+        fs::write(synced.join("b.org"), "").unwrap();
+        symlink(&synced, root.join("synced")).unwrap();
+        // Links back up the tree and to a visited directory
+        symlink(&root, root.join("dir/loop")).unwrap();
+        symlink(&synced, root.join("dir/again")).unwrap();
+        symlink(temp_dir.path().join("missing"), root.join("broken")).unwrap();
+
+        assert_eq!(
+            files(FileIter::new(&root, false).unwrap(), &root),
+            vec![PathBuf::from("a.org")]
+        );
+        let followed = files(FileIter::new(&root, true).unwrap(), &root);
+        assert_eq!(followed.len(), 2);
+        assert!(followed.contains(&PathBuf::from("a.org")));
+        assert!(followed.iter().any(|file| file.ends_with("b.org")));
+    }
+
+    #[test]
+    fn test_skip_hidden() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join(".templates/daily")).unwrap();
+        fs::create_dir_all(root.join("daily")).unwrap();
+        fs::write(root.join(".templates/template.org"), "").unwrap();
+        fs::write(root.join(".templates/daily/template.org"), "").unwrap();
+        fs::write(root.join("daily/a.org"), "").unwrap();
+        fs::write(root.join(".#a.org"), "").unwrap();
+
+        assert_eq!(
+            files(FileIter::new(root, false).unwrap(), root),
+            vec![PathBuf::from("daily/a.org")]
+        );
+        assert!(is_hidden(Path::new(".templates/daily/template.org")));
+        assert!(!is_hidden(Path::new("daily/a.org")));
+    }
+}
@@ -4,10 +4,11 @@
 //! It should reduce the file lookup to just fetching updated files.
 
 use std::{
-    hash::{DefaultHasher, Hash, Hasher},
+    collections::HashMap,
     io,
     path::{Path, PathBuf},
     sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use dashmap::{mapref::multiple::RefMulti, DashMap};
@@ -16,25 +17,54 @@ use sqlx::SqlitePool;
 use crate::{
     cache::{file::OrgFile, fileiter::FileIter},
     server::types::RoamID,
-    sqlite::files::insert_file,
-    transform::node_builder,
+    sqlite::{
+        aliases, diagnostics,
+        files::{delete_file, get_files, get_node_ids, insert_file},
+        fts,
+    },
+    transform::{
+        diagnostics::{check as check_syntax, locate_nodes},
+        node_builder,
+    },
 };
 
 mod file;
 mod fileiter;
 
+pub use fileiter::is_hidden;
+
 #[derive(Debug)]
 pub struct OrgCacheEntry {
     path: PathBuf,
     content: String,
+    /// Last modification as unix timestamp.
+    mtime: Option<i64>,
+    /// Creation as unix timestamp. Falls back to `mtime` if the file system
+    /// does not record it.
+    created: Option<i64>,
 }
 
 impl OrgCacheEntry {
     pub fn new<P: AsRef<Path>, PP: AsRef<Path>>(root: P, path: PP) -> io::Result<Self> {
+        let relative = path.as_ref().strip_prefix(root).unwrap().to_path_buf();
+        Self::read(path, relative)
+    }
+
+    /// Read the file at `path`, which is indexed as `relative`.
+    pub fn read<P: AsRef<Path>>(path: P, relative: PathBuf) -> io::Result<Self> {
         let mut file = OrgFile::open(&path)?;
+        let metadata = std::fs::metadata(&path).ok();
+        let unix = |time: io::Result<SystemTime>| {
+            let since_epoch = time.ok()?.duration_since(UNIX_EPOCH).ok()?;
+            i64::try_from(since_epoch.as_secs()).ok()
+        };
+        let mtime = metadata.as_ref().and_then(|m| unix(m.modified()));
+        let created = metadata.as_ref().and_then(|m| unix(m.created())).or(mtime);
         Ok(Self {
-            path: path.as_ref().strip_prefix(root).unwrap().to_path_buf(),
+            path: relative,
             content: file.read_to_string()?,
+            mtime,
+            created,
         })
     }
 
@@ -46,13 +76,30 @@ impl OrgCacheEntry {
         self.path.as_path()
     }
 
+    pub fn mtime(&self) -> Option<i64> {
+        self.mtime
+    }
+
+    pub fn created(&self) -> Option<i64> {
+        self.created
+    }
+
     pub fn get_hash(&self) -> u64 {
-        let mut hasher = DefaultHasher::new();
-        self.content.hash(&mut hasher);
-        hasher.finish()
+        content_hash(&self.content)
     }
 }
 
+/// Hash of the content of a file, as stored in the database. It is the
+/// 64 bit FNV-1a hash, which unlike `DefaultHasher` stays the same across
+/// Rust versions.
+pub fn content_hash(content: &str) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+    content.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
 #[derive(Debug)]
 pub enum InvalidatedBy {
     Path(PathBuf),
@@ -75,6 +122,13 @@ impl From<RoamID> for InvalidatedBy {
 pub struct OrgCache {
     /// Path to the root of the org-roamers directory.
     path: PathBuf,
+    /// Additional roots as `(mount, path)`. Their files are indexed below
+    /// `mount`, as if `path` was the directory `mount` of the root.
+    mounts: Vec<(PathBuf, PathBuf)>,
+    /// Whether symlinked directories and files are indexed.
+    follow_symlinks: bool,
+    /// Larger files are not read, `0` for no limit.
+    max_file_size: u64,
     lookup: DashMap<RoamID, Arc<OrgCacheEntry>>,
 }
 
@@ -82,12 +136,107 @@ impl OrgCache {
     pub fn new(root: PathBuf) -> Self {
         Self {
             path: root,
+            mounts: Vec::new(),
+            follow_symlinks: false,
+            max_file_size: 0,
             lookup: DashMap::new(),
         }
     }
 
+    /// Follow symlinks and junctions when looking for org files.
+    pub fn follow_symlinks(&mut self, follow: bool) {
+        self.follow_symlinks = follow;
+    }
+
+    /// Whether symlinks and junctions are followed.
+    pub fn follows_symlinks(&self) -> bool {
+        self.follow_symlinks
+    }
+
+    /// Refuse to read files larger than `bytes`. `0` disables the limit.
+    pub fn max_file_size(&mut self, bytes: u64) {
+        self.max_file_size = bytes;
+    }
+
+    /// Index the files below `path` as if they were in the directory `mount`
+    /// of the root. Files of the root within `mount` are shadowed.
+    pub fn mount(&mut self, mount: PathBuf, path: PathBuf) {
+        self.mounts.push((mount, path));
+    }
+
+    /// The root followed by all mounted roots.
+    pub fn roots(&self) -> impl Iterator<Item = &Path> {
+        std::iter::once(self.path.as_path())
+            .chain(self.mounts.iter().map(|(_, path)| path.as_path()))
+    }
+
+    /// Location on disk of `file`, which is relative to the root like the
+    /// files in the index.
+    pub fn resolve<P: AsRef<Path>>(&self, file: P) -> PathBuf {
+        let (root, rest) = self.locate(file.as_ref());
+        root.join(rest)
+    }
+
+    /// The root containing `file` and the path of `file` within it.
+    pub fn locate<'a>(&'a self, file: &'a Path) -> (&'a Path, &'a Path) {
+        for (mount, path) in &self.mounts {
+            if let Ok(rest) = file.strip_prefix(mount) {
+                return (path, rest);
+            }
+        }
+        (&self.path, file)
+    }
+
+    /// Name of the file at `path` in the index, `None` if it is not below
+    /// any root.
+    pub fn relative<P: AsRef<Path>>(&self, path: P) -> Option<PathBuf> {
+        let path = path.as_ref();
+        for (mount, root) in &self.mounts {
+            if let Ok(rest) = path.strip_prefix(root) {
+                return Some(mount.join(rest));
+            }
+        }
+        path.strip_prefix(&self.path).ok().map(Path::to_path_buf)
+    }
+
+    /// Read the file at `path`, which is either below one of the roots or
+    /// relative to the root. Files above the size limit fail with
+    /// [`io::ErrorKind::FileTooLarge`] before they are read.
+    pub fn entry<P: AsRef<Path>>(&self, path: P) -> io::Result<OrgCacheEntry> {
+        let path = path.as_ref();
+        let path = match path.is_relative() {
+            true => self.resolve(path),
+            false => path.to_path_buf(),
+        };
+        let relative = self.relative(&path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not below any root", path.display()),
+            )
+        })?;
+        if self.max_file_size > 0 {
+            let size = std::fs::metadata(&path)?.len();
+            if size > self.max_file_size {
+                return Err(io::Error::new(
+                    io::ErrorKind::FileTooLarge,
+                    format!(
+                        "{} has {size} bytes, more than the limit of {} bytes",
+                        path.display(),
+                        self.max_file_size
+                    ),
+                ));
+            }
+        }
+        OrgCacheEntry::read(path, relative)
+    }
+
+    /// Fill the cache from the files below the root. Files whose hash did not
+    /// change since they were stored in `con` are not parsed again, files
+    /// that no longer exist are removed from `con`.
     pub async fn rebuild(&mut self, con: &SqlitePool) -> anyhow::Result<()> {
-        let file_iter = FileIter::new(&self.path)?;
+        let file_iter = self.org_files()?;
+        let mut indexed: HashMap<String, u64> = get_files(con).await?.into_iter().collect();
+        let mut unchanged = 0;
 
         for file_or_error in file_iter {
             let file_path = match file_or_error {
@@ -98,20 +247,52 @@ impl OrgCache {
                 }
             };
 
-            let cache_entry = match OrgCacheEntry::new(self.path.as_path(), file_path.as_path()) {
+            let cache_entry = match self.entry(&file_path) {
                 Ok(entry) => entry,
                 Err(err) => {
                     tracing::error!("{err}");
+                    if let Some(relative) = self.relative(&file_path) {
+                        // Drop what was indexed before, only the problem is kept
+                        let file = relative.to_string_lossy();
+                        if indexed.remove(file.as_ref()).is_some() {
+                            delete_file(con, &file).await?;
+                        }
+                        diagnostics::record_file_error(con, &file, &err).await;
+                    }
                     continue;
                 }
             };
 
-            if let Err(err) = insert_file(con, cache_entry.path(), cache_entry.get_hash()).await {
+            let file_path = cache_entry.path().to_string_lossy().to_string();
+            if indexed.remove(&file_path) == Some(cache_entry.get_hash()) {
+                let cache_entry = Arc::new(cache_entry);
+                for id in get_node_ids(con, &file_path).await? {
+                    self.lookup.insert(id.into(), cache_entry.clone());
+                }
+                unchanged += 1;
+                continue;
+            }
+
+            if let Err(err) = insert_file(
+                con,
+                cache_entry.path(),
+                cache_entry.get_hash(),
+                cache_entry.mtime(),
+                cache_entry.created(),
+            )
+            .await
+            {
+                tracing::error!("{err}");
+            }
+            if let Err(err) = fts::index_file(con, &file_path, cache_entry.content()).await {
                 tracing::error!("{err}");
             }
 
-            let file_path = cache_entry.path().to_string_lossy().to_string();
-            let nodes = node_builder::get_nodes(cache_entry.content(), &file_path);
+            let (nodes, tasks) =
+                node_builder::get_nodes_and_tasks(cache_entry.content(), &file_path);
+            if let Err(err) = node_builder::insert_tasks(con, &file_path, &tasks).await {
+                tracing::error!("{err}");
+            }
 
             let cache_entry = Arc::new(cache_entry);
             for node in &nodes {
@@ -119,36 +300,42 @@ impl OrgCache {
                     .insert(node.uuid.clone().into(), cache_entry.clone());
             }
 
-            node_builder::insert_nodes(con, nodes).await;
+            let mut found = check_syntax(cache_entry.content());
+            found.extend(node_builder::insert_nodes(con, nodes).await);
+            locate_nodes(cache_entry.content(), &mut found);
+            if let Err(err) = diagnostics::replace_file_diagnostics(con, &file_path, &found).await {
+                tracing::error!("Failed to store diagnostics of {file_path}: {err}");
+            }
+        }
+
+        // Files deleted while the server was not running
+        for file in indexed.into_keys() {
+            delete_file(con, &file).await?;
+        }
+        if unchanged > 0 {
+            tracing::info!("Skipped {unchanged} unchanged files");
         }
 
         Ok(())
     }
 
+    /// Look up a node by title or alias.
     pub async fn get_by_name(
         &self,
         con: &SqlitePool,
         name: &str,
     ) -> Option<(RoamID, Arc<OrgCacheEntry>)> {
-        let stmnt = r#"
-            SELECT id FROM nodes
-            WHERE title = ?;
-        "#;
-
-        let id: (String,) = sqlx::query_as(stmnt)
-            .bind(name)
-            .fetch_one(con)
+        let id: RoamID = aliases::resolve_name(con, name)
             .await
-            .unwrap();
-
-        match self.retrieve(&id.0.as_str().into()) {
-            Some(content) => Some((id.0.into(), content)),
-            None => None,
-        }
+            .inspect_err(|err| tracing::error!("Failed to resolve {name}: {err}"))
+            .ok()??
+            .into();
+        let content = self.retrieve(&id)?;
+        Some((id, content))
     }
 
     pub fn submit<P: AsRef<Path>>(&self, id: RoamID, path: P) -> anyhow::Result<()> {
-        let cache_entry = OrgCacheEntry::new(&self.path, path)?;
+        let cache_entry = self.entry(path)?;
         let cache_entry_arc = Arc::new(cache_entry);
 
         tracing::info!("Submitted {:?} into cache.", cache_entry_arc.path());
@@ -197,15 +384,40 @@ impl OrgCache {
         }
     }
 
+    /// Iterate over all org files below the roots. Mounted roots that can
+    /// not be read are skipped.
+    pub fn org_files(&self) -> io::Result<impl Iterator<Item = io::Result<PathBuf>>> {
+        let mut iters = vec![FileIter::new(&self.path, self.follow_symlinks)?];
+        for (mount, path) in &self.mounts {
+            match FileIter::new(path, self.follow_symlinks) {
+                Ok(iter) => iters.push(iter),
+                Err(err) => tracing::error!(
+                    "Failed to read {} mounted at {}: {err}",
+                    path.display(),
+                    mount.display()
+                ),
+            }
+        }
+        Ok(iters.into_iter().flatten())
+    }
+
+    /// Drop all entries of a file, e.g. because it was deleted. `path` is
+    /// relative to the root.
+    pub fn remove_file(&self, path: &Path) {
+        self.lookup.retain(|_, entry| entry.path() != path);
+    }
+
     pub fn invalidate<T: Into<InvalidatedBy>>(&self, by: T) {
         let by = by.into();
 
         let keys_to_invalidate: Vec<(RoamID, PathBuf)> = match by {
             InvalidatedBy::Path(ref path) => {
-                let rel_path = path.strip_prefix(self.path.as_path()).unwrap();
+                let Some(rel_path) = self.relative(path) else {
+                    return;
+                };
                 self.lookup
                     .iter()
-                    .filter(|elem| elem.value().path.as_path() == rel_path)
+                    .filter(|elem| elem.value().path == rel_path)
                     .map(|elem| (elem.key().clone(), path.to_path_buf()))
                     .collect()
             }
@@ -249,6 +461,12 @@ mod tests {
         file_path
     }
 
+    #[test]
+    fn test_content_hash() {
+        assert_eq!(content_hash(""), 0xcbf29ce484222325);
+        assert_eq!(content_hash("a"), 0xaf63dc4c8601ec8c);
+    }
+
     #[test]
     fn test_submit_updates_all_nodes_from_same_file() {
         let temp_dir = TempDir::new().unwrap();
@@ -486,4 +704,53 @@ Content 1 UPDATED
         let arc_strong_count = Arc::strong_count(ptr1.value());
         assert_eq!(arc_strong_count, 3); // 3 entries in the map
     }
+
+    #[test]
+    fn test_mounted_roots() {
+        let root = TempDir::new().unwrap();
+        let work = TempDir::new().unwrap();
+        let mut cache = OrgCache::new(root.path().to_path_buf());
+        cache.mount("work".into(), work.path().to_path_buf());
+
+        create_test_org_file(root.path(), "a.org", "* a");
+        fs::create_dir(work.path().join("projects")).unwrap();
+        let b = create_test_org_file(&work.path().join("projects"), "b.org", "* b");
+
+        assert_eq!(cache.resolve("work/projects/b.org"), b);
+        assert_eq!(cache.resolve("a.org"), root.path().join("a.org"));
+        assert_eq!(
+            cache.relative(&b),
+            Some(PathBuf::from("work/projects/b.org"))
+        );
+        assert_eq!(cache.relative("/elsewhere/c.org"), None);
+        assert_eq!(
+            cache.entry("work/projects/b.org").unwrap().path(),
+            Path::new("work/projects/b.org")
+        );
+
+        let mut files: Vec<PathBuf> = cache
+            .org_files()
+            .unwrap()
+            .map(|file| cache.relative(file.unwrap()).unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![PathBuf::from("a.org"), PathBuf::from("work/projects/b.org")]
+        );
+    }
+
+    #[test]
+    fn test_max_file_size() {
+        let root = TempDir::new().unwrap();
+        let mut cache = OrgCache::new(root.path().to_path_buf());
+        create_test_org_file(root.path(), "small.org", "* a");
+        create_test_org_file(root.path(), "large.org", &"* a\n".repeat(100));
+
+        assert!(cache.entry("large.org").is_ok());
+        cache.max_file_size(64);
+        assert!(cache.entry("small.org").is_ok());
+        let err = cache.entry("large.org").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::FileTooLarge);
+    }
 }
@@ -0,0 +1,183 @@
+//! Aggregation of broadcasts. Messages passed to
+//! [`ServerState::broadcast_to_websockets`] are collected for
+//! [`BROADCAST_WINDOW`] and coalesced before they are sent, so bulk changes
+//! (e.g. a `git pull` touching hundreds of files) do not flood the clients.
+
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use tokio_util::sync::CancellationToken;
+
+use crate::{client::message::WebSocketMessage, graph::GraphUpdate, ServerState};
+
+pub const BROADCAST_WINDOW: Duration = Duration::from_millis(250);
+
+pub fn flusher(state: Arc<ServerState>, cancellation_token: CancellationToken) {
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = cancellation_token.cancelled() => break,
+                _ = state.broadcast_pending.notified() => {
+                    tokio::time::sleep(BROADCAST_WINDOW).await;
+                    state.flush_broadcasts();
+                }
+            }
+        }
+    });
+}
+
+/// Merge messages that only describe the latest state. File change counts
+/// are summed up, graph updates are combined into one, of progress and link
+/// reports only the last one is kept. Merged messages take the place of
+/// their last occurrence, everything else keeps its order.
+pub fn coalesce(messages: Vec<WebSocketMessage>) -> Vec<WebSocketMessage> {
+    let files_changed: usize = messages
+        .iter()
+        .map(|message| match message {
+            WebSocketMessage::StatusUpdate { files_changed } => *files_changed,
+            _ => 0,
+        })
+        .sum();
+
+    let mut graph_update: Option<GraphUpdate> = None;
+    for message in &messages {
+        if let WebSocketMessage::GraphUpdate(update) = message {
+            match &mut graph_update {
+                Some(merged) => merged.merge(update.clone()),
+                None => graph_update = Some(update.clone()),
+            }
+        }
+    }
+
+    let mut status_update = false;
+    let mut broken_links = false;
+    let mut buffer_modified = false;
+    let mut reindex_jobs = HashSet::new();
+
+    let mut coalesced: Vec<WebSocketMessage> = messages
+        .into_iter()
+        .rev()
+        .filter_map(|message| match message {
+            WebSocketMessage::StatusUpdate { .. } if status_update => None,
+            WebSocketMessage::StatusUpdate { .. } => {
+                status_update = true;
+                Some(WebSocketMessage::StatusUpdate { files_changed })
+            }
+            WebSocketMessage::BrokenLinks { .. } if broken_links => None,
+            WebSocketMessage::BrokenLinks { .. } => {
+                broken_links = true;
+                Some(message)
+            }
+            WebSocketMessage::BufferModified if buffer_modified => None,
+            WebSocketMessage::BufferModified => {
+                buffer_modified = true;
+                Some(message)
+            }
+            WebSocketMessage::GraphUpdate(_) => graph_update
+                .take()
+                .filter(|update| !update.is_empty())
+                .map(WebSocketMessage::GraphUpdate),
+            WebSocketMessage::ReindexProgress { job_id, .. } => {
+                reindex_jobs.insert(job_id).then_some(message)
+            }
+            message => Some(message),
+        })
+        .collect();
+    coalesced.reverse();
+    coalesced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{NodeKind, RoamLink, RoamNode};
+
+    fn json(messages: &[WebSocketMessage]) -> Vec<String> {
+        messages
+            .iter()
+            .map(|message| serde_json::to_string(message).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_coalesce() {
+        let messages = vec![
+            WebSocketMessage::StatusUpdate { files_changed: 1 },
+            WebSocketMessage::ReindexProgress {
+                job_id: 1,
+                done: 1,
+                total: 3,
+            },
+            WebSocketMessage::StatusUpdate { files_changed: 2 },
+            WebSocketMessage::ReindexProgress {
+                job_id: 1,
+                done: 3,
+                total: 3,
+            },
+            WebSocketMessage::Reindexed {
+                job_id: 1,
+                discrepancies: 0,
+            },
+            WebSocketMessage::BrokenLinks { count: 1 },
+            WebSocketMessage::BrokenLinks { count: 2 },
+        ];
+        let expected = vec![
+            WebSocketMessage::StatusUpdate { files_changed: 3 },
+            WebSocketMessage::ReindexProgress {
+                job_id: 1,
+                done: 3,
+                total: 3,
+            },
+            WebSocketMessage::Reindexed {
+                job_id: 1,
+                discrepancies: 0,
+            },
+            WebSocketMessage::BrokenLinks { count: 2 },
+        ];
+        assert_eq!(json(&coalesce(messages)), json(&expected));
+    }
+
+    #[test]
+    fn test_coalesce_graph_updates() {
+        let node = |id: &str| RoamNode {
+            title: id.into(),
+            id: id.into(),
+            parent: "".into(),
+            num_links: 0,
+            pinned: false,
+            kind: NodeKind::Node,
+            summary: None,
+            group: None,
+        };
+        let link = RoamLink {
+            from: "a".into(),
+            to: "b".into(),
+        };
+        let messages = vec![
+            WebSocketMessage::GraphUpdate(GraphUpdate {
+                new_nodes: vec![node("a"), node("b")],
+                new_links: vec![link.clone()],
+                ..Default::default()
+            }),
+            WebSocketMessage::StatusUpdate { files_changed: 1 },
+            WebSocketMessage::GraphUpdate(GraphUpdate {
+                new_nodes: vec![node("c")],
+                ..Default::default()
+            }),
+            WebSocketMessage::GraphUpdate(GraphUpdate {
+                removed_nodes: vec!["b".into()],
+                removed_links: vec![link.clone()],
+                ..Default::default()
+            }),
+        ];
+        let expected = vec![
+            WebSocketMessage::StatusUpdate { files_changed: 1 },
+            WebSocketMessage::GraphUpdate(GraphUpdate {
+                new_nodes: vec![node("a"), node("c")],
+                removed_nodes: vec!["b".into()],
+                removed_links: vec![link],
+                ..Default::default()
+            }),
+        ];
+        assert_eq!(json(&coalesce(messages)), json(&expected));
+    }
+}
@@ -4,10 +4,13 @@ use axum::extract::ws::{Message, WebSocket};
 use futures_util::{stream::SplitSink, SinkExt};
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant};
 
 use crate::{
-    client::WebSocketClient,
+    client::{topic::Topic, WebSocketClient},
+    collab,
     search::{Feeder, SearchProviderList, SearchResultEntry},
+    server::types::RoamID,
     ServerState,
 };
 
@@ -21,7 +24,18 @@ use crate::{
 pub enum WebSocketMessage {
     /// Search request from client
     #[serde(rename = "search_request")]
-    SearchRequest { query: String, request_id: String },
+    SearchRequest {
+        query: String,
+        request_id: String,
+        /// Send one hit per file with the number of hits in it.
+        #[serde(default)]
+        group_by_file: bool,
+        /// Search-as-you-type: match words starting with the words of the
+        /// query instead of whole words. Queries are debounced by
+        /// `search.debounce_ms`.
+        #[serde(default)]
+        prefix: bool,
+    },
 
     /// Search results response to client
     #[serde(rename = "search_response")]
@@ -40,12 +54,64 @@ pub enum WebSocketMessage {
     #[serde(rename = "status_update")]
     StatusUpdate { files_changed: usize },
 
+    /// Changes of the graph after a file was indexed
+    #[serde(rename = "graph_update")]
+    GraphUpdate(crate::graph::GraphUpdate),
+
     /// Node visited notification
     #[serde(rename = "node_visited")]
     NodeVisited {
         node_id: crate::server::types::RoamID,
     },
 
+    /// The link checker found a different set of broken links
+    #[serde(rename = "broken_links")]
+    BrokenLinks { count: usize },
+
+    /// Progress of a reindex job
+    #[serde(rename = "reindex_progress")]
+    ReindexProgress {
+        job_id: u64,
+        done: usize,
+        total: usize,
+    },
+
+    /// A reindex job finished
+    #[serde(rename = "reindexed")]
+    Reindexed { job_id: u64, discrepancies: usize },
+
+    /// Only receive broadcasts of `topics` from now on, in addition to the
+    /// topics subscribed before. Without a subscription every broadcast is
+    /// received.
+    #[serde(rename = "subscribe")]
+    Subscribe { topics: Vec<Topic> },
+
+    /// Stop receiving broadcasts of `topics`.
+    #[serde(rename = "unsubscribe")]
+    Unsubscribe { topics: Vec<Topic> },
+
+    /// Join the shared document of the file of node `id`, see
+    /// `crate::collab`.
+    #[serde(rename = "collab_join")]
+    CollabJoin { id: RoamID },
+
+    /// The whole shared document, sent after joining
+    #[serde(rename = "collab_sync")]
+    CollabSync { id: RoamID, update: Vec<u8> },
+
+    /// Changes of a shared document, sent by and relayed to its clients
+    #[serde(rename = "collab_update")]
+    CollabUpdate { id: RoamID, update: Vec<u8> },
+
+    /// Stop editing the shared document of the file of node `id`
+    #[serde(rename = "collab_leave")]
+    CollabLeave { id: RoamID },
+
+    /// Two versions of `file` conflicted. The file keeps one, `copy` next
+    /// to it the other.
+    #[serde(rename = "conflict")]
+    Conflict { file: String, copy: String },
+
     /// Buffer modified notification
     #[serde(rename = "buffer_modified")]
     BufferModified,
@@ -85,9 +151,30 @@ impl WebSocketMessage {
                     tracing::error!("Couln't send conf resp: {err}");
                 };
             }
-            Self::SearchRequest { query, request_id } => {
-                Self::handle_search(app_state, sender, client, query, request_id).await
+            Self::SearchRequest {
+                query,
+                request_id,
+                group_by_file,
+                prefix,
+            } => {
+                let feeder = Feeder::new(query.clone(), *group_by_file).with_prefix(*prefix);
+                Self::handle_search(app_state, sender, client, feeder, request_id).await
+            }
+            Self::NodeVisited { node_id } => {
+                // The client navigated on its own, only this connection moves.
+                // Echoes of visits that came from Emacs are not sent back.
+                if app_state.working_id(client.client_id).as_ref() != Some(node_id) {
+                    app_state.set_working_id(client.client_id, node_id.clone());
+                    app_state.publish_ui_selection(client.client_id, node_id.clone());
+                }
             }
+            Self::Subscribe { topics } => app_state.subscribe(client.client_id, topics),
+            Self::Unsubscribe { topics } => app_state.unsubscribe(client.client_id, topics),
+            Self::CollabJoin { id } => collab::join(&app_state, client.client_id, id),
+            Self::CollabUpdate { id, update } => {
+                collab::update(&app_state, client.client_id, id, update)
+            }
+            Self::CollabLeave { id } => collab::leave(&app_state, client.client_id, id),
             unsupported => {
                 tracing::error!("Unsupported request: {unsupported:?}");
             }
@@ -116,14 +203,13 @@ impl WebSocketMessage {
         app_state: Arc<ServerState>,
         _sender: &mut SplitSink<WebSocket, Message>,
         client: &mut WebSocketClient,
-        query: &str,
+        feeder: Feeder,
         request_id: &str,
     ) {
-        let start = std::time::Instant::now();
         tracing::info!(
             "Processing search request from client {}: {}",
             client.client_id,
-            query
+            feeder.query()
         );
 
         let Some((searcher_providers, mpsc_receiver)) = &mut client.search else {
@@ -142,16 +228,15 @@ impl WebSocketMessage {
         // Store the current request_id so we can use it when sending results
         client.current_request_id = Some(request_id.to_string());
 
-        tracing::info!("Starting search providers (took {:?})", start.elapsed());
-
-        // Start the search (non-blocking)
-        searcher_providers
-            .feed(app_state, Feeder::new(query.to_string()))
-            .await;
-
-        tracing::info!("Search providers started (took {:?})", start.elapsed());
+        // Prefix searches are sent on every keystroke. They wait a moment,
+        // a newer query replaces the pending one.
+        let debounce = Duration::from_millis(app_state.config.search.debounce_ms);
+        if feeder.is_prefix() && !debounce.is_zero() {
+            client.pending_search = Some((Instant::now() + debounce, feeder));
+            return;
+        }
+        client.pending_search = None;
 
-        // Don't block here - results will be received in the main select! loop
-        // The mpsc_receiver is polled in the WebSocketClient::handle_connection method
+        client.start_search(app_state, feeder).await;
     }
 }
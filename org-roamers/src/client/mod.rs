@@ -11,54 +11,212 @@
 //! - Ping/pong keep-alive mechanism
 //! - Simple message handling without broadcasting
 
-use std::sync::Arc;
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
-use axum::extract::ws::{Message, WebSocket};
+use axum::extract::ws::{close_code, CloseFrame, Message, WebSocket};
 use futures_util::{SinkExt, StreamExt};
-use tokio::sync::mpsc;
-use tokio::time::Duration;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::time::{Duration, Instant};
 use tracing::{error, info, warn};
 
 use crate::{
-    client::message::WebSocketMessage,
-    search::{SearchProviderList, SearchResultEntry},
+    client::{message::WebSocketMessage, topic::Topic},
+    search::{Feeder, SearchProviderList, SearchResultEntry},
+    server::types::RoamID,
     ServerState,
 };
 
+pub mod broadcast;
 pub mod message;
+pub mod topic;
+
+/// Number of messages that are queued for a connection before they are
+/// held back in its overflow.
+pub const CONNECTION_BUFFER: usize = 64;
+/// Messages that are held back for a slow connection. If coalescing does not
+/// get the overflow below this, the oldest messages are dropped.
+const MAX_OVERFLOW: usize = 256;
+
+/// Orders the activity of all connections, see [`WebSocketConnection::touch`].
+static ACTIVITY: AtomicU64 = AtomicU64::new(0);
+
+/// How a client is connected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionKind {
+    WebSocket,
+    /// Server-sent events of `/events`
+    Sse,
+}
+
+/// Server side state of a registered WebSocket connection
+pub struct WebSocketConnection {
+    pub(crate) sender: mpsc::Sender<WebSocketMessage>,
+    /// Messages that did not fit into the channel, sent before any newer
+    /// message once the client catches up.
+    overflow: Vec<WebSocketMessage>,
+    pub(crate) kind: ConnectionKind,
+    /// Authenticated user of the connection (None if auth disabled)
+    pub(crate) user: Option<String>,
+    pub(crate) user_agent: Option<String>,
+    /// Unix timestamp of the connect in seconds
+    pub(crate) connected_at: i64,
+    /// Node that is currently visited on this connection
+    pub(crate) working_id: Option<RoamID>,
+    /// Increases whenever the connection is used, the connection of a user
+    /// with the highest value was active most recently.
+    pub(crate) last_active: u64,
+    /// Messages handed to the connection's channel
+    pub(crate) messages_sent: u64,
+    /// Messages dropped because the client was too slow
+    pub(crate) messages_dropped: u64,
+    /// Subscribed topics, `None` until the client subscribes
+    pub(crate) topics: Option<HashSet<Topic>>,
+}
+
+impl WebSocketConnection {
+    pub fn new(
+        sender: mpsc::Sender<WebSocketMessage>,
+        kind: ConnectionKind,
+        user: Option<String>,
+        user_agent: Option<String>,
+    ) -> Self {
+        Self {
+            sender,
+            overflow: Vec::new(),
+            kind,
+            user,
+            user_agent,
+            connected_at: OffsetDateTime::now_utc().unix_timestamp(),
+            working_id: None,
+            last_active: ACTIVITY.fetch_add(1, Ordering::Relaxed),
+            messages_sent: 0,
+            messages_dropped: 0,
+            topics: None,
+        }
+    }
+
+    /// Mark the connection as the most recently active one.
+    pub(crate) fn touch(&mut self) {
+        self.last_active = ACTIVITY.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Whether a broadcast of `topics` is sent to this connection.
+    pub(crate) fn wants(&self, topics: &[Topic]) -> bool {
+        match &self.topics {
+            None => true,
+            Some(subscribed) => topics.iter().any(|topic| subscribed.contains(topic)),
+        }
+    }
+
+    /// Add `topics` to the subscriptions.
+    pub(crate) fn subscribe(&mut self, topics: &[Topic]) {
+        self.topics
+            .get_or_insert_with(HashSet::new)
+            .extend(topics.iter().cloned());
+    }
+
+    /// Remove `topics` from the subscriptions. A connection that did not
+    /// subscribe yet keeps the other global topics.
+    pub(crate) fn unsubscribe(&mut self, topics: &[Topic]) {
+        let subscribed = self
+            .topics
+            .get_or_insert_with(|| Topic::GLOBAL.into_iter().collect());
+        for topic in topics {
+            subscribed.remove(topic);
+        }
+    }
+
+    /// Number of messages held back for a slow client.
+    pub(crate) fn queued(&self) -> usize {
+        self.overflow.len()
+    }
+
+    /// Queue a message without waiting for a slow client. Returns `false`
+    /// if the connection is closed.
+    pub(crate) fn send(&mut self, message: WebSocketMessage) -> bool {
+        self.overflow.push(message);
+
+        let mut pending = std::mem::take(&mut self.overflow).into_iter();
+        while let Some(message) = pending.next() {
+            match self.sender.try_send(message) {
+                Ok(()) => self.messages_sent += 1,
+                Err(TrySendError::Full(message)) => {
+                    self.overflow.push(message);
+                    self.overflow.extend(pending);
+                    break;
+                }
+                Err(TrySendError::Closed(_)) => return false,
+            }
+        }
+
+        if self.overflow.len() > 1 {
+            self.overflow = broadcast::coalesce(std::mem::take(&mut self.overflow));
+        }
+        if self.overflow.len() > MAX_OVERFLOW {
+            let dropped = self.overflow.len() - MAX_OVERFLOW;
+            warn!("Dropping {dropped} messages for a slow client");
+            self.overflow.drain(..dropped);
+            self.messages_dropped += dropped as u64;
+        }
+        true
+    }
+}
 
 /// Simple WebSocket client that handles a single connection
 pub struct WebSocketClient {
     pub(crate) search: Option<(SearchProviderList, mpsc::Receiver<SearchResultEntry>)>,
     pub(crate) current_request_id: Option<String>,
+    /// Debounced search and when it starts
+    pub(crate) pending_search: Option<(Instant, Feeder)>,
     socket: Option<WebSocket>,
+    server_rx: Option<mpsc::Receiver<WebSocketMessage>>,
     pub(crate) client_id: u64,
 }
 
 impl WebSocketClient {
-    pub fn new(socket: WebSocket, client_id: u64) -> Self {
+    pub fn new(
+        socket: WebSocket,
+        server_rx: mpsc::Receiver<WebSocketMessage>,
+        client_id: u64,
+    ) -> Self {
         Self {
             search: None,
             current_request_id: None,
+            pending_search: None,
             socket: Some(socket),
+            server_rx: Some(server_rx),
             client_id,
         }
     }
 
+    /// Run the search providers. Results are sent in the main loop of
+    /// [`WebSocketClient::handle_connection`].
+    pub(crate) async fn start_search(&mut self, app_state: Arc<ServerState>, feeder: Feeder) {
+        let Some((searcher_providers, _)) = &mut self.search else {
+            return;
+        };
+        let start = std::time::Instant::now();
+        searcher_providers.feed(app_state, feeder).await;
+        tracing::info!("Search providers started (took {:?})", start.elapsed());
+    }
+
     /// Handle the WebSocket connection lifecycle
     pub async fn handle_connection(mut self, app_state: Arc<ServerState>) {
-        let (mut sender, mut receiver) = self.socket.unwrap().split();
-        self.socket = None;
+        let (mut sender, mut receiver) = self.socket.take().unwrap().split();
+        let mut server_rx = self.server_rx.take().unwrap();
         let client_id = self.client_id;
 
         info!("WebSocket client {} connected", client_id);
 
-        // Create a channel for receiving messages from the server
-        let (server_tx, mut server_rx) = mpsc::unbounded_channel::<WebSocketMessage>();
-
-        // Register this connection with the server state
-        app_state.register_websocket_connection(server_tx);
-
         // Set up ping interval for keep-alive
         let mut ping_interval = tokio::time::interval(Duration::from_secs(30));
         ping_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
@@ -73,6 +231,7 @@ impl WebSocketClient {
             .await
         {
             error!("Failed to send initial ping to client {}: {}", client_id, e);
+            app_state.unregister_websocket_connection(client_id);
             return;
         }
 
@@ -122,7 +281,15 @@ impl WebSocketClient {
                             }
                         }
                         None => {
+                            // Disconnected by an admin or the server is shutting down
                             info!("Server message channel closed for client {}", client_id);
+                            let frame = CloseFrame {
+                                code: close_code::AWAY,
+                                reason: "Closed by the server".into(),
+                            };
+                            if let Err(e) = sender.send(Message::Close(Some(frame))).await {
+                                warn!("Failed to send close frame to client {}: {}", client_id, e);
+                            }
                             break;
                         }
                     }
@@ -138,6 +305,18 @@ impl WebSocketClient {
                     }
                 }
 
+                // Start a debounced search once no newer query came in
+                _ = async {
+                    match &self.pending_search {
+                        Some((at, _)) => tokio::time::sleep_until(*at).await,
+                        None => std::future::pending::<()>().await,
+                    }
+                } => {
+                    if let Some((_, feeder)) = self.pending_search.take() {
+                        self.start_search(app_state.clone(), feeder).await;
+                    }
+                }
+
                 // Handle search results
                 search_result = async {
                     if let Some((_, receiver)) = &mut self.search {
@@ -174,11 +353,126 @@ impl WebSocketClient {
 }
 
 /// Handle a new WebSocket connection with a simple 1:1 approach
-pub async fn handle_websocket(socket: WebSocket, app_state: Arc<ServerState>) {
-    // Use a simple counter for client IDs - in production you might want something more robust
-    static CLIENT_COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
-    let client_id = CLIENT_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+pub async fn handle_websocket(
+    socket: WebSocket,
+    app_state: Arc<ServerState>,
+    user: Option<String>,
+    user_agent: Option<String>,
+) {
+    // Create a channel for receiving messages from the server
+    let (server_tx, server_rx) = mpsc::channel::<WebSocketMessage>(CONNECTION_BUFFER);
 
-    let client = WebSocketClient::new(socket, client_id);
+    // Register this connection with the server state. The connection id is
+    // also used as client id, so unregistering removes the right entry.
+    let client_id = app_state.register_websocket_connection(
+        server_tx,
+        ConnectionKind::WebSocket,
+        user,
+        user_agent,
+    );
+
+    let client = WebSocketClient::new(socket, server_rx, client_id);
     client.handle_connection(app_state).await;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slow_connection_overflow() {
+        let (sender, mut receiver) = mpsc::channel(1);
+        let mut connection =
+            WebSocketConnection::new(sender, ConnectionKind::WebSocket, None, None);
+
+        for _ in 0..3 {
+            assert!(connection.send(WebSocketMessage::StatusUpdate { files_changed: 1 }));
+        }
+        assert_eq!(connection.queued(), 1);
+        assert_eq!(connection.messages_sent, 1);
+        assert!(matches!(
+            connection.overflow[0],
+            WebSocketMessage::StatusUpdate { files_changed: 2 }
+        ));
+
+        receiver.try_recv().unwrap();
+        assert!(connection.send(WebSocketMessage::BufferModified));
+        assert!(matches!(
+            receiver.try_recv().unwrap(),
+            WebSocketMessage::StatusUpdate { files_changed: 2 }
+        ));
+        assert_eq!(connection.overflow.len(), 1);
+
+        drop(receiver);
+        assert!(!connection.send(WebSocketMessage::BufferModified));
+    }
+
+    #[test]
+    fn test_subscriptions() {
+        let (sender, _receiver) = mpsc::channel(1);
+        let mut connection =
+            WebSocketConnection::new(sender, ConnectionKind::WebSocket, None, None);
+        let node = Topic::Node("a".into());
+        assert!(connection.wants(&[Topic::Reindex]));
+
+        connection.subscribe(&[Topic::Graph, node.clone()]);
+        assert!(connection.wants(&[Topic::Graph]));
+        assert!(connection.wants(&[Topic::Visits, node.clone()]));
+        assert!(!connection.wants(&[Topic::Visits]));
+
+        connection.unsubscribe(&[Topic::Graph]);
+        assert!(!connection.wants(&[Topic::Graph]));
+        assert!(connection.wants(&[node]));
+
+        let (sender, _receiver) = mpsc::channel(1);
+        let mut connection = WebSocketConnection::new(sender, ConnectionKind::Sse, None, None);
+        connection.unsubscribe(&[Topic::Reindex]);
+        assert!(connection.wants(&[Topic::Status]));
+        assert!(!connection.wants(&[Topic::Reindex]));
+    }
+
+    #[tokio::test]
+    async fn test_node_visited_moves_one_connection() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = crate::config::Config {
+            org_roamers_root: temp_dir.path().to_path_buf(),
+            database: crate::config::DatabaseConfig {
+                path: Some(temp_dir.path().join("roam.db")),
+            },
+            ..Default::default()
+        };
+        let state = ServerState::new(config).await.unwrap();
+
+        let (sender, mut first_rx) = mpsc::channel(4);
+        let first =
+            state.register_websocket_connection(sender, ConnectionKind::WebSocket, None, None);
+        let (sender, mut second_rx) = mpsc::channel(4);
+        let second =
+            state.register_websocket_connection(sender, ConnectionKind::WebSocket, None, None);
+        state.set_working_id(first, "a".into());
+
+        // The most recently active connection follows
+        assert_eq!(state.send_node_visited(None, None, "b".into()), Some(first));
+        assert_eq!(state.working_id(first), Some("b".into()));
+        assert_eq!(state.working_id(second), None);
+        assert!(matches!(
+            first_rx.try_recv().unwrap(),
+            WebSocketMessage::NodeVisited { node_id } if node_id == "b".into()
+        ));
+        assert!(second_rx.try_recv().is_err());
+
+        // Unless Emacs names a connection
+        assert_eq!(
+            state.send_node_visited(None, Some(second), "c".into()),
+            Some(second)
+        );
+        assert_eq!(state.working_id(first), Some("b".into()));
+        assert_eq!(state.working_id(second), Some("c".into()));
+        assert!(first_rx.try_recv().is_err());
+
+        assert_eq!(
+            state.send_node_visited(Some("other"), None, "d".into()),
+            None
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//! Topics of broadcasts. Clients subscribe to topics with
+//! [`WebSocketMessage::Subscribe`], connections that never subscribed
+//! receive every broadcast.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{client::message::WebSocketMessage, server::types::RoamID};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Topic {
+    /// Changes of the graph, `graph`
+    Graph,
+    /// Changed files and buffers modified in Emacs, `status`
+    Status,
+    /// Nodes visited in Emacs, `visits`
+    Visits,
+    /// Reports of the link checker, `links`
+    Links,
+    /// Progress of reindex jobs, `reindex`
+    Reindex,
+    /// Graph changes and visits that touch a single node, `node:<id>`
+    Node(RoamID),
+}
+
+impl Topic {
+    /// Topics that are not tied to a node. Together they cover every
+    /// broadcast.
+    pub const GLOBAL: [Topic; 5] = [
+        Topic::Graph,
+        Topic::Status,
+        Topic::Visits,
+        Topic::Links,
+        Topic::Reindex,
+    ];
+}
+
+impl TryFrom<String> for Topic {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "graph" => Ok(Topic::Graph),
+            "status" => Ok(Topic::Status),
+            "visits" => Ok(Topic::Visits),
+            "links" => Ok(Topic::Links),
+            "reindex" => Ok(Topic::Reindex),
+            other => match other.strip_prefix("node:") {
+                Some(id) if !id.is_empty() => Ok(Topic::Node(id.into())),
+                _ => Err(format!("Unknown topic: {other}")),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Topic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Topic::Graph => write!(f, "graph"),
+            Topic::Status => write!(f, "status"),
+            Topic::Visits => write!(f, "visits"),
+            Topic::Links => write!(f, "links"),
+            Topic::Reindex => write!(f, "reindex"),
+            Topic::Node(id) => write!(f, "node:{}", id.id()),
+        }
+    }
+}
+
+impl From<Topic> for String {
+    fn from(value: Topic) -> Self {
+        value.to_string()
+    }
+}
+
+/// Topics a message belongs to. Replies to a single client belong to none.
+pub fn topics(message: &WebSocketMessage) -> Vec<Topic> {
+    match message {
+        WebSocketMessage::StatusUpdate { .. }
+        | WebSocketMessage::BufferModified
+        | WebSocketMessage::Conflict { .. } => vec![Topic::Status],
+        WebSocketMessage::GraphUpdate(update) => {
+            let nodes = update
+                .new_nodes
+                .iter()
+                .chain(&update.updated_nodes)
+                .map(|node| &node.id)
+                .chain(&update.removed_nodes);
+            let links = update
+                .new_links
+                .iter()
+                .chain(&update.removed_links)
+                .flat_map(|link| [&link.from, &link.to]);
+            let mut topics = vec![Topic::Graph];
+            for id in nodes.chain(links) {
+                let topic = Topic::Node(id.clone());
+                if !topics.contains(&topic) {
+                    topics.push(topic);
+                }
+            }
+            topics
+        }
+        WebSocketMessage::NodeVisited { node_id } => {
+            vec![Topic::Visits, Topic::Node(node_id.clone())]
+        }
+        WebSocketMessage::BrokenLinks { .. } => vec![Topic::Links],
+        WebSocketMessage::ReindexProgress { .. } | WebSocketMessage::Reindexed { .. } => {
+            vec![Topic::Reindex]
+        }
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{GraphUpdate, RoamLink};
+
+    #[test]
+    fn test_parse() {
+        let topics: Vec<Topic> =
+            serde_json::from_str(r#"["graph", "status", "node:abc"]"#).unwrap();
+        assert_eq!(
+            topics,
+            vec![Topic::Graph, Topic::Status, Topic::Node("abc".into())]
+        );
+        assert_eq!(
+            serde_json::to_string(&topics).unwrap(),
+            r#"["graph","status","node:abc"]"#
+        );
+        assert!(Topic::try_from("node:".to_string()).is_err());
+        assert!(Topic::try_from("everything".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_topics() {
+        let update = GraphUpdate {
+            new_links: vec![RoamLink {
+                from: "a".into(),
+                to: "b".into(),
+            }],
+            removed_nodes: vec!["a".into()],
+            ..Default::default()
+        };
+        assert_eq!(
+            topics(&WebSocketMessage::GraphUpdate(update)),
+            vec![
+                Topic::Graph,
+                Topic::Node("a".into()),
+                Topic::Node("b".into())
+            ]
+        );
+        assert_eq!(
+            topics(&WebSocketMessage::StatusUpdate { files_changed: 1 }),
+            vec![Topic::Status]
+        );
+        assert!(topics(&WebSocketMessage::Pong).is_empty());
+    }
+}
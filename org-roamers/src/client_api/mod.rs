@@ -0,0 +1,377 @@
+//! # client_api module
+//! Typed async client for the HTTP and websocket API of a running server,
+//! for the GUI, integration tests and tools scripting the server. Enabled by
+//! the `client_api` feature.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), org_roamers::client_api::ApiError> {
+//! use org_roamers::client_api::{ApiClient, GraphQuery};
+//!
+//! let client = ApiClient::new("http://localhost:5000")?;
+//! let graph = client.graph(&GraphQuery::default()).await?;
+//! let mut ws = client.websocket().await?;
+//! let hits = ws.search("rust").await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Endpoints without a method of their own can be called with
+//! [`ApiClient::get`] and [`ApiClient::post`].
+
+mod websocket;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::{
+    cookie::{CookieStore, Jar},
+    Method, RequestBuilder, StatusCode, Url,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+pub use crate::client::{message::WebSocketMessage, topic::Topic, ConnectionKind};
+pub use crate::graph::GraphUpdate;
+pub use crate::search::SearchResultEntry;
+pub use crate::server::types::{
+    BacklinksResponse, ConnectionInfo, ConnectionsResponse, EditConflict, GraphData, LiteGraph,
+    NeighborsResponse, NodeSource, OrgAsHTMLResponse, OutlineResponse, RoamID, RoamLink, RoamNode,
+    RoamTitle, SimilarNode, SimilarResponse, StatusResponse, VaultIndex,
+};
+pub use websocket::WsClient;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    #[error("Invalid server url {0}")]
+    InvalidUrl(String),
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    /// The server answered with an error status.
+    #[error("Server responded with {status}: {body}")]
+    Status { status: StatusCode, body: String },
+    /// The file changed since its source was read, see
+    /// [`ApiClient::edit_source`].
+    #[error("The file changed since it was read")]
+    Conflict(EditConflict),
+    #[error(transparent)]
+    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("The websocket was closed")]
+    Closed,
+}
+
+pub type Result<T> = std::result::Result<T, ApiError>;
+
+/// Filters of [`ApiClient::graph`], like the query parameters of `/graph`.
+#[derive(Debug, Clone, Default)]
+pub struct GraphQuery {
+    /// Only nodes with one of these tags
+    pub tags: Vec<String>,
+    /// No nodes with one of these tags
+    pub exclude: Vec<String>,
+    /// `nodes`, `files` or `tags`, the server default if `None`
+    pub mode: Option<String>,
+    pub summaries: bool,
+    pub groups: bool,
+    /// Name of a saved graph view
+    pub view: Option<String>,
+}
+
+impl GraphQuery {
+    fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = vec![];
+        if !self.tags.is_empty() {
+            params.push(("tags", self.tags.join(",")));
+        }
+        if !self.exclude.is_empty() {
+            params.push(("exclude", self.exclude.join(",")));
+        }
+        if let Some(mode) = &self.mode {
+            params.push(("mode", mode.clone()));
+        }
+        if self.summaries {
+            params.push(("summaries", "true".to_string()));
+        }
+        if self.groups {
+            params.push(("groups", "true".to_string()));
+        }
+        if let Some(view) = &self.view {
+            params.push(("view", view.clone()));
+        }
+        params
+    }
+}
+
+/// Client of a server at a base url like `http://localhost:5000`. The
+/// session cookie of [`ApiClient::login`] is kept and also sent when
+/// opening websockets.
+#[derive(Clone)]
+pub struct ApiClient {
+    base: Url,
+    http: reqwest::Client,
+    cookies: Arc<Jar>,
+}
+
+impl ApiClient {
+    pub fn new(base: &str) -> Result<Self> {
+        let mut base = Url::parse(base).map_err(|_| ApiError::InvalidUrl(base.to_string()))?;
+        if !base.path().ends_with('/') {
+            let path = format!("{}/", base.path());
+            base.set_path(&path);
+        }
+        let cookies = Arc::new(Jar::default());
+        let http = reqwest::Client::builder()
+            .cookie_provider(cookies.clone())
+            .build()?;
+        Ok(Self {
+            base,
+            http,
+            cookies,
+        })
+    }
+
+    fn url(&self, path: &str) -> Result<Url> {
+        self.base
+            .join(path.trim_start_matches('/'))
+            .map_err(|_| ApiError::InvalidUrl(path.to_string()))
+    }
+
+    fn request(&self, method: Method, path: &str) -> Result<RequestBuilder> {
+        Ok(self.http.request(method, self.url(path)?))
+    }
+
+    /// Send `request` and fail on error statuses.
+    async fn send(request: RequestBuilder) -> Result<reqwest::Response> {
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_client_error() || status.is_server_error() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(ApiError::Status { status, body });
+        }
+        Ok(response)
+    }
+
+    async fn json<T: DeserializeOwned>(request: RequestBuilder) -> Result<T> {
+        Ok(Self::send(request).await?.json().await?)
+    }
+
+    async fn fetch<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        Self::json(self.request(Method::GET, path)?).await
+    }
+
+    /// GET `path` with `query` and deserialize the response.
+    pub async fn get<T, Q>(&self, path: &str, query: &Q) -> Result<T>
+    where
+        T: DeserializeOwned,
+        Q: Serialize + ?Sized,
+    {
+        Self::json(self.request(Method::GET, path)?.query(query)).await
+    }
+
+    /// POST `body` as JSON to `path` and deserialize the response.
+    pub async fn post<T, B>(&self, path: &str, body: &B) -> Result<T>
+    where
+        T: DeserializeOwned,
+        B: Serialize + ?Sized,
+    {
+        Self::json(self.request(Method::POST, path)?.json(body)).await
+    }
+
+    /// Start a session, required if the server has authentication enabled.
+    pub async fn login(&self, username: &str, password: &str) -> Result<()> {
+        let credentials = serde_json::json!({ "username": username, "password": password });
+        Self::send(self.request(Method::POST, "/api/login")?.json(&credentials)).await?;
+        Ok(())
+    }
+
+    pub async fn logout(&self) -> Result<()> {
+        Self::send(self.request(Method::POST, "/api/logout")?).await?;
+        Ok(())
+    }
+
+    /// GET /status
+    pub async fn status(&self) -> Result<StatusResponse> {
+        self.fetch("/status").await
+    }
+
+    /// GET /status/wait: wait up to `timeout` for a change of the vault after
+    /// `since`. `None` if nothing changed.
+    pub async fn wait_status(
+        &self,
+        since: u64,
+        timeout: Duration,
+    ) -> Result<Option<StatusResponse>> {
+        let request = self
+            .request(Method::GET, "/status/wait")?
+            .query(&[("since", since), ("timeout", timeout.as_secs())]);
+        let response = Self::send(request).await?;
+        if response.status() == StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+        Ok(Some(response.json().await?))
+    }
+
+    /// GET /graph
+    pub async fn graph(&self, query: &GraphQuery) -> Result<GraphData> {
+        self.get("/graph", &query.params()).await
+    }
+
+    /// GET /graph/lite
+    pub async fn lite_graph(&self, top: Option<usize>) -> Result<LiteGraph> {
+        self.get("/graph/lite", &[("top", top)]).await
+    }
+
+    /// GET /graph/neighborhood
+    pub async fn neighborhood(&self, id: &RoamID, depth: u32) -> Result<GraphData> {
+        let query = [("id", id.id().to_string()), ("depth", depth.to_string())];
+        self.get("/graph/neighborhood", &query).await
+    }
+
+    /// GET /org: the node rendered to HTML with its links.
+    pub async fn org_html(&self, id: &RoamID) -> Result<OrgAsHTMLResponse> {
+        self.get("/org", &[("id", id.id())]).await
+    }
+
+    /// GET /org by title or alias.
+    pub async fn org_html_by_title(&self, title: &str) -> Result<OrgAsHTMLResponse> {
+        self.get("/org", &[("title", title)]).await
+    }
+
+    /// GET /node/outline
+    pub async fn outline(&self, id: &RoamID) -> Result<OutlineResponse> {
+        self.get("/node/outline", &[("id", id.id())]).await
+    }
+
+    /// GET /node/neighbors
+    pub async fn neighbors(&self, id: &RoamID) -> Result<NeighborsResponse> {
+        self.get("/node/neighbors", &[("id", id.id())]).await
+    }
+
+    /// GET /backlinks
+    pub async fn backlinks(&self, id: &RoamID) -> Result<BacklinksResponse> {
+        self.get("/backlinks", &[("id", id.id())]).await
+    }
+
+    /// GET /similar: nodes related to the node, best first.
+    pub async fn similar(&self, id: &RoamID, limit: usize) -> Result<SimilarResponse> {
+        let query = [("id", id.id().to_string()), ("limit", limit.to_string())];
+        self.get("/similar", &query).await
+    }
+
+    /// GET /tags
+    pub async fn tags(&self) -> Result<Vec<String>> {
+        self.fetch("/tags").await
+    }
+
+    /// GET /export/index.json
+    pub async fn index(&self) -> Result<VaultIndex> {
+        self.fetch("/export/index.json").await
+    }
+
+    /// GET /node/source: content of the file of the node and its hash.
+    pub async fn source(&self, id: &RoamID) -> Result<NodeSource> {
+        self.get("/node/source", &[("id", id.id())]).await
+    }
+
+    /// GET /node/source?scope=node: content of the subtree of the node and
+    /// the hash of its file.
+    pub async fn node_source(&self, id: &RoamID) -> Result<NodeSource> {
+        self.get("/node/source", &[("id", id.id()), ("scope", "node")])
+            .await
+    }
+
+    /// PUT /node/source: replace the file of the node. `hash` is the one of
+    /// the content the edit is based on, if the file changed since, the
+    /// edit fails with [`ApiError::Conflict`].
+    pub async fn edit_source(&self, id: &RoamID, content: &str, hash: &str) -> Result<NodeSource> {
+        let request = self
+            .request(Method::PUT, "/node/source")?
+            .query(&[("id", id.id())])
+            .json(&serde_json::json!({ "content": content, "hash": hash }));
+        Self::edit(request).await
+    }
+
+    /// PUT /org: replace only the subtree of the node, as returned by
+    /// [`ApiClient::node_source`]. Conflicts are reported like in
+    /// [`ApiClient::edit_source`].
+    pub async fn edit_node(&self, id: &RoamID, content: &str, hash: &str) -> Result<NodeSource> {
+        let request = self
+            .request(Method::PUT, "/org")?
+            .query(&[("id", id.id()), ("hash", hash)])
+            .body(content.to_string());
+        Self::edit(request).await
+    }
+
+    async fn edit(request: RequestBuilder) -> Result<NodeSource> {
+        let response = request.send().await?;
+        if response.status() == StatusCode::CONFLICT {
+            return Err(ApiError::Conflict(response.json().await?));
+        }
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(ApiError::Status { status, body });
+        }
+        Ok(response.json().await?)
+    }
+
+    /// GET /admin/connections: the websocket and server-sent events clients.
+    pub async fn connections(&self) -> Result<ConnectionsResponse> {
+        self.fetch("/admin/connections").await
+    }
+
+    /// Open a websocket, e.g. to search or to receive updates of the graph.
+    pub async fn websocket(&self) -> Result<WsClient> {
+        let mut url = self.url("/ws")?;
+        let scheme = match url.scheme() {
+            "https" => "wss",
+            _ => "ws",
+        };
+        url.set_scheme(scheme)
+            .map_err(|_| ApiError::InvalidUrl(url.to_string()))?;
+        let cookie = self.cookies.cookies(&self.base);
+        WsClient::connect(
+            url.as_str(),
+            cookie.as_ref().map(|cookie| cookie.as_bytes()),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url() {
+        let client = ApiClient::new("http://localhost:5000").unwrap();
+        assert_eq!(
+            client.url("/graph").unwrap().as_str(),
+            "http://localhost:5000/graph"
+        );
+        // Servers behind a reverse proxy keep their prefix.
+        let client = ApiClient::new("http://example.com/roam").unwrap();
+        assert_eq!(
+            client.url("/graph/lite").unwrap().as_str(),
+            "http://example.com/roam/graph/lite"
+        );
+        assert!(ApiClient::new("localhost").is_err());
+    }
+
+    #[test]
+    fn test_graph_query() {
+        let query = GraphQuery {
+            tags: vec!["a".to_string(), "b".to_string()],
+            summaries: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            query.params(),
+            vec![
+                ("tags", "a,b".to_string()),
+                ("summaries", "true".to_string())
+            ]
+        );
+        assert!(GraphQuery::default().params().is_empty());
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{client::IntoClientRequest, http::HeaderValue, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+
+use super::{ApiError, Result, SearchResultEntry, Topic, WebSocketMessage};
+
+/// Search results are streamed without an end marker. A search is over once
+/// no hit arrived for this long.
+const SEARCH_IDLE: Duration = Duration::from_millis(300);
+
+/// Websocket connection to `/ws`, see [`super::ApiClient::websocket`].
+pub struct WsClient {
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    /// Messages received while waiting for the reply to a request
+    buffered: VecDeque<WebSocketMessage>,
+    /// Whether the search providers were set up for this connection
+    search_ready: bool,
+    next_request: u64,
+}
+
+impl WsClient {
+    pub(super) async fn connect(url: &str, cookie: Option<&[u8]>) -> Result<Self> {
+        let mut request = url.into_client_request()?;
+        if let Some(cookie) = cookie.and_then(|cookie| HeaderValue::from_bytes(cookie).ok()) {
+            request.headers_mut().insert("cookie", cookie);
+        }
+        let (stream, _) = connect_async(request).await?;
+        Ok(Self {
+            stream,
+            buffered: VecDeque::new(),
+            search_ready: false,
+            next_request: 0,
+        })
+    }
+
+    pub async fn send(&mut self, message: &WebSocketMessage) -> Result<()> {
+        let text = serde_json::to_string(message)?;
+        self.stream.send(Message::Text(text)).await?;
+        Ok(())
+    }
+
+    /// Next message of the server. `None` once the connection is closed.
+    pub async fn next(&mut self) -> Result<Option<WebSocketMessage>> {
+        if let Some(message) = self.buffered.pop_front() {
+            return Ok(Some(message));
+        }
+        self.receive().await
+    }
+
+    async fn receive(&mut self) -> Result<Option<WebSocketMessage>> {
+        while let Some(message) = self.stream.next().await {
+            match message? {
+                Message::Text(text) => return Ok(Some(serde_json::from_str(&text)?)),
+                Message::Close(_) => return Ok(None),
+                // Pings are answered by tungstenite.
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    /// Only receive broadcasts of `topics`, see [`Topic`].
+    pub async fn subscribe(&mut self, topics: Vec<Topic>) -> Result<()> {
+        self.send(&WebSocketMessage::Subscribe { topics }).await
+    }
+
+    pub async fn unsubscribe(&mut self, topics: Vec<Topic>) -> Result<()> {
+        self.send(&WebSocketMessage::Unsubscribe { topics }).await
+    }
+
+    /// Search all providers for `query`. Broadcasts received meanwhile are
+    /// kept for [`WsClient::next`].
+    pub async fn search(&mut self, query: &str) -> Result<Vec<SearchResultEntry>> {
+        if !self.search_ready {
+            self.send(&WebSocketMessage::SearchConfigurationRequest)
+                .await?;
+            loop {
+                match self.receive().await?.ok_or(ApiError::Closed)? {
+                    WebSocketMessage::SearchConfigurationResponse { .. } => break,
+                    other => self.buffered.push_back(other),
+                }
+            }
+            self.search_ready = true;
+        }
+
+        self.next_request += 1;
+        let request_id = format!("client-api-{}", self.next_request);
+        self.send(&WebSocketMessage::SearchRequest {
+            query: query.to_string(),
+            request_id: request_id.clone(),
+            group_by_file: false,
+            prefix: false,
+        })
+        .await?;
+
+        let mut results = vec![];
+        while let Ok(message) = tokio::time::timeout(SEARCH_IDLE, self.receive()).await {
+            match message?.ok_or(ApiError::Closed)? {
+                WebSocketMessage::SearchResponse {
+                    request_id: id,
+                    results: hit,
+                } if id == request_id => results.push(hit),
+                // Late hits of an earlier search
+                WebSocketMessage::SearchResponse { .. } => {}
+                other => self.buffered.push_back(other),
+            }
+        }
+        Ok(results)
+    }
+
+    pub async fn close(mut self) -> Result<()> {
+        self.stream.close(None).await?;
+        Ok(())
+    }
+}
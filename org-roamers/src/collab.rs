@@ -0,0 +1,468 @@
+//! Collaborative editing of files over the websocket. Every file that is
+//! edited has one shared [`yrs`] document; clients exchange updates of it and
+//! the flusher writes the merged text back to the file.
+//!
+//! The protocol on the websocket:
+//! - `collab_join { id }` joins the document of the file of the node `id` and
+//!   is answered with `collab_sync { id, update }`, the whole document.
+//! - `collab_update { id, update }` changes the document. It is relayed to the
+//!   other clients of the document.
+//! - `collab_leave { id }` stops receiving updates.
+//!
+//! Updates are encoded as yrs v1 updates. Text offsets count UTF-16 code
+//! units, like Yjs in the browser.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use dashmap::DashMap;
+use tokio_util::sync::CancellationToken;
+use yrs::updates::decoder::Decode;
+use yrs::{
+    Doc, GetString, OffsetKind, Options, ReadTxn, StateVector, Text, TextRef, Transact, Update,
+};
+
+use crate::cache::content_hash;
+use crate::client::message::WebSocketMessage;
+use crate::server::{services::conflict_service, types::RoamID};
+use crate::{watcher, ServerState};
+
+/// How often edited documents are written to their file.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Shared document of a file.
+pub struct CollabDoc {
+    doc: Doc,
+    text: TextRef,
+    /// Hash of the file content the document was last synchronized with
+    base_hash: u64,
+    /// Connections editing the document, with the node they joined through
+    members: HashMap<u64, RoamID>,
+    /// Whether the document has edits that are not written to the file yet
+    dirty: bool,
+}
+
+impl CollabDoc {
+    pub fn new(content: &str) -> Self {
+        let doc = Doc::with_options(Options {
+            offset_kind: OffsetKind::Utf16,
+            ..Options::default()
+        });
+        let text = doc.get_or_insert_text("content");
+        text.insert(&mut doc.transact_mut(), 0, content);
+        Self {
+            doc,
+            text,
+            base_hash: content_hash(content),
+            members: HashMap::new(),
+            dirty: false,
+        }
+    }
+
+    pub fn content(&self) -> String {
+        self.text.get_string(&self.doc.transact())
+    }
+
+    /// The whole document as one update, sent to clients that join.
+    pub fn state(&self) -> Vec<u8> {
+        self.doc
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default())
+    }
+
+    /// Apply an update of a client.
+    pub fn apply(&mut self, update: &[u8]) -> anyhow::Result<()> {
+        let update = Update::decode_v1(update)?;
+        self.doc.transact_mut().apply_update(update)?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Replace the text with `content`, e.g. after the file changed on disk.
+    /// Returns the update that brings the clients up to date.
+    pub fn reload(&mut self, content: &str) -> Vec<u8> {
+        let mut txn = self.doc.transact_mut();
+        let before = txn.state_vector();
+        let len = self.text.len(&txn);
+        self.text.remove_range(&mut txn, 0, len);
+        self.text.insert(&mut txn, 0, content);
+        let update = txn.encode_state_as_update_v1(&before);
+        drop(txn);
+
+        self.base_hash = content_hash(content);
+        self.dirty = false;
+        update
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+}
+
+/// Open documents by the file they edit, relative to the root.
+#[derive(Default)]
+pub struct Collab {
+    docs: DashMap<PathBuf, CollabDoc>,
+}
+
+impl Collab {
+    /// Add `connection_id` to the document of `file`, which is opened with
+    /// `content` if no one edits it yet. Returns the whole document.
+    pub fn join(&self, connection_id: u64, id: RoamID, file: &Path, content: &str) -> Vec<u8> {
+        let mut doc = self
+            .docs
+            .entry(file.to_path_buf())
+            .or_insert_with(|| CollabDoc::new(content));
+        doc.members.insert(connection_id, id);
+        doc.state()
+    }
+
+    /// Apply an update of `connection_id` to the document of `file`. Returns
+    /// the other members, with the node they joined through.
+    pub fn apply(
+        &self,
+        connection_id: u64,
+        file: &Path,
+        update: &[u8],
+    ) -> anyhow::Result<Vec<(u64, RoamID)>> {
+        let Some(mut doc) = self.docs.get_mut(file) else {
+            anyhow::bail!("{} is not edited collaboratively", file.display());
+        };
+        if !doc.members.contains_key(&connection_id) {
+            anyhow::bail!("Connection {connection_id} did not join {}", file.display());
+        }
+        doc.apply(update)?;
+        Ok(others(&doc, connection_id))
+    }
+
+    /// Remove `connection_id` from the document of `file`, or from all
+    /// documents if `file` is `None`. Documents without members are closed
+    /// once their edits are written.
+    pub fn leave(&self, connection_id: u64, file: Option<&Path>) {
+        for mut doc in self.docs.iter_mut() {
+            if file.is_none_or(|file| doc.key() == file) {
+                doc.members.remove(&connection_id);
+            }
+        }
+        self.docs
+            .retain(|_, doc| !doc.members.is_empty() || doc.is_dirty());
+    }
+
+    /// Take the text of every document with unwritten edits, with the hash
+    /// of the file content the edits are based on. The documents count as
+    /// written from now on.
+    pub fn take_dirty(&self) -> Vec<(PathBuf, String, u64)> {
+        let mut dirty = Vec::new();
+        for mut doc in self.docs.iter_mut() {
+            if doc.dirty {
+                let content = doc.content();
+                let base_hash = std::mem::replace(&mut doc.base_hash, content_hash(&content));
+                doc.dirty = false;
+                dirty.push((doc.key().clone(), content, base_hash));
+            }
+        }
+        self.docs.retain(|_, doc| !doc.members.is_empty());
+        dirty
+    }
+
+    /// `file` was indexed with `content`. Documents are reloaded on other
+    /// changes than their own writes. Unwritten edits of a reloaded document
+    /// conflict with the change and are returned, so they are not lost.
+    pub fn file_changed(&self, file: &Path, content: &str) -> FileChange {
+        let Some(mut doc) = self.docs.get_mut(file) else {
+            return FileChange::default();
+        };
+        if doc.base_hash == content_hash(content) {
+            return FileChange::default();
+        }
+        let lost = doc.dirty.then(|| doc.content());
+        let update = doc.reload(content);
+        let updates = doc
+            .members
+            .iter()
+            .map(|(connection_id, id)| (*connection_id, id.clone(), update.clone()))
+            .collect();
+        FileChange { updates, lost }
+    }
+}
+
+/// Result of [`Collab::file_changed`].
+#[derive(Default)]
+pub struct FileChange {
+    /// Updates for the members of the document, with the node they joined
+    /// through
+    pub updates: Vec<(u64, RoamID, Vec<u8>)>,
+    /// Text of the document with the edits that conflicted with the change
+    pub lost: Option<String>,
+}
+
+fn others(doc: &CollabDoc, connection_id: u64) -> Vec<(u64, RoamID)> {
+    doc.members
+        .iter()
+        .filter(|(member, _)| **member != connection_id)
+        .map(|(member, id)| (*member, id.clone()))
+        .collect()
+}
+
+fn enabled(state: &ServerState) -> bool {
+    state.config.collab && !state.config.read_only
+}
+
+/// File of the node `id`, relative to the root.
+fn file_of(state: &ServerState, id: &RoamID) -> Option<PathBuf> {
+    state
+        .cache
+        .retrieve(id)
+        .map(|entry| entry.path().to_path_buf())
+}
+
+/// Handle `collab_join` of `connection_id`.
+pub fn join(state: &ServerState, connection_id: u64, id: &RoamID) {
+    if !enabled(state) {
+        tracing::warn!(
+            "Connection {connection_id} tried to join {} without collab enabled",
+            id.id()
+        );
+        return;
+    }
+    let Some(file) = file_of(state, id) else {
+        tracing::error!("Cannot join unknown node {}", id.id());
+        return;
+    };
+    let entry = match state.cache.entry(&file) {
+        Ok(entry) => entry,
+        Err(err) => {
+            tracing::error!("Failed to open {}: {err}", file.display());
+            return;
+        }
+    };
+    let update = state
+        .collab
+        .join(connection_id, id.clone(), &file, entry.content());
+    let message = WebSocketMessage::CollabSync {
+        id: id.clone(),
+        update,
+    };
+    state.send_to_websocket(connection_id, message);
+}
+
+/// Handle `collab_update` of `connection_id`.
+pub fn update(state: &ServerState, connection_id: u64, id: &RoamID, update: &[u8]) {
+    if !enabled(state) {
+        return;
+    }
+    let Some(file) = file_of(state, id) else {
+        tracing::error!("Cannot update unknown node {}", id.id());
+        return;
+    };
+    match state.collab.apply(connection_id, &file, update) {
+        Ok(members) => {
+            for (member, id) in members {
+                let update = update.to_vec();
+                state.send_to_websocket(member, WebSocketMessage::CollabUpdate { id, update });
+            }
+        }
+        Err(err) => tracing::error!("Rejected update of connection {connection_id}: {err}"),
+    }
+}
+
+/// Handle `collab_leave` of `connection_id`.
+pub fn leave(state: &ServerState, connection_id: u64, id: &RoamID) {
+    if let Some(file) = file_of(state, id) {
+        state.collab.leave(connection_id, Some(&file));
+    }
+}
+
+/// Send the updates of a file that was indexed to the clients editing it.
+/// Conflicting edits of the clients are kept in a conflict copy.
+pub fn file_changed(state: &ServerState, file: &Path, content: &str) {
+    let change = state.collab.file_changed(file, content);
+    if let Some(lost) = change.lost {
+        if let Err(err) = conflict_service::preserve(state, file, &lost) {
+            tracing::error!("Failed to keep the edits of {}: {err}", file.display());
+        }
+    }
+    for (member, id, update) in change.updates {
+        state.send_to_websocket(member, WebSocketMessage::CollabUpdate { id, update });
+    }
+}
+
+/// Write edited documents to their files every [`FLUSH_INTERVAL`].
+pub fn flusher(state: Arc<ServerState>, cancellation_token: CancellationToken) {
+    if !enabled(&state) {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            tokio::select! {
+                _ = cancellation_token.cancelled() => {
+                    flush(&state).await;
+                    tracing::info!("Collab flusher cancelled");
+                    break;
+                }
+                _ = interval.tick() => flush(&state).await,
+            }
+        }
+    });
+}
+
+/// Write all documents with unwritten edits and index them.
+pub async fn flush(state: &ServerState) {
+    let dirty = state.collab.take_dirty();
+    if dirty.is_empty() {
+        return;
+    }
+
+    for (file, content, base_hash) in &dirty {
+        let path = state.cache.resolve(file);
+        // The file changed after the edits began and the watcher did not
+        // index it yet
+        let on_disk = std::fs::read_to_string(&path).ok();
+        if on_disk.as_deref().map(content_hash) != Some(*base_hash) {
+            if let Err(err) = conflict_service::preserve(state, file, content) {
+                tracing::error!("Failed to keep the edits of {}: {err}", file.display());
+            }
+            if let Some(on_disk) = on_disk {
+                file_changed(state, file, &on_disk);
+            }
+            continue;
+        }
+        if let Err(err) = std::fs::write(&path, content) {
+            tracing::error!("Failed to write {}: {err}", file.display());
+            continue;
+        }
+        if let Err(err) = watcher::update_file(state, &path).await {
+            tracing::error!("Failed to index {}: {err}", file.display());
+        }
+    }
+
+    state.notify_vault_changed();
+    state.broadcast_to_websockets(WebSocketMessage::StatusUpdate {
+        files_changed: dirty.len(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Insert `text` at `index` in a replica of `state` and return the update.
+    fn edit(state: &[u8], index: u32, text: &str) -> Vec<u8> {
+        let replica = Doc::with_options(Options {
+            offset_kind: OffsetKind::Utf16,
+            ..Options::default()
+        });
+        let content = replica.get_or_insert_text("content");
+        let mut txn = replica.transact_mut();
+        txn.apply_update(Update::decode_v1(state).unwrap()).unwrap();
+        let before = txn.state_vector();
+        content.insert(&mut txn, index, text);
+        txn.encode_state_as_update_v1(&before)
+    }
+
+    #[test]
+    fn test_concurrent_edits_merge() {
+        let collab = Collab::default();
+        let file = Path::new("note.org");
+        let state = collab.join(1, "a".into(), file, "* Heading\n");
+        collab.join(2, "a".into(), file, "ignored");
+
+        let first = edit(&state, 0, "#+title: Note\n");
+        let second = edit(&state, 10, "Body\n");
+        assert_eq!(
+            collab.apply(1, file, &first).unwrap(),
+            vec![(2, "a".into())]
+        );
+        assert_eq!(
+            collab.apply(2, file, &second).unwrap(),
+            vec![(1, "a".into())]
+        );
+
+        let dirty = collab.take_dirty();
+        assert_eq!(
+            dirty,
+            vec![(
+                file.to_path_buf(),
+                "#+title: Note\n* Heading\nBody\n".into(),
+                content_hash("* Heading\n")
+            )]
+        );
+        assert!(collab.take_dirty().is_empty());
+        // The own write is not reloaded
+        let change = collab.file_changed(file, &dirty[0].1);
+        assert!(change.updates.is_empty() && change.lost.is_none());
+    }
+
+    #[test]
+    fn test_external_change_reloads() {
+        let collab = Collab::default();
+        let file = Path::new("note.org");
+        collab.join(1, "a".into(), file, "old\n");
+
+        let change = collab.file_changed(file, "new\n");
+        assert_eq!(change.updates.len(), 1);
+        assert_eq!(change.lost, None);
+        assert_eq!(collab.docs.get(file).unwrap().content(), "new\n");
+
+        collab.leave(1, None);
+        assert!(collab.docs.is_empty());
+    }
+
+    #[test]
+    fn test_external_change_conflicts_with_unwritten_edits() {
+        let collab = Collab::default();
+        let file = Path::new("note.org");
+        let state = collab.join(1, "a".into(), file, "old\n");
+        collab.apply(1, file, &edit(&state, 4, "shared\n")).unwrap();
+
+        let change = collab.file_changed(file, "external\n");
+        assert_eq!(change.lost.as_deref(), Some("old\nshared\n"));
+        assert_eq!(change.updates.len(), 1);
+        let doc = collab.docs.get(file).unwrap();
+        assert_eq!(doc.content(), "external\n");
+        assert!(!doc.is_dirty());
+    }
+
+    #[test]
+    fn test_update_requires_join() {
+        let collab = Collab::default();
+        let file = Path::new("note.org");
+        let state = collab.join(1, "a".into(), file, "text");
+        assert!(collab.apply(2, file, &edit(&state, 0, "x")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_closed_connection_leaves() {
+        use crate::client::ConnectionKind;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = crate::config::Config {
+            org_roamers_root: temp_dir.path().to_path_buf(),
+            database: crate::config::DatabaseConfig {
+                path: Some(temp_dir.path().join("roam.db")),
+            },
+            ..Default::default()
+        };
+        let state = ServerState::new(config).await.unwrap();
+
+        let (sender, receiver) = tokio::sync::mpsc::channel(4);
+        let connection =
+            state.register_websocket_connection(sender, ConnectionKind::WebSocket, None, None);
+        let file = Path::new("note.org");
+        state.collab.join(connection, "a".into(), file, "text");
+
+        // Broadcasting to a closed connection drops it from its documents
+        drop(receiver);
+        state.broadcast_to_websockets(WebSocketMessage::StatusUpdate { files_changed: 1 });
+        state.flush_broadcasts();
+
+        assert!(!state.websocket_connections.contains_key(&connection));
+        assert!(state.collab.docs.get(file).is_none());
+    }
+}
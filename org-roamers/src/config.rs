@@ -1,5 +1,9 @@
-use std::path::PathBuf;
+use std::{
+    collections::BTreeMap,
+    path::{Component, Path, PathBuf},
+};
 
+use rust_stemmers::Algorithm;
 use serde::{Deserialize, Serialize};
 
 pub const DEFAULT_CONFIG: &str = include_str!("../../conf.json");
@@ -7,19 +11,97 @@ pub const ENV_VAR_NAME: &str = "ROAMERS_DIR";
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct HttpServerConfig {
-    pub host: String,
+    pub host: Host,
     pub port: u16,
+    /// Accept HTTP/2 without TLS (h2c) next to HTTP/1.1.
+    #[serde(default = "default_true")]
+    pub http2: bool,
+    /// Interval of HTTP/2 keep-alive pings. `0` disables keep-alive, also
+    /// for HTTP/1.1.
+    #[serde(default = "default_keep_alive_secs")]
+    pub keep_alive_secs: u64,
+    /// Streams a single HTTP/2 connection may open at the same time.
+    #[serde(default = "default_max_concurrent_streams")]
+    pub max_concurrent_streams: u32,
+    /// Requests handled at the same time over all connections. `0` means
+    /// no limit. Websockets, event streams and other requests that a client
+    /// keeps open are not counted.
+    #[serde(default)]
+    pub max_concurrent_requests: usize,
+}
+
+fn default_keep_alive_secs() -> u64 {
+    60
+}
+
+fn default_max_concurrent_streams() -> u32 {
+    256
 }
 
 impl Default for HttpServerConfig {
     fn default() -> Self {
         Self {
-            host: "localhost".to_string(),
+            host: "localhost".into(),
             port: 5000,
+            http2: true,
+            keep_alive_secs: default_keep_alive_secs(),
+            max_concurrent_streams: default_max_concurrent_streams(),
+            max_concurrent_requests: 0,
+        }
+    }
+}
+
+impl HttpServerConfig {
+    /// Addresses to bind a listener to, one per host.
+    pub fn bind_addresses(&self) -> Vec<String> {
+        self.host
+            .hosts()
+            .iter()
+            .map(|host| host_port(host, self.port))
+            .collect()
+    }
+}
+
+/// One or several hosts the server listens on, e.g. `"localhost"` or
+/// `["127.0.0.1", "[::1]"]`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Host {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl Host {
+    pub fn hosts(&self) -> &[String] {
+        match self {
+            Host::Single(host) => std::slice::from_ref(host),
+            Host::Multiple(hosts) => hosts,
         }
     }
 }
 
+impl From<&str> for Host {
+    fn from(host: &str) -> Self {
+        Host::Single(host.to_string())
+    }
+}
+
+impl From<String> for Host {
+    fn from(host: String) -> Self {
+        Host::Single(host)
+    }
+}
+
+/// Join `host` and `port`, wrapping IPv6 literals in brackets.
+pub fn host_port(host: &str, port: u16) -> String {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.contains(':') {
+        format!("[{host}]:{port}")
+    } else {
+        format!("{host}:{port}")
+    }
+}
+
 #[derive(Serialize, Deserialize, Default, Clone)]
 pub struct EnvAdvice {
     pub on: String,
@@ -28,10 +110,111 @@ pub struct EnvAdvice {
     pub text_styling: String,
 }
 
-#[derive(Serialize, Deserialize, Default, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct HtmlExportSettings {
     pub respect_noexport: bool,
     pub env_advices: Vec<EnvAdvice>,
+    /// Prepended to every CSS class in the exported HTML, e.g. `roam-` to
+    /// avoid collisions with the stylesheet of an embedding page.
+    #[serde(default)]
+    pub class_prefix: String,
+    /// Wrap the document in a `<div>` and each section in a `<section>`.
+    #[serde(default = "default_true")]
+    pub wrappers: bool,
+    /// Replace org entities like `\alpha` with their HTML counterpart. If
+    /// unset, entities are exported as written.
+    #[serde(default = "default_true")]
+    pub entities: bool,
+    /// Replace `---`, `--`, `...` and straight quotes with their typographic
+    /// counterparts, like `org-export-with-smart-quotes`.
+    #[serde(default)]
+    pub smart_punctuation: bool,
+    /// Export `\nbsp` and `\ ` as `&nbsp;`. If unset, they become plain
+    /// spaces.
+    #[serde(default = "default_true")]
+    pub non_breaking_spaces: bool,
+    /// Export inline tasks as a boxed block. If unset, they are dropped like
+    /// with `org-export-with-inlinetasks` set to `nil`.
+    #[serde(default = "default_true")]
+    pub inlinetasks: bool,
+    /// Rules for link types the exporter does not know, like `roam:` or
+    /// `denote:` links. The first rule of the type of a link applies.
+    #[serde(default)]
+    pub link_rewrites: Vec<LinkRewrite>,
+}
+
+impl Default for HtmlExportSettings {
+    fn default() -> Self {
+        Self {
+            respect_noexport: false,
+            env_advices: vec![],
+            class_prefix: String::new(),
+            wrappers: true,
+            entities: true,
+            smart_punctuation: false,
+            non_breaking_spaces: true,
+            inlinetasks: true,
+            link_rewrites: vec![],
+        }
+    }
+}
+
+impl HtmlExportSettings {
+    /// `name` with the configured class prefix.
+    pub fn class(&self, name: &str) -> String {
+        format!("{}{}", self.class_prefix, name)
+    }
+
+    /// Whether a rewrite rule resolves links by title, which requires the
+    /// titles of all nodes.
+    pub fn rewrites_titles(&self) -> bool {
+        self.link_rewrites
+            .iter()
+            .any(|rewrite| rewrite.kind == LinkRewriteKind::Title)
+    }
+}
+
+/// Rewrite of the links of one type, see
+/// [`HtmlExportSettings::link_rewrites`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LinkRewrite {
+    /// Link type without the colon, e.g. `denote` for `[[denote:...]]`.
+    #[serde(rename = "type")]
+    pub link_type: String,
+    /// What the link is rewritten to. `{path}` is replaced by the link
+    /// without its type.
+    #[serde(default = "default_rewrite_target")]
+    pub target: String,
+    #[serde(default)]
+    pub kind: LinkRewriteKind,
+}
+
+fn default_rewrite_target() -> String {
+    "{path}".to_string()
+}
+
+impl LinkRewrite {
+    /// Target of the link `path` if the rule applies to its type.
+    pub fn apply(&self, path: &str) -> Option<String> {
+        let (link_type, rest) = path.split_once(':')?;
+        (link_type == self.link_type).then(|| self.target.replace("{path}", rest))
+    }
+}
+
+/// How the target of a [`LinkRewrite`] is interpreted.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum LinkRewriteKind {
+    /// An url the link points to
+    #[default]
+    Url,
+    /// The id of a node
+    Id,
+    /// The title or an alias of a node
+    Title,
+}
+
+fn default_true() -> bool {
+    true
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -40,6 +223,22 @@ pub struct LatexConfig {
     pub latex_opt: Vec<String>,
     pub dvisvgm_cmd: String,
     pub dvisvgm_opt: Vec<String>,
+    /// Lines added to the preamble of every fragment, after the
+    /// `#+latex_header` lines of its file.
+    #[serde(default)]
+    pub preamble: Vec<String>,
+    /// Directory rendered fragments are cached in. `org-roamers` in the
+    /// temporary directory of the system if unset.
+    #[serde(default)]
+    pub cache_dir: Option<PathBuf>,
+    /// Bytes the cached SVGs may take up before the least recently used ones
+    /// are removed. `0` disables the limit.
+    #[serde(default = "default_latex_cache_size")]
+    pub cache_size: u64,
+}
+
+fn default_latex_cache_size() -> u64 {
+    64 * 1024 * 1024
 }
 
 impl Default for LatexConfig {
@@ -57,10 +256,395 @@ impl Default for LatexConfig {
                 "--precision=6".into(),
                 "--verbosity=0".into(),
             ],
+            preamble: vec![],
+            cache_dir: None,
+            cache_size: default_latex_cache_size(),
+        }
+    }
+}
+
+/// Settings applied when building the graph.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct GraphConfig {
+    /// Nodes carrying any of these tags never appear in the graph, but are
+    /// still indexed.
+    #[serde(default)]
+    pub exclude_tags: Vec<String>,
+}
+
+/// Settings applied to search results.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SearchConfig {
+    /// Nodes carrying any of these tags are never returned by a search, but
+    /// are still indexed.
+    #[serde(default)]
+    pub exclude_tags: Vec<String>,
+    /// Language of the Snowball stemmer used by the full text search, e.g.
+    /// `"English"` (Porter2). Words then match their inflections, "linking"
+    /// finds "links". Full text search matches whole words if unset. Prefix
+    /// searches are never stemmed.
+    #[serde(default)]
+    pub stemming: Option<Algorithm>,
+    /// Milliseconds a prefix search waits for the next keystroke before it
+    /// runs. Superseded queries are dropped. `0` runs every query.
+    #[serde(default = "default_search_debounce_ms")]
+    pub debounce_ms: u64,
+}
+
+fn default_search_debounce_ms() -> u64 {
+    150
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            exclude_tags: Vec::new(),
+            stemming: None,
+            debounce_ms: default_search_debounce_ms(),
+        }
+    }
+}
+
+/// Settings of the background job that looks for broken links.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LinkCheckConfig {
+    /// Minutes between two checks. `0` disables the checker.
+    #[serde(default = "default_link_check_interval")]
+    pub interval_minutes: u64,
+    /// Also request every `http` and `https` link and report failing ones.
+    #[serde(default)]
+    pub check_external: bool,
+    /// Timeout of a single request to an external link.
+    #[serde(default = "default_link_check_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_link_check_interval() -> u64 {
+    60
+}
+
+fn default_link_check_timeout() -> u64 {
+    10
+}
+
+impl Default for LinkCheckConfig {
+    fn default() -> Self {
+        Self {
+            interval_minutes: default_link_check_interval(),
+            check_external: false,
+            timeout_secs: default_link_check_timeout(),
+        }
+    }
+}
+
+/// Headers added to all HTML responses. `null` omits a header.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SecurityHeadersConfig {
+    #[serde(default = "default_content_security_policy")]
+    pub content_security_policy: Option<String>,
+    #[serde(default = "default_frame_options")]
+    pub x_frame_options: Option<String>,
+    #[serde(default = "default_referrer_policy")]
+    pub referrer_policy: Option<String>,
+}
+
+/// Allows everything the bundled web UI needs: inline styles of exported
+/// blocks, images from data urls and the websocket connection.
+fn default_content_security_policy() -> Option<String> {
+    Some(
+        concat!(
+            "default-src 'self'; script-src 'self'; style-src 'self' 'unsafe-inline'; ",
+            "img-src 'self' data: blob:; font-src 'self' data:; connect-src 'self' ws: wss:; ",
+            "object-src 'none'; base-uri 'self'; frame-ancestors 'self'"
+        )
+        .to_string(),
+    )
+}
+
+fn default_frame_options() -> Option<String> {
+    Some("SAMEORIGIN".to_string())
+}
+
+fn default_referrer_policy() -> Option<String> {
+    Some("strict-origin-when-cross-origin".to_string())
+}
+
+impl Default for SecurityHeadersConfig {
+    fn default() -> Self {
+        Self {
+            content_security_policy: default_content_security_policy(),
+            x_frame_options: default_frame_options(),
+            referrer_policy: default_referrer_policy(),
+        }
+    }
+}
+
+/// Advertisement of the server on the local network.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MdnsConfig {
+    /// Advertise the server as `_org-roamers._tcp`.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Instance name shown to browsing devices.
+    #[serde(default = "default_mdns_name")]
+    pub name: String,
+}
+
+fn default_mdns_name() -> String {
+    "org-roamers".to_string()
+}
+
+impl Default for MdnsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            name: default_mdns_name(),
+        }
+    }
+}
+
+/// Tuning of the file system watcher enabled by `fs_watcher`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WatcherConfig {
+    /// Only watch the root and the directories directly below it, which
+    /// needs far fewer inotify watches on huge vaults. Deeper directories are
+    /// picked up by periodic rescans.
+    #[serde(default)]
+    pub top_level_only: bool,
+    /// Interval of the rescans in `top_level_only` mode.
+    #[serde(default = "default_rescan_minutes")]
+    pub rescan_minutes: u64,
+}
+
+fn default_rescan_minutes() -> u64 {
+    10
+}
+
+impl Default for WatcherConfig {
+    fn default() -> Self {
+        Self {
+            top_level_only: false,
+            rescan_minutes: default_rescan_minutes(),
+        }
+    }
+}
+
+/// A directory indexed in addition to `org_roamers_root`, e.g. a separate
+/// dailies folder or another repository.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RootConfig {
+    pub path: PathBuf,
+    /// Relative path the files of the root appear under in the index, e.g.
+    /// `work` for `work/notes.org`. It shadows a directory of the same name
+    /// in `org_roamers_root`.
+    pub mount: PathBuf,
+    /// Same as `watcher.top_level_only`, for this root only.
+    #[serde(default)]
+    pub top_level_only: bool,
+}
+
+impl RootConfig {
+    /// Mounts have to be relative paths without `..`.
+    pub fn is_valid(&self) -> bool {
+        is_relative_subpath(&self.mount)
+    }
+}
+
+/// Whether `path` is a non-empty relative path without `..`, i.e. it stays
+/// below the directory it is joined to.
+fn is_relative_subpath(path: &Path) -> bool {
+    !path.as_os_str().is_empty()
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// Deleted files are moved to the trash instead of being removed.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TrashConfig {
+    /// Directory relative to `org_roamers_root` that holds deleted files.
+    #[serde(default = "default_trash_dir")]
+    pub dir: PathBuf,
+    /// Days after which deleted files are removed for good. `0` keeps them
+    /// forever.
+    #[serde(default = "default_trash_retention_days")]
+    pub retention_days: u32,
+}
+
+fn default_trash_dir() -> PathBuf {
+    ".trash".into()
+}
+
+fn default_trash_retention_days() -> u32 {
+    30
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self {
+            dir: default_trash_dir(),
+            retention_days: default_trash_retention_days(),
+        }
+    }
+}
+
+/// Where the index is stored.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct DatabaseConfig {
+    /// SQLite file the index and the user state (preferences, pins,
+    /// history, ...) are stored in, created if missing. Only changed files
+    /// are parsed on startup. If unset, the database is kept in memory: the
+    /// index is rebuilt on every start and the user state is lost.
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+}
+
+/// Columns of the `/board` endpoint.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BoardConfig {
+    /// TODO keywords in column order. Keywords found in the vault but not
+    /// listed here are appended in alphabetical order.
+    #[serde(default = "default_board_columns")]
+    pub columns: Vec<String>,
+}
+
+fn default_board_columns() -> Vec<String> {
+    vec!["TODO".to_string(), "DONE".to_string()]
+}
+
+impl Default for BoardConfig {
+    fn default() -> Self {
+        Self {
+            columns: default_board_columns(),
+        }
+    }
+}
+
+/// Tasks listed by the `/agenda` endpoint.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AgendaConfig {
+    /// Keywords of finished tasks, which are left out.
+    #[serde(default = "default_agenda_done_keywords")]
+    pub done_keywords: Vec<String>,
+    /// Days shown if no end date is requested.
+    #[serde(default = "default_agenda_days")]
+    pub days: u32,
+}
+
+fn default_agenda_done_keywords() -> Vec<String> {
+    vec!["DONE".to_string()]
+}
+
+fn default_agenda_days() -> u32 {
+    7
+}
+
+impl Default for AgendaConfig {
+    fn default() -> Self {
+        Self {
+            done_keywords: default_agenda_done_keywords(),
+            days: default_agenda_days(),
+        }
+    }
+}
+
+/// Archiving of nodes that were not touched for a long time.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ArchiveConfig {
+    /// Directory relative to `org_roamers_root` that holds the archives.
+    /// Archived content is stored in `<file>_archive` files like
+    /// `org-archive-subtree` does, which are not indexed.
+    #[serde(default = "default_archive_dir")]
+    pub dir: PathBuf,
+    /// Months without changes after which nodes are archived.
+    #[serde(default = "default_archive_months")]
+    pub months: u32,
+}
+
+fn default_archive_dir() -> PathBuf {
+    "archive".into()
+}
+
+fn default_archive_months() -> u32 {
+    12
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            dir: default_archive_dir(),
+            months: default_archive_months(),
+        }
+    }
+}
+
+/// Rules checked per node by `/diagnostics/lint`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LintConfig {
+    /// Report nodes without tags, including inherited ones.
+    #[serde(default = "default_true")]
+    pub missing_tags: bool,
+    /// Report titles longer than this many characters. `0` disables the rule.
+    #[serde(default = "default_lint_max_title_length")]
+    pub max_title_length: usize,
+    /// Report nodes without backlinks whose file is older than this many
+    /// days. `0` disables the rule.
+    #[serde(default = "default_lint_orphan_days")]
+    pub orphan_days: u32,
+    /// Report nodes with a TODO keyword but no deadline.
+    #[serde(default = "default_true")]
+    pub todo_without_deadline: bool,
+}
+
+fn default_lint_max_title_length() -> usize {
+    80
+}
+
+fn default_lint_orphan_days() -> u32 {
+    30
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            missing_tags: true,
+            max_title_length: default_lint_max_title_length(),
+            orphan_days: default_lint_orphan_days(),
+            todo_without_deadline: true,
         }
     }
 }
 
+/// Spaced repetition of notes with the `/review` endpoints.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ReviewConfig {
+    /// Nodes carrying any of these tags are reviewed.
+    #[serde(default = "default_review_tags")]
+    pub tags: Vec<String>,
+}
+
+fn default_review_tags() -> Vec<String> {
+    vec!["srs".to_string()]
+}
+
+impl Default for ReviewConfig {
+    fn default() -> Self {
+        Self {
+            tags: default_review_tags(),
+        }
+    }
+}
+
+/// Check whether any of `tags` is contained in `exclude_tags`. Tags are
+/// compared case insensitively.
+pub fn is_excluded<S: AsRef<str>>(exclude_tags: &[String], tags: &[S]) -> bool {
+    tags.iter().any(|tag| {
+        exclude_tags
+            .iter()
+            .any(|excluded| excluded.eq_ignore_ascii_case(tag.as_ref()))
+    })
+}
+
 #[derive(Serialize, Deserialize, Clone, Default, Copy)]
 pub enum AssetPolicy {
     AllowAll,
@@ -69,6 +653,59 @@ pub enum AssetPolicy {
     AllowChildrenOfRoot,
 }
 
+/// Where captured nodes are stored. Paths are relative to
+/// `org_roamers_root`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum CaptureTarget {
+    /// Each capture creates a new file. The pattern supports the placeholders
+    /// `%Y`, `%m`, `%d`, `%H`, `%M`, `%S` and `${slug}`.
+    NewFile { pattern: String },
+    /// Append a headline to the end of a file.
+    File { file: PathBuf },
+    /// Append a headline below the heading of the current day in a datetree
+    /// like `org-capture` does.
+    Datetree { file: PathBuf },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CaptureTemplate {
+    /// Name used to select the template.
+    pub name: String,
+    pub target: CaptureTarget,
+    /// Prepended to the title of captured headlines, e.g. `"TODO "`.
+    #[serde(default)]
+    pub headline_prefix: String,
+    /// Tags that are added to every captured node.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Additional properties that are added to the property drawer.
+    #[serde(default)]
+    pub properties: BTreeMap<String, String>,
+}
+
+fn default_capture_templates() -> Vec<CaptureTemplate> {
+    vec![
+        CaptureTemplate {
+            name: "default".to_string(),
+            target: CaptureTarget::NewFile {
+                pattern: "%Y%m%d%H%M%S-${slug}.org".to_string(),
+            },
+            headline_prefix: String::new(),
+            tags: vec![],
+            properties: BTreeMap::new(),
+        },
+        CaptureTemplate {
+            name: "daily".to_string(),
+            target: CaptureTarget::NewFile {
+                pattern: "daily/%Y-%m-%d.org".to_string(),
+            },
+            headline_prefix: String::new(),
+            tags: vec![],
+            properties: BTreeMap::new(),
+        },
+    ]
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct AuthConfig {
     /// Enable authentication system
@@ -78,6 +715,11 @@ pub struct AuthConfig {
     /// WARNING: Ensure config file has restricted permissions (chmod 600)
     pub users: Vec<User>,
 
+    /// Users allowed to use the `/admin` endpoints, e.g. the audit log.
+    /// Without authentication every client may use them.
+    #[serde(default)]
+    pub admins: Vec<String>,
+
     /// Session configuration
     #[serde(default)]
     pub session: SessionConfig,
@@ -106,6 +748,20 @@ pub struct SessionConfig {
 
     /// Interval in minutes to run cleanup of expired sessions
     pub cleanup_interval_minutes: u64,
+
+    /// SQLite file the sessions are stored in, created if missing. Sessions
+    /// are kept in memory and lost on restart if unset.
+    #[serde(default)]
+    pub database: Option<PathBuf>,
+
+    /// Lifetime in days of sessions created with "remember me". The lifetime
+    /// restarts on every refresh. `0` disables "remember me".
+    #[serde(default = "default_remember_me_days")]
+    pub remember_me_days: u64,
+}
+
+fn default_remember_me_days() -> u64 {
+    30
 }
 
 impl Default for SessionConfig {
@@ -115,6 +771,8 @@ impl Default for SessionConfig {
             expiry_duration_hours: 24,
             secure_cookie: bool::default(),
             cleanup_interval_minutes: 60,
+            database: None,
+            remember_me_days: default_remember_me_days(),
         }
     }
 }
@@ -134,6 +792,7 @@ impl Default for AuthConfig {
         Self {
             enabled: false,
             users: Vec::new(),
+            admins: Vec::new(),
             session: SessionConfig::default(),
         }
     }
@@ -143,6 +802,20 @@ impl Default for AuthConfig {
 pub struct Config {
     /// Path to the root of the org-roamers / org-roam directory.
     pub org_roamers_root: PathBuf,
+    /// Directories outside of `org_roamers_root` whose files are indexed
+    /// and watched as well.
+    #[serde(default)]
+    pub extra_roots: Vec<RootConfig>,
+    /// Index and watch symlinked directories, and junctions on Windows, e.g.
+    /// folders synced by Syncthing or Dropbox and linked into the root.
+    /// Images and other assets are served through these links as well.
+    #[serde(default = "default_follow_symlinks")]
+    pub follow_symlinks: bool,
+    /// Files larger than this many bytes are skipped with a warning in
+    /// `/diagnostics/files`, e.g. logs dropped into the vault. `0` disables
+    /// the limit.
+    #[serde(default = "default_max_file_size")]
+    pub max_file_size: u64,
     /// Settings that configure the webserver.
     pub http_server_config: HttpServerConfig,
     /// HTML settings when exporting org environments to HTML.
@@ -151,6 +824,20 @@ pub struct Config {
     pub root: PathBuf,
     /// Use the filesystem watcher
     pub fs_watcher: bool,
+    #[serde(default)]
+    pub watcher: WatcherConfig,
+    /// Only mount routes that do not modify the vault or stored state, e.g.
+    /// for a public mirror.
+    #[serde(default)]
+    pub read_only: bool,
+    /// Let websocket clients edit files together through a shared CRDT
+    /// document. Ignored in `read_only` mode.
+    #[serde(default)]
+    pub collab: bool,
+    /// Local time (`HH:MM`) at which the whole vault is reindexed every day,
+    /// as a safety net for missed file system events.
+    #[serde(default)]
+    pub reindex_schedule: Option<String>,
     /// LaTeX settings for rendering fragments
     pub latex_config: LatexConfig,
     /// Settings on asset loading restrictions
@@ -158,19 +845,129 @@ pub struct Config {
     /// Authentication configuration (optional - defaults to disabled)
     #[serde(default)]
     pub authentication: Option<AuthConfig>,
+    /// Directory relative to `org_roamers_root` where uploaded files are stored.
+    #[serde(default = "default_attachment_dir")]
+    pub attachment_dir: PathBuf,
+    /// Templates used by the capture and daily note endpoints.
+    #[serde(default = "default_capture_templates")]
+    pub capture_templates: Vec<CaptureTemplate>,
+    /// Graphs with more nodes than this are aggregated per file. `0` disables
+    /// the aggregation.
+    #[serde(default = "default_graph_lod_threshold")]
+    pub graph_lod_threshold: usize,
+    #[serde(default)]
+    pub graph: GraphConfig,
+    #[serde(default)]
+    pub search: SearchConfig,
+    #[serde(default)]
+    pub link_check: LinkCheckConfig,
+    #[serde(default)]
+    pub mdns: MdnsConfig,
+    #[serde(default)]
+    pub security_headers: SecurityHeadersConfig,
+    #[serde(default)]
+    pub trash: TrashConfig,
+    #[serde(default)]
+    pub board: BoardConfig,
+    #[serde(default)]
+    pub agenda: AgendaConfig,
+    #[serde(default)]
+    pub review: ReviewConfig,
+    #[serde(default)]
+    pub lint: LintConfig,
+    #[serde(default)]
+    pub archive: ArchiveConfig,
+    #[serde(default)]
+    pub database: DatabaseConfig,
+    /// org-roam database (schema version 20) that is imported into an empty
+    /// index on startup. Only files changed since org-roam indexed them are
+    /// parsed.
+    #[serde(default)]
+    pub org_roam_db_import: Option<PathBuf>,
+}
+
+fn default_follow_symlinks() -> bool {
+    true
+}
+
+fn default_max_file_size() -> u64 {
+    16 * 1024 * 1024
+}
+
+fn default_attachment_dir() -> PathBuf {
+    "attachments".into()
+}
+
+fn default_graph_lod_threshold() -> usize {
+    5000
+}
+
+impl Config {
+    /// The attachment directory has to be a relative path without `..`.
+    pub fn attachment_dir_is_valid(&self) -> bool {
+        is_relative_subpath(&self.attachment_dir)
+    }
+
+    pub fn capture_template(&self, name: &str) -> Option<&CaptureTemplate> {
+        self.capture_templates
+            .iter()
+            .find(|template| template.name == name)
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             org_roamers_root: "~/notes/".into(),
+            extra_roots: Vec::new(),
+            follow_symlinks: default_follow_symlinks(),
+            max_file_size: default_max_file_size(),
             http_server_config: HttpServerConfig::default(),
             org_to_html: HtmlExportSettings::default(),
             root: "./web/dist/".into(),
             fs_watcher: false,
+            watcher: WatcherConfig::default(),
+            read_only: false,
+            collab: false,
+            reindex_schedule: None,
             latex_config: LatexConfig::default(),
             asset_policy: AssetPolicy::default(),
             authentication: None,
+            attachment_dir: default_attachment_dir(),
+            capture_templates: default_capture_templates(),
+            graph_lod_threshold: default_graph_lod_threshold(),
+            graph: GraphConfig::default(),
+            search: SearchConfig::default(),
+            link_check: LinkCheckConfig::default(),
+            mdns: MdnsConfig::default(),
+            security_headers: SecurityHeadersConfig::default(),
+            trash: TrashConfig::default(),
+            board: BoardConfig::default(),
+            agenda: AgendaConfig::default(),
+            review: ReviewConfig::default(),
+            lint: LintConfig::default(),
+            archive: ArchiveConfig::default(),
+            database: DatabaseConfig::default(),
+            org_roam_db_import: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attachment_dir_is_valid() {
+        let with_dir = |dir: &str| Config {
+            attachment_dir: dir.into(),
+            ..Default::default()
+        };
+        assert!(with_dir("attachments").attachment_dir_is_valid());
+        assert!(with_dir("assets/uploads").attachment_dir_is_valid());
+        assert!(!with_dir("").attachment_dir_is_valid());
+        assert!(!with_dir("/tmp/uploads").attachment_dir_is_valid());
+        assert!(!with_dir("../uploads").attachment_dir_is_valid());
+        assert!(!with_dir("assets/../../uploads").attachment_dir_is_valid());
+    }
+}
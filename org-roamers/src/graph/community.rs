@@ -0,0 +1,258 @@
+//! Community detection with the Louvain method. Links are treated as
+//! undirected edges of weight one, so a pair of nodes linking each other is
+//! connected twice as strongly.
+
+use std::collections::{BTreeMap, HashMap};
+
+use super::{GraphData, RoamID};
+
+/// Rounds of local moving per level. Louvain usually converges within a few.
+const MAX_ROUNDS: usize = 100;
+
+/// Assign every node of `graph` to a community. Communities are numbered
+/// from `0` by decreasing size, ties are ordered by their smallest node id.
+/// The result does not depend on the order of nodes and links.
+pub fn louvain(graph: &GraphData) -> BTreeMap<RoamID, usize> {
+    let mut ids: Vec<&RoamID> = graph.nodes.iter().map(|node| &node.id).collect();
+    ids.sort();
+    ids.dedup();
+    let index: HashMap<&RoamID, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+
+    let mut adjacency: Vec<BTreeMap<usize, f64>> = vec![BTreeMap::new(); ids.len()];
+    for link in &graph.links {
+        let (Some(&from), Some(&to)) = (index.get(&link.from), index.get(&link.to)) else {
+            continue;
+        };
+        if from == to {
+            continue;
+        }
+        *adjacency[from].entry(to).or_default() += 1.0;
+        *adjacency[to].entry(from).or_default() += 1.0;
+    }
+
+    // Community of every original node, refined level by level.
+    let mut membership: Vec<usize> = (0..ids.len()).collect();
+    loop {
+        let (communities, moved) = local_moving(&adjacency);
+        if !moved {
+            break;
+        }
+        for community in membership.iter_mut() {
+            *community = communities[*community];
+        }
+        adjacency = aggregate(&adjacency, &communities);
+    }
+
+    let mut sizes: BTreeMap<usize, (usize, usize)> = BTreeMap::new();
+    for (node, community) in membership.iter().enumerate() {
+        let entry = sizes.entry(*community).or_insert((0, node));
+        entry.0 += 1;
+    }
+    let mut order: Vec<(usize, (usize, usize))> = sizes.into_iter().collect();
+    order.sort_by_key(|(_, (size, first))| (std::cmp::Reverse(*size), *first));
+    let numbers: HashMap<usize, usize> = order
+        .iter()
+        .enumerate()
+        .map(|(number, (community, _))| (*community, number))
+        .collect();
+
+    ids.into_iter()
+        .zip(membership)
+        .map(|(id, community)| (id.clone(), numbers[&community]))
+        .collect()
+}
+
+/// Move nodes to the neighboring community with the largest modularity gain
+/// until no move improves it. Returns the community of every node,
+/// numbered from `0`, and whether any node moved.
+fn local_moving(adjacency: &[BTreeMap<usize, f64>]) -> (Vec<usize>, bool) {
+    let degrees: Vec<f64> = adjacency.iter().map(|edges| edges.values().sum()).collect();
+    let total: f64 = degrees.iter().sum();
+    let mut community: Vec<usize> = (0..adjacency.len()).collect();
+    if total == 0.0 {
+        return (community, false);
+    }
+    let mut totals = degrees.clone();
+
+    let mut moved = false;
+    for _ in 0..MAX_ROUNDS {
+        let mut improved = false;
+        for node in 0..adjacency.len() {
+            let current = community[node];
+            let degree = degrees[node];
+            totals[current] -= degree;
+
+            let mut weights: BTreeMap<usize, f64> = BTreeMap::new();
+            for (&neighbor, &weight) in &adjacency[node] {
+                if neighbor != node {
+                    *weights.entry(community[neighbor]).or_default() += weight;
+                }
+            }
+            let gain = |c: usize, weight: f64| weight - totals[c] * degree / total;
+            let mut best = current;
+            let mut best_gain = gain(current, weights.get(&current).copied().unwrap_or(0.0));
+            for (&candidate, &weight) in &weights {
+                let candidate_gain = gain(candidate, weight);
+                if candidate_gain > best_gain + f64::EPSILON {
+                    best = candidate;
+                    best_gain = candidate_gain;
+                }
+            }
+
+            totals[best] += degree;
+            if best != current {
+                community[node] = best;
+                improved = true;
+            }
+        }
+        if !improved {
+            break;
+        }
+        moved = true;
+    }
+
+    let mut numbers: HashMap<usize, usize> = HashMap::new();
+    for c in community.iter_mut() {
+        let next = numbers.len();
+        *c = *numbers.entry(*c).or_insert(next);
+    }
+    (community, moved)
+}
+
+/// Graph of the communities. Edges within a community become a self loop.
+fn aggregate(adjacency: &[BTreeMap<usize, f64>], community: &[usize]) -> Vec<BTreeMap<usize, f64>> {
+    let count = community.iter().max().map_or(0, |max| max + 1);
+    let mut aggregated = vec![BTreeMap::new(); count];
+    for (node, edges) in adjacency.iter().enumerate() {
+        for (&neighbor, &weight) in edges {
+            *aggregated[community[node]]
+                .entry(community[neighbor])
+                .or_default() += weight;
+        }
+    }
+    aggregated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{NodeKind, RoamLink, RoamNode};
+
+    fn graph(nodes: &[&str], links: &[(&str, &str)]) -> GraphData {
+        GraphData {
+            nodes: nodes
+                .iter()
+                .map(|id| RoamNode {
+                    title: (*id).into(),
+                    id: (*id).into(),
+                    parent: "".into(),
+                    num_links: 0,
+                    pinned: false,
+                    kind: NodeKind::Node,
+                    summary: None,
+                    group: None,
+                })
+                .collect(),
+            links: links
+                .iter()
+                .map(|(from, to)| RoamLink {
+                    from: (*from).into(),
+                    to: (*to).into(),
+                })
+                .collect(),
+            layout: Default::default(),
+            aggregated: false,
+            zoom: None,
+        }
+    }
+
+    fn clique<'a>(nodes: &[&'a str]) -> Vec<(&'a str, &'a str)> {
+        let mut links = vec![];
+        for (i, from) in nodes.iter().enumerate() {
+            for to in &nodes[i + 1..] {
+                links.push((*from, *to));
+            }
+        }
+        links
+    }
+
+    fn members(clusters: &BTreeMap<RoamID, usize>, cluster: usize) -> Vec<&str> {
+        clusters
+            .iter()
+            .filter(|(_, c)| **c == cluster)
+            .map(|(id, _)| id.id())
+            .collect()
+    }
+
+    #[test]
+    fn test_two_cliques() {
+        let mut links = clique(&["a1", "a2", "a3", "a4"]);
+        links.extend(clique(&["b1", "b2", "b3", "b4", "b5"]));
+        links.push(("a4", "b1"));
+        let nodes = ["a1", "a2", "a3", "a4", "b1", "b2", "b3", "b4", "b5"];
+        let clusters = louvain(&graph(&nodes, &links));
+        assert_eq!(members(&clusters, 0), vec!["b1", "b2", "b3", "b4", "b5"]);
+        assert_eq!(members(&clusters, 1), vec!["a1", "a2", "a3", "a4"]);
+    }
+
+    #[test]
+    fn test_ring_of_cliques() {
+        let groups: Vec<Vec<String>> = (0..6)
+            .map(|g| (0..4).map(|n| format!("{g}-{n}")).collect())
+            .collect();
+        let mut links: Vec<(String, String)> = vec![];
+        for group in &groups {
+            let names: Vec<&str> = group.iter().map(String::as_str).collect();
+            links.extend(
+                clique(&names)
+                    .into_iter()
+                    .map(|(a, b)| (a.to_string(), b.to_string())),
+            );
+        }
+        for g in 0..groups.len() {
+            let next = &groups[(g + 1) % groups.len()];
+            links.push((groups[g][3].clone(), next[0].clone()));
+        }
+        let nodes: Vec<&str> = groups.iter().flatten().map(String::as_str).collect();
+        let links: Vec<(&str, &str)> = links
+            .iter()
+            .map(|(a, b)| (a.as_str(), b.as_str()))
+            .collect();
+
+        let clusters = louvain(&graph(&nodes, &links));
+        for group in &groups {
+            let cluster = clusters[&RoamID::from(group[0].as_str())];
+            assert_eq!(
+                members(&clusters, cluster),
+                group.iter().map(String::as_str).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_isolated_and_unknown() {
+        let clusters = louvain(&graph(
+            &["a", "b", "c", "lonely"],
+            &[
+                ("a", "b"),
+                ("b", "c"),
+                ("c", "a"),
+                ("a", "missing"),
+                ("a", "a"),
+            ],
+        ));
+        assert_eq!(members(&clusters, 0), vec!["a", "b", "c"]);
+        assert_eq!(members(&clusters, 1), vec!["lonely"]);
+    }
+
+    #[test]
+    fn test_order_independent() {
+        let mut links = clique(&["a", "b", "c"]);
+        links.extend(clique(&["x", "y", "z"]));
+        links.push(("c", "x"));
+        let forward = louvain(&graph(&["a", "b", "c", "x", "y", "z"], &links));
+        links.reverse();
+        let backward = louvain(&graph(&["z", "y", "x", "c", "b", "a"], &links));
+        assert_eq!(forward, backward);
+    }
+}
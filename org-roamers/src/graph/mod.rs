@@ -0,0 +1,203 @@
+//! Graph types and the delta between two graphs, as sent to websocket
+//! clients after a file changed. Embedders that build graphs themselves use
+//! [`diff`] to produce the same incremental updates as the watcher.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+pub mod community;
+
+pub use crate::server::types::{
+    GraphData, NodeKind, NodePosition, RoamID, RoamLink, RoamNode, RoamTitle,
+};
+
+/// Changes that turn one graph into another.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GraphUpdate {
+    pub new_nodes: Vec<RoamNode>,
+    /// Nodes that exist in both graphs but differ, e.g. in their title
+    pub updated_nodes: Vec<RoamNode>,
+    pub new_links: Vec<RoamLink>,
+    pub removed_nodes: Vec<RoamID>,
+    pub removed_links: Vec<RoamLink>,
+}
+
+impl GraphUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.new_nodes.is_empty()
+            && self.updated_nodes.is_empty()
+            && self.new_links.is_empty()
+            && self.removed_nodes.is_empty()
+            && self.removed_links.is_empty()
+    }
+
+    /// Combine with an update that happened after this one, so applying the
+    /// result has the effect of applying both. Removals of `later` win over
+    /// additions of this update and the other way round.
+    pub fn merge(&mut self, later: GraphUpdate) {
+        for id in &later.removed_nodes {
+            self.new_nodes.retain(|node| &node.id != id);
+            self.updated_nodes.retain(|node| &node.id != id);
+            if !self.removed_nodes.contains(id) {
+                self.removed_nodes.push(id.clone());
+            }
+        }
+        for node in later.new_nodes {
+            self.updated_nodes.retain(|updated| updated.id != node.id);
+            self.new_nodes.retain(|new| new.id != node.id);
+            // A node that was removed before existed all along.
+            match self.removed_nodes.iter().position(|id| *id == node.id) {
+                Some(index) => {
+                    self.removed_nodes.remove(index);
+                    self.updated_nodes.push(node);
+                }
+                None => self.new_nodes.push(node),
+            }
+        }
+        for node in later.updated_nodes {
+            match self.new_nodes.iter_mut().find(|new| new.id == node.id) {
+                Some(new) => *new = node,
+                None => {
+                    self.updated_nodes.retain(|updated| updated.id != node.id);
+                    self.updated_nodes.push(node);
+                }
+            }
+        }
+
+        for link in later.removed_links {
+            self.new_links.retain(|new| *new != link);
+            if !self.removed_links.contains(&link) {
+                self.removed_links.push(link);
+            }
+        }
+        for link in later.new_links {
+            self.removed_links.retain(|removed| *removed != link);
+            if !self.new_links.contains(&link) {
+                self.new_links.push(link);
+            }
+        }
+    }
+}
+
+/// Compute the update that turns `old` into `new`. Nodes are matched by id,
+/// nodes and links keep the order of the graph they are taken from.
+pub fn diff(old: &GraphData, new: &GraphData) -> GraphUpdate {
+    let old_nodes: HashMap<&RoamID, &RoamNode> =
+        old.nodes.iter().map(|node| (&node.id, node)).collect();
+    let new_ids: HashSet<&RoamID> = new.nodes.iter().map(|node| &node.id).collect();
+    let old_links: HashSet<&RoamLink> = old.links.iter().collect();
+    let new_links: HashSet<&RoamLink> = new.links.iter().collect();
+
+    let mut update = GraphUpdate::default();
+    for node in &new.nodes {
+        match old_nodes.get(&node.id) {
+            None => update.new_nodes.push(node.clone()),
+            Some(stored) if *stored != node => update.updated_nodes.push(node.clone()),
+            Some(_) => {}
+        }
+    }
+    update.removed_nodes = old
+        .nodes
+        .iter()
+        .filter(|node| !new_ids.contains(&node.id))
+        .map(|node| node.id.clone())
+        .collect();
+
+    let mut seen = HashSet::new();
+    update.new_links = new
+        .links
+        .iter()
+        .filter(|link| !old_links.contains(link) && seen.insert(*link))
+        .cloned()
+        .collect();
+    let mut seen = HashSet::new();
+    update.removed_links = old
+        .links
+        .iter()
+        .filter(|link| !new_links.contains(link) && seen.insert(*link))
+        .cloned()
+        .collect();
+
+    update
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: &str, title: &str) -> RoamNode {
+        RoamNode {
+            title: title.into(),
+            id: id.into(),
+            parent: "".into(),
+            num_links: 0,
+            pinned: false,
+            kind: NodeKind::Node,
+            summary: None,
+            group: None,
+        }
+    }
+
+    fn link(from: &str, to: &str) -> RoamLink {
+        RoamLink {
+            from: from.into(),
+            to: to.into(),
+        }
+    }
+
+    fn graph(nodes: Vec<RoamNode>, links: Vec<RoamLink>) -> GraphData {
+        GraphData {
+            nodes,
+            links,
+            layout: Default::default(),
+            aggregated: false,
+            zoom: None,
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = graph(
+            vec![node("a", "A"), node("b", "B"), node("c", "C")],
+            vec![link("a", "b"), link("b", "c")],
+        );
+        let new = graph(
+            vec![node("a", "A"), node("b", "B renamed"), node("d", "D")],
+            vec![link("a", "b"), link("b", "d"), link("b", "d")],
+        );
+
+        let update = diff(&old, &new);
+        assert_eq!(update.new_nodes, vec![node("d", "D")]);
+        assert_eq!(update.updated_nodes, vec![node("b", "B renamed")]);
+        assert_eq!(update.removed_nodes, vec![RoamID::from("c")]);
+        assert_eq!(update.new_links, vec![link("b", "d")]);
+        assert_eq!(update.removed_links, vec![link("b", "c")]);
+
+        assert!(diff(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_merge() {
+        let a = graph(vec![node("a", "A")], vec![]);
+        let b = graph(vec![node("a", "A"), node("b", "B")], vec![link("a", "b")]);
+        let c = graph(vec![node("a", "A renamed")], vec![]);
+
+        let mut update = diff(&a, &b);
+        update.merge(diff(&b, &c));
+        assert!(update.new_nodes.is_empty());
+        assert_eq!(update.updated_nodes, vec![node("a", "A renamed")]);
+        assert_eq!(update.removed_nodes, vec![RoamID::from("b")]);
+        assert!(update.new_links.is_empty());
+        assert_eq!(update.removed_links, vec![link("a", "b")]);
+
+        // A node removed and added again is updated
+        let mut update = diff(&b, &a);
+        update.merge(diff(&a, &b));
+        assert!(update.new_nodes.is_empty());
+        assert!(update.removed_nodes.is_empty());
+        assert_eq!(update.updated_nodes, vec![node("b", "B")]);
+        assert_eq!(update.new_links, vec![link("a", "b")]);
+        assert!(update.removed_links.is_empty());
+    }
+}
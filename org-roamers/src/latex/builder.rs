@@ -1,9 +1,3 @@
-use std::{
-    env, fs,
-    hash::{DefaultHasher, Hash, Hasher},
-    path::PathBuf,
-};
-
 const PREAMBLE: &str = concat!(
     "\\documentclass{article}\n",
     "\\usepackage[T1]{fontenc}\n",
@@ -49,39 +43,9 @@ impl<'a> LatexBuilder<'a> {
     }
 }
 
-pub struct LatexPathBuilder {
-    path: PathBuf,
-}
-
-impl LatexPathBuilder {
-    pub fn new() -> Self {
-        let mut dir = env::temp_dir();
-        dir.push("org-roamers/");
-        if !dir.exists() {
-            let _ = fs::create_dir_all(&dir);
-        }
-        Self { path: dir }
-    }
-
-    pub fn build(&mut self, filename: &str) -> (PathBuf, PathBuf, PathBuf) {
-        let mut hasher = DefaultHasher::default();
-        filename.hash(&mut hasher);
-        let hash = hasher.finish();
-        let mut path_tex = self.path.clone();
-        let mut path_dvi = self.path.clone();
-        let mut path_svg = self.path.clone();
-        path_tex.push(format!("{hash}.tex"));
-        path_dvi.push(format!("{hash}.dvi"));
-        path_svg.push(format!("{hash}.svg"));
-        (path_tex, path_dvi, path_svg)
-    }
-}
-
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
-
-    use crate::latex::builder::{LatexBuilder, LatexPathBuilder};
+    use crate::latex::builder::LatexBuilder;
 
     #[test]
     fn test_latex_builder() {
@@ -107,17 +71,4 @@ mod tests {
         );
         assert_eq!(builder.build("green").as_str(), exp);
     }
-
-    #[test]
-    fn test_latex_path_builder() {
-        let mut builder = LatexPathBuilder::new();
-        assert_eq!(
-            builder.build("test"),
-            (
-                PathBuf::from("/tmp/org-roamers/14402189752926126668.tex"),
-                PathBuf::from("/tmp/org-roamers/14402189752926126668.dvi"),
-                PathBuf::from("/tmp/org-roamers/14402189752926126668.svg")
-            )
-        );
-    }
 }
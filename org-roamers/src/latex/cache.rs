@@ -0,0 +1,165 @@
+use std::{
+    env,
+    fs::{self, File},
+    hash::{DefaultHasher, Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::config::LatexConfig;
+
+/// Rendered LaTeX fragments on disk, keyed by everything that affects the
+/// image. The least recently used SVGs are removed once the cache grows
+/// beyond its size limit.
+pub struct LatexCache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+impl LatexCache {
+    pub fn new(config: &LatexConfig) -> Self {
+        let dir = config
+            .cache_dir
+            .clone()
+            .unwrap_or_else(|| env::temp_dir().join("org-roamers"));
+        Self::with_dir(dir, config.cache_size)
+    }
+
+    pub fn with_dir(dir: PathBuf, max_size: u64) -> Self {
+        if let Err(err) = fs::create_dir_all(&dir) {
+            tracing::error!("Failed to create LaTeX cache {}: {err}", dir.display());
+        }
+        Self { dir, max_size }
+    }
+
+    /// Key of a fragment rendered in `color` with the preamble `headers`.
+    pub fn key<T: AsRef<str>>(latex: &str, color: &str, headers: &[T]) -> u64 {
+        let mut hasher = DefaultHasher::default();
+        latex.hash(&mut hasher);
+        color.hash(&mut hasher);
+        for header in headers {
+            header.as_ref().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Paths of the `.tex`, `.dvi` and `.svg` file of `key`.
+    pub fn paths(&self, key: u64) -> (PathBuf, PathBuf, PathBuf) {
+        (
+            self.dir.join(format!("{key}.tex")),
+            self.dir.join(format!("{key}.dvi")),
+            self.dir.join(format!("{key}.svg")),
+        )
+    }
+
+    /// The cached SVG of `key`. A hit counts as use for the eviction.
+    pub fn get(&self, key: u64) -> Option<Vec<u8>> {
+        let (_, _, svg) = self.paths(key);
+        let content = fs::read(&svg).ok()?;
+        if let Err(err) = File::options()
+            .write(true)
+            .open(&svg)
+            .and_then(|file| file.set_modified(SystemTime::now()))
+        {
+            tracing::warn!("Failed to touch {}: {err}", svg.display());
+        }
+        Some(content)
+    }
+
+    /// Remove the intermediate files of `key` once its SVG was rendered and
+    /// evict old SVGs if the cache is too large.
+    pub fn finish(&self, key: u64) {
+        let svg = format!("{key}.svg");
+        let prefix = format!("{key}.");
+        if let Ok(entries) = fs::read_dir(&self.dir) {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if name.starts_with(&prefix) && name != svg {
+                    let _ = fs::remove_file(entry.path());
+                }
+            }
+        }
+        if let Err(err) = self.evict() {
+            tracing::error!("Failed to evict LaTeX cache: {err}");
+        }
+    }
+
+    /// Remove the least recently used SVGs until the cache fits into its
+    /// size limit. A limit of `0` keeps everything.
+    pub fn evict(&self) -> io::Result<()> {
+        if self.max_size == 0 {
+            return Ok(());
+        }
+        let mut svgs = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "svg") {
+                let metadata = entry.metadata()?;
+                svgs.push((metadata.modified()?, metadata.len(), path));
+            }
+        }
+        let mut size: u64 = svgs.iter().map(|(_, len, _)| len).sum();
+        svgs.sort_by_key(|(modified, _, _)| *modified);
+        for (_, len, path) in svgs {
+            if size <= self.max_size {
+                break;
+            }
+            fs::remove_file(&path)?;
+            size -= len;
+        }
+        Ok(())
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_key() {
+        let key = LatexCache::key("$x$", "000000", &["\\usepackage{tikz}"]);
+        assert_eq!(
+            key,
+            LatexCache::key("$x$", "000000", &["\\usepackage{tikz}"])
+        );
+        assert_ne!(
+            key,
+            LatexCache::key("$x$", "ffffff", &["\\usepackage{tikz}"])
+        );
+        assert_ne!(key, LatexCache::key::<&str>("$x$", "000000", &[]));
+    }
+
+    #[test]
+    fn test_evict() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = LatexCache::with_dir(temp_dir.path().to_path_buf(), 10);
+        let now = SystemTime::now();
+        for (key, age) in [(1, 3), (2, 1), (3, 2)] {
+            let (_, _, svg) = cache.paths(key);
+            fs::write(&svg, "12345").unwrap();
+            File::options()
+                .write(true)
+                .open(&svg)
+                .unwrap()
+                .set_modified(now - Duration::from_secs(age))
+                .unwrap();
+        }
+        fs::write(cache.dir().join("2.log"), "log").unwrap();
+
+        cache.finish(2);
+        assert!(cache.get(1).is_none());
+        assert!(cache.get(2).is_some());
+        assert!(cache.get(3).is_some());
+        assert!(!cache.dir().join("2.log").exists());
+    }
+}
@@ -5,24 +5,30 @@ use tokio::process::Command;
 use tracing::info;
 
 use crate::config::LatexConfig;
-use crate::latex::builder::{LatexBuilder, LatexPathBuilder};
+use crate::latex::builder::LatexBuilder;
+
+pub use cache::LatexCache;
 
 mod builder;
+mod cache;
 
+/// Render `latex` as SVG. `headers` are the `#+latex_header` lines of its
+/// file, the configured preamble is appended to them.
 pub async fn get_image(
     config: &LatexConfig,
+    cache: &LatexCache,
     latex: String,
     color: String,
-    headers: Vec<String>,
+    mut headers: Vec<String>,
 ) -> anyhow::Result<Vec<u8>> {
-    // construct all paths for generated files.
-    let (path_tex, path_dvi, path_svg) = LatexPathBuilder::new().build(latex.as_str());
-    if let Ok(mut file) = File::open(path_svg.as_path()).await {
+    headers.extend(config.preamble.iter().cloned());
+    let key = LatexCache::key(&latex, &color, &headers);
+    if let Some(svg) = cache.get(key) {
         info!("Found preexisting content.");
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer).await?;
-        return Ok(buffer);
+        return Ok(svg);
     }
+    // construct all paths for generated files.
+    let (path_tex, path_dvi, path_svg) = cache.paths(key);
 
     // build latex file
     let mut latex_builder = LatexBuilder::new();
@@ -83,5 +89,6 @@ pub async fn get_image(
 
     let mut buffer = Vec::new();
     file.read_to_end(&mut buffer).await?;
+    cache.finish(key);
     Ok(buffer)
 }
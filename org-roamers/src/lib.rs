@@ -16,25 +16,44 @@ mod latex;
 
 mod auth;
 mod client;
+#[cfg(feature = "client_api")]
+pub mod client_api;
+mod collab;
 pub mod config;
+pub mod graph;
+mod link_checker;
+mod mdns;
+mod reindex;
 mod search;
 mod server;
 mod sqlite;
 mod transform;
+mod trash;
 mod watcher;
 
 use sqlx::SqlitePool;
 
 use dashmap::DashMap;
-use std::sync::{atomic::AtomicU64, atomic::Ordering, Arc};
-use tokio::sync::mpsc::{self, UnboundedSender};
-use tokio::time::Instant;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{atomic::AtomicU64, atomic::Ordering, Arc, Mutex, OnceLock, RwLock};
+use time::{OffsetDateTime, UtcOffset};
+use tokio::sync::{mpsc, watch, Notify};
+use tokio::time::{Duration, Instant};
 use tokio_util::sync::CancellationToken;
 
 use crate::auth::{build_user_store, UserStore};
 use crate::cache::OrgCache;
-use crate::client::message::WebSocketMessage;
+use crate::client::{
+    broadcast, message::WebSocketMessage, topic, topic::Topic, ConnectionKind, WebSocketConnection,
+};
+use crate::collab::Collab;
 use crate::config::Config;
+use crate::latex::LatexCache;
+use crate::server::emacs::EmacsFollow;
+use crate::server::types::{LinkReport, ReindexReport, RoamID};
+use crate::watcher::WatcherStatus;
 
 pub struct ServerState {
     /// Read-only configuration
@@ -44,22 +63,97 @@ pub struct ServerState {
     /// Org cache
     pub cache: OrgCache,
     /// WebSocket connections
-    pub websocket_connections: DashMap<u64, UnboundedSender<WebSocketMessage>>,
+    pub websocket_connections: DashMap<u64, WebSocketConnection>,
     /// Atomic counter for connection IDs
     pub next_connection_id: AtomicU64,
     /// User authentication store (None if auth disabled)
     pub user_store: Option<UserStore>,
+    /// Nodes selected in the web UI that Emacs follows
+    pub emacs_follow: EmacsFollow,
+    /// Sequence number that is incremented on every change of the vault
+    pub vault_changes: watch::Sender<u64>,
+    /// Broken links found by the last run of the link checker
+    pub link_report: RwLock<LinkReport>,
+    /// Discrepancies found by the last reindex
+    pub reindex_report: RwLock<ReindexReport>,
+    /// Atomic counter for reindex job IDs
+    pub next_reindex_job: AtomicU64,
+    /// Held while a reindex runs, so jobs run one after another
+    pub reindex_lock: tokio::sync::Mutex<()>,
+    /// Addresses the server listens on, set once all listeners are bound.
+    /// Ports are resolved, so a configured port `0` shows the chosen one.
+    pub listening: watch::Sender<Vec<SocketAddr>>,
+    /// Broadcasts waiting to be coalesced and sent by the flusher
+    pub pending_broadcasts: Mutex<Vec<WebSocketMessage>>,
+    pub broadcast_pending: Notify,
+    /// State of the file system watcher, surfaced by `/healthz`
+    pub watcher_status: RwLock<WatcherStatus>,
+    /// Rendered LaTeX fragments
+    pub latex_cache: LatexCache,
+    /// Files edited collaboratively over the websocket
+    pub collab: Collab,
+    /// Offset of the local time zone, see [`init_local_offset`]
+    pub local_offset: UtcOffset,
+}
+
+/// Offset of the local time zone, `None` if it could not be determined.
+static LOCAL_OFFSET: OnceLock<Option<UtcOffset>> = OnceLock::new();
+
+/// Determine the offset of the local time zone for dates written to notes,
+/// the agenda and the reindex schedule. Call it first thing in `main`,
+/// before the tokio runtime or any other thread is started: the time zone
+/// can only be read soundly while the process is single-threaded.
+/// [`ServerState`]s use UTC if it was not called.
+pub fn init_local_offset() {
+    LOCAL_OFFSET.get_or_init(|| UtcOffset::current_local_offset().ok());
 }
 
 impl ServerState {
     pub async fn new(conf: Config) -> anyhow::Result<ServerState> {
-        let sqlite_con = sqlite::init_db().await?;
+        if !conf.attachment_dir_is_valid() {
+            anyhow::bail!(
+                "attachment_dir {:?} has to be a relative path without `..`",
+                conf.attachment_dir
+            );
+        }
+
+        let sqlite_con = sqlite::init_db(conf.database.path.as_deref()).await?;
 
         let mut org_cache = OrgCache::new(conf.org_roamers_root.to_path_buf());
+        org_cache.follow_symlinks(conf.follow_symlinks);
+        org_cache.max_file_size(conf.max_file_size);
+        for root in &conf.extra_roots {
+            if !root.is_valid() {
+                tracing::error!(
+                    "Ignoring root {:?} with invalid mount {:?}",
+                    root.path,
+                    root.mount
+                );
+                continue;
+            }
+            org_cache.mount(root.mount.clone(), root.path.clone());
+        }
+
+        if let Some(org_roam_db) = &conf.org_roam_db_import {
+            if sqlite::files::get_files(&sqlite_con).await?.is_empty() {
+                let imported = sqlite::import::import_org_roam_db(
+                    &sqlite_con,
+                    org_roam_db,
+                    &conf.org_roamers_root,
+                )
+                .await?;
+                tracing::info!("Imported {imported} files from {org_roam_db:?}");
+            }
+        }
 
         org_cache.rebuild(&sqlite_con).await?;
 
         let user_store = build_user_store(&conf)?;
+        let local_offset = LOCAL_OFFSET.get().copied().flatten().unwrap_or_else(|| {
+            tracing::warn!("Local time zone unknown, dates are in UTC");
+            UtcOffset::UTC
+        });
+        let latex_cache = LatexCache::new(&conf.latex_config);
 
         Ok(ServerState {
             sqlite: sqlite_con,
@@ -68,43 +162,229 @@ impl ServerState {
             websocket_connections: DashMap::new(),
             next_connection_id: AtomicU64::new(1),
             user_store,
+            emacs_follow: EmacsFollow::default(),
+            vault_changes: watch::Sender::new(0),
+            listening: watch::Sender::new(vec![]),
+            link_report: RwLock::new(LinkReport::default()),
+            reindex_report: RwLock::new(ReindexReport::default()),
+            next_reindex_job: AtomicU64::new(1),
+            reindex_lock: tokio::sync::Mutex::new(()),
+            pending_broadcasts: Mutex::new(vec![]),
+            broadcast_pending: Notify::new(),
+            watcher_status: RwLock::new(WatcherStatus::Disabled),
+            latex_cache,
+            collab: Collab::default(),
+            local_offset,
         })
     }
 
-    /// Register a new WebSocket connection
+    /// The current local time, see [`init_local_offset`].
+    pub fn now(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc().to_offset(self.local_offset)
+    }
+
+    /// Register a new WebSocket connection of `user`. `user` is `None` if
+    /// authentication is disabled.
     pub fn register_websocket_connection(
         &self,
-        sender: mpsc::UnboundedSender<WebSocketMessage>,
+        sender: mpsc::Sender<WebSocketMessage>,
+        kind: ConnectionKind,
+        user: Option<String>,
+        user_agent: Option<String>,
     ) -> u64 {
         let connection_id = self.next_connection_id.fetch_add(1, Ordering::SeqCst);
-        self.websocket_connections.insert(connection_id, sender);
+        let connection = WebSocketConnection::new(sender, kind, user, user_agent);
+        self.websocket_connections.insert(connection_id, connection);
         connection_id
     }
 
     /// Unregister a WebSocket connection
     pub fn unregister_websocket_connection(&self, connection_id: u64) {
         self.websocket_connections.remove(&connection_id);
+        self.collab.leave(connection_id, None);
+    }
+
+    /// Send a message to one connection right away, regardless of its
+    /// subscriptions.
+    pub fn send_to_websocket(&self, connection_id: u64, message: WebSocketMessage) {
+        let closed = match self.websocket_connections.get_mut(&connection_id) {
+            Some(mut connection) => !connection.send(message),
+            None => false,
+        };
+        if closed {
+            self.unregister_websocket_connection(connection_id);
+        }
+    }
+
+    /// Remember the node that is currently visited on a connection, which
+    /// makes it the most recently active connection of its user.
+    pub fn set_working_id(&self, connection_id: u64, id: RoamID) {
+        if let Some(mut connection) = self.websocket_connections.get_mut(&connection_id) {
+            connection.working_id = Some(id);
+            connection.touch();
+        }
     }
 
-    /// Send a message to all connected WebSocket clients
+    /// Get the node that is currently visited on a connection.
+    pub fn working_id(&self, connection_id: u64) -> Option<RoamID> {
+        self.websocket_connections
+            .get(&connection_id)
+            .and_then(|connection| connection.working_id.clone())
+    }
+
+    /// Add `topics` to the subscriptions of a connection.
+    pub fn subscribe(&self, connection_id: u64, topics: &[Topic]) {
+        if let Some(mut connection) = self.websocket_connections.get_mut(&connection_id) {
+            connection.subscribe(topics);
+        }
+    }
+
+    /// Remove `topics` from the subscriptions of a connection.
+    pub fn unsubscribe(&self, connection_id: u64, topics: &[Topic]) {
+        if let Some(mut connection) = self.websocket_connections.get_mut(&connection_id) {
+            connection.unsubscribe(topics);
+        }
+    }
+
+    /// Signal waiting clients that the vault changed.
+    pub fn notify_vault_changed(&self) {
+        self.vault_changes.send_modify(|seq| *seq += 1);
+    }
+
+    /// Expose a node selected in the web UI on `connection_id` to Emacs.
+    pub fn publish_ui_selection(&self, connection_id: u64, id: RoamID) {
+        let Some(connection) = self.websocket_connections.get(&connection_id) else {
+            return;
+        };
+        let owner = connection
+            .user
+            .as_deref()
+            .unwrap_or(sqlite::preferences::GLOBAL_USER);
+        self.emacs_follow.publish(owner, id);
+    }
+
+    /// Send a message to the connected WebSocket clients subscribed to its
+    /// topics. Messages are delayed by [`broadcast::BROADCAST_WINDOW`] and
+    /// coalesced.
     pub fn broadcast_to_websockets(&self, message: WebSocketMessage) {
+        self.pending_broadcasts.lock().unwrap().push(message);
+        self.broadcast_pending.notify_one();
+    }
+
+    /// Send all pending broadcasts right away.
+    pub fn flush_broadcasts(&self) {
+        let messages = std::mem::take(&mut *self.pending_broadcasts.lock().unwrap());
+        for message in broadcast::coalesce(messages) {
+            self.broadcast_where(message, |_| true);
+        }
+    }
+
+    /// Move one connection of `user` to a node visited elsewhere, e.g. in
+    /// Emacs: `connection` if it belongs to `user`, otherwise the most
+    /// recently active connection of `user`. Returns the connection, `None`
+    /// if `user` has none.
+    pub fn send_node_visited(
+        &self,
+        user: Option<&str>,
+        connection: Option<u64>,
+        id: RoamID,
+    ) -> Option<u64> {
+        let owned_by_user = |connection_id: &u64| {
+            self.websocket_connections
+                .get(connection_id)
+                .is_some_and(|connection| connection.user.as_deref() == user)
+        };
+        let connection_id = connection.filter(owned_by_user).or_else(|| {
+            self.websocket_connections
+                .iter()
+                .filter(|entry| entry.user.as_deref() == user)
+                .max_by_key(|entry| entry.last_active)
+                .map(|entry| *entry.key())
+        })?;
+
+        self.set_working_id(connection_id, id.clone());
+        self.send_to_websocket(connection_id, WebSocketMessage::NodeVisited { node_id: id });
+        Some(connection_id)
+    }
+
+    fn broadcast_where<F>(&self, message: WebSocketMessage, predicate: F)
+    where
+        F: Fn(&WebSocketConnection) -> bool,
+    {
+        let topics = topic::topics(&message);
         let mut failed_connections = Vec::new();
 
-        for entry in self.websocket_connections.iter() {
-            let (connection_id, sender) = entry.pair();
-            if sender.send(message.clone()).is_err() {
+        for mut entry in self.websocket_connections.iter_mut() {
+            let (connection_id, connection) = entry.pair_mut();
+            if connection.wants(&topics)
+                && predicate(connection)
+                && !connection.send(message.clone())
+            {
                 failed_connections.push(*connection_id);
             }
         }
 
         // Remove failed connections
         for connection_id in failed_connections {
-            self.websocket_connections.remove(&connection_id);
+            self.unregister_websocket_connection(connection_id);
         }
     }
 }
 
+/// Serialize the whole vault index, the same document `/export/index.json`
+/// serves.
+pub async fn export_index(state: &ServerState) -> anyhow::Result<String> {
+    let index = server::services::export_service::vault_index(&state.sqlite).await?;
+    Ok(serde_json::to_string_pretty(&index)?)
+}
+
+/// Render the flashcards of the vault as a file for Anki's text import, the
+/// same document `/export/anki.txt` serves.
+pub fn export_anki(state: &ServerState) -> anyhow::Result<String> {
+    let cards = server::services::flashcard_service::collect(&state.cache)?;
+    Ok(server::services::flashcard_service::to_anki(&cards))
+}
+
+/// Render every node to a page of a static site in `out`, see
+/// [`transform::export`]. Returns the number of pages written.
+pub async fn export_static(state: &ServerState, out: &Path) -> anyhow::Result<usize> {
+    transform::export::export_site(&state.sqlite, &state.cache, &state.config.org_to_html, out)
+        .await
+}
+
+/// Merge the file node `remove` into `keep`, the same operation
+/// `/node/merge` performs. Returns the files that changed.
+pub async fn merge_nodes(
+    state: &ServerState,
+    keep: &str,
+    remove: &str,
+) -> anyhow::Result<Vec<String>> {
+    use server::services::audit_service::{self, AuditAction};
+
+    let merged = server::services::merge_service::merge(state, keep, remove, None).await?;
+    audit_service::record(&state.sqlite, None, AuditAction::Merge, Some(&merged.file)).await;
+
+    let mut changed = vec![merged.file];
+    changed.extend(merged.rewritten);
+    changed.push(merged.trashed);
+    Ok(changed)
+}
+
+/// Time a running reindex gets to finish its writes on shutdown.
+const REINDEX_GRACE: Duration = Duration::from_secs(30);
+
+/// Run the server until SIGINT or SIGTERM is received.
 pub async fn start(state: ServerState) -> anyhow::Result<()> {
+    start_with_shutdown(state, shutdown_signal()).await
+}
+
+/// Run the server until `shutdown` completes. Background tasks are stopped,
+/// clients are disconnected and the database is closed before it returns,
+/// so applications embedding the server can stop it on their own terms.
+pub async fn start_with_shutdown<F>(state: ServerState, shutdown: F) -> anyhow::Result<()>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
     let start = Instant::now();
 
     tracing::info!(
@@ -114,38 +394,110 @@ pub async fn start(state: ServerState) -> anyhow::Result<()> {
 
     let use_fs_watcher = state.config.fs_watcher;
 
-    let host = &state.config.http_server_config.host;
-    let port = &state.config.http_server_config.port;
-    let url = format!("{}:{}", host, port);
+    let addresses = state.config.http_server_config.bind_addresses();
 
     let app_state = Arc::new(state);
 
     let cancellation_token = CancellationToken::new();
+    // Background tasks are also stopped if binding fails
+    let _stop_tasks = cancellation_token.clone().drop_guard();
 
     if use_fs_watcher {
-        watcher::watcher(app_state.clone(), cancellation_token.clone())
-            .await
-            .unwrap();
+        watcher::watcher(app_state.clone(), cancellation_token.clone());
+        watcher::rescanner(app_state.clone(), cancellation_token.clone());
 
         tracing::info!("File watcher enabled");
     }
 
-    let app = server::build_server(app_state.clone()).await;
+    broadcast::flusher(app_state.clone(), cancellation_token.clone());
+    link_checker::link_checker(app_state.clone(), cancellation_token.clone());
+    reindex::reindex_scheduler(app_state.clone(), cancellation_token.clone());
+    mdns::advertise(app_state.clone(), cancellation_token.clone());
+    trash::purger(app_state.clone(), cancellation_token.clone());
+    collab::flusher(app_state.clone(), cancellation_token.clone());
 
-    tracing::info!("Server listening on {}", url);
-    let listener = tokio::net::TcpListener::bind(&url).await.unwrap();
+    let http_config = &app_state.config.http_server_config;
+    let app = server::http::limit(server::build_server(app_state.clone()).await, http_config);
+
+    let mut listeners = Vec::with_capacity(addresses.len());
+    let mut bound = Vec::with_capacity(addresses.len());
+    for address in addresses {
+        let listener = tokio::net::TcpListener::bind(&address).await?;
+        let local_addr = listener.local_addr()?;
+        tracing::info!("Server listening on {}", local_addr);
+        listeners.push(listener);
+        bound.push(local_addr);
+    }
+    app_state.listening.send_replace(bound);
 
     let end = Instant::now();
     tracing::info!("Startup took {}ms.", (end - start).as_millis());
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(async move {
-            tokio::signal::ctrl_c().await.ok();
-            tracing::info!("Shutdown signal received, stopping server...");
-            cancellation_token.cancel();
-        })
-        .await
-        .unwrap();
+    tokio::spawn({
+        let cancellation_token = cancellation_token.clone();
+        async move {
+            tokio::select! {
+                _ = shutdown => {
+                    tracing::info!("Shutdown signal received, stopping server...");
+                    cancellation_token.cancel();
+                }
+                _ = cancellation_token.cancelled() => {}
+            }
+        }
+    });
 
+    let servers = listeners.into_iter().map(|listener| {
+        server::http::serve(
+            listener,
+            app.clone(),
+            http_config,
+            cancellation_token.clone(),
+        )
+    });
+    futures_util::future::join_all(servers).await;
+
+    stop(&app_state).await;
     Ok(())
 }
+
+/// Completes on SIGINT, or SIGTERM on unix.
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(err) => tracing::error!("Failed to listen for SIGTERM: {err}"),
+        }
+    }
+    if let Err(err) = tokio::signal::ctrl_c().await {
+        tracing::error!("Failed to listen for SIGINT: {err}");
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Disconnect the clients and flush the database once the HTTP servers
+/// stopped.
+async fn stop(state: &ServerState) {
+    // Queued messages are still delivered before the clients see their
+    // channel close and send a close frame.
+    collab::flush(state).await;
+    state.flush_broadcasts();
+    state.websocket_connections.clear();
+
+    if tokio::time::timeout(REINDEX_GRACE, state.reindex_lock.lock())
+        .await
+        .is_err()
+    {
+        tracing::warn!("Stopping while a reindex is still running");
+    }
+    // Waits for running queries and checkpoints the database.
+    state.sqlite.close().await;
+    tracing::info!("Server stopped");
+}
@@ -0,0 +1,150 @@
+//! Background job that looks for broken links. `id:` links are checked
+//! against the database, external links are requested if
+//! [`LinkCheckConfig::check_external`] is set. The result of the last run is
+//! kept in [`ServerState::link_report`].
+
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use futures_util::{stream, StreamExt};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    client::message::WebSocketMessage,
+    config::LinkCheckConfig,
+    server::types::{BrokenLink, LinkReport},
+    sqlite::links,
+    transform::title::TitleSanitizer,
+    ServerState,
+};
+
+/// Number of external links that are requested at the same time.
+const CONCURRENT_REQUESTS: usize = 8;
+
+pub fn link_checker(state: Arc<ServerState>, cancellation_token: CancellationToken) {
+    let interval_minutes = state.config.link_check.interval_minutes;
+    if interval_minutes == 0 {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_minutes * 60));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            tokio::select! {
+                _ = cancellation_token.cancelled() => {
+                    tracing::info!("Link checker cancelled");
+                    break;
+                }
+                _ = interval.tick() => run(&state).await,
+            }
+        }
+    });
+}
+
+/// Check all links once, store the report and notify clients if the set of
+/// broken links changed.
+pub async fn run(state: &ServerState) {
+    let report = match check_links(state).await {
+        Ok(report) => report,
+        Err(err) => {
+            tracing::error!("Link check failed: {err}");
+            return;
+        }
+    };
+
+    let count = report.links.len();
+    let changed = {
+        let mut current = state.link_report.write().unwrap();
+        let changed = current.links != report.links;
+        *current = report;
+        changed
+    };
+
+    tracing::info!("Link check found {count} broken links");
+    if changed {
+        state.broadcast_to_websockets(WebSocketMessage::BrokenLinks { count });
+    }
+}
+
+async fn check_links(state: &ServerState) -> anyhow::Result<LinkReport> {
+    let sqlite = &state.sqlite;
+    let title = |title: &str| TitleSanitizer::new().process(title);
+
+    let mut broken: Vec<BrokenLink> = links::get_broken_id_links(sqlite)
+        .await?
+        .into_iter()
+        .map(|(source, source_title, dest)| BrokenLink {
+            source: source.into(),
+            source_title: title(&source_title).into(),
+            link_type: "id".to_string(),
+            dest,
+            error: None,
+        })
+        .collect();
+
+    if state.config.link_check.check_external {
+        let external = links::get_external_links(sqlite).await?;
+        broken.extend(check_external(&state.config.link_check, external).await?);
+    }
+
+    let checked_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .and_then(|since| i64::try_from(since.as_secs()).ok());
+
+    Ok(LinkReport {
+        checked_at,
+        links: broken,
+    })
+}
+
+async fn check_external(
+    config: &LinkCheckConfig,
+    external: Vec<(String, String, String, String)>,
+) -> anyhow::Result<Vec<BrokenLink>> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.timeout_secs))
+        .build()?;
+
+    let broken = stream::iter(external)
+        .map(|(source, source_title, link_type, url)| {
+            let client = client.clone();
+            async move {
+                let error = request(&client, &url).await.err()?;
+                Some(BrokenLink {
+                    source: source.into(),
+                    source_title: TitleSanitizer::new().process(&source_title).into(),
+                    link_type,
+                    dest: url,
+                    error: Some(error),
+                })
+            }
+        })
+        .buffered(CONCURRENT_REQUESTS)
+        .filter_map(|broken| async move { broken })
+        .collect()
+        .await;
+
+    Ok(broken)
+}
+
+/// Request `url`, falling back to `GET` for servers that reject `HEAD`.
+async fn request(client: &reqwest::Client, url: &str) -> Result<(), String> {
+    let response = match client.head(url).send().await {
+        Ok(response) if response.status() != reqwest::StatusCode::METHOD_NOT_ALLOWED => response,
+        _ => client
+            .get(url)
+            .send()
+            .await
+            .map_err(|err| err.to_string())?,
+    };
+
+    match response.status() {
+        status if status.is_success() || status.is_redirection() => Ok(()),
+        status => Err(status.to_string()),
+    }
+}
@@ -0,0 +1,72 @@
+//! Advertise the server on the local network via mDNS, so other devices can
+//! find it without knowing its address. Enabled by [`MdnsConfig::enabled`].
+
+use std::{net::SocketAddr, sync::Arc};
+
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use tokio_util::sync::CancellationToken;
+
+use crate::{config::MdnsConfig, ServerState};
+
+const SERVICE_TYPE: &str = "_org-roamers._tcp.local.";
+
+pub fn advertise(state: Arc<ServerState>, cancellation_token: CancellationToken) {
+    if !state.config.mdns.enabled {
+        return;
+    }
+
+    let mut listening = state.listening.subscribe();
+    tokio::spawn(async move {
+        let addresses = match listening.wait_for(|addresses| !addresses.is_empty()).await {
+            Ok(addresses) => addresses.clone(),
+            Err(_) => return,
+        };
+
+        let daemon = match register(&state.config.mdns, &addresses) {
+            Ok(daemon) => daemon,
+            Err(err) => {
+                tracing::error!("Failed to advertise via mDNS: {err}");
+                return;
+            }
+        };
+
+        cancellation_token.cancelled().await;
+        if let Err(err) = daemon.shutdown() {
+            tracing::error!("Failed to stop mDNS advertisement: {err}");
+        }
+    });
+}
+
+fn register(config: &MdnsConfig, addresses: &[SocketAddr]) -> anyhow::Result<ServiceDaemon> {
+    if addresses.iter().all(|addr| addr.ip().is_loopback()) {
+        tracing::warn!("Server only listens on loopback, mDNS peers will not reach it");
+    }
+
+    // Listeners on unspecified addresses are reachable on every interface, so
+    // let the daemon announce the addresses of all interfaces.
+    let port = addresses[0].port();
+    let ips: Vec<_> = addresses
+        .iter()
+        .map(|addr| addr.ip())
+        .filter(|ip| !ip.is_unspecified())
+        .collect();
+    let host_name = format!("{}.local.", config.name);
+
+    let service = ServiceInfo::new(
+        SERVICE_TYPE,
+        &config.name,
+        &host_name,
+        &ips[..],
+        port,
+        &[("version", env!("CARGO_PKG_VERSION"))][..],
+    )?;
+    let service = match ips.len() == addresses.len() {
+        true => service,
+        false => service.enable_addr_auto(),
+    };
+
+    let daemon = ServiceDaemon::new()?;
+    daemon.register(service)?;
+    tracing::info!("Advertising {} as {SERVICE_TYPE}", config.name);
+    Ok(daemon)
+}
@@ -0,0 +1,218 @@
+//! Full reindex of the vault, either scheduled or requested through
+//! `/admin/reindex`. It is a safety net for file system events the watcher
+//! missed. Every file is indexed again and differences to the previous state
+//! are kept in [`ServerState::reindex_report`].
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use time::{Duration, OffsetDateTime, Time};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    client::message::WebSocketMessage,
+    server::types::ReindexReport,
+    sqlite::{
+        diagnostics,
+        files::{delete_file, get_files},
+    },
+    watcher, ServerState,
+};
+
+pub fn reindex_scheduler(state: Arc<ServerState>, cancellation_token: CancellationToken) {
+    let Some(schedule) = &state.config.reindex_schedule else {
+        return;
+    };
+    let Some(at) = parse_schedule(schedule) else {
+        tracing::error!("Invalid reindex_schedule {schedule:?}, expected HH:MM");
+        return;
+    };
+    tracing::info!("Scheduled full reindex at {schedule}");
+
+    tokio::spawn(async move {
+        loop {
+            let now = state.now();
+            let wait = next_run(now, at) - now;
+            let wait = std::time::Duration::try_from(wait).unwrap_or_default();
+
+            tokio::select! {
+                _ = cancellation_token.cancelled() => {
+                    tracing::info!("Reindex scheduler cancelled");
+                    break;
+                }
+                _ = tokio::time::sleep(wait) => run(&state, next_job_id(&state), None).await,
+            }
+        }
+    });
+}
+
+pub fn next_job_id(state: &ServerState) -> u64 {
+    state.next_reindex_job.fetch_add(1, Ordering::SeqCst)
+}
+
+/// Reindex all files below `scope` (relative to the root), or the whole vault
+/// if it is `None`. Stores the report and notifies clients. Jobs wait for
+/// running jobs to finish.
+pub async fn run(state: &ServerState, job_id: u64, scope: Option<PathBuf>) {
+    let _running = state.reindex_lock.lock().await;
+    let report = match reindex(state, job_id, scope.as_deref()).await {
+        Ok(report) => report,
+        Err(err) => {
+            tracing::error!("Reindex failed: {err}");
+            return;
+        }
+    };
+
+    let discrepancies = report.discrepancies();
+    tracing::info!(
+        "Reindex job {job_id}: {} files, {discrepancies} differed from the index",
+        report.files
+    );
+    if discrepancies > 0 {
+        tracing::warn!(
+            "Reindex found added: {:?}, changed: {:?}, removed: {:?}",
+            report.added,
+            report.changed,
+            report.removed
+        );
+        state.notify_vault_changed();
+    }
+    *state.reindex_report.write().unwrap() = report;
+    state.broadcast_to_websockets(WebSocketMessage::Reindexed {
+        job_id,
+        discrepancies,
+    });
+}
+
+/// Progress is broadcast every this many files.
+const PROGRESS_INTERVAL: usize = 100;
+
+async fn reindex(
+    state: &ServerState,
+    job_id: u64,
+    scope: Option<&Path>,
+) -> anyhow::Result<ReindexReport> {
+    let in_scope = |path: &Path| scope.is_none_or(|scope| path.starts_with(scope));
+    let mut indexed: HashMap<String, u64> = get_files(&state.sqlite)
+        .await?
+        .into_iter()
+        .filter(|(path, _)| in_scope(Path::new(path)))
+        .collect();
+    let mut report = ReindexReport::default();
+
+    let cache = &state.cache;
+    let files: Vec<PathBuf> = state
+        .cache
+        .org_files()?
+        .filter_map(|file_or_error| {
+            file_or_error
+                .inspect_err(|err| tracing::error!("{err}"))
+                .ok()
+        })
+        .filter(|path| cache.relative(path).is_some_and(|path| in_scope(&path)))
+        .collect();
+    let total = files.len();
+
+    for (done, file_path) in files.into_iter().enumerate() {
+        if done % PROGRESS_INTERVAL == 0 {
+            state.broadcast_to_websockets(WebSocketMessage::ReindexProgress {
+                job_id,
+                done,
+                total,
+            });
+        }
+        let cache_entry = match cache.entry(&file_path) {
+            Ok(entry) => entry,
+            Err(err) => {
+                tracing::error!("{err}");
+                if let Some(relative) = cache.relative(&file_path) {
+                    // Drop what was indexed before, only the problem is kept
+                    let file = relative.to_string_lossy().to_string();
+                    if indexed.remove(&file).is_some() {
+                        delete_file(&state.sqlite, &file).await?;
+                        state.cache.remove_file(file.as_ref());
+                        report.removed.push(file.clone());
+                    }
+                    diagnostics::record_file_error(&state.sqlite, &file, &err).await;
+                }
+                continue;
+            }
+        };
+
+        let path = cache_entry.path().to_string_lossy().to_string();
+        match indexed.remove(&path) {
+            None => report.added.push(path.clone()),
+            Some(hash) if hash != cache_entry.get_hash() => report.changed.push(path.clone()),
+            Some(_) => {}
+        }
+
+        if let Err(err) = watcher::index_entry(state, cache_entry).await {
+            tracing::error!("Failed to reindex {path}: {err}");
+        }
+        report.files += 1;
+    }
+    state.broadcast_to_websockets(WebSocketMessage::ReindexProgress {
+        job_id,
+        done: total,
+        total,
+    });
+
+    for path in indexed.into_keys() {
+        delete_file(&state.sqlite, &path).await?;
+        state.cache.remove_file(path.as_ref());
+        report.removed.push(path);
+    }
+
+    report.added.sort();
+    report.changed.sort();
+    report.removed.sort();
+    report.finished_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .and_then(|since| i64::try_from(since.as_secs()).ok());
+
+    Ok(report)
+}
+
+/// Parse a `HH:MM` time of day.
+fn parse_schedule(schedule: &str) -> Option<Time> {
+    let (hour, minute) = schedule.trim().split_once(':')?;
+    Time::from_hms(hour.parse().ok()?, minute.parse().ok()?, 0).ok()
+}
+
+/// The next point in time after `now` at the time of day `at`.
+fn next_run(now: OffsetDateTime, at: Time) -> OffsetDateTime {
+    let today = now.replace_time(at);
+    if today > now {
+        today
+    } else {
+        today + Duration::days(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::{datetime, time};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_schedule() {
+        assert_eq!(parse_schedule("03:00"), Some(time!(03:00)));
+        assert_eq!(parse_schedule("23:59"), Some(time!(23:59)));
+        assert_eq!(parse_schedule("24:00"), None);
+        assert_eq!(parse_schedule("3"), None);
+    }
+
+    #[test]
+    fn test_next_run() {
+        let now = datetime!(2024-03-15 02:00 UTC);
+        assert_eq!(next_run(now, time!(03:00)), datetime!(2024-03-15 03:00 UTC));
+        let now = datetime!(2024-03-15 03:00 UTC);
+        assert_eq!(next_run(now, time!(03:00)), datetime!(2024-03-16 03:00 UTC));
+    }
+}
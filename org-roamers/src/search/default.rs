@@ -4,7 +4,9 @@ use anyhow::Result;
 use futures_util::StreamExt;
 use sqlx::SqlitePool;
 
-use crate::{search::SearchResultSender, transform::title::TitleSanitizer, ServerState};
+use crate::{
+    config::is_excluded, search::SearchResultSender, transform::title::TitleSanitizer, ServerState,
+};
 
 #[derive(PartialEq, Debug)]
 pub struct ForNode<'a> {
@@ -34,6 +36,7 @@ impl<'a> ForNode<'a> {
         con: &SqlitePool,
         sender: &mut SearchResultSender,
         title_sanitizer: F,
+        exclude_tags: &[String],
     ) -> anyhow::Result<()> {
         let param = format_search_param(&self.node_search);
         // Search both node titles and aliases, using DISTINCT to avoid duplicates
@@ -59,11 +62,12 @@ impl<'a> ForNode<'a> {
                         .iter()
                         .any(|f| f.to_lowercase() == e.0.to_lowercase())
                 });
-                if p {
+                let tags: Vec<String> = tags.into_iter().map(|e| e.0).collect();
+                if p && !is_excluded(exclude_tags, &tags) {
                     if let Err(err) = sender.send(
                         title_sanitizer(&element.1).into(),
                         element.0.into(),
-                        tags.into_iter().map(|e| e.0).collect(),
+                        tags,
                         None,
                     ) {
                         tracing::error!("Error sending: {err}");
@@ -76,18 +80,17 @@ impl<'a> ForNode<'a> {
                 let stmnt = "SELECT node_id, tag FROM tags WHERE node_id = ?";
                 let tags: Vec<(String,)> =
                     sqlx::query_as(stmnt).bind(to_query).fetch_all(con).await?;
+                let tags: Vec<String> = tags.into_iter().map(|e| e.0).collect();
+                if is_excluded(exclude_tags, &tags) {
+                    continue;
+                }
                 let title = if row.1.is_empty() {
                     tracing::error!("Title is empty: {:?}", row);
                     String::new()
                 } else {
                     title_sanitizer(&row.1)
                 };
-                if let Err(err) = sender.send(
-                    title.into(),
-                    row.0.into(),
-                    tags.into_iter().map(|e| e.0).collect(),
-                    None,
-                ) {
+                if let Err(err) = sender.send(title.into(), row.0.into(), tags, None) {
                     tracing::error!("Error sending: {err}");
                 };
             }
@@ -120,6 +123,7 @@ impl<'a> ForTag<'a> {
         con: &SqlitePool,
         sender: &mut SearchResultSender,
         title_sanitizer: F,
+        exclude_tags: &[String],
     ) -> anyhow::Result<()> {
         let params = format_tag_param(&self.tag_search);
         let stmnt = "SELECT node_id, tag FROM tags WHERE LOWER(tag) IN ?";
@@ -130,7 +134,17 @@ impl<'a> ForTag<'a> {
             .unzip()
             .await;
         const STMNT: &str = "SELECT id, title FROM nodes WHERE id = ?";
+        const TAGS_STMNT: &str = "SELECT tag FROM tags WHERE node_id = ?";
         for id in ids {
+            if !exclude_tags.is_empty() {
+                let node_tags: Vec<String> = sqlx::query_scalar(TAGS_STMNT)
+                    .bind(&id)
+                    .fetch_all(con)
+                    .await?;
+                if is_excluded(exclude_tags, &node_tags) {
+                    continue;
+                }
+            }
             let tags = tags.clone();
             let (id, display): (String, String) =
                 sqlx::query_as(STMNT).bind(id).fetch_one(con).await?;
@@ -201,10 +215,17 @@ impl<'a> Search<'a> {
         };
 
         let sqlite = con.sqlite.clone();
+        let exclude_tags = &con.config.search.exclude_tags;
 
         match self {
-            Self::ForNode(node) => node.search(&sqlite, sender, title_sanitizer).await,
-            Self::ForTag(tag) => tag.search(&sqlite, sender, title_sanitizer).await,
+            Self::ForNode(node) => {
+                node.search(&sqlite, sender, title_sanitizer, exclude_tags)
+                    .await
+            }
+            Self::ForTag(tag) => {
+                tag.search(&sqlite, sender, title_sanitizer, exclude_tags)
+                    .await
+            }
         }
     }
 }
@@ -224,12 +245,15 @@ impl DefaultSearch {
 
     pub async fn feed(&mut self, state: Arc<ServerState>, f: &super::Feeder) -> anyhow::Result<()> {
         let query = f.s.clone();
-        let mut sender = self.sender.clone();
+        let mut sender = self.sender.for_search(f.group_by_file);
 
         // Wrap the blocking database operation in spawn_blocking
         tokio::spawn(async move {
             let search = Search::new(&query);
-            if let Err(e) = search.search(&mut sender, state).await {
+            if let Err(e) = search.search(&mut sender, state.clone()).await {
+                tracing::error!("Search error: {e}");
+            }
+            if let Err(e) = sender.flush(&state.sqlite).await {
                 tracing::error!("Search error: {e}");
             }
         });
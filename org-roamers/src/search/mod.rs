@@ -1,6 +1,7 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
 use tokio::sync::mpsc;
 
 use crate::{
@@ -10,15 +11,39 @@ use crate::{
 };
 
 mod default;
+pub(crate) mod stemming;
 mod text_search;
 
+#[derive(Clone)]
 pub struct Feeder {
     s: String,
+    /// Send one hit per file with the number of hits in it.
+    group_by_file: bool,
+    /// Match files containing words that start with the words of the query
+    /// instead of whole words. Stemming is skipped.
+    prefix: bool,
 }
 
 impl Feeder {
-    pub fn new(s: String) -> Self {
-        Self { s }
+    pub fn new(s: String, group_by_file: bool) -> Self {
+        Self {
+            s,
+            group_by_file,
+            prefix: false,
+        }
+    }
+
+    pub fn with_prefix(mut self, prefix: bool) -> Self {
+        self.prefix = prefix;
+        self
+    }
+
+    pub fn query(&self) -> &str {
+        &self.s
+    }
+
+    pub fn is_prefix(&self) -> bool {
+        self.prefix
     }
 }
 
@@ -26,6 +51,9 @@ impl Feeder {
 pub struct SearchResultSender {
     provider_id: usize,
     sender: mpsc::Sender<SearchResultEntry>,
+    /// Hits held back until the provider is done. Only set if hits are
+    /// grouped by file.
+    held_back: Option<Arc<Mutex<Vec<SearchResultEntry>>>>,
 }
 
 impl SearchResultSender {
@@ -33,6 +61,7 @@ impl SearchResultSender {
         Self {
             provider_id,
             sender,
+            held_back: None,
         }
     }
 
@@ -40,6 +69,39 @@ impl SearchResultSender {
         self.provider_id
     }
 
+    /// Sender for a single search. If `group_by_file` is set, hits are held
+    /// back until [`SearchResultSender::flush`] is called.
+    pub fn for_search(&self, group_by_file: bool) -> Self {
+        Self {
+            provider_id: self.provider_id,
+            sender: self.sender.clone(),
+            held_back: group_by_file.then(Default::default),
+        }
+    }
+
+    /// Send the held back hits grouped by file.
+    pub async fn flush(&self, sqlite: &SqlitePool) -> anyhow::Result<()> {
+        let Some(held_back) = &self.held_back else {
+            return Ok(());
+        };
+        let hits = std::mem::take(&mut *held_back.lock().unwrap());
+
+        const STMNT: &str = "SELECT file FROM nodes WHERE id = ?";
+        let mut located = Vec::with_capacity(hits.len());
+        for hit in hits {
+            let file: Option<String> = sqlx::query_scalar(STMNT)
+                .bind(hit.id.id())
+                .fetch_optional(sqlite)
+                .await?;
+            located.push((hit, file.unwrap_or_default()));
+        }
+
+        for entry in group_by_file(located) {
+            self.sender.try_send(entry)?;
+        }
+        Ok(())
+    }
+
     pub fn send(
         &self,
         title: RoamTitle,
@@ -47,17 +109,43 @@ impl SearchResultSender {
         tags: Vec<String>,
         preview: Option<(String, usize, usize)>,
     ) -> anyhow::Result<()> {
-        self.sender.try_send(SearchResultEntry {
+        let entry = SearchResultEntry {
             provider: self.provider_id,
             title,
             id,
             tags,
             preview,
-        })?;
+            file: None,
+            matches: None,
+        };
+        match &self.held_back {
+            Some(held_back) => held_back.lock().unwrap().push(entry),
+            None => self.sender.try_send(entry)?,
+        }
         Ok(())
     }
 }
 
+/// Merge hits of the same file into its first hit, which gets the file and
+/// the number of hits. Files keep the order of their first hit.
+fn group_by_file(hits: Vec<(SearchResultEntry, String)>) -> Vec<SearchResultEntry> {
+    let mut grouped: Vec<SearchResultEntry> = Vec::new();
+    for (hit, file) in hits {
+        match grouped
+            .iter_mut()
+            .find(|entry| entry.file.as_deref() == Some(file.as_str()))
+        {
+            Some(entry) => *entry.matches.get_or_insert(1) += 1,
+            None => grouped.push(SearchResultEntry {
+                file: Some(file),
+                matches: Some(1),
+                ..hit
+            }),
+        }
+    }
+    grouped
+}
+
 // TODO: move to src/server/types.rs
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResultEntry {
@@ -70,6 +158,12 @@ pub struct SearchResultEntry {
     /// - the second and third element give the range where the matching exactly
     ///   happened.
     pub preview: Option<(String, usize, usize)>,
+    /// File of the hit. Only set if hits are grouped by file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// Number of hits in `file`. Only set if hits are grouped by file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matches: Option<usize>,
 }
 
 pub enum SearchProvider {
@@ -128,7 +222,7 @@ impl SearchProviderList {
         // Since we can't easily do that with mutable references, we'll spawn tasks directly
         for provider in &mut self.providers {
             let state_clone = state.clone();
-            let query = f.s.clone();
+            let feeder = f.clone();
 
             // Spawn each provider's feed as a separate task
             let task = match provider {
@@ -137,7 +231,7 @@ impl SearchProviderList {
                     tokio::spawn(async move {
                         // TODO: there appears to be no use for the Self::providers...
                         let mut ds = DefaultSearch::new(sender);
-                        ds.feed(state_clone, &Feeder::new(query)).await
+                        ds.feed(state_clone, &feeder).await
                     })
                 }
                 SearchProvider::FullTextSearch(fts) => {
@@ -148,7 +242,7 @@ impl SearchProviderList {
                             sender,
                             cancel_token,
                         };
-                        fts.feed(state_clone, &Feeder::new(query)).await
+                        fts.feed(state_clone, &feeder).await
                     })
                 }
             };
@@ -182,3 +276,48 @@ impl SearchProviderList {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(id: &str, file: &str) -> (SearchResultEntry, String) {
+        let entry = SearchResultEntry {
+            provider: 0,
+            title: RoamTitle::from(id),
+            id: RoamID::from(id),
+            tags: vec![],
+            preview: None,
+            file: None,
+            matches: None,
+        };
+        (entry, file.to_string())
+    }
+
+    #[test]
+    fn test_group_by_file() {
+        let hits = vec![
+            hit("a", "journal.org"),
+            hit("b", "notes.org"),
+            hit("c", "journal.org"),
+            hit("d", "journal.org"),
+        ];
+        let grouped: Vec<_> = group_by_file(hits)
+            .into_iter()
+            .map(|entry| {
+                (
+                    entry.id.id().to_string(),
+                    entry.file.unwrap(),
+                    entry.matches,
+                )
+            })
+            .collect();
+        assert_eq!(
+            grouped,
+            vec![
+                ("a".to_string(), "journal.org".to_string(), Some(3)),
+                ("b".to_string(), "notes.org".to_string(), Some(1)),
+            ]
+        );
+    }
+}
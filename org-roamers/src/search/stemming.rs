@@ -0,0 +1,55 @@
+use std::collections::HashSet;
+
+use rust_stemmers::{Algorithm, Stemmer};
+
+/// Matches text that contains every word of a query, compared by their stems.
+pub struct StemMatcher {
+    stemmer: Stemmer,
+    stems: Vec<String>,
+}
+
+impl StemMatcher {
+    pub fn new(algorithm: Algorithm, query: &str) -> Self {
+        let stemmer = Stemmer::create(algorithm);
+        let stems = words(query)
+            .map(|word| stemmer.stem(&word).to_string())
+            .collect();
+        Self { stemmer, stems }
+    }
+
+    /// Stems of the words of the query.
+    pub fn stems(&self) -> &[String] {
+        &self.stems
+    }
+
+    pub fn matches(&self, content: &str) -> bool {
+        if self.stems.is_empty() {
+            return false;
+        }
+        let stems: HashSet<String> = words(content)
+            .map(|word| self.stemmer.stem(&word).to_string())
+            .collect();
+        self.stems.iter().all(|stem| stems.contains(stem))
+    }
+}
+
+/// Lowercase words of `s`.
+pub(crate) fn words(s: &str) -> impl Iterator<Item = String> + '_ {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stem_matcher() {
+        let matcher = StemMatcher::new(Algorithm::English, "linking notes");
+        assert!(matcher.matches("A note that links to others."));
+        assert!(matcher.matches("Notes get linked."));
+        assert!(!matcher.matches("Nothing linked here."));
+        assert!(!StemMatcher::new(Algorithm::English, "").matches("notes"));
+    }
+}
@@ -1,17 +1,15 @@
 use std::sync::Arc;
 
-use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
 use tokio_util::sync::CancellationToken;
 
 use crate::{
-    search::SearchResultSender,
+    config::is_excluded,
+    search::{stemming::StemMatcher, SearchResultSender},
     server::types::{RoamID, RoamTitle},
+    sqlite::fts,
     ServerState,
 };
 
-// TODO: make this configurable.
-const THRESHOLD: i64 = 90;
-
 pub struct FullTextSeach {
     pub(crate) cancel_token: CancellationToken,
     pub(crate) sender: SearchResultSender,
@@ -36,79 +34,102 @@ impl FullTextSeach {
     }
 
     pub async fn feed(&mut self, state: Arc<ServerState>, f: &super::Feeder) -> anyhow::Result<()> {
-        let matcher = SkimMatcherV2::default();
         let query = f.s.to_string();
+        let prefix = f.prefix;
+        let stem_matcher = state
+            .config
+            .search
+            .stemming
+            .filter(|_| !prefix)
+            .map(|algorithm| StemMatcher::new(algorithm, &query));
         let cancel_token = self.cancel_token.clone();
 
-        const NODE_STMNT: &str = r#"
+        const NODES_STMNT: &str = r#"
         SELECT title, id FROM nodes
-        WHERE id = ?;
+        WHERE file = ?;
         "#;
 
         const TAGS_STMNT: &str = r#"
         SELECT tag FROM tags
         WHERE node_id = ?"#;
 
-        let sender = self.sender.clone();
+        let sender = self.sender.for_search(f.group_by_file);
 
         tokio::spawn(async move {
-            // Collect cache entries and clone sqlite pool before any async operations
-            let (cache_entries, sqlite) = {
-                let cache_entries: Vec<_> = state
-                    .cache
-                    .iter()
-                    .map(|r| {
-                        let (k, v) = r.pair();
-                        (k.clone(), v.content().to_string())
-                    })
-                    .collect();
-                (cache_entries, state.sqlite.clone())
+            let sqlite = state.sqlite.clone();
+            let exclude_tags = &state.config.search.exclude_tags;
+
+            // Inflected words start with their stem in most cases, the stem
+            // matcher then drops the files that do not match.
+            let hits = match &stem_matcher {
+                Some(stem_matcher) => {
+                    let stems = stem_matcher.stems().join(" ");
+                    fts::search(&sqlite, &stems, true).await
+                }
+                None => fts::search(&sqlite, &query, prefix).await,
+            };
+            let hits = match hits {
+                Ok(hits) => hits,
+                Err(err) => {
+                    tracing::error!("Full text search failed: {err}");
+                    return;
+                }
             };
 
-            for (key, content) in cache_entries {
+            for (file, preview) in hits {
                 if cancel_token.is_cancelled() {
                     return;
                 }
 
-                if let Some((score, _index_types)) = matcher.fuzzy_indices(&content, &query) {
-                    if score >= THRESHOLD {
-                        let (title, id): (String, String) = match sqlx::query_as(NODE_STMNT)
-                            .bind(key.id())
-                            .fetch_one(&sqlite)
-                            .await
-                        {
-                            Ok(pair) => pair,
-                            Err(_) => {
-                                tracing::error!("No entry found for {}", key.id());
-                                continue;
-                            }
-                        };
-
-                        let (title, id) = (RoamTitle::from(title), RoamID::from(id));
-
-                        let tags: Vec<String> = match sqlx::query_as(TAGS_STMNT)
-                            .bind(id.id())
-                            .fetch_all(&sqlite)
-                            .await
-                        {
-                            Ok(tags) => tags.into_iter().map(|e: (String,)| e.0).collect(),
-                            Err(err) => {
-                                tracing::error!("An error occured: {err}");
-                                vec![]
-                            }
-                        };
-
-                        // TODO: preview not implemented.
-                        if let Err(err) = sender.send(title, id, tags, None) {
-                            tracing::error!("{err}");
-                        };
-
-                        if cancel_token.is_cancelled() {
-                            return;
+                let nodes: Vec<(String, String)> = match sqlx::query_as(NODES_STMNT)
+                    .bind(&file)
+                    .fetch_all(&sqlite)
+                    .await
+                {
+                    Ok(nodes) => nodes,
+                    Err(err) => {
+                        tracing::error!("No nodes found for {file}: {err}");
+                        continue;
+                    }
+                };
+
+                if let Some(stem_matcher) = &stem_matcher {
+                    let content = nodes
+                        .first()
+                        .and_then(|(_, id)| state.cache.retrieve(&id.as_str().into()));
+                    if !content.is_some_and(|entry| stem_matcher.matches(entry.content())) {
+                        continue;
+                    }
+                }
+
+                for (title, id) in nodes {
+                    let (title, id) = (RoamTitle::from(title), RoamID::from(id));
+
+                    let tags: Vec<String> = match sqlx::query_as(TAGS_STMNT)
+                        .bind(id.id())
+                        .fetch_all(&sqlite)
+                        .await
+                    {
+                        Ok(tags) => tags.into_iter().map(|e: (String,)| e.0).collect(),
+                        Err(err) => {
+                            tracing::error!("An error occured: {err}");
+                            vec![]
                         }
+                    };
+
+                    if is_excluded(exclude_tags, &tags) {
+                        continue;
                     }
+
+                    if let Err(err) = sender.send(title, id, tags, Some(preview.clone())) {
+                        tracing::error!("{err}");
+                    };
                 }
             }
+
+            if let Err(err) = sender.flush(&sqlite).await {
+                tracing::error!("{err}");
+            }
         });
 
         Ok(())
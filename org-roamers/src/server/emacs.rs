@@ -1,12 +1,29 @@
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
+use dashmap::DashMap;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::Notify;
+use tokio::time::{Duration, Instant};
+
+use crate::server::types::{InvalidRoamID, RoamID};
 
 pub enum EmacsRequest {
-    /// Arg: id where point is in
-    BufferOpened(String),
+    /// Node where point is in, and the connection that should follow it.
+    /// The most recently active connection of the user follows without
+    /// `connection`.
+    BufferOpened { id: RoamID, connection: Option<u64> },
     /// Arg: string modified of filename
     BufferModified(String),
+    /// Insert an `id:` link to `dest` into the file of `source`.
+    CreateLink {
+        source: RoamID,
+        dest: RoamID,
+        /// Character offset in the file, the end of the file if `None`.
+        position: Option<usize>,
+        /// Link description, the title of `dest` if `None`.
+        description: Option<String>,
+    },
 }
 
 #[derive(Debug, Clone, thiserror::Error)]
@@ -19,6 +36,16 @@ pub enum EmacsRequestError {
     NoTaskProvided,
     #[error("Unsupported task: {0}")]
     UnsupportedTask(String),
+    #[error("No source provided")]
+    NoSourceProvided,
+    #[error("No dest provided")]
+    NoDestProvided,
+    #[error(transparent)]
+    InvalidID(#[from] InvalidRoamID),
+    #[error("Invalid position: {0}")]
+    InvalidPosition(String),
+    #[error("Invalid connection: {0}")]
+    InvalidConnection(String),
 }
 
 impl IntoResponse for EmacsRequestError {
@@ -32,15 +59,95 @@ pub fn route_emacs_traffic(
     params: HashMap<String, String>,
 ) -> Result<EmacsRequest, EmacsRequestError> {
     match params.get("task") {
-        Some(task) if task == "opened" => match params.get("id") {
-            Some(id) => Ok(EmacsRequest::BufferOpened(id.clone())),
-            None => Err(EmacsRequestError::NoIDProvided),
-        },
+        Some(task) if task == "opened" => {
+            let id = params.get("id").ok_or(EmacsRequestError::NoIDProvided)?;
+            let connection = match params.get("connection") {
+                Some(connection) => Some(
+                    connection
+                        .parse()
+                        .map_err(|_| EmacsRequestError::InvalidConnection(connection.clone()))?,
+                ),
+                None => None,
+            };
+            Ok(EmacsRequest::BufferOpened {
+                id: RoamID::parse(id)?,
+                connection,
+            })
+        }
         Some(task) if task == "modified" => match params.get("file") {
             Some(file) => Ok(EmacsRequest::BufferModified(file.clone())),
             None => Err(EmacsRequestError::NoFileProvided),
         },
+        Some(task) if task == "link" => {
+            let source = params
+                .get("source")
+                .ok_or(EmacsRequestError::NoSourceProvided)?;
+            let dest = params
+                .get("dest")
+                .ok_or(EmacsRequestError::NoDestProvided)?;
+            let position = match params.get("position") {
+                Some(position) => Some(
+                    position
+                        .parse()
+                        .map_err(|_| EmacsRequestError::InvalidPosition(position.clone()))?,
+                ),
+                None => None,
+            };
+            Ok(EmacsRequest::CreateLink {
+                source: RoamID::parse(source)?,
+                dest: RoamID::parse(dest)?,
+                position,
+                description: params.get("description").cloned(),
+            })
+        }
         Some(task) => Err(EmacsRequestError::UnsupportedTask(task.clone())),
         None => Err(EmacsRequestError::NoTaskProvided),
     }
 }
+
+/// Node selections made in the web UI that Emacs can follow. Only the latest
+/// selection of each user is kept, Emacs is not interested in the nodes that
+/// were skipped.
+#[derive(Default)]
+pub struct EmacsFollow {
+    /// owner -> (sequence number, selected node)
+    selections: DashMap<String, (u64, RoamID)>,
+    next_seq: AtomicU64,
+    notify: Notify,
+}
+
+impl EmacsFollow {
+    /// Publish a node selection of `owner`.
+    pub fn publish(&self, owner: &str, id: RoamID) {
+        let seq = self.next_seq.fetch_add(1, Ordering::SeqCst) + 1;
+        self.selections.insert(owner.to_string(), (seq, id));
+        self.notify.notify_waiters();
+    }
+
+    /// Sequence number of the latest selection of `owner`.
+    pub fn current(&self, owner: &str) -> u64 {
+        self.selections.get(owner).map(|sel| sel.0).unwrap_or(0)
+    }
+
+    /// Wait until `owner` selects a node with a sequence number above `since`.
+    /// Returns `None` on timeout.
+    pub async fn wait(&self, owner: &str, since: u64, timeout: Duration) -> Option<(u64, RoamID)> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            // Register before checking, otherwise a publish in between is lost.
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if let Some(selection) = self.selections.get(owner) {
+                if selection.0 > since {
+                    return Some(selection.clone());
+                }
+            }
+
+            if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                return None;
+            }
+        }
+    }
+}
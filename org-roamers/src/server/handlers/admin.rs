@@ -0,0 +1,246 @@
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{Path as AxumPath, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+use crate::{
+    client::{message::WebSocketMessage, topic::Topic},
+    reindex,
+    server::{
+        middleware::auth::CurrentUser,
+        services::{
+            archive_service,
+            audit_service::{self, AuditAction},
+        },
+        types::{AuditEvent, AuditResponse, ConnectionInfo, ConnectionsResponse, ReindexJob},
+    },
+    sqlite::audit,
+    ServerState,
+};
+
+const DEFAULT_AUDIT_LIMIT: i64 = 100;
+const MAX_AUDIT_LIMIT: i64 = 1000;
+
+#[derive(Deserialize)]
+pub struct ReindexParams {
+    /// File or directory relative to the org-roamers root.
+    path: Option<String>,
+}
+
+/// POST /admin/reindex?path=
+/// Start reindexing the vault, or only a file or directory of it. Progress
+/// is broadcast over the websocket under the returned job id.
+pub async fn reindex_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Query(params): Query<ReindexParams>,
+) -> Response {
+    let scope = match params.path.as_deref().map(parse_scope) {
+        None => None,
+        Some(Some(scope)) if app_state.cache.resolve(&scope).exists() => Some(scope),
+        Some(Some(_)) => {
+            return (StatusCode::NOT_FOUND, "No such file or directory").into_response()
+        }
+        Some(None) => return (StatusCode::BAD_REQUEST, "Invalid path").into_response(),
+    };
+
+    let target = scope.as_ref().map(|scope| scope.to_string_lossy());
+    audit_service::record(
+        &app_state.sqlite,
+        user.0.as_deref(),
+        AuditAction::Reindex,
+        target.as_deref(),
+    )
+    .await;
+
+    let job_id = reindex::next_job_id(&app_state);
+    tokio::spawn(async move { reindex::run(&app_state, job_id, scope).await });
+
+    ReindexJob { job_id }.into_response()
+}
+
+#[derive(Deserialize)]
+pub struct ArchiveParams {
+    /// Overrides the configured months without changes.
+    months: Option<u32>,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// POST /admin/archive?months=&dry_run=
+/// Move nodes untouched for the configured number of months into the
+/// archive directory and report what was moved. Whole files qualify if they
+/// were not modified since and no recently modified file links to them,
+/// headlines if all their dates are older. The content is appended to
+/// `<file>_archive` files like `org-archive-subtree` does. With `dry_run`
+/// the candidates are only reported.
+pub async fn archive_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Query(params): Query<ArchiveParams>,
+) -> Response {
+    let months = params.months.unwrap_or(app_state.config.archive.months);
+    if months == 0 {
+        return (StatusCode::BAD_REQUEST, "months must be positive").into_response();
+    }
+    let now = OffsetDateTime::now_utc();
+    let report = match archive_service::archive(&app_state, months, params.dry_run, now).await {
+        Ok(report) => report,
+        Err(err) => {
+            tracing::error!("Failed to archive nodes: {err}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    if !report.dry_run && (!report.files.is_empty() || !report.subtrees.is_empty()) {
+        let mut files: Vec<&str> = report
+            .files
+            .iter()
+            .map(|archived| archived.file.as_str())
+            .chain(
+                report
+                    .subtrees
+                    .iter()
+                    .map(|archived| archived.file.as_str()),
+            )
+            .collect();
+        files.dedup();
+        for file in &files {
+            audit_service::record(
+                &app_state.sqlite,
+                user.0.as_deref(),
+                AuditAction::Archive,
+                Some(file),
+            )
+            .await;
+        }
+        app_state.notify_vault_changed();
+        app_state.broadcast_to_websockets(WebSocketMessage::StatusUpdate {
+            files_changed: files.len(),
+        });
+    }
+    report.into_response()
+}
+
+#[derive(Deserialize)]
+pub struct AuditParams {
+    user: Option<String>,
+    action: Option<AuditAction>,
+    /// Unix timestamp in seconds.
+    since: Option<i64>,
+    limit: Option<i64>,
+}
+
+/// GET /admin/audit?user=&action=&since=&limit=
+/// Query the audit log, newest events first.
+pub async fn audit_log_handler(
+    State(app_state): State<Arc<ServerState>>,
+    Query(params): Query<AuditParams>,
+) -> Response {
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_AUDIT_LIMIT)
+        .clamp(1, MAX_AUDIT_LIMIT);
+    let events = audit::get_events(
+        &app_state.sqlite,
+        params.user.as_deref(),
+        params.action.map(AuditAction::as_str),
+        params.since,
+        limit,
+    )
+    .await;
+
+    match events {
+        Ok(rows) => AuditResponse {
+            events: rows.into_iter().map(AuditEvent::from).collect(),
+        }
+        .into_response(),
+        Err(err) => {
+            tracing::error!("Failed to query audit log: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// GET /admin/connections
+/// Connected websocket and server-sent events clients.
+pub async fn connections_handler(State(app_state): State<Arc<ServerState>>) -> ConnectionsResponse {
+    let mut connections: Vec<ConnectionInfo> = app_state
+        .websocket_connections
+        .iter()
+        .map(|entry| {
+            let (id, connection) = entry.pair();
+            ConnectionInfo {
+                id: *id,
+                kind: connection.kind,
+                user: connection.user.clone(),
+                user_agent: connection.user_agent.clone(),
+                connected_at: connection.connected_at,
+                working_id: connection.working_id.clone(),
+                messages_sent: connection.messages_sent,
+                messages_dropped: connection.messages_dropped,
+                queued: connection.queued(),
+                topics: connection.topics.as_ref().map(|topics| {
+                    let mut topics: Vec<Topic> = topics.iter().cloned().collect();
+                    topics.sort_by_key(Topic::to_string);
+                    topics
+                }),
+            }
+        })
+        .collect();
+    connections.sort_by_key(|connection| connection.id);
+    ConnectionsResponse { connections }
+}
+
+/// DELETE /admin/connections/{id}
+/// Disconnect a client. Its channel is closed, which ends the connection.
+pub async fn disconnect_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    AxumPath(id): AxumPath<u64>,
+) -> StatusCode {
+    if app_state.websocket_connections.remove(&id).is_none() {
+        return StatusCode::NOT_FOUND;
+    }
+
+    audit_service::record(
+        &app_state.sqlite,
+        user.0.as_deref(),
+        AuditAction::Disconnect,
+        Some(&id.to_string()),
+    )
+    .await;
+    StatusCode::NO_CONTENT
+}
+
+/// Only relative paths that stay below the root are accepted.
+fn parse_scope(path: &str) -> Option<PathBuf> {
+    let path = Path::new(path.trim_matches('/'));
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_)))
+        .then(|| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scope() {
+        assert_eq!(parse_scope("daily"), Some(PathBuf::from("daily")));
+        assert_eq!(
+            parse_scope("/daily/x.org"),
+            Some(PathBuf::from("daily/x.org"))
+        );
+        assert_eq!(parse_scope("../secret"), None);
+        assert_eq!(parse_scope("daily/../../secret"), None);
+    }
+}
@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use time::Duration;
+
+use crate::{server::services::agenda_service, transform::timestamps, ServerState};
+
+#[derive(Deserialize)]
+pub struct AgendaParams {
+    /// `YYYY-MM-DD`, defaults to today.
+    from: Option<String>,
+    /// `YYYY-MM-DD`, defaults to the configured number of days after `from`.
+    to: Option<String>,
+}
+
+/// GET /agenda?from=&to=
+/// Headlines scheduled or due within a range of days, like the Org agenda,
+/// including headlines without an `ID`. Tasks with one of
+/// `agenda.done_keywords` are left out; without `to` the next `agenda.days`
+/// days are shown.
+pub async fn get_agenda_handler(
+    State(app_state): State<Arc<ServerState>>,
+    Query(params): Query<AgendaParams>,
+) -> Response {
+    let config = &app_state.config.agenda;
+    let today = app_state.now().date();
+    let from = match params.from.as_deref().map(timestamps::calendar_date) {
+        None => today,
+        Some(Some(from)) => from,
+        Some(None) => {
+            return (StatusCode::BAD_REQUEST, "Expected from as YYYY-MM-DD").into_response()
+        }
+    };
+    let to = match params.to.as_deref().map(timestamps::calendar_date) {
+        None => from + Duration::days(i64::from(config.days)),
+        Some(Some(to)) => to,
+        Some(None) => {
+            return (StatusCode::BAD_REQUEST, "Expected to as YYYY-MM-DD").into_response()
+        }
+    };
+    let (from, to) = (from.to_string(), to.to_string());
+
+    match agenda_service::get_agenda(&app_state.sqlite, &from, &to, &config.done_keywords).await {
+        Ok(agenda) => agenda.into_response(),
+        Err(err) => {
+            tracing::error!("Failed to load agenda: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
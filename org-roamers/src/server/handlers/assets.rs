@@ -1,27 +1,134 @@
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use std::{collections::HashMap, path::Path, sync::Arc};
 
 use axum::{
-    extract::{Query as AxumQuery, State},
-    http::StatusCode,
+    extract::{Multipart, Query as AxumQuery, State},
+    http::{HeaderMap, StatusCode},
     response::{IntoResponse, Response},
 };
 
-use crate::{server::services::asset_service, ServerState};
+use crate::{
+    server::{
+        middleware::auth::CurrentUser,
+        services::{
+            asset_service,
+            audit_service::{self, AuditAction},
+        },
+        types::{RoamID, UploadResponse, UploadedAsset},
+    },
+    ServerState,
+};
+
+/// Uploads are usually pasted screenshots, which easily exceed the default
+/// body limit of axum.
+pub const MAX_UPLOAD_SIZE: usize = 32 * 1024 * 1024;
 
+/// GET /assets?file=&v=
+/// Serve a file of the org-roamers root. `v` is the asset version added by
+/// the HTML export, which makes the response cacheable forever. Supports
+/// `Range` requests. With the default `asset_policy` the file must lie below
+/// its root; `..` is refused, and symlinks pointing outside unless
+/// `follow_symlinks` is set.
 pub async fn serve_assets_handler(
     AxumQuery(params): AxumQuery<HashMap<String, String>>,
     State(app_state): State<Arc<ServerState>>,
+    headers: HeaderMap,
 ) -> Response {
     match params.get("file") {
         Some(path) => {
-            let org_roam_path = app_state.cache.path();
+            let (root, path) = app_state.cache.locate(Path::new(path));
             let asset_policy = app_state.config.asset_policy;
-            asset_service::serve_assets(org_roam_path, PathBuf::from(path), asset_policy)
+            asset_service::serve_assets(
+                root,
+                path.to_path_buf(),
+                asset_policy,
+                app_state.config.follow_symlinks,
+                params.get("v").map(String::as_str),
+                &headers,
+            )
+            .await
         }
         None => StatusCode::NOT_FOUND.into_response(),
     }
 }
 
+/// POST /assets
+/// Store all files of a multipart upload in the attachment directory. If `id`
+/// is given, the returned links are relative to the file of that node.
+pub async fn upload_assets_handler(
+    AxumQuery(params): AxumQuery<HashMap<String, String>>,
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    mut multipart: Multipart,
+) -> Response {
+    let root = app_state.cache.path();
+    let id = match params.get("id").map(|id| RoamID::parse(id)).transpose() {
+        Ok(id) => id,
+        Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    };
+    let link_base = id
+        .and_then(|id| app_state.cache.retrieve(&id))
+        .map(|entry| entry.path().to_path_buf())
+        .unwrap_or_default();
+
+    let mut files = vec![];
+
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(err) => {
+                tracing::error!("Failed to read upload: {err}");
+                return (StatusCode::BAD_REQUEST, err.body_text()).into_response();
+            }
+        };
+
+        let Some(file_name) = field.file_name().map(ToString::to_string) else {
+            continue;
+        };
+
+        let data = match field.bytes().await {
+            Ok(data) => data,
+            Err(err) => {
+                tracing::error!("Failed to read upload {file_name}: {err}");
+                return (StatusCode::BAD_REQUEST, err.body_text()).into_response();
+            }
+        };
+
+        let path = match asset_service::store_upload(
+            root,
+            &app_state.config.attachment_dir,
+            &file_name,
+            &data,
+        ) {
+            Ok(path) => path,
+            Err(err) => {
+                tracing::error!("Failed to store upload {file_name}: {err}");
+                return (StatusCode::BAD_REQUEST, err.to_string()).into_response();
+            }
+        };
+
+        audit_service::record(
+            &app_state.sqlite,
+            user.0.as_deref(),
+            AuditAction::Upload,
+            Some(&path.to_string_lossy()),
+        )
+        .await;
+
+        let link = asset_service::relative_link(&link_base, &path);
+        files.push(UploadedAsset {
+            path: path.to_string_lossy().to_string(),
+            link: format!("[[file:{}]]", link.to_string_lossy()),
+        });
+    }
+
+    if files.is_empty() {
+        return (StatusCode::BAD_REQUEST, "No files provided").into_response();
+    }
+
+    UploadResponse { files }.into_response()
+}
+
 pub async fn fallback_handler(
     uri: axum::http::Uri,
     State(app_state): State<Arc<ServerState>>,
@@ -1,17 +1,31 @@
 use std::sync::Arc;
 
-use axum::{extract::State, http::StatusCode, response::Json};
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::Json,
+};
 use serde::{Deserialize, Serialize};
-use tower_sessions::Session;
+use time::{Duration, OffsetDateTime};
+use tower_sessions::{Expiry, Session};
 
+use crate::server::middleware::auth::record_activity;
+use crate::server::services::audit_service::{self, AuditAction};
 use crate::ServerState;
 
 const SESSION_USER_KEY: &str = "username";
+const SESSION_REMEMBER_KEY: &str = "remember_me";
+const SESSION_USER_AGENT_KEY: &str = "user_agent";
+const SESSION_CREATED_KEY: &str = "created_at";
 
 #[derive(Deserialize)]
 pub struct LoginRequest {
     pub username: String,
     pub password: String,
+    /// Keep the session for `remember_me_days` instead of the configured
+    /// expiry.
+    #[serde(default)]
+    pub remember_me: bool,
 }
 
 #[derive(Serialize)]
@@ -24,6 +38,24 @@ pub struct LoginResponse {
 pub struct SessionInfo {
     pub authenticated: bool,
     pub username: Option<String>,
+    /// Unix timestamp at which the session expires if it is not used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+}
+
+/// Expiry of "remember me" sessions. `None` if disabled.
+fn remember_me_expiry(state: &ServerState) -> Option<Expiry> {
+    let days = state
+        .config
+        .authentication
+        .as_ref()
+        .map_or(0, |auth| auth.session.remember_me_days);
+    (days > 0).then(|| Expiry::OnInactivity(Duration::days(days as i64)))
+}
+
+fn internal_error(err: tower_sessions::session::Error) -> StatusCode {
+    tracing::error!("Failed to update session: {}", err);
+    StatusCode::INTERNAL_SERVER_ERROR
 }
 
 /// POST /api/login
@@ -31,6 +63,7 @@ pub struct SessionInfo {
 pub async fn login_handler(
     State(state): State<Arc<ServerState>>,
     session: Session,
+    headers: HeaderMap,
     Json(credentials): Json<LoginRequest>,
 ) -> Result<Json<LoginResponse>, StatusCode> {
     use tracing::{info, warn};
@@ -52,7 +85,43 @@ pub async fn login_handler(
                 StatusCode::INTERNAL_SERVER_ERROR
             })?;
 
+        let remember_me = match remember_me_expiry(&state) {
+            Some(expiry) if credentials.remember_me => {
+                session.set_expiry(Some(expiry));
+                true
+            }
+            _ => false,
+        };
+        // Metadata shown when listing sessions
+        let user_agent = headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        session
+            .insert(SESSION_REMEMBER_KEY, remember_me)
+            .await
+            .map_err(internal_error)?;
+        session
+            .insert(SESSION_USER_AGENT_KEY, user_agent)
+            .await
+            .map_err(internal_error)?;
+        session
+            .insert(SESSION_CREATED_KEY, now)
+            .await
+            .map_err(internal_error)?;
+        record_activity(&session, true)
+            .await
+            .map_err(internal_error)?;
+
         info!("Login successful for user: {}", credentials.username);
+        audit_service::record(
+            &state.sqlite,
+            Some(&credentials.username),
+            AuditAction::Login,
+            None,
+        )
+        .await;
 
         Ok(Json(LoginResponse {
             success: true,
@@ -60,13 +129,23 @@ pub async fn login_handler(
         }))
     } else {
         warn!("Login failed for user: {}", credentials.username);
+        audit_service::record(
+            &state.sqlite,
+            Some(&credentials.username),
+            AuditAction::LoginFailed,
+            None,
+        )
+        .await;
         Err(StatusCode::UNAUTHORIZED)
     }
 }
 
 /// POST /api/logout
 /// Destroy session and logout user
-pub async fn logout_handler(session: Session) -> Result<StatusCode, StatusCode> {
+pub async fn logout_handler(
+    State(state): State<Arc<ServerState>>,
+    session: Session,
+) -> Result<StatusCode, StatusCode> {
     use tracing::info;
 
     // Get username before clearing session (for logging)
@@ -83,6 +162,7 @@ pub async fn logout_handler(session: Session) -> Result<StatusCode, StatusCode>
 
     if let Some(user) = username {
         info!("Logout successful for user: {}", user);
+        audit_service::record(&state.sqlite, Some(&user), AuditAction::Logout, None).await;
     }
 
     Ok(StatusCode::OK)
@@ -99,5 +179,40 @@ pub async fn check_session_handler(session: Session) -> Result<Json<SessionInfo>
     Ok(Json(SessionInfo {
         authenticated: username.is_some(),
         username,
+        expires_at: None,
+    }))
+}
+
+/// POST /api/session/refresh
+/// Extend the session of a logged in user. "Remember me" sessions get their
+/// full lifetime again.
+pub async fn refresh_session_handler(
+    State(state): State<Arc<ServerState>>,
+    session: Session,
+) -> Result<Json<SessionInfo>, StatusCode> {
+    let username: Option<String> = session
+        .get(SESSION_USER_KEY)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if username.is_none() {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let remember_me: bool = session
+        .get(SESSION_REMEMBER_KEY)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .unwrap_or(false);
+    if let Some(expiry) = remember_me_expiry(&state).filter(|_| remember_me) {
+        session.set_expiry(Some(expiry));
+    }
+    record_activity(&session, true)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(SessionInfo {
+        authenticated: true,
+        username,
+        expires_at: Some(session.expiry_date().unix_timestamp()),
     }))
 }
@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+};
+use serde::Deserialize;
+
+use crate::{
+    server::{
+        services::backlink_service,
+        types::{BacklinksResponse, RoamID},
+    },
+    ServerState,
+};
+
+#[derive(Deserialize)]
+pub struct BacklinksParams {
+    id: RoamID,
+}
+
+/// GET /backlinks?id=
+/// Nodes linking to a node, with the line and position of every link.
+pub async fn get_backlinks_handler(
+    State(app_state): State<Arc<ServerState>>,
+    Query(params): Query<BacklinksParams>,
+) -> Result<BacklinksResponse, StatusCode> {
+    backlink_service::get_backlinks(&app_state.sqlite, &app_state.cache, &params.id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to collect backlinks: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)
+}
@@ -0,0 +1,36 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+
+use crate::{server::services::board_service, ServerState};
+
+#[derive(Deserialize)]
+pub struct BoardParams {
+    tag: Option<String>,
+}
+
+/// GET /board?tag=
+/// Nodes with a TODO keyword grouped into one column per keyword.
+pub async fn get_board_handler(
+    State(app_state): State<Arc<ServerState>>,
+    Query(params): Query<BoardParams>,
+) -> Response {
+    let columns = &app_state.config.board.columns;
+    let tag = params
+        .tag
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty());
+    match board_service::get_board(&app_state.sqlite, columns, tag).await {
+        Ok(board) => board.into_response(),
+        Err(err) => {
+            tracing::error!("Failed to load board: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
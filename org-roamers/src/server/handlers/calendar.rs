@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+
+use crate::{server::services::calendar_service, ServerState};
+
+#[derive(Deserialize)]
+pub struct CalendarParams {
+    /// `YYYY-MM`
+    month: String,
+}
+
+/// GET /calendar?month=YYYY-MM
+/// List the nodes anchored to each day of a month.
+pub async fn get_calendar_handler(
+    State(app_state): State<Arc<ServerState>>,
+    Query(params): Query<CalendarParams>,
+) -> Response {
+    if !calendar_service::is_valid_month(&params.month) {
+        return (StatusCode::BAD_REQUEST, "Expected month as YYYY-MM").into_response();
+    }
+
+    match calendar_service::get_calendar(&app_state.sqlite, &params.month).await {
+        Ok(calendar) => calendar.into_response(),
+        Err(err) => {
+            tracing::error!("Failed to load calendar: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+
+use crate::{
+    client::message::WebSocketMessage,
+    server::{
+        middleware::auth::CurrentUser,
+        services::{
+            audit_service::{self, AuditAction},
+            capture_service::{self, Capture},
+        },
+        types::CaptureResponse,
+    },
+    transform::{node_builder, timestamps},
+    watcher, ServerState,
+};
+
+#[derive(Deserialize)]
+pub struct CaptureRequest {
+    template: Option<String>,
+    title: String,
+    url: Option<String>,
+    body: Option<String>,
+}
+
+/// POST /capture
+/// Create a new node from the payload of a browser extension or an
+/// `org-protocol://` handler. New files start with the front matter of
+/// `.templates/<dir>/template.org`, falling back to the closest parent
+/// directory and finally to `.templates/template.org`.
+pub async fn capture_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Json(request): Json<CaptureRequest>,
+) -> Response {
+    let name = request
+        .template
+        .as_deref()
+        .unwrap_or(capture_service::DEFAULT_TEMPLATE);
+    let Some(template) = app_state.config.capture_template(name) else {
+        return (StatusCode::NOT_FOUND, format!("Unknown template: {name}")).into_response();
+    };
+
+    if request.title.trim().is_empty() {
+        return (StatusCode::BAD_REQUEST, "Title is required").into_response();
+    }
+
+    let capture = Capture {
+        title: request.title.trim(),
+        url: request.url.as_deref().filter(|url| !url.is_empty()),
+        body: request.body.as_deref(),
+    };
+
+    let now = app_state.now();
+    let (id, path) = match capture_service::capture(&app_state.cache, template, &capture, now) {
+        Ok(created) => created,
+        Err(err) => {
+            tracing::error!("Failed to capture {}: {err}", capture.title);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    index_file(&app_state, &path).await;
+    audit_service::record(
+        &app_state.sqlite,
+        user.0.as_deref(),
+        AuditAction::Capture,
+        Some(id.id()),
+    )
+    .await;
+
+    CaptureResponse {
+        id,
+        file: path.to_string_lossy().to_string(),
+    }
+    .into_response()
+}
+
+#[derive(Deserialize)]
+pub struct DailyParams {
+    /// `YYYY-MM-DD`, defaults to today.
+    date: Option<String>,
+}
+
+/// POST /daily
+/// Get the daily note of a day, creating it from the `daily` capture template
+/// if it does not exist yet. New files get the front matter of the directory
+/// template like [`capture_handler`].
+pub async fn daily_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Query(params): Query<DailyParams>,
+) -> Response {
+    let Some(template) = app_state
+        .config
+        .capture_template(capture_service::DAILY_TEMPLATE)
+    else {
+        return (StatusCode::NOT_FOUND, "No daily template configured").into_response();
+    };
+
+    let now = app_state.now();
+    let day = match params.date.as_deref().map(timestamps::calendar_date) {
+        None => now,
+        Some(Some(date)) => date.midnight().assume_offset(now.offset()),
+        Some(None) => return (StatusCode::BAD_REQUEST, "Invalid date").into_response(),
+    };
+
+    let Some(path) = capture_service::daily_file(template, day) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Daily template must create new files",
+        )
+            .into_response();
+    };
+
+    if let Ok(content) = std::fs::read_to_string(app_state.cache.resolve(&path)) {
+        let file = path.to_string_lossy().to_string();
+        return match node_builder::get_nodes(&content, &file).into_iter().next() {
+            Some(node) => CaptureResponse {
+                id: node.uuid.into(),
+                file,
+            }
+            .into_response(),
+            None => (StatusCode::CONFLICT, "Daily note has no id").into_response(),
+        };
+    }
+
+    let title = capture_service::daily_title(day);
+    let capture = Capture {
+        title: &title,
+        url: None,
+        body: None,
+    };
+
+    let (id, path) = match capture_service::capture(&app_state.cache, template, &capture, day) {
+        Ok(created) => created,
+        Err(err) => {
+            tracing::error!("Failed to create daily note {title}: {err}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    index_file(&app_state, &path).await;
+    audit_service::record(
+        &app_state.sqlite,
+        user.0.as_deref(),
+        AuditAction::Daily,
+        Some(id.id()),
+    )
+    .await;
+
+    CaptureResponse {
+        id,
+        file: path.to_string_lossy().to_string(),
+    }
+    .into_response()
+}
+
+/// Index the file right away, the watcher might be disabled.
+async fn index_file(app_state: &ServerState, path: &std::path::Path) {
+    let path = app_state.cache.resolve(path);
+    if let Err(err) = watcher::update_file(app_state, &path).await {
+        tracing::error!("Failed to index captured file {path:?}: {err}");
+    }
+    app_state.notify_vault_changed();
+    app_state.broadcast_to_websockets(WebSocketMessage::StatusUpdate { files_changed: 1 });
+}
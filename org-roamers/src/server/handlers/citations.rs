@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use serde::Deserialize;
+
+use crate::{
+    server::{
+        services::citation_service,
+        types::{CitationResponse, RefsResponse},
+    },
+    ServerState,
+};
+
+/// GET /citations/{key}
+/// Nodes citing a work and its literature notes.
+pub async fn get_citation_handler(
+    State(app_state): State<Arc<ServerState>>,
+    Path(key): Path<String>,
+) -> Result<CitationResponse, StatusCode> {
+    let key = key.trim_start_matches('@');
+    let citation = citation_service::get_citation(&app_state.sqlite, key)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to load citation {key}: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if citation.citing.is_empty() && citation.literature.is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(citation)
+}
+
+#[derive(Deserialize)]
+pub struct RefsParams {
+    /// Only refs of this type, e.g. `cite` for literature notes
+    #[serde(rename = "type")]
+    ref_type: Option<String>,
+}
+
+/// GET /refs?type=
+/// `ROAM_REFS` of all nodes with the number of nodes citing each key.
+pub async fn get_refs_handler(
+    State(app_state): State<Arc<ServerState>>,
+    Query(params): Query<RefsParams>,
+) -> Result<RefsResponse, StatusCode> {
+    citation_service::get_refs(&app_state.sqlite, params.ref_type.as_deref())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to load refs: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
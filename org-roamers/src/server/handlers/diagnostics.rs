@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+use crate::{
+    server::{
+        services::{diagnostics_service, lint_service},
+        types::{
+            DuplicateTitlesResponse, FileDiagnosticsResponse, LinkReport, LintResponse,
+            ReindexReport, RoamID,
+        },
+    },
+    ServerState,
+};
+
+/// GET /diagnostics/duplicate-titles
+/// List names shared by several nodes. Parent resolution and lookups by name
+/// pick an arbitrary node for these.
+pub async fn duplicate_titles_handler(State(app_state): State<Arc<ServerState>>) -> Response {
+    match diagnostics_service::duplicate_titles(&app_state.sqlite).await {
+        Ok(duplicates) => DuplicateTitlesResponse { duplicates }.into_response(),
+        Err(err) => {
+            tracing::error!("Failed to find duplicate titles: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// GET /diagnostics/files
+/// Files that could not be read or only partially indexed, e.g. files larger
+/// than `max_file_size`, unclosed drawers and blocks or duplicate `ID`s, with
+/// the line and column of each problem where known. Problems are cleared
+/// once the file indexes cleanly.
+pub async fn file_diagnostics_handler(State(app_state): State<Arc<ServerState>>) -> Response {
+    match diagnostics_service::file_diagnostics(&app_state.sqlite).await {
+        Ok(files) => FileDiagnosticsResponse { files }.into_response(),
+        Err(err) => {
+            tracing::error!("Failed to load file diagnostics: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// GET /diagnostics/links
+/// Broken links found by the last run of the link checker.
+pub async fn broken_links_handler(State(app_state): State<Arc<ServerState>>) -> LinkReport {
+    app_state.link_report.read().unwrap().clone()
+}
+
+/// GET /diagnostics/reindex
+/// Differences found by the last scheduled reindex.
+pub async fn reindex_report_handler(State(app_state): State<Arc<ServerState>>) -> ReindexReport {
+    app_state.reindex_report.read().unwrap().clone()
+}
+
+#[derive(Deserialize)]
+pub struct LintParams {
+    /// Only check this node
+    id: Option<RoamID>,
+}
+
+/// GET /diagnostics/lint?id=
+/// Nodes violating one of the note quality rules configured in `lint`:
+/// missing tags, overly long titles, no backlinks some days after the file
+/// was created, or a TODO keyword without a deadline.
+pub async fn lint_handler(
+    State(app_state): State<Arc<ServerState>>,
+    Query(params): Query<LintParams>,
+) -> Response {
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    match lint_service::lint(
+        &app_state.sqlite,
+        &app_state.config.lint,
+        params.id.as_ref().map(RoamID::id),
+        now,
+    )
+    .await
+    {
+        Ok(nodes) => LintResponse { nodes }.into_response(),
+        Err(err) => {
+            tracing::error!("Failed to lint nodes: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
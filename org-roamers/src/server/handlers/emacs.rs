@@ -1,40 +1,78 @@
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hasher},
+    path::PathBuf,
+    sync::Arc,
+};
 
 use axum::{
     extract::{Query as AxumQuery, State},
-    http::StatusCode,
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
+    Json,
 };
+use serde::{Deserialize, Serialize};
+use tokio::time::Duration;
 
+use crate::client::message::WebSocketMessage;
+use crate::server::handlers::node::read_source;
+use crate::server::middleware::auth::CurrentUser;
+use crate::server::services::{
+    asset_service::etag_matches,
+    audit_service::{self, AuditAction},
+    emacs_service,
+};
 use crate::server::types::RoamID;
 use crate::{
     server::emacs::{route_emacs_traffic, EmacsRequest},
-    ServerState,
+    watcher, ServerState,
 };
 
+/// POST /emacs?task=
+/// Notifications of the Emacs package:
+/// - `task=opened&id=&connection=` moves a client of the user to the node:
+///   the connection with the id listed by `/admin/connections`, or the
+///   client that was active most recently.
+/// - `task=modified&file=` marks the file as changed.
+/// - `task=link&source=&dest=&position=&description=` inserts
+///   `[[id:dest][Title]]` into the file of `source` at the character offset
+///   `position`, or on a new line at the end of the file without it.
+///   `description` replaces the title of `dest` as link text. Answers `404`
+///   if `source` or `dest` is unknown and is refused with `403` in
+///   read-only mode.
 pub async fn emacs_handler(
     AxumQuery(params): AxumQuery<HashMap<String, String>>,
     State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
 ) -> Response {
     tracing::debug!("Emacs request with params: {:?}", params);
 
     match route_emacs_traffic(params) {
         Ok(req) => {
             match req {
-                EmacsRequest::BufferOpened(id) => {
-                    let roam_id: RoamID = id.clone().into();
-
-                    // Notify all WebSocket clients about node visit
-                    let message =
-                        crate::client::message::WebSocketMessage::NodeVisited { node_id: roam_id };
-                    app_state.broadcast_to_websockets(message);
+                EmacsRequest::BufferOpened { id, connection } => {
+                    // Only one client of the same user follows along
+                    app_state.send_node_visited(user.0.as_deref(), connection, id);
                 }
                 EmacsRequest::BufferModified(file) => {
                     // Notify all WebSocket clients about pending changes
-                    let message = crate::client::message::WebSocketMessage::BufferModified;
+                    let message = WebSocketMessage::BufferModified;
                     app_state.broadcast_to_websockets(message);
 
                     app_state.cache.invalidate(PathBuf::from(file));
+                    app_state.notify_vault_changed();
+                }
+                EmacsRequest::CreateLink {
+                    source,
+                    dest,
+                    position,
+                    description,
+                } => {
+                    if let Err(status) =
+                        create_link(&app_state, &user, &source, &dest, position, description).await
+                    {
+                        return status.into_response();
+                    }
                 }
             }
             StatusCode::NO_CONTENT.into_response()
@@ -42,3 +80,163 @@ pub async fn emacs_handler(
         Err(err) => err.into_response(),
     }
 }
+
+/// Insert a link to `dest` into the file of `source` and index the file, which
+/// broadcasts the new link to the clients.
+async fn create_link(
+    app_state: &ServerState,
+    user: &CurrentUser,
+    source: &RoamID,
+    dest: &RoamID,
+    position: Option<usize>,
+    description: Option<String>,
+) -> Result<(), StatusCode> {
+    if app_state.config.read_only {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let current = read_source(app_state, source)?;
+    // The title is looked up even with a description, to not link to nodes
+    // that do not exist.
+    let title = emacs_service::link_title(&app_state.sqlite, dest)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get title of {}: {err}", dest.id());
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let description = description.unwrap_or(title);
+    let content = emacs_service::insert_link(current.content(), position, dest, &description)
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
+    let file = current.path().to_path_buf();
+    let path = app_state.cache.resolve(&file);
+    if let Err(err) = std::fs::write(&path, content) {
+        tracing::error!("Failed to write {file:?}: {err}");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    // Index right away, the watcher might be disabled.
+    if let Err(err) = watcher::update_file(app_state, &path).await {
+        tracing::error!("Failed to index {file:?} after linking: {err}");
+    }
+    app_state.notify_vault_changed();
+    app_state.broadcast_to_websockets(WebSocketMessage::StatusUpdate { files_changed: 1 });
+
+    audit_service::record(
+        &app_state.sqlite,
+        user.0.as_deref(),
+        AuditAction::Edit,
+        Some(&file.to_string_lossy()),
+    )
+    .await;
+    Ok(())
+}
+
+/// Upper bound for the timeout of [`emacs_follow_handler`].
+const MAX_FOLLOW_TIMEOUT_SECS: u64 = 120;
+
+#[derive(Deserialize)]
+pub struct FollowParams {
+    /// Sequence number of the last selection Emacs has seen. If omitted, only
+    /// selections made after the request are returned.
+    since: Option<u64>,
+    /// Seconds to wait for a selection.
+    timeout: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct FollowResponse {
+    seq: u64,
+    id: RoamID,
+}
+
+/// GET /emacs/follow
+/// Long-poll for the next node selected in the web UI, so Emacs can open it.
+/// Responds with 204 if nothing was selected before the timeout.
+pub async fn emacs_follow_handler(
+    AxumQuery(params): AxumQuery<FollowParams>,
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+) -> Response {
+    let follow = &app_state.emacs_follow;
+    let since = params.since.unwrap_or_else(|| follow.current(user.owner()));
+    let timeout = Duration::from_secs(params.timeout.unwrap_or(30).min(MAX_FOLLOW_TIMEOUT_SECS));
+
+    match follow.wait(user.owner(), since, timeout).await {
+        Some((seq, id)) => Json(FollowResponse { seq, id }).into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+/// GET /emacs/candidates
+/// Titles and aliases of all nodes for `completing-read` as
+/// `[name, id, tags, file]` arrays, see
+/// [`crate::server::types::EmacsCandidates`]. The response carries an ETag,
+/// so Emacs only downloads and parses the list again after it changed.
+pub async fn emacs_candidates_handler(
+    State(app_state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+) -> Response {
+    let body = match emacs_service::candidates(&app_state.sqlite)
+        .await
+        .and_then(|candidates| Ok(serde_json::to_vec(&candidates)?))
+    {
+        Ok(body) => body,
+        Err(err) => {
+            tracing::error!("Failed to list candidates: {err}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let mut hasher = DefaultHasher::new();
+    hasher.write(&body);
+    let etag = format!("\"{:016x}\"", hasher.finish());
+    let cache = [
+        (header::ETAG, etag.clone()),
+        (header::CACHE_CONTROL, "no-cache".to_string()),
+    ];
+    if etag_matches(&headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, cache).into_response();
+    }
+    (cache, [(header::CONTENT_TYPE, "application/json")], body).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_link_to_unknown_node() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path().join("notes");
+        std::fs::create_dir_all(&root).unwrap();
+        let content = ":PROPERTIES:\n:ID: a\n:END:\n#+title: A\n";
+        std::fs::write(root.join("a.org"), content).unwrap();
+
+        let config = crate::config::Config {
+            org_roamers_root: root.clone(),
+            database: crate::config::DatabaseConfig {
+                path: Some(temp_dir.path().join("roam.db")),
+            },
+            ..Default::default()
+        };
+        let state = ServerState::new(config).await.unwrap();
+
+        let description = Some("Missing".to_string());
+        let result = create_link(
+            &state,
+            &CurrentUser(None),
+            &"a".into(),
+            &"missing".into(),
+            None,
+            description,
+        )
+        .await;
+        assert_eq!(result, Err(StatusCode::NOT_FOUND));
+        assert_eq!(
+            std::fs::read_to_string(root.join("a.org")).unwrap(),
+            content
+        );
+    }
+}
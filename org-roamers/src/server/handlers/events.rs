@@ -0,0 +1,88 @@
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    extract::{Query, State},
+    http::HeaderMap,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures_util::{stream, Stream};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+use crate::{
+    client::{message::WebSocketMessage, topic::Topic, ConnectionKind, CONNECTION_BUFFER},
+    server::{handlers::websocket::user_agent, middleware::auth::CurrentUser},
+    ServerState,
+};
+
+/// Unregisters the connection once the client disconnects and the stream is
+/// dropped.
+struct ConnectionGuard {
+    app_state: Arc<ServerState>,
+    connection_id: u64,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        tracing::info!("SSE client {} disconnected", self.connection_id);
+        self.app_state
+            .unregister_websocket_connection(self.connection_id);
+    }
+}
+
+#[derive(Deserialize)]
+pub struct EventsParams {
+    /// Comma separated topics, see [`Topic`].
+    topics: Option<String>,
+}
+
+/// GET /events?topics=
+/// Server-sent events carrying the same messages that are broadcast to
+/// websocket clients, for clients that cannot use websockets. With `topics`
+/// only broadcasts of these topics are sent.
+pub async fn events_handler(
+    State(app_state): State<Arc<ServerState>>,
+    CurrentUser(user): CurrentUser,
+    headers: HeaderMap,
+    Query(params): Query<EventsParams>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let (sender, receiver) = mpsc::channel::<WebSocketMessage>(CONNECTION_BUFFER);
+    let connection_id = app_state.register_websocket_connection(
+        sender,
+        ConnectionKind::Sse,
+        user,
+        user_agent(&headers),
+    );
+    tracing::info!("SSE client {} connected", connection_id);
+
+    if let Some(topics) = params.topics {
+        let topics: Vec<Topic> = topics
+            .split(',')
+            .filter_map(|topic| {
+                Topic::try_from(topic.trim().to_string())
+                    .inspect_err(|err| tracing::warn!("{err}"))
+                    .ok()
+            })
+            .collect();
+        app_state.subscribe(connection_id, &topics);
+    }
+
+    let guard = ConnectionGuard {
+        app_state,
+        connection_id,
+    };
+
+    let stream = stream::unfold((receiver, guard), |(mut receiver, guard)| async move {
+        let message = receiver.recv().await?;
+        let event = match Event::default().json_data(&message) {
+            Ok(event) => event,
+            Err(err) => {
+                tracing::error!("Failed to serialize event: {err}");
+                Event::default().comment("serialization error")
+            }
+        };
+        Some((Ok(event), (receiver, guard)))
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
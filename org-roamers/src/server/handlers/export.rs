@@ -0,0 +1,132 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    server::{
+        services::{bundle_service, export_service, flashcard_service},
+        types::RoamID,
+    },
+    ServerState,
+};
+
+/// GET /export/index.json
+/// Dump nodes, links, tags, aliases and refs of the whole vault. The schema
+/// is [`crate::server::types::VaultIndex`].
+pub async fn export_index_handler(State(app_state): State<Arc<ServerState>>) -> Response {
+    match export_service::vault_index(&app_state.sqlite).await {
+        Ok(index) => index.into_response(),
+        Err(err) => {
+            tracing::error!("Failed to export index: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// GET /export/anki.txt
+/// Flashcards of all `:drill:` and `:anki:` headlines as a file for Anki's
+/// text import. The front and back are taken from the `FRONT` and `BACK`
+/// properties, from `Front` and `Back` (or `Answer`) subheadings, or from
+/// the title and the body.
+pub async fn export_anki_handler(State(app_state): State<Arc<ServerState>>) -> Response {
+    let cards = tokio::task::spawn_blocking(move || flashcard_service::collect(&app_state.cache))
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|cards| cards);
+    match cards {
+        Ok(cards) => (
+            [
+                (header::CONTENT_TYPE, "text/plain; charset=utf-8"),
+                (
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"org-roamers.txt\"",
+                ),
+            ],
+            flashcard_service::to_anki(&cards),
+        )
+            .into_response(),
+        Err(err) => {
+            tracing::error!("Failed to export flashcards: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BundleParams {
+    /// Comma separated node ids
+    ids: Option<String>,
+    tag: Option<String>,
+    /// Also include nodes up to this many `id:` links away
+    #[serde(default)]
+    depth: usize,
+}
+
+/// GET /export/bundle.zip?ids=&tag=&depth=
+/// Zip the files of the selected nodes together with the attachments they
+/// link to, e.g. to share a self-contained part of the vault. `ids` is a
+/// comma separated list of nodes, `tag` adds the nodes carrying the tag and
+/// `depth` the nodes linked from them, up to `depth` links away.
+pub async fn export_bundle_handler(
+    State(app_state): State<Arc<ServerState>>,
+    Query(params): Query<BundleParams>,
+) -> Response {
+    let ids: Result<Vec<String>, _> = params
+        .ids
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| RoamID::parse(id).map(|id| id.id().to_string()))
+        .collect();
+    let ids = match ids {
+        Ok(ids) => ids,
+        Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    };
+    let tag = params
+        .tag
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty());
+    if ids.is_empty() && tag.is_none() {
+        return (StatusCode::BAD_REQUEST, "Missing ids or tag").into_response();
+    }
+
+    let files =
+        match bundle_service::selected_files(&app_state.sqlite, &ids, tag, params.depth).await {
+            Ok(files) if files.is_empty() => return StatusCode::NOT_FOUND.into_response(),
+            Ok(files) => files,
+            Err(err) => {
+                tracing::error!("Failed to select files of bundle: {err}");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+    let zip = tokio::task::spawn_blocking(move || bundle_service::bundle(&app_state.cache, &files))
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|zip| zip);
+    match zip {
+        Ok(zip) => (
+            [
+                (header::CONTENT_TYPE, "application/zip"),
+                (
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"org-roamers.zip\"",
+                ),
+            ],
+            zip,
+        )
+            .into_response(),
+        Err(err) => {
+            tracing::error!("Failed to bundle files: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use serde::Deserialize;
+
+use crate::{
+    client::message::WebSocketMessage,
+    server::{
+        middleware::auth::CurrentUser,
+        services::{
+            audit_service::{self, AuditAction},
+            extract_service::{self, ExtractError},
+        },
+        types::RoamID,
+    },
+    ServerState,
+};
+
+#[derive(Deserialize)]
+pub struct ExtractRequest {
+    /// Headline node that is extracted
+    id: RoamID,
+    /// New file relative to the root. Named after the default capture
+    /// template if missing.
+    file: Option<String>,
+}
+
+/// POST /node/extract
+/// Move a headline node with its subtree into its own file and leave a link
+/// in its place, like `org-roam-extract-subtree`.
+pub async fn extract_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Json(request): Json<ExtractRequest>,
+) -> Response {
+    let now = app_state.now();
+    let extracted =
+        match extract_service::extract(&app_state, request.id.id(), request.file.as_deref(), now)
+            .await
+        {
+            Ok(extracted) => extracted,
+            Err(ExtractError::NotFound(_)) => return StatusCode::NOT_FOUND.into_response(),
+            Err(err @ (ExtractError::NotHeadline(_) | ExtractError::InvalidFile(_))) => {
+                return (StatusCode::BAD_REQUEST, err.to_string()).into_response()
+            }
+            Err(err @ ExtractError::Exists(_)) => {
+                return (StatusCode::CONFLICT, err.to_string()).into_response()
+            }
+            Err(ExtractError::Other(err)) => {
+                tracing::error!("Failed to extract {}: {err}", request.id.id());
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+
+    app_state.notify_vault_changed();
+    app_state.broadcast_to_websockets(WebSocketMessage::StatusUpdate { files_changed: 2 });
+
+    audit_service::record(
+        &app_state.sqlite,
+        user.0.as_deref(),
+        AuditAction::Extract,
+        Some(&extracted.file),
+    )
+    .await;
+
+    extracted.into_response()
+}
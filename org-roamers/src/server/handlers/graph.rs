@@ -1,12 +1,22 @@
 use std::sync::Arc;
 
 use axum::{
-    extract::{Query, State},
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
     response::IntoResponse,
+    Json,
 };
 use serde::Deserialize;
 
-use crate::server::services::graph_service;
+use crate::graph::community;
+use crate::server::middleware::auth::CurrentUser;
+use crate::server::services::{citation_service, graph_service, render_service};
+use crate::server::types::{
+    GraphClusters, GraphData, GraphView, GraphViewFilter, GraphViewsResponse, LiteGraph,
+    NodePosition, RoamID,
+};
+use crate::sqlite::{layout, pins, views};
+use crate::transform::timestamps;
 use crate::ServerState;
 
 #[derive(Deserialize)]
@@ -29,13 +39,501 @@ impl GraphParams {
     }
 }
 
+impl From<&GraphViewFilter> for GraphParams {
+    fn from(filter: &GraphViewFilter) -> Self {
+        Self {
+            tags: filter.tags.clone(),
+            exclude: filter.exclude.clone(),
+        }
+    }
+}
+
+/// Restrict the graph to files changed since a date.
+#[derive(Deserialize, Default)]
+pub struct GraphDateParams {
+    /// `YYYY-MM-DD`
+    modified_after: Option<String>,
+    /// `YYYY-MM-DD`
+    created_after: Option<String>,
+}
+
+impl GraphDateParams {
+    fn is_valid(&self) -> bool {
+        [&self.modified_after, &self.created_after]
+            .into_iter()
+            .flatten()
+            .all(|date| timestamps::is_date(date))
+    }
+}
+
+impl From<&GraphViewFilter> for GraphDateParams {
+    fn from(filter: &GraphViewFilter) -> Self {
+        Self {
+            modified_after: filter.modified_after.clone(),
+            created_after: filter.created_after.clone(),
+        }
+    }
+}
+
+/// How nodes are represented in the graph.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphMode {
+    /// Every node (file or headline) is a graph node.
+    #[default]
+    Nodes,
+    /// All nodes of a file are merged into one graph node.
+    Files,
+    /// Tags are added as graph nodes linked to the nodes carrying them.
+    Tags,
+}
+
+#[derive(Deserialize)]
+pub struct GraphModeParams {
+    #[serde(default)]
+    mode: GraphMode,
+    /// Include a one line summary per node. Off by default, because it
+    /// inflates the payload.
+    #[serde(default)]
+    summaries: bool,
+    /// Include the top level directory of each node as its group.
+    #[serde(default)]
+    groups: bool,
+}
+
+async fn user_graph(
+    app_state: &ServerState,
+    user: &CurrentUser,
+    params: GraphParams,
+    dates: GraphDateParams,
+) -> GraphData {
+    let sqlite = &app_state.sqlite;
+    let (filter_tags, exclude_tags) = params.parse_tags();
+    let pinned = pins::get_pins(sqlite, user.owner())
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect();
+    let graph = graph_service::get_graph_data(sqlite, filter_tags, exclude_tags, &pinned).await;
+    let exclude_tags = &app_state.config.graph.exclude_tags;
+    let graph = graph_service::remove_excluded(sqlite, graph, exclude_tags).await;
+    graph_service::filter_by_file_dates(
+        sqlite,
+        graph,
+        dates.modified_after.as_deref(),
+        dates.created_after.as_deref(),
+    )
+    .await
+}
+
+fn with_layout(mut graph: GraphData, layout: Vec<(String, f64, f64)>) -> GraphData {
+    graph.layout = layout
+        .into_iter()
+        .map(|(id, x, y)| (RoamID::from(id), NodePosition { x, y }))
+        .collect();
+    graph
+}
+
+#[derive(Deserialize)]
+pub struct ViewParams {
+    /// Name of a saved view whose filters replace the tag and date filters.
+    view: Option<String>,
+}
+
 pub async fn get_graph_data_handler(
     State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
     Query(params): Query<GraphParams>,
-) -> impl IntoResponse {
+    Query(GraphModeParams {
+        mode,
+        summaries,
+        groups,
+    }): Query<GraphModeParams>,
+    Query(dates): Query<GraphDateParams>,
+    Query(ViewParams { view }): Query<ViewParams>,
+) -> Result<GraphData, StatusCode> {
     let sqlite = &app_state.sqlite;
-    let (filter_tags, exclude_tags) = params.parse_tags();
-    graph_service::get_graph_data(sqlite, filter_tags, exclude_tags).await
+    let view = match view {
+        Some(name) => Some(
+            load_view(sqlite, user.owner(), &name)
+                .await?
+                .ok_or(StatusCode::NOT_FOUND)?,
+        ),
+        None => None,
+    };
+    let (params, dates) = match &view {
+        Some(view) => ((&view.filter).into(), (&view.filter).into()),
+        None => (params, dates),
+    };
+    if !dates.is_valid() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let mut graph = user_graph(&app_state, &user, params, dates).await;
+    let threshold = app_state.config.graph_lod_threshold;
+    match mode {
+        GraphMode::Nodes if threshold > 0 && graph.nodes.len() > threshold => {
+            tracing::info!(
+                "Graph has {} nodes, aggregating per file",
+                graph.nodes.len()
+            );
+            graph = graph_service::aggregate_by_file(sqlite, graph).await;
+        }
+        GraphMode::Nodes => {}
+        GraphMode::Files => graph = graph_service::aggregate_by_file(sqlite, graph).await,
+        GraphMode::Tags => graph = graph_service::add_tag_nodes(sqlite, graph).await,
+    }
+    if summaries {
+        graph = graph_service::add_summaries(sqlite, graph).await;
+    }
+    if groups {
+        graph = graph_service::add_groups(sqlite, graph).await;
+    }
+    let layout = layout::get_layout(sqlite, user.owner())
+        .await
+        .unwrap_or_default();
+    let mut graph = with_layout(graph, layout);
+    if let Some(view) = view {
+        graph.layout.extend(view.layout);
+        graph.zoom = Some(view.zoom);
+    }
+    Ok(graph)
+}
+
+#[derive(Deserialize)]
+pub struct LiteParams {
+    /// Keep only this many nodes, ordered by degree.
+    top: Option<usize>,
+}
+
+/// GET /graph/lite?top=
+/// Compact graph with ids, truncated titles and degrees only.
+pub async fn get_lite_graph_handler(
+    State(app_state): State<Arc<ServerState>>,
+    Query(LiteParams { top }): Query<LiteParams>,
+) -> Result<LiteGraph, StatusCode> {
+    let exclude_tags = &app_state.config.graph.exclude_tags;
+    graph_service::get_lite_graph(&app_state.sqlite, exclude_tags, top)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to build lite graph: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// GET /graph/cluster/{id}
+/// Expand an aggregated cluster into its members. Accepts the same tag and
+/// date filters as `/graph`.
+pub async fn get_graph_cluster_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+    Query(params): Query<GraphParams>,
+    Query(dates): Query<GraphDateParams>,
+) -> Result<GraphData, StatusCode> {
+    if !dates.is_valid() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let sqlite = &app_state.sqlite;
+    let graph = user_graph(&app_state, &user, params, dates).await;
+    let cluster = graph_service::expand_cluster(sqlite, graph, &id)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let layout = layout::get_layout(sqlite, user.owner())
+        .await
+        .unwrap_or_default();
+    Ok(with_layout(cluster, layout))
+}
+
+/// GET /graph/clusters
+/// Detect communities of densely linked nodes, e.g. to color them. Accepts
+/// the same tag and date filters as `/graph`.
+pub async fn get_graph_clusters_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Query(params): Query<GraphParams>,
+    Query(dates): Query<GraphDateParams>,
+) -> Result<GraphClusters, StatusCode> {
+    if !dates.is_valid() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let graph = user_graph(&app_state, &user, params, dates).await;
+    let clusters = tokio::task::spawn_blocking(move || community::louvain(&graph))
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to detect communities: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let count = clusters.values().max().map_or(0, |max| max + 1);
+    Ok(GraphClusters { count, clusters })
+}
+
+/// Default hops of `/graph/neighborhood`.
+const DEFAULT_NEIGHBORHOOD_DEPTH: u32 = 2;
+/// Beyond this the neighborhood is usually most of the vault anyway.
+const MAX_NEIGHBORHOOD_DEPTH: u32 = 6;
+
+#[derive(Deserialize)]
+pub struct NeighborhoodParams {
+    id: RoamID,
+    depth: Option<u32>,
+}
+
+/// GET /graph/neighborhood?id=&depth=
+/// Subgraph of the nodes within `depth` links of `id`, for vaults where
+/// fetching the whole graph is too slow.
+pub async fn get_neighborhood_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Query(NeighborhoodParams { id, depth }): Query<NeighborhoodParams>,
+) -> Result<GraphData, StatusCode> {
+    let sqlite = &app_state.sqlite;
+    let depth = depth
+        .unwrap_or(DEFAULT_NEIGHBORHOOD_DEPTH)
+        .min(MAX_NEIGHBORHOOD_DEPTH);
+    let pinned = pins::get_pins(sqlite, user.owner())
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect();
+    let graph = graph_service::get_neighborhood(sqlite, id.id(), depth, &pinned)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get the neighborhood of {}: {err}", id.id());
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let exclude_tags = &app_state.config.graph.exclude_tags;
+    let graph = graph_service::remove_excluded(sqlite, graph, exclude_tags).await;
+    let layout = layout::get_layout(sqlite, user.owner())
+        .await
+        .unwrap_or_default();
+    Ok(with_layout(graph, layout))
+}
+
+#[derive(Deserialize)]
+pub struct RenderParams {
+    /// Only `svg` is supported.
+    format: Option<String>,
+}
+
+/// GET /graph/render?format=svg
+/// Lay out the graph on the server and return it as an image. Accepts the
+/// same tag and date filters as `/graph`. Stored positions are kept.
+pub async fn render_graph_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Query(RenderParams { format }): Query<RenderParams>,
+    Query(params): Query<GraphParams>,
+    Query(dates): Query<GraphDateParams>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    if format.as_deref().is_some_and(|format| format != "svg") {
+        return Err((StatusCode::BAD_REQUEST, "Unsupported format"));
+    }
+    if !dates.is_valid() {
+        return Err((StatusCode::BAD_REQUEST, "Invalid date"));
+    }
+    let sqlite = &app_state.sqlite;
+    let mut graph = user_graph(&app_state, &user, params, dates).await;
+    let threshold = app_state.config.graph_lod_threshold;
+    if threshold > 0 && graph.nodes.len() > threshold {
+        graph = graph_service::aggregate_by_file(sqlite, graph).await;
+    }
+    let layout = layout::get_layout(sqlite, user.owner())
+        .await
+        .unwrap_or_default();
+    let graph = with_layout(graph, layout);
+
+    let svg = tokio::task::spawn_blocking(move || {
+        let positions = render_service::layout(&graph);
+        render_service::render_svg(&graph, &positions)
+    })
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to render graph: {err}");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Failed to render graph")
+    })?;
+
+    Ok(([(header::CONTENT_TYPE, "image/svg+xml")], svg))
+}
+
+/// GET /graph/citations
+/// Bipartite graph of nodes and the works they cite.
+pub async fn get_citation_graph_handler(
+    State(app_state): State<Arc<ServerState>>,
+) -> Result<GraphData, StatusCode> {
+    citation_service::citation_graph(&app_state.sqlite)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to build citation graph: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+#[derive(Deserialize)]
+pub struct LayoutUpdate {
+    nodes: Vec<NodeLayout>,
+}
+
+#[derive(Deserialize)]
+pub struct NodeLayout {
+    id: RoamID,
+    x: f64,
+    y: f64,
+}
+
+/// POST /graph/layout
+/// Store the positions of manually arranged nodes. Positions of nodes not
+/// contained in the request are kept.
+pub async fn set_graph_layout_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Json(update): Json<LayoutUpdate>,
+) -> StatusCode {
+    if update
+        .nodes
+        .iter()
+        .any(|node| !node.x.is_finite() || !node.y.is_finite())
+    {
+        return StatusCode::BAD_REQUEST;
+    }
+
+    let positions: Vec<(String, f64, f64)> = update
+        .nodes
+        .into_iter()
+        .map(|node| (node.id.id().to_string(), node.x, node.y))
+        .collect();
+
+    match layout::insert_positions(&app_state.sqlite, user.owner(), &positions).await {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(err) => {
+            tracing::error!("Failed to store graph layout: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// DELETE /graph/layout
+/// Forget all stored positions so the client lays out the graph again.
+pub async fn clear_graph_layout_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+) -> StatusCode {
+    match layout::clear_layout(&app_state.sqlite, user.owner()).await {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(err) => {
+            tracing::error!("Failed to clear graph layout: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+async fn load_view(
+    sqlite: &sqlx::SqlitePool,
+    user: &str,
+    name: &str,
+) -> Result<Option<GraphView>, StatusCode> {
+    let internal_error = |err: anyhow::Error| {
+        tracing::error!("Failed to load graph view {name}: {err}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    let Some(view) = views::get_view(sqlite, user, name)
+        .await
+        .map_err(internal_error)?
+    else {
+        return Ok(None);
+    };
+    let mut view: GraphView =
+        serde_json::from_str(&view).map_err(|err| internal_error(err.into()))?;
+    view.name = name.to_string();
+    Ok(Some(view))
+}
+
+/// GET /graph/views
+/// List the saved graph views of the current user.
+pub async fn get_graph_views_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+) -> Result<GraphViewsResponse, StatusCode> {
+    let views = views::get_views(&app_state.sqlite, user.owner())
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to load graph views: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .into_iter()
+        .filter_map(|(name, view)| {
+            let mut view: GraphView = serde_json::from_str(&view)
+                .inspect_err(|err| tracing::error!("Invalid graph view {name}: {err}"))
+                .ok()?;
+            view.name = name;
+            Some(view)
+        })
+        .collect();
+    Ok(GraphViewsResponse { views })
+}
+
+/// GET /graph/views/{name}
+pub async fn get_graph_view_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Path(name): Path<String>,
+) -> Result<GraphView, StatusCode> {
+    load_view(&app_state.sqlite, user.owner(), &name)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// PUT /graph/views/{name}
+/// Create or replace a saved view. The name in the body is ignored.
+pub async fn put_graph_view_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Path(name): Path<String>,
+    Json(mut view): Json<GraphView>,
+) -> StatusCode {
+    let dates = GraphDateParams::from(&view.filter);
+    let positions_valid = view
+        .layout
+        .values()
+        .all(|position| position.x.is_finite() && position.y.is_finite());
+    let zoom_valid = view.zoom.is_finite() && view.zoom > 0.0;
+    if name.trim().is_empty() || !dates.is_valid() || !positions_valid || !zoom_valid {
+        return StatusCode::BAD_REQUEST;
+    }
+
+    view.name = name.clone();
+    let json = match serde_json::to_string(&view) {
+        Ok(json) => json,
+        Err(err) => {
+            tracing::error!("Failed to serialize graph view {name}: {err}");
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    };
+    match views::insert_view(&app_state.sqlite, user.owner(), &name, &json).await {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(err) => {
+            tracing::error!("Failed to store graph view {name}: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// DELETE /graph/views/{name}
+pub async fn delete_graph_view_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Path(name): Path<String>,
+) -> StatusCode {
+    match views::delete_view(&app_state.sqlite, user.owner(), &name).await {
+        Ok(true) => StatusCode::NO_CONTENT,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(err) => {
+            tracing::error!("Failed to delete graph view {name}: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
 }
 
 #[cfg(test)]
@@ -135,4 +633,17 @@ mod tests {
         assert_eq!(include, Some(vec!["".to_string()]));
         assert_eq!(exclude, Some(vec!["".to_string()]));
     }
+
+    #[test]
+    fn test_view_filter() {
+        let view: GraphView = serde_json::from_str(
+            r#"{"filter": {"tags": "phd, thesis", "modified_after": "2024-13-01"}}"#,
+        )
+        .unwrap();
+        assert_eq!(view.zoom, 1.0);
+        let (include, exclude) = GraphParams::from(&view.filter).parse_tags();
+        assert_eq!(include, Some(vec!["phd".to_string(), "thesis".to_string()]));
+        assert!(exclude.is_none());
+        assert!(!GraphDateParams::from(&view.filter).is_valid());
+    }
 }
@@ -2,7 +2,10 @@ use std::sync::Arc;
 
 use axum::{extract::State, response::Response};
 
-use crate::{server::services::asset_service, ServerState};
+use crate::{
+    server::{services::asset_service, types::HealthResponse},
+    ServerState,
+};
 
 pub async fn default_route(State(app_state): State<Arc<ServerState>>) -> Response {
     let conf = app_state
@@ -12,3 +15,17 @@ pub async fn default_route(State(app_state): State<Arc<ServerState>>) -> Respons
         .to_string();
     asset_service::default_route_content(app_state, conf, None)
 }
+
+/// GET /healthz
+/// Reports `degraded` while the file system watcher is restarting or polling.
+pub async fn healthz_handler(State(app_state): State<Arc<ServerState>>) -> HealthResponse {
+    let watcher = *app_state.watcher_status.read().unwrap();
+    HealthResponse {
+        status: if watcher.is_degraded() {
+            "degraded"
+        } else {
+            "ok"
+        },
+        watcher,
+    }
+}
@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+
+use crate::{
+    server::{
+        middleware::auth::CurrentUser,
+        services::audit_service::{self, AuditAction},
+        types::{HistoryEntry, HistoryResponse, RoamID},
+    },
+    sqlite::history,
+    ServerState,
+};
+
+const DEFAULT_LIMIT: i64 = 50;
+
+#[derive(Deserialize)]
+pub struct HistoryParams {
+    limit: Option<i64>,
+}
+
+impl HistoryParams {
+    fn limit(&self) -> i64 {
+        self.limit
+            .unwrap_or(DEFAULT_LIMIT)
+            .clamp(1, history::MAX_HISTORY)
+    }
+}
+
+/// GET /history
+/// Breadcrumbs of the current user.
+pub async fn get_history_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Query(params): Query<HistoryParams>,
+) -> Response {
+    let sqlite = &app_state.sqlite;
+    let rows = history::get_history(sqlite, user.owner(), params.limit()).await;
+    let cursor = history::get_cursor(sqlite, user.owner()).await;
+
+    match (rows, cursor) {
+        (Ok(rows), Ok(cursor)) => {
+            let position = cursor.and_then(|cursor| rows.iter().position(|row| row.0 == cursor));
+            HistoryResponse {
+                entries: rows.into_iter().map(HistoryEntry::from).collect(),
+                position,
+            }
+            .into_response()
+        }
+        (Err(err), _) | (_, Err(err)) => {
+            tracing::error!("Failed to load history: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// GET /history/recent
+/// Recently viewed distinct nodes, most recent first.
+pub async fn get_recent_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Query(params): Query<HistoryParams>,
+) -> Response {
+    match history::get_recent(&app_state.sqlite, user.owner(), params.limit()).await {
+        Ok(rows) => {
+            let entries: Vec<HistoryEntry> = rows.into_iter().map(HistoryEntry::from).collect();
+            Json(entries).into_response()
+        }
+        Err(err) => {
+            tracing::error!("Failed to load recent nodes: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// POST /history/{id}
+/// Record a visit of a node.
+pub async fn push_history_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Path(id): Path<RoamID>,
+) -> StatusCode {
+    if app_state.cache.retrieve(&id).is_none() {
+        return StatusCode::NOT_FOUND;
+    }
+
+    let now = time::OffsetDateTime::now_utc().unix_timestamp();
+    match history::push_visit(&app_state.sqlite, user.owner(), id.id(), now).await {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(err) => {
+            tracing::error!("Failed to record visit of {}: {err}", id.id());
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// POST /history/back
+pub async fn back_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+) -> Response {
+    step(&app_state, &user, false).await
+}
+
+/// POST /history/forward
+pub async fn forward_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+) -> Response {
+    step(&app_state, &user, true).await
+}
+
+/// Respond with the id of the node that is now current, or 204 if the end of
+/// the history was reached.
+async fn step(app_state: &ServerState, user: &CurrentUser, forward: bool) -> Response {
+    match history::step(&app_state.sqlite, user.owner(), forward).await {
+        Ok(Some(id)) => Json(RoamID::from(id)).into_response(),
+        Ok(None) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => {
+            tracing::error!("Failed to move in history: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// DELETE /history
+pub async fn clear_history_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+) -> StatusCode {
+    match history::clear_history(&app_state.sqlite, user.owner()).await {
+        Ok(()) => {
+            audit_service::record(
+                &app_state.sqlite,
+                user.0.as_deref(),
+                AuditAction::HistoryCleared,
+                None,
+            )
+            .await;
+            StatusCode::NO_CONTENT
+        }
+        Err(err) => {
+            tracing::error!("Failed to clear history: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
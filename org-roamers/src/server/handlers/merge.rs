@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use serde::Deserialize;
+
+use crate::{
+    client::message::WebSocketMessage,
+    server::{
+        middleware::auth::CurrentUser,
+        services::{
+            audit_service::{self, AuditAction},
+            merge_service::{self, MergeError},
+        },
+    },
+    ServerState,
+};
+
+#[derive(Deserialize)]
+pub struct MergeRequest {
+    /// Node that is kept
+    keep: String,
+    /// Duplicate that is merged into `keep` and deleted
+    remove: String,
+}
+
+/// POST /node/merge
+/// Merge the file node `remove` into `keep` and move the file of `remove` to
+/// the trash. Its body, tags and aliases are added to `keep` and links to
+/// `remove` are rewritten to point at `keep`.
+pub async fn merge_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Json(request): Json<MergeRequest>,
+) -> Response {
+    let merged = match merge_service::merge(
+        &app_state,
+        &request.keep,
+        &request.remove,
+        user.0.as_deref(),
+    )
+    .await
+    {
+        Ok(merged) => merged,
+        Err(err @ (MergeError::SameNode | MergeError::NotFileNode(_))) => {
+            return (StatusCode::BAD_REQUEST, err.to_string()).into_response()
+        }
+        Err(MergeError::NotFound(_)) => return StatusCode::NOT_FOUND.into_response(),
+        Err(MergeError::Other(err)) => {
+            tracing::error!(
+                "Failed to merge {} into {}: {err}",
+                request.remove,
+                request.keep
+            );
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    app_state.notify_vault_changed();
+    app_state.broadcast_to_websockets(WebSocketMessage::StatusUpdate {
+        files_changed: merged.rewritten.len() + 2,
+    });
+
+    audit_service::record(
+        &app_state.sqlite,
+        user.0.as_deref(),
+        AuditAction::Merge,
+        Some(&merged.file),
+    )
+    .await;
+
+    merged.into_response()
+}
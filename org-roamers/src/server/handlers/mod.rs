@@ -1,9 +1,32 @@
+pub mod admin;
+pub mod agenda;
 pub mod assets;
 pub mod auth;
+pub mod backlinks;
+pub mod board;
+pub mod calendar;
+pub mod capture;
+pub mod citations;
+pub mod diagnostics;
 pub mod emacs;
+pub mod events;
+pub mod export;
+pub mod extract;
 pub mod graph;
 pub mod health;
+pub mod history;
 pub mod latex;
+pub mod merge;
+pub mod moves;
+pub mod node;
 pub mod org;
+pub mod pins;
+pub mod preferences;
+pub mod query;
+pub mod review;
+pub mod similar;
+pub mod status;
 pub mod tags;
+pub mod timeline;
+pub mod trash;
 pub mod websocket;
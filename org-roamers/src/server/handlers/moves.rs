@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use serde::Deserialize;
+
+use crate::{
+    client::message::WebSocketMessage,
+    server::{
+        middleware::auth::CurrentUser,
+        services::{
+            audit_service::{self, AuditAction},
+            move_service::{self, MoveError},
+        },
+        types::RoamID,
+    },
+    ServerState,
+};
+
+#[derive(Deserialize)]
+pub struct MoveRequest {
+    /// Node whose file is moved
+    id: RoamID,
+    /// New file relative to the root
+    file: String,
+}
+
+/// POST /node/move
+/// Move or rename the file of a node. Relative links in the file and links
+/// of other files to it are rewritten, so they keep resolving.
+pub async fn move_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Json(request): Json<MoveRequest>,
+) -> Response {
+    let moved = match move_service::move_file(&app_state, request.id.id(), &request.file).await {
+        Ok(moved) => moved,
+        Err(MoveError::NotFound(_)) => return StatusCode::NOT_FOUND.into_response(),
+        Err(err @ MoveError::InvalidFile(_)) => {
+            return (StatusCode::BAD_REQUEST, err.to_string()).into_response()
+        }
+        Err(err @ MoveError::Exists(_)) => {
+            return (StatusCode::CONFLICT, err.to_string()).into_response()
+        }
+        Err(MoveError::Other(err)) => {
+            tracing::error!(
+                "Failed to move {} to {}: {err}",
+                request.id.id(),
+                request.file
+            );
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    app_state.notify_vault_changed();
+    app_state.broadcast_to_websockets(WebSocketMessage::StatusUpdate {
+        files_changed: moved.rewritten.len() + 1,
+    });
+
+    audit_service::record(
+        &app_state.sqlite,
+        user.0.as_deref(),
+        AuditAction::Move,
+        Some(&moved.file),
+    )
+    .await;
+
+    moved.into_response()
+}
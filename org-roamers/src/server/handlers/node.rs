@@ -0,0 +1,166 @@
+use std::{io, sync::Arc};
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+
+use crate::{
+    cache::{content_hash, OrgCacheEntry},
+    client::message::WebSocketMessage,
+    server::{
+        middleware::auth::CurrentUser,
+        services::{
+            audit_service::{self, AuditAction},
+            conflict_service, edit_service, node_service,
+        },
+        types::{EditConflict, NeighborsResponse, NodeSource, OutlineResponse, RoamID},
+    },
+    transform::subtree::Subtree,
+    watcher, ServerState,
+};
+
+#[derive(Deserialize)]
+pub struct NodeParams {
+    id: RoamID,
+}
+
+/// GET /node/outline?id=
+/// Heading tree of the file containing the node.
+pub async fn get_outline_handler(
+    State(app_state): State<Arc<ServerState>>,
+    Query(params): Query<NodeParams>,
+) -> Result<OutlineResponse, StatusCode> {
+    node_service::get_outline(&app_state.cache, &params.id).ok_or(StatusCode::NOT_FOUND)
+}
+
+/// GET /node/neighbors?id=
+/// Nodes linking to and linked from the node.
+pub async fn get_neighbors_handler(
+    State(app_state): State<Arc<ServerState>>,
+    Query(params): Query<NodeParams>,
+) -> Result<NeighborsResponse, StatusCode> {
+    node_service::get_neighbors(&app_state.sqlite, &params.id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get neighbors of {}: {err}", params.id.id());
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// Read the file of `id` from disk, so edits are checked against what is
+/// actually stored and not against a possibly stale cache.
+pub(crate) fn read_source(
+    app_state: &ServerState,
+    id: &RoamID,
+) -> Result<OrgCacheEntry, StatusCode> {
+    let entry = app_state.cache.retrieve(id).ok_or(StatusCode::NOT_FOUND)?;
+    app_state.cache.entry(entry.path()).map_err(|err| {
+        if err.kind() == io::ErrorKind::NotFound {
+            return StatusCode::NOT_FOUND;
+        }
+        tracing::error!("Failed to read {:?}: {err}", entry.path());
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+#[derive(Deserialize)]
+pub struct SourceParams {
+    id: RoamID,
+    /// `file` (default) or `node` for only the subtree of the node
+    scope: Option<String>,
+}
+
+/// GET /node/source?id=&scope=
+/// Content of the file containing the node, or of the node itself with
+/// `scope=node`, together with the hash that is required to edit it.
+pub async fn get_source_handler(
+    State(app_state): State<Arc<ServerState>>,
+    Query(params): Query<SourceParams>,
+) -> Result<NodeSource, StatusCode> {
+    let source = read_source(&app_state, &params.id)?;
+    let content = match params.scope.as_deref() {
+        Some("node") => {
+            Subtree::get(params.id.clone(), source.content()).ok_or(StatusCode::NOT_FOUND)?
+        }
+        Some("file") | None => source.content().to_string(),
+        Some(_) => return Err(StatusCode::BAD_REQUEST),
+    };
+    Ok(NodeSource {
+        file: source.path().to_string_lossy().to_string(),
+        hash: edit_service::format_hash(source.get_hash()),
+        content,
+        id: params.id,
+    })
+}
+
+#[derive(Deserialize)]
+pub struct EditRequest {
+    content: String,
+    /// Hash of the content the edit is based on, as returned by
+    /// `GET /node/source`.
+    hash: String,
+}
+
+/// PUT /node/source?id=
+/// Replace the content of the file containing the node. If the file changed
+/// since the client read it, the write is rejected with `409 Conflict` and the
+/// rejected content is kept in a conflict copy next to the file.
+pub async fn edit_source_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Query(params): Query<NodeParams>,
+    Json(request): Json<EditRequest>,
+) -> Response {
+    let current = match read_source(&app_state, &params.id) {
+        Ok(current) => current,
+        Err(status) => return status.into_response(),
+    };
+
+    let current_hash = edit_service::format_hash(current.get_hash());
+    if current_hash != request.hash {
+        if let Err(err) = conflict_service::preserve(&app_state, current.path(), &request.content) {
+            tracing::error!("Failed to keep the rejected edit: {err}");
+        }
+        return EditConflict {
+            hash: current_hash,
+            diff: edit_service::diff(current.content(), &request.content),
+            content: current.content().to_string(),
+        }
+        .into_response();
+    }
+
+    let file = current.path().to_path_buf();
+    let path = app_state.cache.resolve(&file);
+    if let Err(err) = std::fs::write(&path, &request.content) {
+        tracing::error!("Failed to write {file:?}: {err}");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    // Index right away, the watcher might be disabled.
+    if let Err(err) = watcher::update_file(&app_state, &path).await {
+        tracing::error!("Failed to index edited file {file:?}: {err}");
+    }
+    app_state.notify_vault_changed();
+    app_state.broadcast_to_websockets(WebSocketMessage::StatusUpdate { files_changed: 1 });
+
+    let file = file.to_string_lossy().to_string();
+    audit_service::record(
+        &app_state.sqlite,
+        user.0.as_deref(),
+        AuditAction::Edit,
+        Some(&file),
+    )
+    .await;
+
+    NodeSource {
+        id: params.id,
+        file,
+        hash: edit_service::format_hash(content_hash(&request.content)),
+        content: request.content,
+    }
+    .into_response()
+}
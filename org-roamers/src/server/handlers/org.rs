@@ -5,12 +5,27 @@ use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
 };
+use serde::Deserialize;
 
 use crate::{
-    server::services::org_service::{self, Query},
-    ServerState,
+    cache::content_hash,
+    client::message::WebSocketMessage,
+    server::{
+        handlers::node::read_source,
+        middleware::auth::CurrentUser,
+        services::{
+            audit_service::{self, AuditAction},
+            conflict_service, edit_service, org_service,
+        },
+        types::{EditConflict, NodeSource, RoamID},
+    },
+    sqlite::aliases,
+    transform::subtree::Subtree,
+    watcher, ServerState,
 };
 
+/// GET /org?id= or /org?title=
+/// `title` matches titles and aliases and resolves to the canonical node.
 pub async fn get_org_as_html_handler(
     AxumQuery(params): AxumQuery<HashMap<String, String>>,
     State(app_state): State<Arc<ServerState>>,
@@ -20,15 +35,105 @@ pub async fn get_org_as_html_handler(
         .cloned()
         .unwrap_or_else(|| "file".to_string());
 
-    let query = match params.get("id") {
-        Some(id) => Query::ById(id.clone().into()),
+    let id = match params.get("id") {
+        Some(id) => match RoamID::parse(id) {
+            Ok(id) => id,
+            Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+        },
         None => match params.get("title") {
-            Some(title) => Query::ByTitle(title.clone().into()),
+            Some(title) => match aliases::resolve_name(&app_state.sqlite, title).await {
+                Ok(Some(id)) => id.into(),
+                Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+                Err(err) => {
+                    tracing::error!("Failed to resolve {title}: {err}");
+                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                }
+            },
             None => return StatusCode::NOT_FOUND.into_response(),
         },
     };
 
-    org_service::get_org_as_html(app_state, query, scope)
-        .await
-        .into_response()
+    match org_service::get_org_as_html(app_state, id, scope).await {
+        Some(response) => response.into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct EditOrgParams {
+    id: RoamID,
+    /// Hash of the file the edit is based on, as returned by
+    /// `GET /node/source`.
+    hash: String,
+}
+
+/// PUT /org?id=&hash=
+/// Replace the subtree of the node, or the whole file for file nodes, with
+/// the org content of the body. If the file changed since the client read
+/// it, the write is rejected with `409 Conflict` and the file with the
+/// rejected subtree is kept in a conflict copy. Content that no longer
+/// contains the node is rejected with `422`.
+pub async fn edit_org_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    AxumQuery(params): AxumQuery<EditOrgParams>,
+    body: String,
+) -> Response {
+    let current = match read_source(&app_state, &params.id) {
+        Ok(current) => current,
+        Err(status) => return status.into_response(),
+    };
+    let Some(subtree) = Subtree::get(params.id.clone(), current.content()) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let current_hash = edit_service::format_hash(current.get_hash());
+    if current_hash != params.hash {
+        let rejected = edit_service::replace_subtree(current.content(), &params.id, &body);
+        let rejected = rejected.as_deref().unwrap_or(&body);
+        if let Err(err) = conflict_service::preserve(&app_state, current.path(), rejected) {
+            tracing::error!("Failed to keep the rejected edit: {err}");
+        }
+        return EditConflict {
+            hash: current_hash,
+            diff: edit_service::diff(&subtree, &body),
+            content: subtree,
+        }
+        .into_response();
+    }
+
+    let Some(content) = edit_service::replace_subtree(current.content(), &params.id, &body) else {
+        return StatusCode::UNPROCESSABLE_ENTITY.into_response();
+    };
+
+    let file = current.path().to_path_buf();
+    let path = app_state.cache.resolve(&file);
+    if let Err(err) = std::fs::write(&path, &content) {
+        tracing::error!("Failed to write {file:?}: {err}");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    // Index right away, the watcher might be disabled.
+    if let Err(err) = watcher::update_file(&app_state, &path).await {
+        tracing::error!("Failed to index edited file {file:?}: {err}");
+    }
+    app_state.notify_vault_changed();
+    app_state.broadcast_to_websockets(WebSocketMessage::StatusUpdate { files_changed: 1 });
+
+    let file = file.to_string_lossy().to_string();
+    audit_service::record(
+        &app_state.sqlite,
+        user.0.as_deref(),
+        AuditAction::Edit,
+        Some(&file),
+    )
+    .await;
+
+    NodeSource {
+        hash: edit_service::format_hash(content_hash(&content)),
+        content: Subtree::get(params.id.clone(), &content).unwrap_or(body),
+        id: params.id,
+        file,
+    }
+    .into_response()
 }
@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use crate::{
+    server::{
+        middleware::auth::CurrentUser,
+        types::{PinnedNode, RoamID},
+    },
+    sqlite::pins,
+    ServerState,
+};
+
+/// GET /pins
+/// List the pinned nodes of the current user.
+pub async fn get_pins_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+) -> Response {
+    match pins::get_pins(&app_state.sqlite, user.owner()).await {
+        Ok(pins) => {
+            let pins: Vec<PinnedNode> = pins
+                .into_iter()
+                .map(|(id, title)| PinnedNode {
+                    id: id.into(),
+                    title: title.map(Into::into),
+                })
+                .collect();
+            Json(pins).into_response()
+        }
+        Err(err) => {
+            tracing::error!("Failed to load pins: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// PUT /pins/{id}
+pub async fn pin_node_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Path(id): Path<RoamID>,
+) -> StatusCode {
+    if app_state.cache.retrieve(&id).is_none() {
+        return StatusCode::NOT_FOUND;
+    }
+
+    match pins::insert_pin(&app_state.sqlite, user.owner(), id.id()).await {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(err) => {
+            tracing::error!("Failed to pin {}: {err}", id.id());
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// DELETE /pins/{id}
+pub async fn unpin_node_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Path(id): Path<RoamID>,
+) -> StatusCode {
+    match pins::delete_pin(&app_state.sqlite, user.owner(), id.id()).await {
+        Ok(true) => StatusCode::NO_CONTENT,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(err) => {
+            tracing::error!("Failed to unpin {}: {err}", id.id());
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use crate::{server::middleware::auth::CurrentUser, sqlite::preferences, ServerState};
+
+/// GET /preferences
+/// Get all preferences of the current user as a JSON object.
+pub async fn get_preferences_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+) -> Response {
+    match preferences::get_preferences(&app_state.sqlite, user.owner()).await {
+        Ok(preferences) => Json(preferences).into_response(),
+        Err(err) => {
+            tracing::error!("Failed to load preferences: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// PUT /preferences/{key}
+/// Store an arbitrary JSON value under `key`.
+pub async fn set_preference_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Path(key): Path<String>,
+    Json(value): Json<serde_json::Value>,
+) -> StatusCode {
+    if !is_valid_key(&key) {
+        return StatusCode::BAD_REQUEST;
+    }
+
+    match preferences::set_preference(&app_state.sqlite, user.owner(), &key, &value).await {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(err) => {
+            tracing::error!("Failed to store preference {key}: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// DELETE /preferences/{key}
+pub async fn delete_preference_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Path(key): Path<String>,
+) -> StatusCode {
+    match preferences::delete_preference(&app_state.sqlite, user.owner(), &key).await {
+        Ok(true) => StatusCode::NO_CONTENT,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(err) => {
+            tracing::error!("Failed to delete preference {key}: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= 64
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_key() {
+        assert!(is_valid_key("graph.layout"));
+        assert!(is_valid_key("dark-mode_2"));
+        assert!(!is_valid_key(""));
+        assert!(!is_valid_key("a/b"));
+        assert!(!is_valid_key(&"k".repeat(65)));
+    }
+}
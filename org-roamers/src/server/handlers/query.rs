@@ -0,0 +1,42 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+
+use crate::{
+    server::services::query_service::{self, NodeQuery},
+    ServerState,
+};
+
+const DEFAULT_LIMIT: u32 = 200;
+
+#[derive(Deserialize)]
+pub struct QueryRequest {
+    query: NodeQuery,
+    limit: Option<u32>,
+}
+
+/// POST /query
+/// Evaluate a structured query against the index and return matching nodes.
+pub async fn query_handler(
+    State(app_state): State<Arc<ServerState>>,
+    Json(request): Json<QueryRequest>,
+) -> Response {
+    if request.query.num_predicates() > query_service::MAX_PREDICATES {
+        return (StatusCode::BAD_REQUEST, "Query is too complex").into_response();
+    }
+
+    let limit = request.limit.unwrap_or(DEFAULT_LIMIT);
+    match query_service::run_query(&app_state.sqlite, &request.query, limit).await {
+        Ok(matches) => Json(matches).into_response(),
+        Err(err) => {
+            tracing::error!("Failed to run query: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
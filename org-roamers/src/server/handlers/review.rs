@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+use crate::{
+    server::{
+        middleware::auth::CurrentUser,
+        services::review_service::{self, ReviewError},
+        types::RoamID,
+    },
+    ServerState,
+};
+
+#[derive(Deserialize)]
+pub struct NextParams {
+    /// Number of due nodes to return, defaults to 1
+    limit: Option<usize>,
+}
+
+/// GET /review/next?limit=
+/// Due nodes carrying one of the configured `review.tags` (`:srs:` by
+/// default), most overdue first.
+pub async fn next_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Query(params): Query<NextParams>,
+) -> Response {
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    match review_service::next(
+        &app_state.sqlite,
+        user.owner(),
+        &app_state.config.review.tags,
+        now,
+        params.limit.unwrap_or(1),
+    )
+    .await
+    {
+        Ok(queue) => queue.into_response(),
+        Err(err) => {
+            tracing::error!("Failed to load review queue: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct GradeRequest {
+    id: RoamID,
+    /// Recall quality from 0 (blackout) to 5 (perfect)
+    grade: u8,
+}
+
+/// POST /review/grade
+/// Record a review of `{"id": ..., "grade": 0-5}` and schedule the next one
+/// with SM-2.
+pub async fn grade_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Json(request): Json<GradeRequest>,
+) -> Response {
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    match review_service::grade(
+        &app_state.sqlite,
+        user.owner(),
+        request.id.id(),
+        request.grade,
+        now,
+    )
+    .await
+    {
+        Ok(item) => item.into_response(),
+        Err(err @ ReviewError::InvalidGrade(_)) => {
+            (StatusCode::BAD_REQUEST, err.to_string()).into_response()
+        }
+        Err(ReviewError::NotFound(_)) => StatusCode::NOT_FOUND.into_response(),
+        Err(ReviewError::Other(err)) => {
+            tracing::error!("Failed to grade {}: {err}", request.id.id());
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+};
+use serde::Deserialize;
+
+use crate::{
+    server::{
+        services::similar_service,
+        types::{RoamID, SimilarResponse},
+    },
+    ServerState,
+};
+
+const DEFAULT_LIMIT: usize = 10;
+const MAX_LIMIT: usize = 100;
+
+#[derive(Deserialize)]
+pub struct SimilarParams {
+    id: RoamID,
+    limit: Option<usize>,
+}
+
+/// GET /similar?id=&limit=
+/// Nodes of other files related to a node by shared tags, shared links and
+/// rare words they both use (TF-IDF, stemmed like the full text search),
+/// e.g. to suggest notes that should be linked. Each result carries its
+/// score, the shared tags and links, and whether it is linked already.
+pub async fn get_similar_handler(
+    State(app_state): State<Arc<ServerState>>,
+    Query(params): Query<SimilarParams>,
+) -> Result<SimilarResponse, StatusCode> {
+    let relations = similar_service::load(&app_state.sqlite, &params.id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to load nodes similar to {}: {err}", params.id.id());
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+    let stemming = app_state.config.search.stemming;
+    tokio::task::spawn_blocking(move || {
+        similar_service::rank(&app_state.cache, &relations, stemming, limit)
+    })
+    .await
+    .map_err(|err| {
+        tracing::error!("Failed to rank similar nodes: {err}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
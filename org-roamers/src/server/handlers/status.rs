@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use tokio::time::Duration;
+
+use crate::{server::types::StatusResponse, ServerState};
+
+/// Upper bound for the timeout of [`wait_status_handler`].
+const MAX_WAIT_SECS: u64 = 120;
+
+/// GET /status
+/// Counter of the changes of the vault, to pass to [`wait_status_handler`].
+pub async fn status_handler(State(app_state): State<Arc<ServerState>>) -> StatusResponse {
+    StatusResponse {
+        seq: *app_state.vault_changes.borrow(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct WaitParams {
+    since: u64,
+    /// Seconds to wait for a change.
+    timeout: Option<u64>,
+}
+
+/// GET /status/wait?since=<seq>
+/// Block until the vault changed after `since`. Responds with 204 if nothing
+/// changed before the timeout.
+pub async fn wait_status_handler(
+    State(app_state): State<Arc<ServerState>>,
+    Query(params): Query<WaitParams>,
+) -> Response {
+    let timeout = Duration::from_secs(params.timeout.unwrap_or(30).min(MAX_WAIT_SECS));
+    let mut receiver = app_state.vault_changes.subscribe();
+
+    let changed = tokio::time::timeout(timeout, receiver.wait_for(|seq| *seq > params.since)).await;
+
+    match changed {
+        Ok(Ok(seq)) => StatusResponse { seq: *seq }.into_response(),
+        Ok(Err(_)) => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+        Err(_) => StatusCode::NO_CONTENT.into_response(),
+    }
+}
@@ -0,0 +1,36 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+};
+use serde::Deserialize;
+
+use crate::{
+    server::{
+        services::timeline_service,
+        types::{RoamID, TimelineResponse},
+    },
+    ServerState,
+};
+
+#[derive(Deserialize)]
+pub struct TimelineParams {
+    id: RoamID,
+}
+
+/// GET /timeline?id=
+/// Creation, modifications, timestamps and backlinks of a node in
+/// chronological order.
+pub async fn get_timeline_handler(
+    State(app_state): State<Arc<ServerState>>,
+    Query(params): Query<TimelineParams>,
+) -> Result<TimelineResponse, StatusCode> {
+    timeline_service::get_timeline(&app_state.sqlite, &app_state.cache, &params.id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to build timeline: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)
+}
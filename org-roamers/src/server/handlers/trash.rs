@@ -0,0 +1,121 @@
+use std::{io, path::Path as FsPath, sync::Arc};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+
+use crate::{
+    client::message::WebSocketMessage,
+    server::{
+        middleware::auth::CurrentUser,
+        services::{
+            audit_service::{self, AuditAction},
+            trash_service,
+        },
+        types::{RoamID, TrashEntry, TrashResponse},
+    },
+    sqlite::trash,
+    watcher, ServerState,
+};
+
+#[derive(Deserialize)]
+pub struct DeleteParams {
+    id: RoamID,
+}
+
+/// DELETE /node?id=
+/// Move the file of a file level node to the trash. It can be restored until
+/// it is purged after `trash.retention_days`.
+pub async fn delete_node_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Query(params): Query<DeleteParams>,
+) -> Response {
+    let sqlite = &app_state.sqlite;
+    let file = match trash::get_node_file(sqlite, params.id.id()).await {
+        Ok(Some((file, 0))) => file,
+        Ok(Some(_)) => {
+            return (StatusCode::BAD_REQUEST, "Only file nodes can be deleted").into_response()
+        }
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            tracing::error!("Failed to get node {}: {err}", params.id.id());
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    if let Err(err) = trash_service::trash_file(&app_state, &file, user.0.as_deref()).await {
+        tracing::error!("Failed to move {file} to the trash: {err}");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    app_state.notify_vault_changed();
+    app_state.broadcast_to_websockets(WebSocketMessage::StatusUpdate { files_changed: 1 });
+
+    audit_service::record(sqlite, user.0.as_deref(), AuditAction::Delete, Some(&file)).await;
+
+    StatusCode::NO_CONTENT.into_response()
+}
+
+/// GET /trash
+/// Nodes of deleted files that can still be restored.
+pub async fn get_trash_handler(State(app_state): State<Arc<ServerState>>) -> Response {
+    match trash::get_deleted(&app_state.sqlite).await {
+        Ok(rows) => TrashResponse {
+            entries: rows.into_iter().map(TrashEntry::from).collect(),
+        }
+        .into_response(),
+        Err(err) => {
+            tracing::error!("Failed to get trash: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// POST /trash/{id}/restore
+/// Move the file of a deleted node back to its original location.
+pub async fn restore_handler(
+    State(app_state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    Path(id): Path<RoamID>,
+) -> Response {
+    let sqlite = &app_state.sqlite;
+    let (file, trash_path) = match trash::get_deleted_node(sqlite, id.id()).await {
+        Ok(Some((_, _, file, trash_path, _, _))) => (file, trash_path),
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            tracing::error!("Failed to get deleted node {}: {err}", id.id());
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    match trash_service::restore(
+        &app_state.cache,
+        FsPath::new(&trash_path),
+        FsPath::new(&file),
+    ) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+            return (StatusCode::CONFLICT, err.to_string()).into_response()
+        }
+        Err(err) => {
+            tracing::error!("Failed to restore {file}: {err}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    if let Err(err) = trash::delete_trash_entry(sqlite, &trash_path).await {
+        tracing::error!("Failed to remove trash entry {trash_path}: {err}");
+    }
+    if let Err(err) = watcher::update_file(&app_state, &app_state.cache.resolve(&file)).await {
+        tracing::error!("Failed to index restored file {file}: {err}");
+    }
+    app_state.notify_vault_changed();
+    app_state.broadcast_to_websockets(WebSocketMessage::StatusUpdate { files_changed: 1 });
+
+    audit_service::record(sqlite, user.0.as_deref(), AuditAction::Restore, Some(&file)).await;
+
+    StatusCode::NO_CONTENT.into_response()
+}
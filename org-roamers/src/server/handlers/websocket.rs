@@ -2,15 +2,37 @@ use std::sync::Arc;
 
 use axum::{
     extract::{ws::WebSocketUpgrade, State},
+    http::{header, HeaderMap},
     response::Response,
 };
 
-use crate::{client::handle_websocket, ServerState};
+use crate::{client::handle_websocket, server::middleware::auth::CurrentUser, ServerState};
 
+/// GET /ws
+/// Websocket of the web UI. Clients receive every broadcast until they send
+/// `{"type": "subscribe", "topics": [...]}`, afterwards only the messages of
+/// the subscribed topics: `graph`, `status`, `visits`, `links`, `reindex`
+/// and `node:<id>`. `unsubscribe` removes topics again.
+///
+/// With `collab` enabled, clients edit files together: `collab_join`
+/// answers with the document of the node's file as a yrs (Yjs) update and
+/// `collab_update`s are relayed to the other clients of the file. Edits
+/// that conflict with a change on disk are kept in a conflict copy, which
+/// is announced by a `conflict` message on the `status` topic.
 pub async fn websocket_handler(
     ws: WebSocketUpgrade,
     State(app_state): State<Arc<ServerState>>,
+    CurrentUser(user): CurrentUser,
+    headers: HeaderMap,
 ) -> Response {
     let app_state_clone = app_state.clone();
-    ws.on_upgrade(move |socket| handle_websocket(socket, app_state_clone))
+    let user_agent = user_agent(&headers);
+    ws.on_upgrade(move |socket| handle_websocket(socket, app_state_clone, user, user_agent))
+}
+
+pub(crate) fn user_agent(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
 }
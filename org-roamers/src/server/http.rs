@@ -0,0 +1,137 @@
+//! Connection handling of the HTTP server. `axum::serve` does not expose
+//! protocol settings, so connections are served with hyper directly.
+
+use std::{sync::Arc, time::Duration};
+
+use axum::{extract::Request, middleware::Next, Router};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo, TokioTimer},
+    server::{conn::auto, graceful::GracefulShutdown},
+    service::TowerToHyperService,
+};
+use tokio::{net::TcpListener, sync::Semaphore};
+use tokio_util::sync::CancellationToken;
+
+use crate::config::HttpServerConfig;
+
+/// Time open connections get to finish after shutdown was requested.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+
+/// Routes that stay open for as long as a client follows them. They do not
+/// count towards `max_concurrent_requests`, otherwise a few open tabs would
+/// use up the limit.
+const LONG_LIVED: &[&str] = &["/ws", "/events", "/status/wait", "/emacs/follow"];
+
+/// Apply server wide limits to the application.
+pub fn limit(app: Router, config: &HttpServerConfig) -> Router {
+    let semaphore = match config.max_concurrent_requests {
+        0 => return app,
+        limit => Arc::new(Semaphore::new(limit)),
+    };
+
+    app.layer(axum::middleware::from_fn(
+        move |request: Request, next: Next| {
+            let semaphore = semaphore.clone();
+            async move {
+                if LONG_LIVED.contains(&request.uri().path()) {
+                    return next.run(request).await;
+                }
+                // The semaphore is never closed.
+                let _permit = semaphore.acquire_owned().await;
+                next.run(request).await
+            }
+        },
+    ))
+}
+
+fn builder(config: &HttpServerConfig) -> auto::Builder<TokioExecutor> {
+    let mut builder = auto::Builder::new(TokioExecutor::new());
+    builder.http1().keep_alive(config.keep_alive_secs > 0);
+    if !config.http2 {
+        return builder.http1_only();
+    }
+
+    let mut http2 = builder.http2();
+    http2.max_concurrent_streams(config.max_concurrent_streams);
+    if config.keep_alive_secs > 0 {
+        http2
+            .timer(TokioTimer::new())
+            .keep_alive_interval(Duration::from_secs(config.keep_alive_secs));
+    }
+    builder
+}
+
+/// Serve `app` on `listener` until `shutdown` is cancelled.
+pub async fn serve(
+    listener: TcpListener,
+    app: Router,
+    config: &HttpServerConfig,
+    shutdown: CancellationToken,
+) {
+    let builder = builder(config);
+    let graceful = GracefulShutdown::new();
+
+    loop {
+        let stream = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    tracing::error!("Failed to accept connection: {err}");
+                    continue;
+                }
+            },
+        };
+
+        let service = TowerToHyperService::new(app.clone());
+        let connection = builder
+            .serve_connection_with_upgrades(TokioIo::new(stream), service)
+            .into_owned();
+        let connection = graceful.watch(connection);
+        tokio::spawn(async move {
+            if let Err(err) = connection.await {
+                tracing::debug!("Connection closed with error: {err}");
+            }
+        });
+    }
+
+    if tokio::time::timeout(SHUTDOWN_GRACE, graceful.shutdown())
+        .await
+        .is_err()
+    {
+        tracing::warn!("Closing connections that did not finish in {SHUTDOWN_GRACE:?}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http, routing::get};
+    use tower::Service;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_long_lived_routes_are_not_limited() {
+        let app = Router::new()
+            .route("/events", get(std::future::pending::<()>))
+            .route("/slow", get(std::future::pending::<()>))
+            .route("/status", get(|| async {}));
+        let config = HttpServerConfig {
+            max_concurrent_requests: 1,
+            ..Default::default()
+        };
+        let app = limit(app, &config);
+        let request = |path: &str| http::Request::get(path).body(Body::empty()).unwrap();
+        let timeout = Duration::from_millis(100);
+
+        let _events = tokio::spawn(app.clone().call(request("/events")));
+        tokio::task::yield_now().await;
+        let status = tokio::time::timeout(timeout, app.clone().call(request("/status"))).await;
+        assert!(status.unwrap().unwrap().status().is_success());
+
+        let _slow = tokio::spawn(app.clone().call(request("/slow")));
+        tokio::task::yield_now().await;
+        let status = tokio::time::timeout(timeout, app.clone().call(request("/status"))).await;
+        assert!(status.is_err());
+    }
+}
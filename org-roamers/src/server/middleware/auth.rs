@@ -1,16 +1,21 @@
 use axum::{
     body::Body,
-    extract::{Request, State},
-    http::StatusCode,
+    extract::{FromRequestParts, Request, State},
+    http::{request::Parts, StatusCode},
     middleware::Next,
     response::Response,
 };
 use std::sync::Arc;
 use tower_sessions::Session;
 
-use crate::ServerState;
+use crate::{config::AuthConfig, ServerState};
 
 const SESSION_USER_KEY: &str = "username";
+const SESSION_LAST_ACTIVITY_KEY: &str = "last_activity";
+
+/// Minimum number of seconds between two updates of the last activity, so
+/// not every request writes the session.
+const ACTIVITY_RESOLUTION_SECS: i64 = 60;
 
 /// Middleware to require authentication
 /// Checks if session contains an authenticated user
@@ -31,6 +36,114 @@ pub async fn require_auth(
         return Err(StatusCode::UNAUTHORIZED);
     }
 
+    if let Err(err) = record_activity(&session, false).await {
+        tracing::error!("Failed to update session activity: {err}");
+    }
+
     // User is authenticated, proceed
     Ok(next.run(request).await)
 }
+
+/// Middleware to restrict routes to the users listed in `admins` of the
+/// authentication config, see [`check_admin`].
+pub async fn require_admin(
+    State(state): State<Arc<ServerState>>,
+    user: CurrentUser,
+    request: Request<Body>,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let auth = state.config.authentication.as_ref();
+    check_admin(auth, user.0.as_deref())?;
+    Ok(next.run(request).await)
+}
+
+/// Whether `user` may use admin routes. Without authentication every client
+/// is an admin.
+pub fn check_admin(auth: Option<&AuthConfig>, user: Option<&str>) -> Result<(), StatusCode> {
+    let Some(auth) = auth.filter(|auth| auth.enabled) else {
+        return Ok(());
+    };
+    match user {
+        Some(user) if auth.admins.iter().any(|admin| admin == user) => Ok(()),
+        Some(user) => {
+            tracing::warn!("User {user} is not allowed to use admin routes");
+            Err(StatusCode::FORBIDDEN)
+        }
+        None => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+/// Store the current time as last activity of `session`. Unless `force` is
+/// set, recent activity is not overwritten.
+pub async fn record_activity(
+    session: &Session,
+    force: bool,
+) -> Result<(), tower_sessions::session::Error> {
+    let now = time::OffsetDateTime::now_utc().unix_timestamp();
+    let last: Option<i64> = session.get(SESSION_LAST_ACTIVITY_KEY).await?;
+    if force || last.is_none_or(|last| now - last >= ACTIVITY_RESOLUTION_SECS) {
+        session.insert(SESSION_LAST_ACTIVITY_KEY, now).await?;
+    }
+    Ok(())
+}
+
+/// The user that issued a request. `None` if authentication is disabled, in
+/// which case all clients share the same per-user data.
+pub struct CurrentUser(pub Option<String>);
+
+impl CurrentUser {
+    /// Key under which per-user data is stored.
+    pub fn owner(&self) -> &str {
+        self.0
+            .as_deref()
+            .unwrap_or(crate::sqlite::preferences::GLOBAL_USER)
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for CurrentUser {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        // The session layer is only installed if authentication is enabled.
+        let Some(session) = parts.extensions.get::<Session>() else {
+            return Ok(CurrentUser(None));
+        };
+
+        let username: Option<String> = session.get(SESSION_USER_KEY).await.map_err(|e| {
+            tracing::error!("Failed to get session: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+        Ok(CurrentUser(username))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_admin() {
+        let auth = AuthConfig {
+            enabled: true,
+            admins: vec!["root".into()],
+            ..Default::default()
+        };
+        assert_eq!(check_admin(Some(&auth), Some("root")), Ok(()));
+        assert_eq!(
+            check_admin(Some(&auth), Some("guest")),
+            Err(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(
+            check_admin(Some(&auth), None),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+
+        let disabled = AuthConfig {
+            enabled: false,
+            ..auth
+        };
+        assert_eq!(check_admin(Some(&disabled), None), Ok(()));
+        assert_eq!(check_admin(None, None), Ok(()));
+    }
+}
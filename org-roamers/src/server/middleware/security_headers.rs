@@ -0,0 +1,56 @@
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+
+use crate::ServerState;
+
+/// Middleware adding the configured security headers to HTML responses.
+/// Headers already set by a handler are kept.
+pub async fn security_headers(
+    State(state): State<Arc<ServerState>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+    if !is_html {
+        return response;
+    }
+
+    let config = &state.config.security_headers;
+    let headers = [
+        (
+            header::CONTENT_SECURITY_POLICY,
+            &config.content_security_policy,
+        ),
+        (header::X_FRAME_OPTIONS, &config.x_frame_options),
+        (header::REFERRER_POLICY, &config.referrer_policy),
+    ];
+    for (name, value) in headers {
+        insert_header(&mut response, name, value.as_deref());
+    }
+
+    response
+}
+
+fn insert_header(response: &mut Response, name: HeaderName, value: Option<&str>) {
+    let Some(value) = value else {
+        return;
+    };
+    match HeaderValue::from_str(value) {
+        Ok(value) => {
+            response.headers_mut().entry(name).or_insert(value);
+        }
+        Err(err) => tracing::error!("Invalid value for {name}: {err}"),
+    }
+}
@@ -5,21 +5,27 @@ use crate::{
     ServerState,
 };
 use axum::{
+    extract::DefaultBodyLimit,
     middleware as axum_middleware,
-    routing::{get, post},
+    routing::{delete, get, post, put},
     Router,
 };
-use handlers::{assets, auth, emacs as emacs_handler, graph, health, latex, org, tags, websocket};
+use handlers::{
+    admin, agenda, assets, auth, backlinks, board, calendar, capture, citations, diagnostics,
+    emacs as emacs_handler, events, export, extract, graph, health, history, latex, merge, moves,
+    node, org, pins, preferences, query, review, similar, status, tags, timeline, trash, websocket,
+};
 use time::Duration;
 use tower_http::cors::CorsLayer;
 use tower_sessions::{session_store::ExpiredDeletion, Expiry, SessionManagerLayer};
 use tracing::info;
 
 mod data;
-mod emacs;
+pub(crate) mod emacs;
 mod handlers;
+pub(crate) mod http;
 mod middleware;
-mod services;
+pub(crate) mod services;
 pub mod types;
 
 pub async fn build_server_with_auth(
@@ -28,8 +34,18 @@ pub async fn build_server_with_auth(
 ) -> Router {
     info!("Setting up authentication middleware...");
 
-    // Create session store from existing pool (clone for session use)
-    let session_store = crate::auth::session_store::create_session_store(app_state.sqlite.clone())
+    // Use a separate database if configured, so sessions survive restarts.
+    // Otherwise share the in-memory pool.
+    let session_pool = match &auth_config.session.database {
+        Some(path) => {
+            info!("Storing sessions in {}", path.display());
+            crate::auth::session_store::open_session_db(path)
+                .await
+                .expect("Failed to open session database")
+        }
+        None => app_state.sqlite.clone(),
+    };
+    let session_store = crate::auth::session_store::create_session_store(session_pool)
         .await
         .expect("Failed to initialize session store");
 
@@ -70,30 +86,28 @@ pub async fn build_server_with_auth(
 
     // Build protected and public routers separately, then merge
     // Protected routes - API endpoints that require authentication
-    let protected = Router::new()
-        .route("/assets", get(assets::serve_assets_handler))
-        .route("/org", get(org::get_org_as_html_handler))
-        .route("/graph", get(graph::get_graph_data_handler))
-        .route("/tags", get(tags::get_tags_handler))
-        .route("/latex", get(latex::get_latex_svg_handler))
-        .route("/ws", get(websocket::websocket_handler))
-        .route("/emacs", post(emacs_handler::emacs_handler))
-        .layer(axum_middleware::from_fn_with_state(
-            app_state.clone(),
-            middleware::auth::require_auth,
-        ));
+    let protected = api_routes(&app_state).layer(axum_middleware::from_fn_with_state(
+        app_state.clone(),
+        middleware::auth::require_auth,
+    ));
 
     // Public routes - static assets and auth endpoints (no auth required)
     let public = Router::new()
         .route("/", get(health::default_route))
+        .route("/healthz", get(health::healthz_handler))
         .route("/api/login", post(auth::login_handler))
         .route("/api/logout", post(auth::logout_handler))
         .route("/api/session", get(auth::check_session_handler))
+        .route("/api/session/refresh", post(auth::refresh_session_handler))
         .fallback(assets::fallback_handler);
 
     public
         .merge(protected)
         .layer(session_layer)
+        .layer(axum_middleware::from_fn_with_state(
+            app_state.clone(),
+            middleware::security_headers::security_headers,
+        ))
         .with_state(app_state.clone())
 }
 
@@ -108,14 +122,141 @@ pub async fn build_server(app_state: Arc<ServerState>) -> Router {
     // No authentication - return router without session layer
     Router::new()
         .route("/", get(health::default_route))
+        .route("/healthz", get(health::healthz_handler))
+        .merge(api_routes(&app_state))
+        .fallback(assets::fallback_handler)
+        .layer(CorsLayer::permissive().allow_credentials(true))
+        .layer(axum_middleware::from_fn_with_state(
+            app_state.clone(),
+            middleware::security_headers::security_headers,
+        ))
+        .with_state(app_state.clone())
+}
+
+/// API routes shared by the server with and without authentication. In
+/// read-only mode only routes that do not modify the vault or stored state
+/// are mounted.
+fn api_routes(app_state: &Arc<ServerState>) -> Router<Arc<ServerState>> {
+    let read_only = app_state.config.read_only;
+    let router = Router::new()
+        .merge(admin_routes(app_state))
+        .route("/assets", get(assets::serve_assets_handler))
         .route("/org", get(org::get_org_as_html_handler))
+        .route("/node/outline", get(node::get_outline_handler))
+        .route("/node/neighbors", get(node::get_neighbors_handler))
+        .route("/node/source", get(node::get_source_handler))
         .route("/graph", get(graph::get_graph_data_handler))
+        .route("/graph/cluster/{id}", get(graph::get_graph_cluster_handler))
+        .route("/graph/clusters", get(graph::get_graph_clusters_handler))
+        .route("/graph/lite", get(graph::get_lite_graph_handler))
+        .route("/graph/neighborhood", get(graph::get_neighborhood_handler))
+        .route("/graph/render", get(graph::render_graph_handler))
+        .route("/graph/citations", get(graph::get_citation_graph_handler))
+        .route("/graph/views", get(graph::get_graph_views_handler))
+        .route("/graph/views/{name}", get(graph::get_graph_view_handler))
+        .route("/citations/{key}", get(citations::get_citation_handler))
+        .route("/refs", get(citations::get_refs_handler))
+        .route("/export/index.json", get(export::export_index_handler))
+        .route("/export/anki.txt", get(export::export_anki_handler))
+        .route("/export/bundle.zip", get(export::export_bundle_handler))
         .route("/tags", get(tags::get_tags_handler))
+        .route("/calendar", get(calendar::get_calendar_handler))
+        .route("/board", get(board::get_board_handler))
+        .route("/agenda", get(agenda::get_agenda_handler))
+        .route("/timeline", get(timeline::get_timeline_handler))
+        .route("/backlinks", get(backlinks::get_backlinks_handler))
+        .route("/similar", get(similar::get_similar_handler))
+        .route("/query", post(query::query_handler))
+        .route(
+            "/diagnostics/duplicate-titles",
+            get(diagnostics::duplicate_titles_handler),
+        )
+        .route(
+            "/diagnostics/files",
+            get(diagnostics::file_diagnostics_handler),
+        )
+        .route("/diagnostics/links", get(diagnostics::broken_links_handler))
+        .route("/diagnostics/lint", get(diagnostics::lint_handler))
+        .route(
+            "/diagnostics/reindex",
+            get(diagnostics::reindex_report_handler),
+        )
         .route("/latex", get(latex::get_latex_svg_handler))
         .route("/ws", get(websocket::websocket_handler))
+        .route("/events", get(events::events_handler))
+        .route("/status", get(status::status_handler))
+        .route("/status/wait", get(status::wait_status_handler))
         .route("/emacs", post(emacs_handler::emacs_handler))
-        .route("/assets", get(assets::serve_assets_handler))
-        .fallback(assets::fallback_handler)
-        .layer(CorsLayer::permissive().allow_credentials(true))
-        .with_state(app_state.clone())
+        .route("/emacs/follow", get(emacs_handler::emacs_follow_handler))
+        .route(
+            "/emacs/candidates",
+            get(emacs_handler::emacs_candidates_handler),
+        )
+        .route("/preferences", get(preferences::get_preferences_handler))
+        .route("/history", get(history::get_history_handler))
+        .route("/history/recent", get(history::get_recent_handler))
+        .route("/pins", get(pins::get_pins_handler))
+        .route("/review/next", get(review::next_handler))
+        .route("/trash", get(trash::get_trash_handler));
+
+    if read_only {
+        info!("Read-only mode: mutating endpoints are disabled");
+        return router;
+    }
+
+    router
+        .route(
+            "/assets",
+            post(assets::upload_assets_handler)
+                .layer(DefaultBodyLimit::max(assets::MAX_UPLOAD_SIZE)),
+        )
+        .route(
+            "/graph/layout",
+            post(graph::set_graph_layout_handler).delete(graph::clear_graph_layout_handler),
+        )
+        .route(
+            "/graph/views/{name}",
+            put(graph::put_graph_view_handler).delete(graph::delete_graph_view_handler),
+        )
+        .route("/node", delete(trash::delete_node_handler))
+        .route("/org", put(org::edit_org_handler))
+        .route("/node/source", put(node::edit_source_handler))
+        .route("/node/merge", post(merge::merge_handler))
+        .route("/node/extract", post(extract::extract_handler))
+        .route("/node/move", post(moves::move_handler))
+        .route("/trash/{id}/restore", post(trash::restore_handler))
+        .route("/capture", post(capture::capture_handler))
+        .route("/daily", post(capture::daily_handler))
+        .route(
+            "/preferences/{key}",
+            put(preferences::set_preference_handler).delete(preferences::delete_preference_handler),
+        )
+        .route("/history", delete(history::clear_history_handler))
+        .route("/history/back", post(history::back_handler))
+        .route("/history/forward", post(history::forward_handler))
+        .route("/history/{id}", post(history::push_history_handler))
+        .route(
+            "/pins/{id}",
+            put(pins::pin_node_handler).delete(pins::unpin_node_handler),
+        )
+        .route("/review/grade", post(review::grade_handler))
+}
+
+/// Routes below `/admin`, restricted to the admins of the authentication
+/// config.
+fn admin_routes(app_state: &Arc<ServerState>) -> Router<Arc<ServerState>> {
+    let router = Router::new()
+        .route("/admin/audit", get(admin::audit_log_handler))
+        .route("/admin/connections", get(admin::connections_handler));
+    let router = match app_state.config.read_only {
+        true => router,
+        false => router
+            .route("/admin/archive", post(admin::archive_handler))
+            .route("/admin/reindex", post(admin::reindex_handler))
+            .route("/admin/connections/{id}", delete(admin::disconnect_handler)),
+    };
+    router.route_layer(axum_middleware::from_fn_with_state(
+        app_state.clone(),
+        middleware::auth::require_admin,
+    ))
 }
@@ -0,0 +1,111 @@
+use sqlx::SqlitePool;
+
+use crate::server::types::{AgendaItem, AgendaKind, AgendaResponse};
+use crate::sqlite::tasks::{self, TaskRow};
+use crate::transform::title::TitleSanitizer;
+
+/// Tasks scheduled or due between the `YYYY-MM-DD` dates `from` and `to`.
+/// Tasks with one of the `done` keywords are left out.
+pub async fn get_agenda(
+    sqlite: &SqlitePool,
+    from: &str,
+    to: &str,
+    done: &[String],
+) -> anyhow::Result<AgendaResponse> {
+    let rows = tasks::get_tasks_between(sqlite, from, to).await?;
+    Ok(AgendaResponse {
+        from: from.to_string(),
+        to: to.to_string(),
+        items: agenda_items(rows, from, to, done),
+    })
+}
+
+/// One item per date of a task within the range, like the Org agenda: a
+/// task that is both scheduled and due in the range appears twice. Items
+/// are ordered by date, then by priority.
+fn agenda_items(rows: Vec<TaskRow>, from: &str, to: &str, done: &[String]) -> Vec<AgendaItem> {
+    let in_range = |date: &Option<String>| {
+        date.as_deref()
+            .is_some_and(|date| (from..=to).contains(&date))
+    };
+    let mut items = vec![];
+    for (title, file, node, todo, priority, scheduled, deadline) in rows {
+        if todo.as_ref().is_some_and(|todo| done.contains(todo)) {
+            continue;
+        }
+        let title = TitleSanitizer::new().process(&title);
+        for (date, kind) in [
+            (&deadline, AgendaKind::Deadline),
+            (&scheduled, AgendaKind::Scheduled),
+        ] {
+            if !in_range(date) {
+                continue;
+            }
+            items.push(AgendaItem {
+                date: date.clone().unwrap_or_default(),
+                kind,
+                title: title.as_str().into(),
+                file: file.clone(),
+                node: node.clone().map(Into::into),
+                todo: todo.clone(),
+                priority: priority.clone(),
+            });
+        }
+    }
+    // Items without a priority come after the ones with one.
+    items.sort_by(|a, b| {
+        (&a.date, a.priority.is_none(), &a.priority, a.title.title()).cmp(&(
+            &b.date,
+            b.priority.is_none(),
+            &b.priority,
+            b.title.title(),
+        ))
+    });
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(title: &str, todo: &str, scheduled: &str, deadline: &str) -> TaskRow {
+        let some = |s: &str| (!s.is_empty()).then(|| s.to_string());
+        (
+            title.to_string(),
+            "a.org".to_string(),
+            None,
+            some(todo),
+            None,
+            some(scheduled),
+            some(deadline),
+        )
+    }
+
+    #[test]
+    fn test_agenda_items() {
+        let mut urgent = row("Urgent", "TODO", "2024-05-02", "");
+        urgent.4 = Some("A".to_string());
+        let rows = vec![
+            row("Report", "TODO", "2024-05-01", "2024-05-03"),
+            row("Later", "TODO", "2024-06-01", "2024-05-02"),
+            row("Finished", "DONE", "2024-05-01", ""),
+            row("Meeting", "", "2024-05-02", ""),
+            urgent,
+        ];
+        let items = agenda_items(rows, "2024-05-01", "2024-05-03", &["DONE".to_string()]);
+        let summary: Vec<(&str, &str, AgendaKind)> = items
+            .iter()
+            .map(|item| (item.date.as_str(), item.title.title(), item.kind))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("2024-05-01", "Report", AgendaKind::Scheduled),
+                ("2024-05-02", "Urgent", AgendaKind::Scheduled),
+                ("2024-05-02", "Later", AgendaKind::Deadline),
+                ("2024-05-02", "Meeting", AgendaKind::Scheduled),
+                ("2024-05-03", "Report", AgendaKind::Deadline),
+            ]
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use time::OffsetDateTime;
+
+use crate::server::services::capture_service::{self, heading_level};
+use crate::server::services::move_service;
+use crate::server::types::{ArchiveReport, ArchivedFile, ArchivedSubtree};
+use crate::sqlite::files;
+use crate::transform::subtree::Subtree;
+use crate::{watcher, ServerState};
+
+const DAY: i64 = 24 * 60 * 60;
+/// Months are counted as 30 days.
+const MONTH: i64 = 30 * DAY;
+
+/// Move nodes that were not touched for `months` into the archive directory.
+/// Whole files qualify if they were not modified since and no recently
+/// modified file links to them. Headlines of recently modified files
+/// qualify if all their dates are older and nothing recent links to them.
+/// With `dry_run` the candidates are only reported.
+pub async fn archive(
+    state: &ServerState,
+    months: u32,
+    dry_run: bool,
+    now: OffsetDateTime,
+) -> anyhow::Result<ArchiveReport> {
+    let cutoff = now.unix_timestamp() - i64::from(months) * MONTH;
+    let cutoff_date = OffsetDateTime::from_unix_timestamp(cutoff)?
+        .date()
+        .to_string();
+    let dir = &state.config.archive.dir;
+
+    let linked = recently_linked(&state.sqlite, cutoff).await?;
+    let mut report = ArchiveReport {
+        dry_run,
+        ..Default::default()
+    };
+
+    const FILES: &str = concat!(
+        "SELECT f.file, COUNT(n.id) FROM files f\n",
+        "JOIN nodes n ON n.file = f.file\n",
+        "WHERE f.mtime < ?\n",
+        "GROUP BY f.file ORDER BY f.file;"
+    );
+    let stale: Vec<(String, i64)> = sqlx::query_as(FILES)
+        .bind(cutoff)
+        .fetch_all(&state.sqlite)
+        .await?;
+    for (file, nodes) in stale {
+        let ids = files::get_node_ids(&state.sqlite, &file).await?;
+        if ids.iter().any(|id| linked.contains(id)) {
+            continue;
+        }
+        report.files.push(ArchivedFile {
+            archive: archive_path(dir, &file),
+            file,
+            nodes: nodes as usize,
+        });
+    }
+
+    const SUBTREES: &str = concat!(
+        "SELECT n.id, n.title, n.file FROM nodes n\n",
+        "JOIN files f ON f.file = n.file\n",
+        "JOIN dates d ON d.node_id = n.id\n",
+        "WHERE n.level > 0 AND f.mtime >= ?\n",
+        "GROUP BY n.id HAVING MAX(d.date) < ?\n",
+        "ORDER BY n.file, n.id;"
+    );
+    let subtrees: Vec<(String, String, String)> = sqlx::query_as(SUBTREES)
+        .bind(cutoff)
+        .bind(&cutoff_date)
+        .fetch_all(&state.sqlite)
+        .await?;
+    for (id, title, file) in subtrees {
+        if linked.contains(&id) {
+            continue;
+        }
+        report.subtrees.push(ArchivedSubtree {
+            id: id.into(),
+            title: title.into(),
+            archive: archive_path(dir, &file),
+            file,
+        });
+    }
+
+    if dry_run {
+        return Ok(report);
+    }
+
+    let cache = &state.cache;
+    // Archives are kept below the root, even for files of mounted roots.
+    let root = cache.path();
+    for archived in &report.files {
+        let content = fs::read_to_string(cache.resolve(&archived.file))?;
+        let relocated = move_service::relocate_links(&content, &archived.file, &archived.archive);
+        append(root, &archived.archive, &relocated)?;
+        fs::remove_file(cache.resolve(&archived.file))?;
+        if let Err(err) = files::delete_file(&state.sqlite, &archived.file).await {
+            tracing::error!(
+                "Failed to remove {} from the database: {err}",
+                archived.file
+            );
+        }
+        cache.remove_file(Path::new(&archived.file));
+    }
+
+    let mut changed = vec![];
+    report.subtrees.retain(|archived| {
+        let path = cache.resolve(&archived.file);
+        let Ok(content) = fs::read_to_string(&path) else {
+            return false;
+        };
+        // Subtrees of archived headlines are gone with their parent.
+        let Some(subtree) = Subtree::get(archived.id.clone(), &content) else {
+            return false;
+        };
+        let entry = archive_entry(&subtree, &archived.file, now);
+        let relocated = move_service::relocate_links(&entry, &archived.file, &archived.archive);
+        if let Err(err) = append(root, &archived.archive, &relocated)
+            .and_then(|_| Ok(fs::write(&path, content.replacen(&subtree, "", 1))?))
+        {
+            tracing::error!("Failed to archive {}: {err}", archived.id.id());
+            return false;
+        }
+        changed.push(path);
+        true
+    });
+    changed.dedup();
+    for path in changed {
+        if let Err(err) = watcher::update_file(state, &path).await {
+            tracing::error!("Failed to index {}: {err}", path.display());
+        }
+    }
+
+    Ok(report)
+}
+
+/// Nodes linked by nodes of files modified since `cutoff`.
+async fn recently_linked(
+    sqlite: &sqlx::SqlitePool,
+    cutoff: i64,
+) -> anyhow::Result<HashSet<String>> {
+    const STMNT: &str = concat!(
+        "SELECT DISTINCT l.dest FROM links l\n",
+        "JOIN nodes n ON n.id = l.source\n",
+        "JOIN files f ON f.file = n.file\n",
+        "WHERE l.type = 'id' AND l.source != l.dest AND f.mtime >= ?;"
+    );
+    let rows: Vec<(String,)> = sqlx::query_as(STMNT).bind(cutoff).fetch_all(sqlite).await?;
+    Ok(rows.into_iter().map(|(id,)| id).collect())
+}
+
+/// Archive of `file` within `dir`, named like the archives of Org mode, e.g.
+/// `archive/notes/a.org_archive`.
+fn archive_path(dir: &Path, file: &str) -> String {
+    let mut path = PathBuf::from(dir).join(file).into_os_string();
+    path.push("_archive");
+    path.to_string_lossy().to_string()
+}
+
+fn append(root: &Path, archive: &str, content: &str) -> anyhow::Result<()> {
+    let path = root.join(archive);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    if file.metadata()?.len() > 0 {
+        file.write_all(b"\n")?;
+    }
+    file.write_all(content.as_bytes())?;
+    if !content.ends_with('\n') {
+        file.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// `subtree` promoted to the top level with the `ARCHIVE_TIME` and
+/// `ARCHIVE_FILE` properties of `org-archive-subtree`.
+fn archive_entry(subtree: &str, file: &str, now: OffsetDateTime) -> String {
+    let level = subtree.lines().next().and_then(heading_level).unwrap_or(1);
+    let weekday = now.weekday().to_string();
+    let time = format!(
+        "{} {} {}",
+        capture_service::expand_pattern("%Y-%m-%d", "", now),
+        &weekday[..3],
+        capture_service::expand_pattern("%H:%M", "", now),
+    );
+    let properties = format!(":ARCHIVE_TIME: {time}\n:ARCHIVE_FILE: {file}\n");
+
+    let mut entry = String::with_capacity(subtree.len() + properties.len());
+    let mut in_drawer = false;
+    let mut added = false;
+    for line in subtree.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if !added && trimmed.eq_ignore_ascii_case(":PROPERTIES:") {
+            in_drawer = true;
+        } else if in_drawer && trimmed.eq_ignore_ascii_case(":END:") {
+            entry.push_str(&properties);
+            in_drawer = false;
+            added = true;
+        }
+        match heading_level(line) {
+            Some(child) if child >= level => entry.push_str(&line[level - 1..]),
+            _ => entry.push_str(line),
+        }
+    }
+    if !entry.ends_with('\n') {
+        entry.push('\n');
+    }
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn test_archive_path() {
+        assert_eq!(
+            archive_path(Path::new("archive"), "notes/a.org"),
+            "archive/notes/a.org_archive"
+        );
+    }
+
+    #[test]
+    fn test_archive_entry() {
+        let subtree = concat!(
+            "** DONE Old :work:\n",
+            "CLOSED: [2020-01-01 Wed]\n",
+            ":PROPERTIES:\n",
+            ":ID: old\n",
+            ":END:\n",
+            "Text\n",
+            "*** Child\n",
+        );
+        assert_eq!(
+            archive_entry(subtree, "a.org", datetime!(2024-03-05 09:07 UTC)),
+            concat!(
+                "* DONE Old :work:\n",
+                "CLOSED: [2020-01-01 Wed]\n",
+                ":PROPERTIES:\n",
+                ":ID: old\n",
+                ":ARCHIVE_TIME: 2024-03-05 Tue 09:07\n",
+                ":ARCHIVE_FILE: a.org\n",
+                ":END:\n",
+                "Text\n",
+                "** Child\n",
+            )
+        );
+    }
+}
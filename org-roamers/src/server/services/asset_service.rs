@@ -1,12 +1,16 @@
-use std::fs::File;
-use std::io::Read;
-use std::path::{Path, PathBuf};
+use std::fs::{self, OpenOptions};
+use std::io::{self, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
+use std::time::UNIX_EPOCH;
 
 use axum::{
+    body::Body,
     http::{HeaderMap, StatusCode},
     response::{IntoResponse, Response},
 };
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
 
 use crate::config::AssetPolicy;
 use crate::server::data::{self, DataLoader};
@@ -115,52 +119,74 @@ pub fn default_route_content(_db: Arc<ServerState>, root: String, url: Option<St
     (StatusCode::OK, headers, bytes).into_response()
 }
 
-pub fn serve_assets<P: AsRef<Path>>(root: P, file: PathBuf, asset_policy: AssetPolicy) -> Response {
+/// Serve a file of the org-roamers root. Responses carry an ETag of the
+/// [`asset_version`]. If `version` matches it, the url changes with the
+/// content and the response can be cached forever. Files are streamed and single byte
+/// ranges are honored, so large PDFs and images can be loaded partially.
+pub async fn serve_assets<P: AsRef<Path>>(
+    root: P,
+    file: PathBuf,
+    asset_policy: AssetPolicy,
+    follow_symlinks: bool,
+    version: Option<&str>,
+    request_headers: &HeaderMap,
+) -> Response {
     let file_path = match asset_policy {
         AssetPolicy::AllowAll => file.clone(),
-        AssetPolicy::AllowChildrenOfRoot => root.as_ref().join(&file),
+        AssetPolicy::AllowChildrenOfRoot => {
+            match resolve_asset(root.as_ref(), &file, follow_symlinks) {
+                Ok(path) => path,
+                Err(status) => {
+                    tracing::warn!("Refusing to serve {file:?}: {status}");
+                    return status.into_response();
+                }
+            }
+        }
         AssetPolicy::ForbidAll => {
             tracing::warn!("Cannot serve {file:?} because of access policy restrictions.");
             return StatusCode::from_u16(403).unwrap().into_response();
         }
     };
 
-    let mime = match file.extension() {
-        Some(extension) => match extension.to_str().unwrap() {
-            "jpeg" | "jpg" => "image/jpeg",
-            "png" => "image/png",
-            "gif" => "image/gif",
-            "svg" => "image/svg+xml",
-            "webp" => "image/webp",
-            // Font file support for KaTeX
-            "woff2" => "font/woff2",
-            "woff" => "font/woff",
-            "ttf" => "font/ttf",
-            "otf" => "font/otf",
-            "eot" => "application/vnd.ms-fontobject",
-            _ => return StatusCode::NOT_FOUND.into_response(),
-        },
-        _ => {
+    let extension = match file.extension() {
+        Some(extension) => extension.to_string_lossy().to_lowercase(),
+        None => {
             tracing::error!("No file extension provided.");
             return StatusCode::NOT_FOUND.into_response();
         }
     };
-
-    let mut buffer = vec![];
-    let mut source_file = match File::open(&file_path) {
-        Ok(file) => file,
-        Err(_) => return StatusCode::NOT_FOUND.into_response(),
+    let Some(mime) = asset_mime(&extension) else {
+        return StatusCode::NOT_FOUND.into_response();
     };
 
-    if source_file.read_to_end(&mut buffer).is_err() {
+    let len = match fs::metadata(&file_path) {
+        Ok(metadata) if metadata.is_file() => metadata.len(),
+        _ => return StatusCode::NOT_FOUND.into_response(),
+    };
+    let Some(version_tag) = asset_version(&file_path) else {
         return StatusCode::NOT_FOUND.into_response();
-    }
+    };
+    let etag = format!("\"{version_tag}\"");
 
     let mut headers = HeaderMap::new();
-    headers.insert("content-type", mime.parse().unwrap());
+    headers.insert("etag", etag.parse().unwrap());
+    headers.insert("accept-ranges", "bytes".parse().unwrap());
+    headers.insert("x-content-type-options", "nosniff".parse().unwrap());
+    if extension == "svg" {
+        // SVGs opened directly must not run their scripts in our origin
+        headers.insert(
+            "content-security-policy",
+            "script-src 'none'".parse().unwrap(),
+        );
+    }
 
-    // Add caching headers - only apply aggressive caching in release builds
-    if cfg!(debug_assertions) {
+    if version == Some(version_tag.as_str()) {
+        // The url changes with the content
+        headers.insert(
+            "cache-control",
+            "public, max-age=31536000, immutable".parse().unwrap(),
+        );
+    } else if cfg!(debug_assertions) {
         // Development mode: minimal caching to avoid stale content
         headers.insert(
             "cache-control",
@@ -172,18 +198,16 @@ pub fn serve_assets<P: AsRef<Path>>(root: P, file: PathBuf, asset_policy: AssetP
         );
     } else {
         // Release mode: optimized caching for better performance
-        match PathBuf::from(&file)
-            .extension()
-            .and_then(|ext| ext.to_str())
-        {
-            Some("woff2") | Some("woff") | Some("ttf") | Some("otf") | Some("eot") => {
+        match extension.as_str() {
+            "woff2" | "woff" | "ttf" | "otf" | "eot" => {
                 // Font files can be cached for a long time
                 headers.insert(
                     "cache-control",
                     "public, max-age=31536000, immutable".parse().unwrap(),
                 );
             }
-            Some("png") | Some("jpg") | Some("jpeg") | Some("gif") | Some("svg") | Some("webp") => {
+            "png" | "jpg" | "jpeg" | "gif" | "svg" | "webp" | "avif" | "bmp" | "ico" | "tif"
+            | "tiff" => {
                 // Images can be cached for a moderate time
                 headers.insert("cache-control", "public, max-age=604800".parse().unwrap());
             }
@@ -198,5 +222,393 @@ pub fn serve_assets<P: AsRef<Path>>(root: P, file: PathBuf, asset_policy: AssetP
         );
     }
 
-    (StatusCode::OK, headers, buffer).into_response()
+    if etag_matches(request_headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+
+    headers.insert("content-type", mime.parse().unwrap());
+
+    // A stale `If-Range` asks for the whole file instead of a part of it
+    let if_range = request_headers
+        .get("if-range")
+        .and_then(|value| value.to_str().ok());
+    let range = match if_range {
+        Some(tag) if tag != etag => Ok(None),
+        _ => byte_range(request_headers, len),
+    };
+    let (status, start, end) = match range {
+        Ok(Some((start, end))) => {
+            headers.insert(
+                "content-range",
+                format!("bytes {start}-{end}/{len}").parse().unwrap(),
+            );
+            (StatusCode::PARTIAL_CONTENT, start, end + 1)
+        }
+        Ok(None) => (StatusCode::OK, 0, len),
+        Err(()) => {
+            headers.insert("content-range", format!("bytes */{len}").parse().unwrap());
+            return (StatusCode::RANGE_NOT_SATISFIABLE, headers).into_response();
+        }
+    };
+    headers.insert("content-length", (end - start).into());
+
+    let mut source_file = match tokio::fs::File::open(&file_path).await {
+        Ok(file) => file,
+        Err(_) => return StatusCode::NOT_FOUND.into_response(),
+    };
+    if start > 0 {
+        if let Err(err) = source_file.seek(SeekFrom::Start(start)).await {
+            tracing::error!("Failed to seek in {}: {err}", file_path.display());
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+    let body = Body::from_stream(ReaderStream::new(source_file.take(end - start)));
+    (status, headers, body).into_response()
+}
+
+/// Resolve `file` below `root`. Paths leaving the root through `..` are
+/// rejected. Symlinks pointing outside of the root are only followed with
+/// `follow_symlinks`, like the files of symlinked directories are only
+/// indexed then.
+pub(crate) fn resolve_asset(
+    root: &Path,
+    file: &Path,
+    follow_symlinks: bool,
+) -> Result<PathBuf, StatusCode> {
+    let lexical = file
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    if !lexical {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let root = root.canonicalize().map_err(|_| StatusCode::NOT_FOUND)?;
+    let path = root
+        .join(file)
+        .canonicalize()
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    if !follow_symlinks && !path.starts_with(&root) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(path)
+}
+
+/// Inclusive byte range requested by the `Range` header for a file of `len`
+/// bytes. `Ok(None)` serves the whole file, which is also the answer to
+/// malformed and multipart ranges. Unsatisfiable ranges are an error.
+fn byte_range(request_headers: &HeaderMap, len: u64) -> Result<Option<(u64, u64)>, ()> {
+    let Some(value) = request_headers
+        .get("range")
+        .and_then(|value| value.to_str().ok())
+    else {
+        return Ok(None);
+    };
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+    if spec.contains(',') {
+        return Ok(None);
+    }
+    let Some((start, end)) = spec.split_once('-') else {
+        return Ok(None);
+    };
+    let (start, end) = (start.trim(), end.trim());
+
+    let (start, end) = if start.is_empty() {
+        // Suffix range: the last `end` bytes
+        let Ok(suffix) = end.parse::<u64>() else {
+            return Ok(None);
+        };
+        if suffix == 0 || len == 0 {
+            return Err(());
+        }
+        (len.saturating_sub(suffix), len - 1)
+    } else {
+        let Ok(start) = start.parse::<u64>() else {
+            return Ok(None);
+        };
+        let end = match end {
+            "" => u64::MAX,
+            end => match end.parse::<u64>() {
+                Ok(end) if end >= start => end,
+                _ => return Ok(None),
+            },
+        };
+        if start >= len {
+            return Err(());
+        }
+        (start, end.min(len - 1))
+    };
+    Ok(Some((start, end)))
+}
+
+/// Check `If-None-Match` of a request against the ETag of the response.
+pub(crate) fn etag_matches(request_headers: &HeaderMap, etag: &str) -> bool {
+    request_headers
+        .get_all("if-none-match")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == etag || tag == "*")
+}
+
+/// Version of the asset at `path`, used as ETag and for cache busting. It
+/// is derived from the modification time and size, so the file is never
+/// read and large PDFs cost as little as small images.
+pub fn asset_version(path: &Path) -> Option<String> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(format!("{:x}-{:x}", modified.as_nanos(), metadata.len()))
+}
+
+/// Mime types of all files that can be served from (and uploaded to) the
+/// org-roamers root.
+fn asset_mime(extension: &str) -> Option<&'static str> {
+    let mime = match extension {
+        "jpeg" | "jpg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "bmp" => "image/bmp",
+        "ico" => "image/x-icon",
+        "tif" | "tiff" => "image/tiff",
+        "pdf" => "application/pdf",
+        // Font file support for KaTeX
+        "woff2" => "font/woff2",
+        "woff" => "font/woff",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "eot" => "application/vnd.ms-fontobject",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+/// Store an uploaded file in `attachment_dir` below `root`. Existing files are
+/// never overwritten, instead a numeric suffix is appended to the name. Returns
+/// the path of the stored file relative to `root`.
+pub fn store_upload<P: AsRef<Path>>(
+    root: P,
+    attachment_dir: &Path,
+    file_name: &str,
+    data: &[u8],
+) -> io::Result<PathBuf> {
+    let file_name = Path::new(file_name)
+        .file_name()
+        .map(PathBuf::from)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "No file name provided"))?;
+
+    let (stem, extension) = match (file_name.file_stem(), file_name.extension()) {
+        (Some(stem), Some(extension)) => (
+            stem.to_string_lossy().to_string(),
+            extension.to_string_lossy().to_lowercase(),
+        ),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("No file extension provided: {file_name:?}"),
+            ))
+        }
+    };
+
+    if asset_mime(&extension).is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Unsupported file extension: {extension}"),
+        ));
+    }
+
+    let dir = root.as_ref().join(attachment_dir);
+    fs::create_dir_all(&dir)?;
+
+    let mut counter = 0;
+    loop {
+        let name = match counter {
+            0 => format!("{stem}.{extension}"),
+            n => format!("{stem}-{n}.{extension}"),
+        };
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(dir.join(&name))
+        {
+            Ok(mut file) => {
+                file.write_all(data)?;
+                tracing::info!("Stored upload {name} in {dir:?}");
+                return Ok(attachment_dir.join(name));
+            }
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => counter += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Construct the path of `target` as seen from the directory of `from_file`.
+/// Both paths must be relative to the org-roamers root. This is the path org
+/// expects in `file:` links.
+pub fn relative_link(from_file: &Path, target: &Path) -> PathBuf {
+    let from_dir: Vec<Component> = from_file
+        .parent()
+        .map(|p| p.components().collect())
+        .unwrap_or_default();
+    let target: Vec<Component> = target.components().collect();
+
+    let common = from_dir
+        .iter()
+        .zip(target.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut path = PathBuf::new();
+    for _ in common..from_dir.len() {
+        path.push("..");
+    }
+    for component in &target[common..] {
+        path.push(component);
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_etag_matches() {
+        let mut headers = HeaderMap::new();
+        assert!(!etag_matches(&headers, "\"a\""));
+        headers.insert("if-none-match", "\"b\", W/\"a\"".parse().unwrap());
+        assert!(etag_matches(&headers, "\"a\""));
+        assert!(!etag_matches(&headers, "\"c\""));
+    }
+
+    #[test]
+    fn test_asset_version() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("a.png");
+        fs::write(&path, b"image").unwrap();
+        let version = asset_version(&path).unwrap();
+        assert_eq!(asset_version(&path), Some(version.clone()));
+        fs::write(&path, b"changed image").unwrap();
+        assert_ne!(asset_version(&path), Some(version));
+        assert_eq!(asset_version(&temp_dir.path().join("missing.png")), None);
+    }
+
+    fn resolve_asset_strict(root: &Path, file: &Path) -> Result<PathBuf, StatusCode> {
+        resolve_asset(root, file, false)
+    }
+
+    #[test]
+    fn test_resolve_asset() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir_all(root.join("img")).unwrap();
+        fs::write(root.join("img/a.png"), b"image").unwrap();
+        fs::write(temp_dir.path().join("secret.png"), b"secret").unwrap();
+
+        let resolved = resolve_asset_strict(&root, Path::new("./img/a.png")).unwrap();
+        assert_eq!(resolved, root.canonicalize().unwrap().join("img/a.png"));
+        for file in ["../secret.png", "img/../../secret.png", "/etc/passwd"] {
+            assert_eq!(
+                resolve_asset_strict(&root, Path::new(file)),
+                Err(StatusCode::FORBIDDEN)
+            );
+        }
+        assert_eq!(
+            resolve_asset_strict(&root, Path::new("img/missing.png")),
+            Err(StatusCode::NOT_FOUND)
+        );
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(temp_dir.path().join("secret.png"), root.join("link.png"))
+                .unwrap();
+            assert_eq!(
+                resolve_asset_strict(&root, Path::new("link.png")),
+                Err(StatusCode::FORBIDDEN)
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_asset_in_symlinked_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        let synced = temp_dir.path().join("synced");
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(synced.join("img")).unwrap();
+        fs::write(synced.join("img/a.png"), b"image").unwrap();
+        std::os::unix::fs::symlink(&synced, root.join("synced")).unwrap();
+
+        let file = Path::new("synced/img/a.png");
+        assert_eq!(
+            resolve_asset(&root, file, true),
+            Ok(synced.canonicalize().unwrap().join("img/a.png"))
+        );
+        assert_eq!(
+            resolve_asset(&root, file, false),
+            Err(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(
+            resolve_asset(&root, Path::new("synced/../../secret.png"), true),
+            Err(StatusCode::FORBIDDEN)
+        );
+    }
+
+    #[test]
+    fn test_byte_range() {
+        let range = |value: &str, len| {
+            let mut headers = HeaderMap::new();
+            headers.insert("range", value.parse().unwrap());
+            byte_range(&headers, len)
+        };
+        assert_eq!(byte_range(&HeaderMap::new(), 10), Ok(None));
+        assert_eq!(range("bytes=0-3", 10), Ok(Some((0, 3))));
+        assert_eq!(range("bytes=4-", 10), Ok(Some((4, 9))));
+        assert_eq!(range("bytes=5-100", 10), Ok(Some((5, 9))));
+        assert_eq!(range("bytes=-3", 10), Ok(Some((7, 9))));
+        assert_eq!(range("bytes=-30", 10), Ok(Some((0, 9))));
+        assert_eq!(range("bytes=10-", 10), Err(()));
+        assert_eq!(range("bytes=-0", 10), Err(()));
+        assert_eq!(range("bytes=0-1,4-5", 10), Ok(None));
+        assert_eq!(range("bytes=5-2", 10), Ok(None));
+        assert_eq!(range("items=0-1", 10), Ok(None));
+    }
+
+    #[test]
+    fn test_relative_link_same_dir() {
+        let link = relative_link(Path::new("note.org"), Path::new("attachments/a.png"));
+        assert_eq!(link, PathBuf::from("attachments/a.png"));
+    }
+
+    #[test]
+    fn test_relative_link_sub_dir() {
+        let link = relative_link(
+            Path::new("projects/x/note.org"),
+            Path::new("attachments/a.png"),
+        );
+        assert_eq!(link, PathBuf::from("../../attachments/a.png"));
+    }
+
+    #[test]
+    fn test_store_upload_does_not_overwrite() {
+        let root = TempDir::new().unwrap();
+        let dir = Path::new("attachments");
+        let first = store_upload(root.path(), dir, "../../shot.PNG", b"1").unwrap();
+        let second = store_upload(root.path(), dir, "shot.png", b"2").unwrap();
+        assert_eq!(first, PathBuf::from("attachments/shot.png"));
+        assert_eq!(second, PathBuf::from("attachments/shot-1.png"));
+        assert_eq!(fs::read(root.path().join(first)).unwrap(), b"1");
+    }
+
+    #[test]
+    fn test_store_upload_rejects_unknown_extension() {
+        let root = TempDir::new().unwrap();
+        let res = store_upload(root.path(), Path::new("attachments"), "run.sh", b"");
+        assert!(res.is_err());
+    }
 }
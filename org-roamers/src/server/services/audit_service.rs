@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use time::OffsetDateTime;
+
+use crate::sqlite::audit;
+
+/// Events that are recorded in the audit log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Login,
+    LoginFailed,
+    Logout,
+    Upload,
+    Capture,
+    Daily,
+    Edit,
+    HistoryCleared,
+    Reindex,
+    Delete,
+    Restore,
+    Merge,
+    Extract,
+    Move,
+    Archive,
+    Disconnect,
+}
+
+impl AuditAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Login => "login",
+            Self::LoginFailed => "login_failed",
+            Self::Logout => "logout",
+            Self::Upload => "upload",
+            Self::Capture => "capture",
+            Self::Daily => "daily",
+            Self::Edit => "edit",
+            Self::HistoryCleared => "history_cleared",
+            Self::Reindex => "reindex",
+            Self::Delete => "delete",
+            Self::Restore => "restore",
+            Self::Merge => "merge",
+            Self::Extract => "extract",
+            Self::Move => "move",
+            Self::Archive => "archive",
+            Self::Disconnect => "disconnect",
+        }
+    }
+}
+
+/// Append an event to the audit log. Failing to record an event is logged
+/// but never fails the audited request.
+pub async fn record(
+    sqlite: &SqlitePool,
+    user: Option<&str>,
+    action: AuditAction,
+    target: Option<&str>,
+) {
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    if let Err(err) = audit::insert_event(sqlite, now, user, action.as_str(), target).await {
+        tracing::error!("Failed to record audit event {}: {err}", action.as_str());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action_names_match_serde() {
+        for action in [
+            AuditAction::Login,
+            AuditAction::LoginFailed,
+            AuditAction::HistoryCleared,
+        ] {
+            let json = serde_json::to_string(&action).unwrap();
+            assert_eq!(json, format!("\"{}\"", action.as_str()));
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+use sqlx::SqlitePool;
+
+use crate::cache::OrgCache;
+use crate::server::types::{Backlink, BacklinksResponse, OutgoingLink, RoamID};
+use crate::sqlite::files::get_node_ids;
+use crate::transform::title::TitleSanitizer;
+
+/// Nodes linking to `id`, every link with the line it is in. Returns `None`
+/// if the node does not exist.
+pub async fn get_backlinks(
+    sqlite: &SqlitePool,
+    cache: &OrgCache,
+    id: &RoamID,
+) -> anyhow::Result<Option<BacklinksResponse>> {
+    const NODE: &str = "SELECT 1 FROM nodes WHERE id = ?;";
+    const SOURCES: &str = concat!(
+        "SELECT DISTINCT n.id, n.title, n.file FROM links l\n",
+        "JOIN nodes n ON n.id = l.source\n",
+        "WHERE l.type = 'id' AND l.dest = ? AND l.source != l.dest\n",
+        "ORDER BY n.file, n.title, n.id;"
+    );
+
+    let exists: Option<(i64,)> = sqlx::query_as(NODE)
+        .bind(id.id())
+        .fetch_optional(sqlite)
+        .await?;
+    if exists.is_none() {
+        return Ok(None);
+    }
+    let sources: Vec<(String, String, String)> = sqlx::query_as(SOURCES)
+        .bind(id.id())
+        .fetch_all(sqlite)
+        .await?;
+
+    // Links of every file are only searched once, also if several of its
+    // nodes link to `id`.
+    let mut occurrences: HashMap<String, Vec<Occurrence>> = HashMap::new();
+    let mut backlinks = vec![];
+    for (source_id, title, file) in sources {
+        if !occurrences.contains_key(&file) {
+            let found = match cache.retrieve(&source_id.as_str().into()) {
+                Some(entry) => {
+                    let nodes = get_node_ids(sqlite, &file).await?;
+                    find_links(entry.content(), id.id(), &nodes)
+                }
+                None => vec![],
+            };
+            occurrences.insert(file.clone(), found);
+        }
+
+        let source = OutgoingLink {
+            display: TitleSanitizer::new().process(&title).into(),
+            id: source_id.as_str().into(),
+        };
+        let found: Vec<&Occurrence> = occurrences[&file]
+            .iter()
+            .filter(|occurrence| occurrence.owner.as_deref() == Some(source_id.as_str()))
+            .collect();
+        if found.is_empty() {
+            backlinks.push(Backlink {
+                source,
+                file,
+                line: None,
+                column: None,
+                context: None,
+            });
+            continue;
+        }
+        for occurrence in found {
+            backlinks.push(Backlink {
+                source: source.clone(),
+                file: file.clone(),
+                line: Some(occurrence.line),
+                column: Some(occurrence.column),
+                context: Some(occurrence.context.clone()),
+            });
+        }
+    }
+
+    Ok(Some(BacklinksResponse {
+        id: id.clone(),
+        backlinks,
+    }))
+}
+
+#[derive(Debug, PartialEq)]
+struct Occurrence {
+    /// Node the link belongs to, i.e. the node of `nodes` whose `:ID:` is the
+    /// closest above the link.
+    owner: Option<String>,
+    line: usize,
+    column: usize,
+    context: String,
+}
+
+/// Find all `id:` links to `target` in `content`. `nodes` are the ids of the
+/// nodes of the file.
+fn find_links(content: &str, target: &str, nodes: &[String]) -> Vec<Occurrence> {
+    let needle = format!("[[id:{target}]");
+    let mut owner = None;
+    let mut found = vec![];
+    for (index, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.len() > 4 && trimmed[..4].eq_ignore_ascii_case(":ID:") {
+            let id = trimmed[4..].trim();
+            if nodes.iter().any(|node| node == id) {
+                owner = Some(id.to_string());
+            }
+            continue;
+        }
+        for (start, _) in line.match_indices(&needle) {
+            found.push(Occurrence {
+                owner: owner.clone(),
+                line: index + 1,
+                column: line[..start].chars().count() + 1,
+                context: line.trim().to_string(),
+            });
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_links() {
+        let content = concat!(
+            ":PROPERTIES:\n",
+            ":ID: file\n",
+            ":END:\n",
+            "#+title: File\n",
+            "See [[id:target][Target]].\n",
+            "* Heading\n",
+            ":PROPERTIES:\n",
+            ":id: heading\n",
+            ":END:\n",
+            "  Again [[id:target]] and [[id:other][x]].\n",
+        );
+        let nodes = vec!["file".to_string(), "heading".to_string()];
+        let found = find_links(content, "target", &nodes);
+        assert_eq!(
+            found,
+            vec![
+                Occurrence {
+                    owner: Some("file".into()),
+                    line: 5,
+                    column: 5,
+                    context: "See [[id:target][Target]].".into(),
+                },
+                Occurrence {
+                    owner: Some("heading".into()),
+                    line: 10,
+                    column: 9,
+                    context: "Again [[id:target]] and [[id:other][x]].".into(),
+                },
+            ]
+        );
+    }
+}
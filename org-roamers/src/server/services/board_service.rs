@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+
+use sqlx::SqlitePool;
+
+use crate::server::types::{BoardColumn, BoardResponse, BoardTask};
+use crate::transform::title::TitleSanitizer;
+
+/// `(todo, id, title, file, priority, scheduled, deadline)`
+type TaskRow = (
+    String,
+    String,
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+);
+
+/// Group all nodes with a TODO keyword into columns. If `tag` is set, only
+/// nodes carrying it (directly or inherited) are included.
+pub async fn get_board(
+    sqlite: &SqlitePool,
+    columns: &[String],
+    tag: Option<&str>,
+) -> anyhow::Result<BoardResponse> {
+    const STMNT: &str = concat!(
+        "SELECT n.todo, n.id, n.title, n.file, n.priority, n.scheduled, n.deadline\n",
+        "FROM nodes n\n",
+        "WHERE n.todo IS NOT NULL AND n.todo != ''\n",
+        "AND (?1 IS NULL OR EXISTS (SELECT 1 FROM tags t WHERE t.node_id = n.id AND t.tag = ?1));"
+    );
+
+    let rows: Vec<TaskRow> = sqlx::query_as(STMNT).bind(tag).fetch_all(sqlite).await?;
+    Ok(build_board(rows, columns))
+}
+
+fn build_board(rows: Vec<TaskRow>, columns: &[String]) -> BoardResponse {
+    let mut by_keyword: BTreeMap<String, Vec<BoardTask>> = BTreeMap::new();
+    for (todo, id, title, file, priority, scheduled, deadline) in rows {
+        by_keyword.entry(todo).or_default().push(BoardTask {
+            id: id.into(),
+            title: TitleSanitizer::new().process(&title).into(),
+            file,
+            priority,
+            scheduled,
+            deadline,
+        });
+    }
+
+    let mut board: Vec<BoardColumn> = columns
+        .iter()
+        .map(|keyword| BoardColumn {
+            keyword: keyword.clone(),
+            tasks: by_keyword.remove(keyword).unwrap_or_default(),
+        })
+        .collect();
+    board.extend(
+        by_keyword
+            .into_iter()
+            .map(|(keyword, tasks)| BoardColumn { keyword, tasks }),
+    );
+
+    for column in &mut board {
+        // Tasks without priority or deadline go last.
+        column.tasks.sort_by(|a, b| {
+            (
+                a.priority.is_none(),
+                &a.priority,
+                a.deadline.is_none(),
+                &a.deadline,
+            )
+                .cmp(&(
+                    b.priority.is_none(),
+                    &b.priority,
+                    b.deadline.is_none(),
+                    &b.deadline,
+                ))
+                .then_with(|| a.title.title().cmp(b.title.title()))
+        });
+    }
+
+    BoardResponse { columns: board }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(todo: &str, id: &str, priority: Option<&str>, deadline: Option<&str>) -> TaskRow {
+        (
+            todo.to_string(),
+            id.to_string(),
+            id.to_string(),
+            "tasks.org".to_string(),
+            priority.map(str::to_string),
+            None,
+            deadline.map(str::to_string),
+        )
+    }
+
+    #[test]
+    fn test_build_board() {
+        let rows = vec![
+            row("TODO", "c", None, None),
+            row("TODO", "b", None, Some("2024-01-01")),
+            row("TODO", "a", Some("A"), None),
+            row("WAITING", "d", None, None),
+        ];
+        let columns = vec!["TODO".to_string(), "NEXT".to_string(), "DONE".to_string()];
+        let board = build_board(rows, &columns);
+
+        let keywords: Vec<&str> = board.columns.iter().map(|c| c.keyword.as_str()).collect();
+        assert_eq!(keywords, vec!["TODO", "NEXT", "DONE", "WAITING"]);
+        let ids: Vec<&str> = board.columns[0].tasks.iter().map(|t| t.id.id()).collect();
+        assert_eq!(ids, vec!["a", "b", "c"]);
+        assert!(board.columns[1].tasks.is_empty());
+    }
+}
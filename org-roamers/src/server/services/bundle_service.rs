@@ -0,0 +1,146 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::{Component, Path, PathBuf};
+
+use sqlx::SqlitePool;
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+use crate::cache::OrgCache;
+use crate::server::services::move_service::file_link;
+use crate::transform::html::link_target;
+
+/// Files of the nodes `ids` and the nodes tagged `tag`. Nodes linked by `id:`
+/// links are added up to `depth` links away.
+pub async fn selected_files(
+    sqlite: &SqlitePool,
+    ids: &[String],
+    tag: Option<&str>,
+    depth: usize,
+) -> anyhow::Result<BTreeSet<String>> {
+    const TAGGED: &str = "SELECT node_id FROM tags WHERE tag = ?;";
+    const LINKED: &str = "SELECT dest FROM links WHERE source = ? AND type = 'id';";
+    const FILE: &str = "SELECT file FROM nodes WHERE id = ?;";
+
+    let mut nodes: HashSet<String> = ids.iter().cloned().collect();
+    if let Some(tag) = tag {
+        let tagged: Vec<String> = sqlx::query_scalar(TAGGED)
+            .bind(tag)
+            .fetch_all(sqlite)
+            .await?;
+        nodes.extend(tagged);
+    }
+    let mut frontier: Vec<String> = nodes.iter().cloned().collect();
+    for _ in 0..depth {
+        let mut next = vec![];
+        for id in frontier {
+            let linked: Vec<String> = sqlx::query_scalar(LINKED)
+                .bind(&id)
+                .fetch_all(sqlite)
+                .await?;
+            next.extend(linked.into_iter().filter(|dest| nodes.insert(dest.clone())));
+        }
+        frontier = next;
+    }
+
+    let mut files = BTreeSet::new();
+    for id in nodes {
+        let file: Option<String> = sqlx::query_scalar(FILE)
+            .bind(&id)
+            .fetch_optional(sqlite)
+            .await?;
+        files.extend(file);
+    }
+    Ok(files)
+}
+
+/// Files other than org files that `content` of `file` links to, e.g.
+/// images. Links leaving the root are ignored.
+pub fn attachments(file: &str, content: &str) -> Vec<PathBuf> {
+    content
+        .match_indices("[[")
+        .filter_map(|(start, _)| {
+            let link = &content[start + 2..];
+            let link = &link[..link.find(']')?];
+            let (_, path, _) = file_link(link)?;
+            let target = link_target(file, path);
+            let is_org = target.extension().is_some_and(|ext| ext == "org");
+            (!is_org && inside_root(file, path)).then_some(target)
+        })
+        .collect()
+}
+
+/// Whether the link `path` in `file` points below the root.
+fn inside_root(file: &str, path: &str) -> bool {
+    let parent = Path::new(file).parent().unwrap_or(Path::new(""));
+    let mut depth = 0usize;
+    for component in parent.components().chain(Path::new(path).components()) {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::ParentDir if depth == 0 => return false,
+            Component::ParentDir => depth -= 1,
+            Component::CurDir => {}
+            _ => return false,
+        }
+    }
+    depth > 0
+}
+
+/// Zip `files` and the attachments they link to. Paths in the archive are
+/// relative to the root, so links keep resolving once it is extracted.
+pub fn bundle(cache: &OrgCache, files: &BTreeSet<String>) -> anyhow::Result<Vec<u8>> {
+    let mut entries: BTreeSet<PathBuf> = BTreeSet::new();
+    for file in files {
+        let content = fs::read_to_string(cache.resolve(file))?;
+        entries.extend(
+            attachments(file, &content)
+                .into_iter()
+                .filter(|attachment| cache.resolve(attachment).is_file()),
+        );
+        entries.insert(PathBuf::from(file));
+    }
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+    for entry in entries {
+        zip.start_file(entry.to_string_lossy(), options)?;
+        zip.write_all(&fs::read(cache.resolve(&entry))?)?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use zip::ZipArchive;
+
+    #[test]
+    fn test_attachments() {
+        let content = concat!(
+            "[[./img/a.png]] [[file:../doc.pdf::3][Doc]] [[file:other.org]]\n",
+            "[[../../outside.png]] [[https://example.com/b.png]]\n",
+        );
+        assert_eq!(
+            attachments("notes/a.org", content),
+            vec![PathBuf::from("notes/img/a.png"), PathBuf::from("doc.pdf")]
+        );
+    }
+
+    #[test]
+    fn test_bundle() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("img")).unwrap();
+        fs::write(root.join("a.org"), "[[./img/a.png]] [[./missing.png]]").unwrap();
+        fs::write(root.join("img/a.png"), "png").unwrap();
+        fs::write(root.join("b.org"), "not selected").unwrap();
+
+        let files = BTreeSet::from(["a.org".to_string()]);
+        let zip = bundle(&OrgCache::new(root.to_path_buf()), &files).unwrap();
+        let archive = ZipArchive::new(Cursor::new(zip)).unwrap();
+        let mut names: Vec<&str> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(names, vec!["a.org", "img/a.png"]);
+    }
+}
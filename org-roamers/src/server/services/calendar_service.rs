@@ -0,0 +1,47 @@
+use std::collections::BTreeMap;
+
+use sqlx::SqlitePool;
+
+use crate::server::types::{CalendarEntry, CalendarResponse};
+use crate::transform::title::TitleSanitizer;
+
+/// Nodes per day of `month` (`YYYY-MM`).
+pub async fn get_calendar(sqlite: &SqlitePool, month: &str) -> anyhow::Result<CalendarResponse> {
+    const STMNT: &str = concat!(
+        "SELECT DISTINCT d.date, n.id, n.title FROM dates d\n",
+        "JOIN nodes n ON n.id = d.node_id\n",
+        "WHERE d.date LIKE ?\n",
+        "ORDER BY d.date, n.title, n.id;"
+    );
+
+    let rows: Vec<(String, String, String)> = sqlx::query_as(STMNT)
+        .bind(format!("{month}-%"))
+        .fetch_all(sqlite)
+        .await?;
+
+    let mut days: BTreeMap<String, Vec<CalendarEntry>> = BTreeMap::new();
+    for (date, id, title) in rows {
+        days.entry(date).or_default().push(CalendarEntry {
+            id: id.into(),
+            title: TitleSanitizer::new().process(&title).into(),
+        });
+    }
+
+    Ok(CalendarResponse {
+        month: month.to_string(),
+        days,
+    })
+}
+
+/// Check that `month` is of the form `YYYY-MM`.
+pub fn is_valid_month(month: &str) -> bool {
+    match month.split_once('-') {
+        Some((year, month)) => {
+            year.len() == 4
+                && year.chars().all(|c| c.is_ascii_digit())
+                && month.len() == 2
+                && month.parse::<u8>().is_ok_and(|m| (1..=12).contains(&m))
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,421 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use time::OffsetDateTime;
+
+use crate::cache::OrgCache;
+use crate::config::{CaptureTarget, CaptureTemplate};
+use crate::server::services::template_service::FileTemplate;
+use crate::server::types::RoamID;
+
+/// Name of the template that is used if the request does not specify one.
+pub const DEFAULT_TEMPLATE: &str = "default";
+/// Name of the template that is used for daily notes.
+pub const DAILY_TEMPLATE: &str = "daily";
+
+/// Captured data as sent by a browser extension or an `org-protocol://`
+/// handler.
+pub struct Capture<'a> {
+    pub title: &'a str,
+    pub url: Option<&'a str>,
+    pub body: Option<&'a str>,
+}
+
+/// Create a new node from `capture` according to `template`. New files get
+/// the front matter of their [`FileTemplate`]. Returns the id of the node
+/// and the path of the modified file relative to the root.
+pub fn capture(
+    cache: &OrgCache,
+    template: &CaptureTemplate,
+    capture: &Capture,
+    now: OffsetDateTime,
+) -> io::Result<(RoamID, PathBuf)> {
+    let id = new_id();
+
+    let path = match &template.target {
+        CaptureTarget::NewFile { pattern } => {
+            let path = PathBuf::from(expand_pattern(pattern, capture.title, now));
+            let target = cache.resolve(&path);
+            create_parent(&target)?;
+            let file_template = FileTemplate::find(cache.path(), &path).unwrap_or_default();
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(target)?;
+            let note = render_file_note(id.id(), template, &file_template, capture);
+            file.write_all(note.as_bytes())?;
+            path
+        }
+        CaptureTarget::File { file } => {
+            let target = cache.resolve(file);
+            create_parent(&target)?;
+            let mut content = fs::read_to_string(&target).unwrap_or_default();
+            ensure_newline(&mut content);
+            content.push_str(&render_entry(id.id(), 1, template, capture));
+            fs::write(target, content)?;
+            file.clone()
+        }
+        CaptureTarget::Datetree { file } => {
+            let target = cache.resolve(file);
+            create_parent(&target)?;
+            let content = fs::read_to_string(&target).unwrap_or_default();
+            let entry = render_entry(id.id(), 4, template, capture);
+            fs::write(target, insert_into_datetree(&content, now, &entry))?;
+            file.clone()
+        }
+    };
+
+    Ok((id, path))
+}
+
+/// File of the daily note of the day of `now`. Only templates that create
+/// new files can be used for daily notes.
+pub fn daily_file(template: &CaptureTemplate, now: OffsetDateTime) -> Option<PathBuf> {
+    match &template.target {
+        CaptureTarget::NewFile { pattern } => {
+            Some(expand_pattern(pattern, &daily_title(now), now).into())
+        }
+        _ => None,
+    }
+}
+
+pub fn daily_title(now: OffsetDateTime) -> String {
+    format!(
+        "{:04}-{:02}-{:02}",
+        now.year(),
+        now.month() as u8,
+        now.day()
+    )
+}
+
+pub fn new_id() -> RoamID {
+    uuid::Uuid::new_v4().to_string().into()
+}
+
+fn create_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) => fs::create_dir_all(parent),
+        None => Ok(()),
+    }
+}
+
+fn ensure_newline(content: &mut String) {
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+}
+
+fn render_properties(
+    note: &mut String,
+    id: &str,
+    template: &CaptureTemplate,
+    extra: &[(String, String)],
+    capture: &Capture,
+) {
+    note.push_str(":PROPERTIES:\n");
+    note.push_str(&format!(":ID:       {id}\n"));
+    if let Some(url) = capture.url {
+        note.push_str(&format!(":ROAM_REFS: {}\n", single_line(url)));
+    }
+    for (key, value) in &template.properties {
+        note.push_str(&format!(
+            ":{}: {}\n",
+            key.to_uppercase(),
+            single_line(value)
+        ));
+    }
+    for (key, value) in extra {
+        if !template
+            .properties
+            .keys()
+            .any(|k| k.eq_ignore_ascii_case(key))
+        {
+            note.push_str(&format!(":{key}: {value}\n"));
+        }
+    }
+    note.push_str(":END:\n");
+}
+
+fn render_body(note: &mut String, capture: &Capture) {
+    if let Some(body) = capture.body {
+        note.push('\n');
+        note.push_str(body.trim_end());
+        note.push('\n');
+    }
+}
+
+/// A file level node. Properties, tags and keywords of `file_template` are
+/// added, its body follows the captured body.
+fn render_file_note(
+    id: &str,
+    template: &CaptureTemplate,
+    file_template: &FileTemplate,
+    capture: &Capture,
+) -> String {
+    let mut note = String::new();
+    render_properties(&mut note, id, template, &file_template.properties, capture);
+    note.push_str(&format!("#+title: {}\n", single_line(capture.title)));
+    let mut tags = template.tags.clone();
+    for tag in &file_template.tags {
+        if !tags.contains(tag) {
+            tags.push(tag.clone());
+        }
+    }
+    if !tags.is_empty() {
+        note.push_str(&format!("#+filetags: :{}:\n", tags.join(":")));
+    }
+    for (key, value) in &file_template.keywords {
+        note.push_str(&format!("#+{key}: {value}\n"));
+    }
+    render_body(&mut note, capture);
+    if !file_template.body.is_empty() {
+        note.push('\n');
+        note.push_str(&file_template.body);
+        note.push('\n');
+    }
+    note
+}
+
+/// A headline node at `level`.
+fn render_entry(id: &str, level: usize, template: &CaptureTemplate, capture: &Capture) -> String {
+    let mut note = format!(
+        "{} {}{}",
+        "*".repeat(level),
+        template.headline_prefix,
+        single_line(capture.title)
+    );
+    if !template.tags.is_empty() {
+        note.push_str(&format!(" :{}:", template.tags.join(":")));
+    }
+    note.push('\n');
+    render_properties(&mut note, id, template, &[], capture);
+    render_body(&mut note, capture);
+    note
+}
+
+/// Newlines would end the keyword or property early.
+fn single_line(s: &str) -> String {
+    s.lines().map(str::trim).collect::<Vec<_>>().join(" ")
+}
+
+/// Replace the placeholders `%Y`, `%m`, `%d`, `%H`, `%M`, `%S` and `${slug}`.
+pub fn expand_pattern(pattern: &str, title: &str, now: OffsetDateTime) -> String {
+    pattern
+        .replace("${slug}", &slugify(title))
+        .replace("%Y", &format!("{:04}", now.year()))
+        .replace("%m", &format!("{:02}", now.month() as u8))
+        .replace("%d", &format!("{:02}", now.day()))
+        .replace("%H", &format!("{:02}", now.hour()))
+        .replace("%M", &format!("{:02}", now.minute()))
+        .replace("%S", &format!("{:02}", now.second()))
+}
+
+/// Same as `org-roam-node-slug`: lowercase, everything that is not
+/// alphanumeric is replaced by a single underscore.
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::with_capacity(title.len());
+    for c in title.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.ends_with('_') {
+            slug.push('_');
+        }
+    }
+    slug.trim_matches('_').to_string()
+}
+
+/// Insert `entry` at the end of the day of `now` in the datetree of
+/// `content`. Missing year, month and day headings are created in order.
+fn insert_into_datetree(content: &str, now: OffsetDateTime, entry: &str) -> String {
+    let mut lines: Vec<String> = content.lines().map(ToString::to_string).collect();
+
+    let year = format!("{:04}", now.year());
+    let month = format!("{year}-{:02} {}", now.month() as u8, now.month());
+    let day = format!("{} {}", daily_title(now), now.weekday());
+
+    let (start, end) = (0, lines.len());
+    let (start, end) = find_or_insert_heading(&mut lines, start, end, 1, &year);
+    let (start, end) = find_or_insert_heading(&mut lines, start + 1, end, 2, &month);
+    let (_, end) = find_or_insert_heading(&mut lines, start + 1, end, 3, &day);
+
+    lines.insert(end, entry.trim_end().to_string());
+
+    let mut result = lines.join("\n");
+    result.push('\n');
+    result
+}
+
+pub fn heading_level(line: &str) -> Option<usize> {
+    let level = line.chars().take_while(|c| *c == '*').count();
+    (level > 0 && line[level..].starts_with(' ')).then_some(level)
+}
+
+/// Find the heading `title` at `level` within `lines[start..end]`, inserting
+/// it in sorted order if it is missing. Returns the index of the heading and
+/// the end of its subtree.
+fn find_or_insert_heading(
+    lines: &mut Vec<String>,
+    start: usize,
+    end: usize,
+    level: usize,
+    title: &str,
+) -> (usize, usize) {
+    let mut insert_at = end;
+    for i in start..end {
+        if heading_level(&lines[i]) != Some(level) {
+            continue;
+        }
+        let heading = lines[i][level..].trim();
+        if heading == title {
+            let subtree_end = (i + 1..end)
+                .find(|j| heading_level(&lines[*j]).is_some_and(|l| l <= level))
+                .unwrap_or(end);
+            return (i, subtree_end);
+        }
+        if heading > title {
+            insert_at = i;
+            break;
+        }
+    }
+
+    lines.insert(insert_at, format!("{} {}", "*".repeat(level), title));
+    (insert_at, insert_at + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use time::macros::datetime;
+
+    fn template(target: CaptureTarget) -> CaptureTemplate {
+        CaptureTemplate {
+            name: "test".to_string(),
+            target,
+            headline_prefix: "TODO ".to_string(),
+            tags: vec!["web".to_string()],
+            properties: BTreeMap::from([("source".to_string(), "ext".to_string())]),
+        }
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Vec<T> in Rust"), "vec_t_in_rust");
+        assert_eq!(slugify("  Über  Notes! "), "über_notes");
+    }
+
+    #[test]
+    fn test_expand_pattern() {
+        let now = datetime!(2024-03-05 07:08:09 UTC);
+        assert_eq!(
+            expand_pattern("%Y%m%d%H%M%S-${slug}.org", "A Page", now),
+            "20240305070809-a_page.org"
+        );
+    }
+
+    #[test]
+    fn test_render_file_note() {
+        let template = template(CaptureTarget::NewFile {
+            pattern: String::new(),
+        });
+        let capture = Capture {
+            title: "A\nPage",
+            url: Some("https://example.com"),
+            body: Some("quote\n\n"),
+        };
+        let expected = concat!(
+            ":PROPERTIES:\n",
+            ":ID:       abc\n",
+            ":ROAM_REFS: https://example.com\n",
+            ":SOURCE: ext\n",
+            ":END:\n",
+            "#+title: A Page\n",
+            "#+filetags: :web:\n",
+            "\n",
+            "quote\n"
+        );
+        assert_eq!(
+            render_file_note("abc", &template, &FileTemplate::default(), &capture),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_render_file_note_with_template() {
+        let template = template(CaptureTarget::NewFile {
+            pattern: String::new(),
+        });
+        let file_template = FileTemplate {
+            properties: vec![
+                ("CATEGORY".into(), "journal".into()),
+                ("SOURCE".into(), "template".into()),
+            ],
+            keywords: vec![("startup".into(), "overview".into())],
+            tags: vec!["web".into(), "daily".into()],
+            body: "* Tasks".into(),
+        };
+        let capture = Capture {
+            title: "Page",
+            url: None,
+            body: Some("quote"),
+        };
+        let expected = concat!(
+            ":PROPERTIES:\n",
+            ":ID:       abc\n",
+            ":SOURCE: ext\n",
+            ":CATEGORY: journal\n",
+            ":END:\n",
+            "#+title: Page\n",
+            "#+filetags: :web:daily:\n",
+            "#+startup: overview\n",
+            "\n",
+            "quote\n",
+            "\n",
+            "* Tasks\n"
+        );
+        assert_eq!(
+            render_file_note("abc", &template, &file_template, &capture),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_insert_into_datetree() {
+        let now = datetime!(2024-03-15 12:00 UTC);
+        let content = concat!(
+            "#+title: Journal\n",
+            "* 2024\n",
+            "** 2024-03 March\n",
+            "*** 2024-03-01 Friday\n",
+            "**** Old entry\n",
+            "** 2024-04 April\n",
+        );
+        let expected = concat!(
+            "#+title: Journal\n",
+            "* 2024\n",
+            "** 2024-03 March\n",
+            "*** 2024-03-01 Friday\n",
+            "**** Old entry\n",
+            "*** 2024-03-15 Friday\n",
+            "**** New entry\n",
+            "** 2024-04 April\n",
+        );
+        assert_eq!(
+            insert_into_datetree(content, now, "**** New entry\n"),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_insert_into_empty_datetree() {
+        let now = datetime!(2024-03-15 12:00 UTC);
+        let expected = concat!(
+            "* 2024\n",
+            "** 2024-03 March\n",
+            "*** 2024-03-15 Friday\n",
+            "**** New entry\n",
+        );
+        assert_eq!(insert_into_datetree("", now, "**** New entry\n"), expected);
+    }
+}
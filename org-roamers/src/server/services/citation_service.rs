@@ -0,0 +1,177 @@
+use std::collections::{BTreeMap, HashMap};
+
+use sqlx::SqlitePool;
+
+use crate::server::types::{
+    CitationResponse, GraphData, NodeKind, OutgoingLink, RefEntry, RefsResponse, RoamLink, RoamNode,
+};
+use crate::transform::title::TitleSanitizer;
+
+/// Bipartite graph of the nodes citing works and the cited works. Links
+/// point from the citing node to the work.
+pub async fn citation_graph(sqlite: &SqlitePool) -> anyhow::Result<GraphData> {
+    const STMNT: &str = concat!(
+        "SELECT DISTINCT n.id, n.title, n.parent, c.cite_key FROM citations c\n",
+        "JOIN nodes n ON n.id = c.node_id\n",
+        "ORDER BY c.cite_key, n.id;"
+    );
+    let rows: Vec<(String, String, Option<String>, String)> =
+        sqlx::query_as(STMNT).fetch_all(sqlite).await?;
+    let rows = rows
+        .into_iter()
+        .map(|(id, title, parent, key)| {
+            let title = TitleSanitizer::new().process(&title);
+            (id, title, parent, key)
+        })
+        .collect();
+    Ok(build_graph(rows))
+}
+
+/// Build the graph from `(id, title, parent, key)` rows.
+fn build_graph(rows: Vec<(String, String, Option<String>, String)>) -> GraphData {
+    let mut graph = GraphData {
+        nodes: vec![],
+        links: vec![],
+        layout: BTreeMap::new(),
+        aggregated: false,
+        zoom: None,
+    };
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let mut add = |graph: &mut GraphData, id: String, node: RoamNode| {
+        let i = *index.entry(id).or_insert_with(|| {
+            graph.nodes.push(node);
+            graph.nodes.len() - 1
+        });
+        graph.nodes[i].num_links += 1;
+    };
+
+    for (id, title, parent, key) in rows {
+        let cite_id = format!("cite:{key}");
+        add(
+            &mut graph,
+            id.clone(),
+            RoamNode {
+                title: title.into(),
+                id: id.as_str().into(),
+                parent: parent.unwrap_or_default().into(),
+                num_links: 0,
+                pinned: false,
+                kind: NodeKind::Node,
+                summary: None,
+                group: None,
+            },
+        );
+        add(
+            &mut graph,
+            cite_id.clone(),
+            RoamNode {
+                title: format!("@{key}").into(),
+                id: cite_id.as_str().into(),
+                parent: "".into(),
+                num_links: 0,
+                pinned: false,
+                kind: NodeKind::Citation,
+                summary: None,
+                group: None,
+            },
+        );
+        graph.links.push(RoamLink {
+            from: id.into(),
+            to: cite_id.into(),
+        });
+    }
+
+    graph
+}
+
+/// Nodes citing `key` and the literature notes of `key`.
+pub async fn get_citation(sqlite: &SqlitePool, key: &str) -> anyhow::Result<CitationResponse> {
+    const CITING: &str = concat!(
+        "SELECT DISTINCT n.id, n.title FROM citations c\n",
+        "JOIN nodes n ON n.id = c.node_id\n",
+        "WHERE c.cite_key = ?\n",
+        "ORDER BY n.title, n.id;"
+    );
+    const LITERATURE: &str = concat!(
+        "SELECT DISTINCT n.id, n.title FROM refs r\n",
+        "JOIN nodes n ON n.id = r.node_id\n",
+        "WHERE r.type = 'cite' AND r.ref = ?\n",
+        "ORDER BY n.title, n.id;"
+    );
+
+    let link = |(id, title): (String, String)| OutgoingLink {
+        display: TitleSanitizer::new().process(&title).into(),
+        id: id.into(),
+    };
+    let citing: Vec<(String, String)> = sqlx::query_as(CITING).bind(key).fetch_all(sqlite).await?;
+    let literature: Vec<(String, String)> = sqlx::query_as(LITERATURE)
+        .bind(key)
+        .fetch_all(sqlite)
+        .await?;
+
+    Ok(CitationResponse {
+        key: key.to_string(),
+        citing: citing.into_iter().map(link).collect(),
+        literature: literature.into_iter().map(link).collect(),
+    })
+}
+
+/// Refs of all nodes, optionally only those of `ref_type`. Literature notes
+/// are the nodes with `cite` refs.
+pub async fn get_refs(sqlite: &SqlitePool, ref_type: Option<&str>) -> anyhow::Result<RefsResponse> {
+    const STMNT: &str = concat!(
+        "SELECT r.ref, r.type, n.id, n.title,\n",
+        "    (SELECT COUNT(DISTINCT c.node_id) FROM citations c\n",
+        "     WHERE r.type = 'cite' AND c.cite_key = r.ref) AS cited_by\n",
+        "FROM refs r\n",
+        "JOIN nodes n ON n.id = r.node_id\n",
+        "WHERE ?1 IS NULL OR r.type = ?1\n",
+        "ORDER BY r.type, r.ref, n.title, n.id;"
+    );
+    let rows: Vec<(String, String, String, String, i64)> = sqlx::query_as(STMNT)
+        .bind(ref_type)
+        .fetch_all(sqlite)
+        .await?;
+
+    Ok(RefsResponse {
+        refs: rows
+            .into_iter()
+            .map(|(reference, ref_type, id, title, cited_by)| RefEntry {
+                reference,
+                ref_type,
+                node: OutgoingLink {
+                    display: TitleSanitizer::new().process(&title).into(),
+                    id: id.into(),
+                },
+                cited_by: cited_by as usize,
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_graph() {
+        let row = |id: &str, key: &str| (id.to_string(), id.to_string(), None, key.to_string());
+        let rows = vec![
+            row("a", "doe2020"),
+            row("b", "doe2020"),
+            row("a", "roe2021"),
+        ];
+        let graph = build_graph(rows);
+
+        assert_eq!(graph.nodes.len(), 4);
+        assert_eq!(graph.links.len(), 3);
+        let doe = graph
+            .nodes
+            .iter()
+            .find(|node| node.id.id() == "cite:doe2020")
+            .unwrap();
+        assert_eq!(doe.kind, NodeKind::Citation);
+        assert_eq!(doe.num_links, 2);
+        assert_eq!(graph.nodes[0].num_links, 2);
+    }
+}
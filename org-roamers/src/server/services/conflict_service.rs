@@ -0,0 +1,76 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use time::OffsetDateTime;
+
+use crate::{client::message::WebSocketMessage, ServerState};
+
+/// Name of the copy that keeps the losing version of `file`. It does not end
+/// in `.org`, so the copy is not indexed next to the file.
+pub fn conflict_path(file: &Path, timestamp: i64, attempt: usize) -> PathBuf {
+    let mut name = file.as_os_str().to_os_string();
+    match attempt {
+        0 => name.push(format!(".conflict-{timestamp}")),
+        _ => name.push(format!(".conflict-{timestamp}-{attempt}")),
+    }
+    PathBuf::from(name)
+}
+
+/// Write `content` to a new conflict copy of the file at `path`, see
+/// [`conflict_path`]. Existing copies are never overwritten.
+pub fn write_copy(path: &Path, content: &str, timestamp: i64) -> io::Result<PathBuf> {
+    let mut attempt = 0;
+    loop {
+        let copy = conflict_path(path, timestamp, attempt);
+        match OpenOptions::new().write(true).create_new(true).open(&copy) {
+            Ok(mut file) => {
+                file.write_all(content.as_bytes())?;
+                return Ok(copy);
+            }
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Keep `content`, the version of `file` that lost a conflict, in a copy next
+/// to it and tell the clients. `file` is relative to the root. Returns the
+/// copy, relative to the root as well.
+pub fn preserve(state: &ServerState, file: &Path, content: &str) -> io::Result<PathBuf> {
+    let timestamp = OffsetDateTime::now_utc().unix_timestamp();
+    let copy = write_copy(&state.cache.resolve(file), content, timestamp)?;
+    let copy = file.with_file_name(copy.file_name().unwrap_or_default());
+
+    tracing::warn!(
+        "Conflicting edits of {}, kept the other version in {}",
+        file.display(),
+        copy.display()
+    );
+    state.broadcast_to_websockets(WebSocketMessage::Conflict {
+        file: file.to_string_lossy().to_string(),
+        copy: copy.to_string_lossy().to_string(),
+    });
+    Ok(copy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_copy_keeps_existing_copies() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("note.org");
+
+        let first = write_copy(&path, "first", 100).unwrap();
+        let second = write_copy(&path, "second", 100).unwrap();
+
+        assert_eq!(first, dir.path().join("note.org.conflict-100"));
+        assert_eq!(second, dir.path().join("note.org.conflict-100-1"));
+        assert_eq!(std::fs::read_to_string(first).unwrap(), "first");
+        assert_eq!(std::fs::read_to_string(second).unwrap(), "second");
+    }
+}
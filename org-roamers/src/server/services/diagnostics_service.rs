@@ -0,0 +1,116 @@
+use std::collections::{BTreeMap, HashSet};
+
+use sqlx::SqlitePool;
+
+use crate::server::types::{
+    DiagnosticSeverity, DuplicateNode, DuplicateTitle, FileDiagnostic, FileDiagnostics,
+};
+use crate::sqlite::diagnostics::{self, DiagnosticRow};
+use crate::transform::title::TitleSanitizer;
+
+/// Names shared by more than one node. Titles and aliases are both
+/// considered, because lookups by name match either of them.
+pub async fn duplicate_titles(sqlite: &SqlitePool) -> anyhow::Result<Vec<DuplicateTitle>> {
+    const STMNT: &str = concat!(
+        "SELECT n.title, n.id, n.title, n.file, 0 FROM nodes n\n",
+        "UNION ALL\n",
+        "SELECT a.alias, n.id, n.title, n.file, 1 FROM aliases a\n",
+        "JOIN nodes n ON n.id = a.node_id;"
+    );
+
+    let rows: Vec<(String, String, String, String, bool)> =
+        sqlx::query_as(STMNT).fetch_all(sqlite).await?;
+
+    let mut duplicates = group_duplicates(rows);
+    for node in duplicates.iter_mut().flat_map(|dup| dup.nodes.iter_mut()) {
+        node.title = TitleSanitizer::new().process(node.title.title()).into();
+    }
+    Ok(duplicates)
+}
+
+/// Group `(name, id, title, file, alias)` rows by name and keep the names used
+/// by more than one node.
+fn group_duplicates(rows: Vec<(String, String, String, String, bool)>) -> Vec<DuplicateTitle> {
+    let mut by_name: BTreeMap<String, Vec<DuplicateNode>> = BTreeMap::new();
+    for (name, id, title, file, alias) in rows {
+        let name = name.trim();
+        if name.is_empty() {
+            continue;
+        }
+        by_name
+            .entry(name.to_string())
+            .or_default()
+            .push(DuplicateNode {
+                id: id.into(),
+                title: title.into(),
+                file,
+                alias,
+            });
+    }
+
+    by_name
+        .into_iter()
+        .filter_map(|(name, nodes)| {
+            let ids: HashSet<&str> = nodes.iter().map(|node| node.id.id()).collect();
+            (ids.len() > 1).then_some(DuplicateTitle { name, nodes })
+        })
+        .collect()
+}
+
+/// Problems found while indexing, grouped by file.
+pub async fn file_diagnostics(sqlite: &SqlitePool) -> anyhow::Result<Vec<FileDiagnostics>> {
+    Ok(group_by_file(diagnostics::get_diagnostics(sqlite).await?))
+}
+
+/// Group rows ordered by file.
+fn group_by_file(rows: Vec<DiagnosticRow>) -> Vec<FileDiagnostics> {
+    let mut files: Vec<FileDiagnostics> = vec![];
+    for (file, severity, line, column, node, message) in rows {
+        let diagnostic = FileDiagnostic {
+            severity: match severity.as_str() {
+                "warning" => DiagnosticSeverity::Warning,
+                _ => DiagnosticSeverity::Error,
+            },
+            line: line.map(|line| line as usize),
+            column: column.map(|column| column as usize),
+            node,
+            message,
+        };
+        match files.last_mut() {
+            Some(last) if last.file == file => last.diagnostics.push(diagnostic),
+            _ => files.push(FileDiagnostics {
+                file,
+                diagnostics: vec![diagnostic],
+            }),
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(name: &str, id: &str, alias: bool) -> (String, String, String, String, bool) {
+        let file = format!("{id}.org");
+        (name.into(), id.into(), name.into(), file, alias)
+    }
+
+    #[test]
+    fn test_group_duplicates() {
+        let rows = vec![
+            row("Rust", "1", false),
+            row("Rust", "2", false),
+            row("Emacs", "3", false),
+            // An alias equal to the own title is not a duplicate.
+            row("Emacs", "3", true),
+            row("Rust", "4", true),
+        ];
+        let duplicates = group_duplicates(rows);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].name, "Rust");
+        let ids: Vec<&str> = duplicates[0].nodes.iter().map(|n| n.id.id()).collect();
+        assert_eq!(ids, vec!["1", "2", "4"]);
+        assert!(duplicates[0].nodes[2].alias);
+    }
+}
@@ -0,0 +1,137 @@
+use std::time::{Duration, Instant};
+
+use similar::{Algorithm, ChangeTag};
+
+use crate::server::types::{DiffHunk, DiffOp, RoamID};
+use crate::transform::subtree::Subtree;
+
+/// Time a diff may take before it settles for a less minimal result.
+const DIFF_DEADLINE: Duration = Duration::from_millis(500);
+
+/// Hashes are sent as hex strings, because JavaScript numbers can not hold
+/// every `u64`.
+pub fn format_hash(hash: u64) -> String {
+    format!("{hash:016x}")
+}
+
+/// `content` with the subtree of `id` replaced by `subtree`. Returns `None`
+/// if the result no longer contains the node, e.g. because its `ID`
+/// property was removed, or if `content` did not contain it to begin with.
+pub fn replace_subtree(content: &str, id: &RoamID, subtree: &str) -> Option<String> {
+    let range = Subtree::range(id.clone(), content)?;
+    let mut replaced = String::with_capacity(content.len() + subtree.len());
+    replaced.push_str(&content[..range.start]);
+    replaced.push_str(subtree);
+    // Keep the following headline on a line of its own.
+    if range.end < content.len() && !subtree.ends_with('\n') {
+        replaced.push('\n');
+    }
+    replaced.push_str(&content[range.end..]);
+    Subtree::range(id.clone(), &replaced).map(|_| replaced)
+}
+
+/// Line based diff from `old` to `new`, computed with Myers' algorithm in
+/// linear space. Diffs that take longer than [`DIFF_DEADLINE`] are less
+/// minimal. Consecutive lines with the same operation are grouped into one
+/// hunk.
+pub fn diff(old: &str, new: &str) -> Vec<DiffHunk> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let deadline = Instant::now() + DIFF_DEADLINE;
+    let ops = similar::capture_diff_slices_deadline(Algorithm::Myers, &old, &new, Some(deadline));
+
+    let mut hunks = vec![];
+    for change in ops.iter().flat_map(|op| op.iter_changes(&old, &new)) {
+        let op = match change.tag() {
+            ChangeTag::Equal => DiffOp::Equal,
+            ChangeTag::Delete => DiffOp::Delete,
+            ChangeTag::Insert => DiffOp::Insert,
+        };
+        push_lines(&mut hunks, op, &[change.value()]);
+    }
+    hunks
+}
+
+fn push_lines(hunks: &mut Vec<DiffHunk>, op: DiffOp, lines: &[&str]) {
+    if lines.is_empty() {
+        return;
+    }
+    match hunks.last_mut() {
+        Some(hunk) if hunk.op == op => hunk.lines.extend(lines.iter().map(|l| l.to_string())),
+        _ => hunks.push(DiffHunk {
+            op,
+            lines: lines.iter().map(|l| l.to_string()).collect(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(op: DiffOp, lines: &[&str]) -> DiffHunk {
+        DiffHunk {
+            op,
+            lines: lines.iter().map(|l| l.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = "* a\nb\nc\nd\n";
+        let new = "* a\nB\nc\nd\ne\n";
+        assert_eq!(
+            diff(old, new),
+            vec![
+                hunk(DiffOp::Equal, &["* a"]),
+                hunk(DiffOp::Delete, &["b"]),
+                hunk(DiffOp::Insert, &["B"]),
+                hunk(DiffOp::Equal, &["c", "d"]),
+                hunk(DiffOp::Insert, &["e"]),
+            ]
+        );
+        assert_eq!(
+            diff(old, old),
+            vec![hunk(DiffOp::Equal, &["* a", "b", "c", "d"])]
+        );
+        assert!(diff("", "").is_empty());
+    }
+
+    #[test]
+    fn test_diff_large_rewrite() {
+        // A quadratic table would need gigabytes for a rewritten subtree of
+        // this size
+        let old: String = (0..20_000).map(|i| format!("old {i}\n")).collect();
+        let new: String = (0..20_000).map(|i| format!("new {i}\n")).collect();
+        let hunks = diff(&old, &new);
+        assert_eq!(
+            hunks.iter().map(|hunk| hunk.op).collect::<Vec<_>>(),
+            vec![DiffOp::Delete, DiffOp::Insert]
+        );
+        assert_eq!(hunks[1].lines.len(), 20_000);
+    }
+
+    #[test]
+    fn test_replace_subtree() {
+        let content = concat!(
+            "* A\n",
+            ":PROPERTIES:\n",
+            ":ID: a\n",
+            ":END:\n",
+            "old\n",
+            "* B\n",
+        );
+        let id = RoamID::from("a");
+        assert_eq!(
+            replace_subtree(content, &id, "* A\n:PROPERTIES:\n:ID: a\n:END:\nnew").as_deref(),
+            Some("* A\n:PROPERTIES:\n:ID: a\n:END:\nnew\n* B\n")
+        );
+        assert_eq!(replace_subtree(content, &id, "* A\nnew\n"), None);
+        assert_eq!(replace_subtree(content, &"b".into(), "* B\n"), None);
+    }
+
+    #[test]
+    fn test_format_hash() {
+        assert_eq!(format_hash(255), "00000000000000ff");
+    }
+}
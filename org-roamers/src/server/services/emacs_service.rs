@@ -0,0 +1,129 @@
+//! Data for the companion Emacs package.
+
+use std::collections::{BTreeSet, HashMap};
+
+use sqlx::SqlitePool;
+
+use crate::server::types::{EmacsCandidate, EmacsCandidates, RoamID};
+use crate::transform::title::TitleSanitizer;
+
+/// Titles and aliases of all nodes, sorted by name. A node with aliases
+/// appears once per name, like in `org-roam-node-find`.
+pub async fn candidates(sqlite: &SqlitePool) -> anyhow::Result<EmacsCandidates> {
+    const NODES: &str = "SELECT id, title, file FROM nodes;";
+    const TAGS: &str = "SELECT DISTINCT node_id, tag FROM tags;";
+    const ALIASES: &str = "SELECT DISTINCT node_id, alias FROM aliases;";
+
+    let nodes: Vec<(String, String, String)> = sqlx::query_as(NODES).fetch_all(sqlite).await?;
+    let mut tags: HashMap<String, BTreeSet<String>> = HashMap::new();
+    for (id, tag) in sqlx::query_as::<_, (String, String)>(TAGS)
+        .fetch_all(sqlite)
+        .await?
+    {
+        tags.entry(id).or_default().insert(tag);
+    }
+    let mut aliases: HashMap<String, Vec<String>> = HashMap::new();
+    for (id, alias) in sqlx::query_as::<_, (String, String)>(ALIASES)
+        .fetch_all(sqlite)
+        .await?
+    {
+        aliases.entry(id).or_default().push(alias);
+    }
+
+    let mut candidates = vec![];
+    for (id, title, file) in nodes {
+        let tags = tags.get(&id).map(org_tags).unwrap_or_default();
+        let names = std::iter::once(TitleSanitizer::new().process(&title))
+            .chain(aliases.remove(&id).unwrap_or_default());
+        for name in names {
+            candidates.push(EmacsCandidate(
+                name,
+                id.as_str().into(),
+                tags.clone(),
+                file.clone(),
+            ));
+        }
+    }
+    candidates.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+
+    Ok(EmacsCandidates { candidates })
+}
+
+/// Tags as Org writes them after a headline.
+fn org_tags(tags: &BTreeSet<String>) -> String {
+    match tags.is_empty() {
+        true => String::new(),
+        false => format!(":{}:", tags.iter().cloned().collect::<Vec<_>>().join(":")),
+    }
+}
+
+/// Title of `id` as link description, `None` if the node does not exist.
+pub async fn link_title(sqlite: &SqlitePool, id: &RoamID) -> anyhow::Result<Option<String>> {
+    let title: Option<(String,)> = sqlx::query_as("SELECT title FROM nodes WHERE id = ?;")
+        .bind(id.id())
+        .fetch_optional(sqlite)
+        .await?;
+    Ok(title.map(|(title,)| TitleSanitizer::new().process(&title)))
+}
+
+/// `content` with a link to `dest` inserted at the character offset
+/// `position`, or on a line of its own at the end if `position` is `None`.
+/// Returns `None` if `position` is past the end of `content`.
+pub fn insert_link(
+    content: &str,
+    position: Option<usize>,
+    dest: &RoamID,
+    description: &str,
+) -> Option<String> {
+    // Brackets would end the description early.
+    let description = description.replace('[', "{").replace(']', "}");
+    let link = format!("[[id:{}][{}]]", dest.id(), description.trim());
+
+    let Some(position) = position else {
+        let mut content = content.to_string();
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str(&link);
+        content.push('\n');
+        return Some(content);
+    };
+    let offset = match content.char_indices().nth(position) {
+        Some((offset, _)) => offset,
+        None if content.chars().count() == position => content.len(),
+        None => return None,
+    };
+    let mut content = content.to_string();
+    content.insert_str(offset, &link);
+    Some(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_org_tags() {
+        assert_eq!(org_tags(&BTreeSet::new()), "");
+        let tags = BTreeSet::from(["b".to_string(), "a".to_string()]);
+        assert_eq!(org_tags(&tags), ":a:b:");
+    }
+
+    #[test]
+    fn test_insert_link() {
+        let dest = RoamID::from("dest");
+        assert_eq!(
+            insert_link("See ö.\n", Some(4), &dest, "Dest").as_deref(),
+            Some("See [[id:dest][Dest]]ö.\n")
+        );
+        assert_eq!(
+            insert_link("ab", Some(2), &dest, "[x]").as_deref(),
+            Some("ab[[id:dest][{x}]]")
+        );
+        assert_eq!(
+            insert_link("* Note", None, &dest, "Dest").as_deref(),
+            Some("* Note\n[[id:dest][Dest]]\n")
+        );
+        assert_eq!(insert_link("ab", Some(3), &dest, "Dest"), None);
+    }
+}
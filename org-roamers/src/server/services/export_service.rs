@@ -0,0 +1,99 @@
+use std::collections::{BTreeMap, HashMap};
+
+use sqlx::SqlitePool;
+
+use crate::server::types::{IndexLink, IndexNode, VaultIndex, VAULT_INDEX_VERSION};
+use crate::transform::{citations, title::TitleSanitizer};
+
+/// `(id, title, file, level, parent, todo, priority, scheduled, deadline,
+/// properties)`
+type NodeRow = (
+    String,
+    String,
+    String,
+    i64,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+);
+
+/// Dump nodes, links, tags, aliases and refs of the whole vault.
+pub async fn vault_index(sqlite: &SqlitePool) -> anyhow::Result<VaultIndex> {
+    const NODES: &str = concat!(
+        "SELECT id, title, file, level, parent, todo, priority, scheduled, deadline, properties\n",
+        "FROM nodes ORDER BY file, id;"
+    );
+    const LINKS: &str =
+        "SELECT DISTINCT source, dest, type FROM links ORDER BY source, dest, type;";
+
+    let rows: Vec<NodeRow> = sqlx::query_as(NODES).fetch_all(sqlite).await?;
+    let tags = group(
+        sqlx::query_as("SELECT node_id, tag FROM tags;")
+            .fetch_all(sqlite)
+            .await?,
+    );
+    let aliases = group(
+        sqlx::query_as("SELECT node_id, alias FROM aliases;")
+            .fetch_all(sqlite)
+            .await?,
+    );
+    let links: Vec<(String, String, String)> = sqlx::query_as(LINKS).fetch_all(sqlite).await?;
+
+    let nodes = rows
+        .into_iter()
+        .map(
+            |(id, title, file, level, parent, todo, priority, scheduled, deadline, properties)| {
+                let properties: BTreeMap<String, String> = properties
+                    .and_then(|properties| serde_json::from_str(&properties).ok())
+                    .unwrap_or_default();
+                let mut tags = tags.get(&id).cloned().unwrap_or_default();
+                tags.sort();
+                tags.dedup();
+                IndexNode {
+                    title: TitleSanitizer::new().process(&title),
+                    file,
+                    level: level as u64,
+                    parent: parent.filter(|p| !p.is_empty()).map(Into::into),
+                    todo,
+                    priority,
+                    scheduled,
+                    deadline,
+                    tags,
+                    aliases: aliases.get(&id).cloned().unwrap_or_default(),
+                    refs: properties
+                        .get("ROAM_REFS")
+                        .map(|refs| citations::parse_refs(refs))
+                        .unwrap_or_default(),
+                    properties,
+                    id: id.into(),
+                }
+            },
+        )
+        .collect();
+
+    let links = links
+        .into_iter()
+        .map(|(source, dest, link_type)| IndexLink {
+            source: source.into(),
+            dest,
+            link_type,
+        })
+        .collect();
+
+    Ok(VaultIndex {
+        version: VAULT_INDEX_VERSION,
+        nodes,
+        links,
+    })
+}
+
+fn group(rows: Vec<(String, String)>) -> HashMap<String, Vec<String>> {
+    let mut grouped: HashMap<String, Vec<String>> = HashMap::new();
+    for (id, value) in rows {
+        grouped.entry(id).or_default().push(value);
+    }
+    grouped
+}
@@ -0,0 +1,239 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+
+use time::OffsetDateTime;
+
+use crate::config::CaptureTarget;
+use crate::server::services::capture_service::{self, heading_level};
+use crate::server::types::ExtractResponse;
+use crate::transform::subtree::Subtree;
+use crate::{watcher, ServerState};
+
+/// File pattern used if the default capture template does not create files.
+const DEFAULT_PATTERN: &str = "%Y%m%d%H%M%S-${slug}.org";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExtractError {
+    #[error("Node {0} does not exist")]
+    NotFound(String),
+    #[error("Node {0} is not a headline")]
+    NotHeadline(String),
+    #[error("Invalid file {0}")]
+    InvalidFile(String),
+    #[error("{0} already exists")]
+    Exists(String),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// Move the headline node `id` with its subtree into a new file, like
+/// `org-roam-extract-subtree`. The id is kept and the headline is replaced by
+/// a link to the new file. Without `file` the file is named after the
+/// default capture template.
+pub async fn extract(
+    state: &ServerState,
+    id: &str,
+    file: Option<&str>,
+    now: OffsetDateTime,
+) -> Result<ExtractResponse, ExtractError> {
+    const NODE: &str = "SELECT file, level, title FROM nodes WHERE id = ?;";
+    let node: Option<(String, i64, String)> = sqlx::query_as(NODE)
+        .bind(id)
+        .fetch_optional(&state.sqlite)
+        .await
+        .map_err(anyhow::Error::from)?;
+    let (source, level, title) = node.ok_or_else(|| ExtractError::NotFound(id.to_string()))?;
+    if level == 0 {
+        return Err(ExtractError::NotHeadline(id.to_string()));
+    }
+    let target = match file {
+        Some(file) => {
+            valid_file(file).ok_or_else(|| ExtractError::InvalidFile(file.to_string()))?
+        }
+        None => capture_service::expand_pattern(file_pattern(state), &title, now).into(),
+    };
+
+    let cache = &state.cache;
+    let content = fs::read_to_string(cache.resolve(&source)).map_err(anyhow::Error::from)?;
+    let (remaining, note) = Subtree::get(id.into(), &content)
+        .and_then(|subtree| split_subtree(&content, &subtree, id, &title))
+        .ok_or_else(|| ExtractError::NotFound(id.to_string()))?;
+
+    match create_file(&cache.resolve(&target), &note) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+            return Err(ExtractError::Exists(target.display().to_string()))
+        }
+        Err(err) => return Err(anyhow::Error::from(err).into()),
+    }
+    fs::write(cache.resolve(&source), remaining).map_err(anyhow::Error::from)?;
+
+    // The node has to leave `source` before it is added with the new file.
+    for file in [Path::new(&source), target.as_path()] {
+        if let Err(err) = watcher::update_file(state, &cache.resolve(file)).await {
+            tracing::error!("Failed to index {}: {err}", file.display());
+        }
+    }
+
+    Ok(ExtractResponse {
+        id: id.into(),
+        file: target.to_string_lossy().to_string(),
+        source,
+    })
+}
+
+fn file_pattern(state: &ServerState) -> &str {
+    let template = state
+        .config
+        .capture_template(capture_service::DEFAULT_TEMPLATE);
+    match template.map(|template| &template.target) {
+        Some(CaptureTarget::NewFile { pattern }) => pattern,
+        _ => DEFAULT_PATTERN,
+    }
+}
+
+/// `file` if it is an org file within the root.
+pub fn valid_file(file: &str) -> Option<PathBuf> {
+    let path = Path::new(file);
+    let valid = path.extension().is_some_and(|ext| ext == "org")
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+    valid.then(|| path.to_path_buf())
+}
+
+fn create_file(path: &Path, content: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)?
+        .write_all(content.as_bytes())
+}
+
+/// Split the headline `subtree` off `content`. Returns `content` with the
+/// headline replaced by a link to `id`, and the subtree as file node: its
+/// property drawer and tags move to the file, children are promoted.
+pub fn split_subtree(
+    content: &str,
+    subtree: &str,
+    id: &str,
+    title: &str,
+) -> Option<(String, String)> {
+    let start = content.find(subtree)?;
+    let mut lines = subtree.split_inclusive('\n').peekable();
+    let headline = lines.next()?;
+    let level = heading_level(headline)?;
+
+    let mut planning = String::new();
+    while let Some(line) = lines.next_if(|line| is_planning(line)) {
+        push_line(&mut planning, line);
+    }
+    let mut drawer = String::new();
+    if lines
+        .peek()
+        .is_some_and(|line| line.trim().eq_ignore_ascii_case(":PROPERTIES:"))
+    {
+        for line in lines.by_ref() {
+            push_line(&mut drawer, line);
+            if line.trim().eq_ignore_ascii_case(":END:") {
+                break;
+            }
+        }
+    }
+    let mut body = String::new();
+    for line in lines {
+        match heading_level(line) {
+            Some(child) if child > level => push_line(&mut body, &line[level..]),
+            _ => push_line(&mut body, line),
+        }
+    }
+
+    let mut note = drawer;
+    note.push_str(&format!("#+title: {title}\n"));
+    let tags = headline_tags(headline);
+    if !tags.is_empty() {
+        note.push_str(&format!("#+filetags: :{}:\n", tags.join(":")));
+    }
+    note.push_str(&planning);
+    note.push_str(&body);
+
+    let remaining = format!(
+        "{}{} [[id:{id}][{title}]]\n{}",
+        &content[..start],
+        "*".repeat(level),
+        &content[start + subtree.len()..]
+    );
+    Some((remaining, note))
+}
+
+fn push_line(buffer: &mut String, line: &str) {
+    buffer.push_str(line);
+    if !line.ends_with('\n') {
+        buffer.push('\n');
+    }
+}
+
+fn is_planning(line: &str) -> bool {
+    let line = line.trim_start();
+    ["SCHEDULED:", "DEADLINE:", "CLOSED:"]
+        .iter()
+        .any(|keyword| line.starts_with(keyword))
+}
+
+/// Tags at the end of a headline, e.g. `:a:b:`.
+fn headline_tags(headline: &str) -> Vec<String> {
+    match headline.split_whitespace().last() {
+        Some(tags) if tags.len() > 1 && tags.starts_with(':') && tags.ends_with(':') => tags
+            .split(':')
+            .filter(|tag| !tag.is_empty())
+            .map(ToString::to_string)
+            .collect(),
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_subtree() {
+        let subtree = concat!(
+            "** TODO Task :work:\n",
+            "SCHEDULED: <2024-01-01 Mon>\n",
+            ":PROPERTIES:\n",
+            ":ID: task\n",
+            ":END:\n",
+            "Body.\n",
+            "*** Child\n",
+        );
+        let content = format!("* Project\n{subtree}** Next\n");
+        let (remaining, note) = split_subtree(&content, subtree, "task", "Task").unwrap();
+        assert_eq!(remaining, "* Project\n** [[id:task][Task]]\n** Next\n");
+        assert_eq!(
+            note,
+            concat!(
+                ":PROPERTIES:\n",
+                ":ID: task\n",
+                ":END:\n",
+                "#+title: Task\n",
+                "#+filetags: :work:\n",
+                "SCHEDULED: <2024-01-01 Mon>\n",
+                "Body.\n",
+                "* Child\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_valid_file() {
+        assert!(valid_file("notes/task.org").is_some());
+        assert!(valid_file("../task.org").is_none());
+        assert!(valid_file("/tmp/task.org").is_none());
+        assert!(valid_file("task.txt").is_none());
+    }
+}
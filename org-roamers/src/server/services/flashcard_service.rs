@@ -0,0 +1,73 @@
+use crate::cache::OrgCache;
+use crate::transform::flashcards::{self, Flashcard};
+
+/// Collect the flashcards of all files in the vault. Files are read from
+/// disk, so this blocks.
+pub fn collect(cache: &OrgCache) -> anyhow::Result<Vec<Flashcard>> {
+    let mut files: Vec<_> = cache
+        .org_files()?
+        .filter_map(|file_or_error| {
+            file_or_error
+                .inspect_err(|err| tracing::error!("{err}"))
+                .ok()
+        })
+        .collect();
+    files.sort();
+
+    let mut cards = vec![];
+    for file in files {
+        match cache.entry(&file) {
+            Ok(entry) => cards.extend(flashcards::flashcards(entry.content())),
+            Err(err) => tracing::error!("Failed to read {}: {err}", file.display()),
+        }
+    }
+    Ok(cards)
+}
+
+/// Render cards as a tab separated file that Anki imports into the `Basic`
+/// note type with front, back and tags columns.
+pub fn to_anki(cards: &[Flashcard]) -> String {
+    let mut out = String::from("#separator:tab\n#html:true\n#notetype:Basic\n#tags column:3\n");
+    for card in cards {
+        let tags: Vec<String> = card.tags.iter().map(|tag| tag.replace(' ', "_")).collect();
+        out.push_str(&field(&card.front));
+        out.push('\t');
+        out.push_str(&field(&card.back));
+        out.push('\t');
+        out.push_str(&tags.join(" "));
+        out.push('\n');
+    }
+    out
+}
+
+/// Escape a field for the html mode of the import. Tabs and newlines would
+/// start a new field or note.
+fn field(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\t', " ")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_anki() {
+        let cards = vec![Flashcard {
+            front: "a < b\tc".to_string(),
+            back: "line\nbreak".to_string(),
+            tags: vec!["rust".to_string(), "two words".to_string()],
+        }];
+        let anki = to_anki(&cards);
+        let mut lines = anki.lines();
+        assert_eq!(lines.next(), Some("#separator:tab"));
+        assert_eq!(
+            lines.last(),
+            Some("a &lt; b c\tline<br>break\trust two_words")
+        );
+    }
+}
@@ -1,22 +1,26 @@
 use futures_util::StreamExt;
 use sqlx::SqlitePool;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path};
 
-use crate::server::types::{GraphData, RoamID, RoamLink, RoamNode};
-use crate::sqlite::olp;
+use crate::config::is_excluded;
+use crate::server::types::{
+    GraphData, LiteGraph, LiteNode, NodeKind, RoamID, RoamLink, RoamNode, LITE_TITLE_LEN,
+};
 use crate::transform::title::TitleSanitizer;
 
 pub async fn get_graph_data(
     sqlite: &SqlitePool,
     filter_tags: Option<Vec<String>>,
     exclude_tags: Option<Vec<String>>,
+    pinned: &HashSet<String>,
 ) -> GraphData {
     let title_sanitizer = |title: &str| {
         let sanitizer = TitleSanitizer::new();
         sanitizer.process(title)
     };
 
-    let string_nodes = match (filter_tags, exclude_tags) {
+    let mut string_nodes = match (filter_tags, exclude_tags) {
         (None, None) => sqlx::query_as::<_, (String, String)>("SELECT id, title FROM nodes;")
             .fetch_all(sqlite)
             .await
@@ -98,25 +102,47 @@ pub async fn get_graph_data(
             .unwrap(),
     };
 
+    // Pinned nodes are always part of the graph, even if filtered by tags.
+    let missing_pins: Vec<&String> = pinned
+        .iter()
+        .filter(|id| !string_nodes.iter().any(|(node, _)| node == *id))
+        .collect();
+    for id in missing_pins {
+        let node =
+            sqlx::query_as::<_, (String, String)>("SELECT id, title FROM nodes WHERE id = ?;")
+                .bind(id)
+                .fetch_optional(sqlite)
+                .await
+                .unwrap_or_default();
+        string_nodes.extend(node);
+    }
+
+    // The parent is the closest ancestor with an id. It is stored at insert
+    // time, matching ancestors by title breaks on duplicate titles.
+    const PARENTS: &str = concat!(
+        "SELECT n.id, p.id FROM nodes n\n",
+        "JOIN nodes p ON p.id = n.parent;"
+    );
+    let parents: HashMap<String, String> = sqlx::query_as(PARENTS)
+        .fetch_all(sqlite)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+
     let mut nodes: Vec<RoamNode> = vec![];
 
     for node in string_nodes {
-        let parent = olp::get_olp(sqlite, &node.0)
-            .await
-            .unwrap_or_default()
-            .pop()
-            .unwrap_or_default();
-        let stmnt = "SELECT id FROM nodes WHERE title = ?";
-        let parent_id: String = sqlx::query_scalar(stmnt)
-            .bind(parent)
-            .fetch_one(sqlite)
-            .await
-            .unwrap_or_default();
+        let parent_id = parents.get(&node.0).cloned().unwrap_or_default();
         nodes.push(RoamNode {
             title: title_sanitizer(&node.1).into(),
             id: node.0.to_string().into(),
             parent: parent_id.into(),
             num_links: 0,
+            pinned: pinned.contains(&node.0),
+            kind: NodeKind::Node,
+            summary: None,
+            group: None,
         });
     }
 
@@ -169,6 +195,26 @@ pub async fn get_graph_data(
         .collect()
         .await;
 
+    // Nodes citing a work are linked to its literature notes, like in
+    // org-roam-ui.
+    const CITE_LINKS: &str = concat!(
+        "SELECT DISTINCT c.node_id, r.node_id FROM citations c\n",
+        "JOIN refs r ON r.type = 'cite' AND r.ref = c.cite_key\n",
+        "WHERE c.node_id != r.node_id;"
+    );
+    let cite_links: Vec<(String, String)> = sqlx::query_as(CITE_LINKS)
+        .fetch_all(sqlite)
+        .await
+        .unwrap_or_default();
+    for (source, dest) in cite_links {
+        if node_ids.contains(&source) && node_ids.contains(&dest) {
+            links.push(RoamLink {
+                from: RoamID::from(source),
+                to: RoamID::from(dest),
+            });
+        }
+    }
+
     // Add parent-child hierarchy links
     for node in &nodes {
         // Only add a link if the node has a non-empty parent
@@ -180,5 +226,549 @@ pub async fn get_graph_data(
         }
     }
 
-    GraphData { nodes, links }
+    // Later steps, e.g. the aggregation and clustering, depend on the order
+    let mut graph = GraphData {
+        nodes,
+        links,
+        layout: Default::default(),
+        aggregated: false,
+        zoom: None,
+    };
+    graph.sort();
+    graph
+}
+
+/// Nodes within `depth` hops of `id`, following `id:` links in both
+/// directions and parent links like the ones of [`get_graph_data`]. Only the subgraph is read from the database, which keeps
+/// this cheap for large vaults. Returns `None` if `id` does not exist.
+pub async fn get_neighborhood(
+    sqlite: &SqlitePool,
+    id: &str,
+    depth: u32,
+    pinned: &HashSet<String>,
+) -> anyhow::Result<Option<GraphData>> {
+    // `hood` holds every node with each distance it was reached at. `UNION`
+    // drops repeated rows, the depth bound ends the recursion.
+    macro_rules! with_hood {
+        ($($select:literal),* $(,)?) => {
+            concat!(
+                "WITH RECURSIVE edges (a, b) AS (\n",
+                "    SELECT source, dest FROM links WHERE type = 'id'\n",
+                "    UNION SELECT dest, source FROM links WHERE type = 'id'\n",
+                "    UNION SELECT parent, id FROM nodes WHERE parent IS NOT NULL\n",
+                "    UNION SELECT id, parent FROM nodes WHERE parent IS NOT NULL\n",
+                "), hood (id, depth) AS (\n",
+                "    SELECT id, 0 FROM nodes WHERE id = ?\n",
+                "    UNION SELECT e.b, h.depth + 1 FROM hood h\n",
+                "    JOIN edges e ON e.a = h.id WHERE h.depth < ?\n",
+                ")\n",
+                $($select),*
+            )
+        };
+    }
+    const NODES: &str = with_hood!(
+        "SELECT n.id, n.title, n.parent,\n",
+        "(SELECT COUNT(*) FROM links l WHERE l.type = 'id'\n",
+        " AND (l.source = n.id OR l.dest = n.id))\n",
+        "FROM nodes n WHERE n.id IN (SELECT id FROM hood);"
+    );
+    const LINKS: &str = with_hood!(
+        "SELECT source, dest FROM links WHERE type = 'id'\n",
+        "AND source IN (SELECT id FROM hood) AND dest IN (SELECT id FROM hood);"
+    );
+
+    let rows: Vec<(String, String, Option<String>, i64)> = sqlx::query_as(NODES)
+        .bind(id)
+        .bind(depth)
+        .fetch_all(sqlite)
+        .await?;
+    if rows.is_empty() {
+        return Ok(None);
+    }
+
+    let node_ids: HashSet<&str> = rows.iter().map(|(id, ..)| id.as_str()).collect();
+    let has = |id: &Option<String>| id.as_deref().is_some_and(|id| node_ids.contains(id));
+    let nodes: Vec<RoamNode> = rows
+        .iter()
+        .map(|(id, title, parent, num_links)| RoamNode {
+            title: TitleSanitizer::new().process(title).into(),
+            id: id.as_str().into(),
+            // Parents outside the neighborhood would leave dangling links.
+            parent: match has(parent) {
+                true => parent.as_deref().unwrap_or_default().into(),
+                false => "".into(),
+            },
+            num_links: *num_links as usize,
+            pinned: pinned.contains(id),
+            kind: NodeKind::Node,
+            summary: None,
+            group: None,
+        })
+        .collect();
+
+    let mut links: Vec<RoamLink> = sqlx::query_as::<_, (String, String)>(LINKS)
+        .bind(id)
+        .bind(depth)
+        .fetch_all(sqlite)
+        .await?
+        .into_iter()
+        .map(|(source, dest)| RoamLink {
+            from: source.into(),
+            to: dest.into(),
+        })
+        .collect();
+    for node in &nodes {
+        if !node.parent.id().is_empty() {
+            links.push(RoamLink {
+                from: node.parent.clone(),
+                to: node.id.clone(),
+            });
+        }
+    }
+
+    Ok(Some(GraphData {
+        nodes,
+        links,
+        layout: Default::default(),
+        aggregated: false,
+        zoom: None,
+    }))
+}
+
+/// Compact graph of all nodes and `id:` links between them, without tag
+/// filters, pins or parent links. `top` keeps only the nodes with the highest
+/// degree.
+pub async fn get_lite_graph(
+    sqlite: &SqlitePool,
+    exclude_tags: &[String],
+    top: Option<usize>,
+) -> anyhow::Result<LiteGraph> {
+    let nodes: Vec<(String, String)> = sqlx::query_as("SELECT id, title FROM nodes;")
+        .fetch_all(sqlite)
+        .await?;
+    let links: Vec<(String, String)> =
+        sqlx::query_as("SELECT DISTINCT source, dest FROM links WHERE type = 'id';")
+            .fetch_all(sqlite)
+            .await?;
+    let excluded: HashSet<String> = match exclude_tags.is_empty() {
+        true => HashSet::new(),
+        false => sqlx::query_as::<_, (String, String)>("SELECT node_id, tag FROM tags;")
+            .fetch_all(sqlite)
+            .await?
+            .into_iter()
+            .filter(|(_, tag)| is_excluded(exclude_tags, &[tag]))
+            .map(|(id, _)| id)
+            .collect(),
+    };
+
+    let nodes = nodes
+        .into_iter()
+        .filter(|(id, _)| !excluded.contains(id))
+        .collect();
+    let mut graph = build_lite_graph(nodes, links, top);
+    for node in &mut graph.nodes {
+        node.title = truncate_title(&TitleSanitizer::new().process(&node.title));
+    }
+    Ok(graph)
+}
+
+/// Build a [`LiteGraph`] from `(id, title)` nodes and `(from, to)` links.
+/// Links to unknown nodes and self links are dropped.
+fn build_lite_graph(
+    nodes: Vec<(String, String)>,
+    links: Vec<(String, String)>,
+    top: Option<usize>,
+) -> LiteGraph {
+    let ids: HashSet<&str> = nodes.iter().map(|(id, _)| id.as_str()).collect();
+    let links: Vec<(String, String)> = links
+        .into_iter()
+        .filter(|(from, to)| from != to && ids.contains(from.as_str()) && ids.contains(to.as_str()))
+        .collect();
+
+    let mut degrees: HashMap<&str, usize> = HashMap::new();
+    for (from, to) in &links {
+        *degrees.entry(from).or_default() += 1;
+        *degrees.entry(to).or_default() += 1;
+    }
+
+    let mut nodes: Vec<LiteNode> = nodes
+        .iter()
+        .map(|(id, title)| LiteNode {
+            id: id.clone().into(),
+            title: title.clone(),
+            degree: degrees.get(id.as_str()).copied().unwrap_or(0),
+        })
+        .collect();
+    if let Some(top) = top {
+        nodes.sort_by(|a, b| b.degree.cmp(&a.degree).then_with(|| a.id.cmp(&b.id)));
+        nodes.truncate(top);
+    }
+    nodes.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let kept: HashSet<&str> = nodes.iter().map(|node| node.id.id()).collect();
+    let mut links: Vec<(RoamID, RoamID)> = links
+        .iter()
+        .filter(|(from, to)| kept.contains(from.as_str()) && kept.contains(to.as_str()))
+        .map(|(from, to)| (from.clone().into(), to.clone().into()))
+        .collect();
+    links.sort();
+
+    LiteGraph { nodes, links }
+}
+
+fn truncate_title(title: &str) -> String {
+    match title.char_indices().nth(LITE_TITLE_LEN) {
+        Some((end, _)) => format!("{}…", title[..end].trim_end()),
+        None => title.to_string(),
+    }
+}
+
+/// Remove all nodes carrying one of `exclude_tags` together with their
+/// links. Unlike the tag filter of [`get_graph_data`] this also applies to
+/// pinned nodes.
+pub async fn remove_excluded(
+    sqlite: &SqlitePool,
+    mut graph: GraphData,
+    exclude_tags: &[String],
+) -> GraphData {
+    if exclude_tags.is_empty() {
+        return graph;
+    }
+
+    const STMNT: &str = "SELECT node_id, tag FROM tags;";
+    let rows: Vec<(String, String)> = sqlx::query_as(STMNT)
+        .fetch_all(sqlite)
+        .await
+        .unwrap_or_default();
+    let excluded: HashSet<String> = rows
+        .into_iter()
+        .filter(|(_, tag)| is_excluded(exclude_tags, &[tag]))
+        .map(|(id, _)| id)
+        .collect();
+
+    graph.nodes.retain(|node| !excluded.contains(node.id.id()));
+    graph
+        .links
+        .retain(|link| !excluded.contains(link.from.id()) && !excluded.contains(link.to.id()));
+    graph
+}
+
+/// Keep only nodes whose file was modified or created on or after the given
+/// `YYYY-MM-DD` dates. Pinned nodes are kept, like with the tag filter.
+pub async fn filter_by_file_dates(
+    sqlite: &SqlitePool,
+    mut graph: GraphData,
+    modified_after: Option<&str>,
+    created_after: Option<&str>,
+) -> GraphData {
+    if modified_after.is_none() && created_after.is_none() {
+        return graph;
+    }
+
+    const STMNT: &str = concat!(
+        "SELECT n.id FROM nodes n INNER JOIN files f ON n.file = f.file ",
+        "WHERE (? IS NULL OR date(f.mtime, 'unixepoch') >= ?) ",
+        "AND (? IS NULL OR date(f.created, 'unixepoch') >= ?);"
+    );
+    let matching: HashSet<String> = sqlx::query_scalar(STMNT)
+        .bind(modified_after)
+        .bind(modified_after)
+        .bind(created_after)
+        .bind(created_after)
+        .fetch_all(sqlite)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+
+    graph
+        .nodes
+        .retain(|node| node.pinned || matching.contains(node.id.id()));
+    let kept: HashSet<String> = graph
+        .nodes
+        .iter()
+        .map(|node| node.id.id().to_string())
+        .collect();
+    graph
+        .links
+        .retain(|link| kept.contains(link.from.id()) && kept.contains(link.to.id()));
+    graph
+}
+
+/// Attach the stored summary to every node of the graph.
+pub async fn add_summaries(sqlite: &SqlitePool, mut graph: GraphData) -> GraphData {
+    const STMNT: &str =
+        "SELECT id, summary FROM nodes WHERE summary IS NOT NULL AND summary != '';";
+    let summaries: HashMap<String, String> = sqlx::query_as(STMNT)
+        .fetch_all(sqlite)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+
+    for node in &mut graph.nodes {
+        node.summary = summaries.get(node.id.id()).cloned();
+    }
+    graph
+}
+
+/// Set the group of every node to the top level directory of its file.
+pub async fn add_groups(sqlite: &SqlitePool, mut graph: GraphData) -> GraphData {
+    const STMNT: &str = "SELECT id, file FROM nodes;";
+    let files: HashMap<String, String> = sqlx::query_as(STMNT)
+        .fetch_all(sqlite)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+
+    for node in &mut graph.nodes {
+        node.group = files.get(node.id.id()).and_then(|file| top_level_dir(file));
+    }
+    graph
+}
+
+/// First component of a path relative to the vault root, if the file is not
+/// in the root itself.
+fn top_level_dir(file: &str) -> Option<String> {
+    let mut components = Path::new(file).components();
+    let first = components.next()?;
+    components.next()?;
+    match first {
+        Component::Normal(dir) => Some(dir.to_string_lossy().to_string()),
+        _ => None,
+    }
+}
+
+/// Map every node id to the id of the top most node of its file.
+async fn file_representatives(sqlite: &SqlitePool) -> HashMap<String, String> {
+    const STMNT: &str = "SELECT id, file, level FROM nodes ORDER BY file, level, id;";
+    let rows: Vec<(String, String, i64)> = sqlx::query_as(STMNT)
+        .fetch_all(sqlite)
+        .await
+        .unwrap_or_default();
+
+    let mut representative: HashMap<String, String> = HashMap::new();
+    let mut file_representative: HashMap<String, String> = HashMap::new();
+    for (id, file, _) in rows {
+        let file_id = file_representative.entry(file).or_insert(id.clone());
+        representative.insert(id, file_id.clone());
+    }
+    representative
+}
+
+/// Merge all nodes of a file into a single node. The merged node uses the id
+/// of the top most node of the file, so it can still be opened. Links between
+/// files are deduplicated and links within a file are dropped.
+pub async fn aggregate_by_file(sqlite: &SqlitePool, graph: GraphData) -> GraphData {
+    // node id -> id of the representative of its file
+    let representative = file_representatives(sqlite).await;
+
+    let titles: HashMap<String, String> = graph
+        .nodes
+        .iter()
+        .map(|node| (node.id.id().to_string(), node.title.title().to_string()))
+        .collect();
+
+    let mut nodes: Vec<RoamNode> = vec![];
+    let mut node_index: HashMap<String, usize> = HashMap::new();
+    for node in graph.nodes {
+        let file_id = representative
+            .get(node.id.id())
+            .cloned()
+            .unwrap_or_else(|| node.id.id().to_string());
+        match node_index.get(&file_id) {
+            Some(index) => {
+                nodes[*index].pinned |= node.pinned;
+                nodes[*index].kind = NodeKind::Cluster;
+            }
+            None => {
+                let title = titles
+                    .get(&file_id)
+                    .cloned()
+                    .unwrap_or_else(|| node.title.title().to_string());
+                node_index.insert(file_id.clone(), nodes.len());
+                nodes.push(RoamNode {
+                    title: title.into(),
+                    id: file_id.into(),
+                    parent: "".into(),
+                    num_links: 0,
+                    pinned: node.pinned,
+                    kind: NodeKind::Node,
+                    summary: None,
+                    group: None,
+                });
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut links = vec![];
+    for link in graph.links {
+        let from = representative.get(link.from.id()).map(String::as_str);
+        let to = representative.get(link.to.id()).map(String::as_str);
+        let (Some(from), Some(to)) = (from, to) else {
+            continue;
+        };
+        if from == to || !seen.insert((from.to_string(), to.to_string())) {
+            continue;
+        }
+        for id in [from, to] {
+            if let Some(index) = node_index.get(id) {
+                nodes[*index].num_links += 1;
+            }
+        }
+        links.push(RoamLink {
+            from: from.into(),
+            to: to.into(),
+        });
+    }
+
+    GraphData {
+        nodes,
+        links,
+        layout: graph.layout,
+        aggregated: true,
+        zoom: None,
+    }
+}
+
+/// Return the members of the cluster containing `id`, as produced by
+/// [`aggregate_by_file`]. Links leaving the cluster point to the cluster of
+/// the other end, so the client can replace the cluster node in place.
+/// Returns `None` if `id` is not part of the graph.
+pub async fn expand_cluster(sqlite: &SqlitePool, graph: GraphData, id: &str) -> Option<GraphData> {
+    let representative = file_representatives(sqlite).await;
+    let cluster = representative.get(id)?;
+    let in_cluster = |id: &str| representative.get(id) == Some(cluster);
+
+    let nodes: Vec<RoamNode> = graph
+        .nodes
+        .into_iter()
+        .filter(|node| in_cluster(node.id.id()))
+        .collect();
+    if nodes.is_empty() {
+        return None;
+    }
+
+    let mut seen = HashSet::new();
+    let mut links = vec![];
+    for link in graph.links {
+        let (from, to) = (link.from.id(), link.to.id());
+        let (from, to) = match (in_cluster(from), in_cluster(to)) {
+            (true, true) => (from, to),
+            (true, false) => match representative.get(to) {
+                Some(other) => (from, other.as_str()),
+                None => continue,
+            },
+            (false, true) => match representative.get(from) {
+                Some(other) => (other.as_str(), to),
+                None => continue,
+            },
+            (false, false) => continue,
+        };
+        if seen.insert((from.to_string(), to.to_string())) {
+            links.push(RoamLink {
+                from: from.into(),
+                to: to.into(),
+            });
+        }
+    }
+
+    Some(GraphData {
+        nodes,
+        links,
+        layout: graph.layout,
+        aggregated: false,
+        zoom: None,
+    })
+}
+
+/// Add a node for every tag of the graph, linked to all nodes carrying it.
+/// Tag nodes use the id `tag:<name>`.
+pub async fn add_tag_nodes(sqlite: &SqlitePool, mut graph: GraphData) -> GraphData {
+    const STMNT: &str = "SELECT DISTINCT node_id, tag FROM tags ORDER BY tag, node_id;";
+    let rows: Vec<(String, String)> = sqlx::query_as(STMNT)
+        .fetch_all(sqlite)
+        .await
+        .unwrap_or_default();
+
+    let node_ids: HashSet<String> = graph
+        .nodes
+        .iter()
+        .map(|node| node.id.id().to_string())
+        .collect();
+
+    let mut tag_index: HashMap<String, usize> = HashMap::new();
+    for (node_id, tag) in rows {
+        if tag.trim().is_empty() || !node_ids.contains(&node_id) {
+            continue;
+        }
+        let tag_id = format!("tag:{tag}");
+        let index = *tag_index.entry(tag.clone()).or_insert_with(|| {
+            graph.nodes.push(RoamNode {
+                title: tag.as_str().into(),
+                id: tag_id.as_str().into(),
+                parent: "".into(),
+                num_links: 0,
+                pinned: false,
+                kind: NodeKind::Tag,
+                summary: None,
+                group: None,
+            });
+            graph.nodes.len() - 1
+        });
+        graph.nodes[index].num_links += 1;
+        graph.links.push(RoamLink {
+            from: tag_id.into(),
+            to: node_id.into(),
+        });
+    }
+
+    graph
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(items: &[(&str, &str)]) -> Vec<(String, String)> {
+        items
+            .iter()
+            .map(|(a, b)| (a.to_string(), b.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_top_level_dir() {
+        assert_eq!(
+            top_level_dir("projects/thesis/intro.org"),
+            Some("projects".to_string())
+        );
+        assert_eq!(top_level_dir("areas/health.org"), Some("areas".to_string()));
+        assert_eq!(top_level_dir("inbox.org"), None);
+    }
+
+    #[test]
+    fn test_build_lite_graph() {
+        let nodes = pairs(&[("a", "A"), ("b", "B"), ("c", "C")]);
+        let links = pairs(&[("a", "b"), ("a", "c"), ("a", "a"), ("b", "missing")]);
+
+        let graph = build_lite_graph(nodes.clone(), links.clone(), None);
+        assert_eq!(graph.nodes.len(), 3);
+        assert_eq!(graph.links.len(), 2);
+        assert_eq!(graph.nodes[0].degree, 2);
+
+        let graph = build_lite_graph(nodes, links, Some(2));
+        let ids: Vec<&str> = graph.nodes.iter().map(|node| node.id.id()).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+        assert_eq!(graph.links, vec![("a".into(), "b".into())]);
+    }
+
+    #[test]
+    fn test_truncate_title() {
+        assert_eq!(truncate_title("short"), "short");
+        let long = "x".repeat(LITE_TITLE_LEN + 5);
+        assert_eq!(truncate_title(&long).chars().count(), LITE_TITLE_LEN + 1);
+    }
 }
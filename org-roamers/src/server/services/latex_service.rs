@@ -58,6 +58,7 @@ pub async fn get_latex_svg_by_index(
     // Render the LaTeX
     let svg = latex::get_image(
         &state.config.latex_config,
+        &state.latex_cache,
         latex_content.clone(),
         color,
         latex_headers,
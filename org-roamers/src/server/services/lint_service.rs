@@ -0,0 +1,140 @@
+use sqlx::SqlitePool;
+
+use crate::config::LintConfig;
+use crate::server::types::{LintRule, LintedNode};
+use crate::transform::title::TitleSanitizer;
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// What the lint rules look at of a node.
+#[derive(Debug, Default)]
+pub struct LintInput<'a> {
+    pub title: &'a str,
+    pub todo: Option<&'a str>,
+    pub deadline: Option<&'a str>,
+    /// Creation time of the file as unix timestamp
+    pub created: Option<i64>,
+    pub tags: i64,
+    pub backlinks: i64,
+}
+
+/// Rules of `config` that `node` violates at `now`.
+pub fn lint_node(config: &LintConfig, node: &LintInput, now: i64) -> Vec<LintRule> {
+    let mut rules = vec![];
+    if config.missing_tags && node.tags == 0 {
+        rules.push(LintRule::MissingTags);
+    }
+    if config.max_title_length > 0 && node.title.chars().count() > config.max_title_length {
+        rules.push(LintRule::TitleTooLong);
+    }
+    let orphan_after = i64::from(config.orphan_days) * DAY;
+    if config.orphan_days > 0
+        && node.backlinks == 0
+        && node
+            .created
+            .is_some_and(|created| now - created > orphan_after)
+    {
+        rules.push(LintRule::NoBacklinks);
+    }
+    if config.todo_without_deadline && node.todo.is_some() && node.deadline.is_none() {
+        rules.push(LintRule::TodoWithoutDeadline);
+    }
+    rules
+}
+
+/// Nodes violating a rule of `config`, or only the node `id`.
+pub async fn lint(
+    sqlite: &SqlitePool,
+    config: &LintConfig,
+    id: Option<&str>,
+    now: i64,
+) -> anyhow::Result<Vec<LintedNode>> {
+    const STMNT: &str = concat!(
+        "SELECT n.id, n.title, n.file, n.todo, n.deadline, f.created,\n",
+        "(SELECT COUNT(*) FROM tags t WHERE t.node_id = n.id),\n",
+        "(SELECT COUNT(*) FROM links l WHERE l.dest = n.id AND l.type = 'id')\n",
+        "FROM nodes n JOIN files f ON f.file = n.file\n",
+        "WHERE ?1 IS NULL OR n.id = ?1\n",
+        "ORDER BY n.file, n.title, n.id;"
+    );
+    type Row = (
+        String,
+        String,
+        String,
+        Option<String>,
+        Option<String>,
+        Option<i64>,
+        i64,
+        i64,
+    );
+    let rows: Vec<Row> = sqlx::query_as(STMNT).bind(id).fetch_all(sqlite).await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(
+            |(id, title, file, todo, deadline, created, tags, backlinks)| {
+                let input = LintInput {
+                    title: &title,
+                    todo: todo.as_deref(),
+                    deadline: deadline.as_deref(),
+                    created,
+                    tags,
+                    backlinks,
+                };
+                let rules = lint_node(config, &input, now);
+                (!rules.is_empty()).then(|| LintedNode {
+                    id: id.into(),
+                    title: TitleSanitizer::new().process(&title).into(),
+                    file,
+                    rules,
+                })
+            },
+        )
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint_node() {
+        let config = LintConfig {
+            max_title_length: 10,
+            ..LintConfig::default()
+        };
+        let now = 100 * DAY;
+        let clean = LintInput {
+            title: "Rust",
+            tags: 1,
+            backlinks: 0,
+            created: Some(now - DAY),
+            ..Default::default()
+        };
+        assert!(lint_node(&config, &clean, now).is_empty());
+
+        let node = LintInput {
+            title: "A rather long title",
+            todo: Some("TODO"),
+            created: Some(now - 31 * DAY),
+            ..Default::default()
+        };
+        assert_eq!(
+            lint_node(&config, &node, now),
+            vec![
+                LintRule::MissingTags,
+                LintRule::TitleTooLong,
+                LintRule::NoBacklinks,
+                LintRule::TodoWithoutDeadline,
+            ]
+        );
+
+        let disabled = LintConfig {
+            missing_tags: false,
+            max_title_length: 0,
+            orphan_days: 0,
+            todo_without_deadline: false,
+        };
+        assert!(lint_node(&disabled, &node, now).is_empty());
+    }
+}
@@ -0,0 +1,337 @@
+use std::fs;
+
+use sqlx::SqlitePool;
+
+use crate::cache::OrgCache;
+use crate::server::services::trash_service;
+use crate::server::types::MergeResponse;
+use crate::sqlite::trash;
+use crate::transform::links::rewrite_id_links;
+use crate::{watcher, ServerState};
+
+#[derive(Debug, thiserror::Error)]
+pub enum MergeError {
+    #[error("A node can not be merged into itself")]
+    SameNode,
+    #[error("Node {0} does not exist")]
+    NotFound(String),
+    #[error("Node {0} is not a file node")]
+    NotFileNode(String),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// Merge the file node `remove` into `keep`. The content of `remove` is
+/// appended to `keep`, tags and aliases are united, links to `remove` are
+/// rewritten to `keep` and the file of `remove` is moved to the trash.
+pub async fn merge(
+    state: &ServerState,
+    keep: &str,
+    remove: &str,
+    user: Option<&str>,
+) -> Result<MergeResponse, MergeError> {
+    const LINKING: &str = concat!(
+        "SELECT DISTINCT n.file FROM links l\n",
+        "JOIN nodes n ON n.id = l.source\n",
+        "WHERE l.type = 'id' AND l.dest = ?;"
+    );
+
+    if keep == remove {
+        return Err(MergeError::SameNode);
+    }
+    let keep_file = file_node(&state.sqlite, keep).await?;
+    let remove_file = file_node(&state.sqlite, remove).await?;
+    let cache = &state.cache;
+
+    let merged = merge_content(
+        &read(cache, &keep_file)?,
+        &read(cache, &remove_file)?,
+        keep,
+        remove,
+    );
+    let linking: Vec<String> = sqlx::query_scalar(LINKING)
+        .bind(remove)
+        .fetch_all(&state.sqlite)
+        .await
+        .map_err(anyhow::Error::from)?;
+
+    write(cache, &keep_file, &merged)?;
+    let mut rewritten = vec![];
+    for file in linking {
+        if file == keep_file || file == remove_file {
+            continue;
+        }
+        let content = read(cache, &file)?;
+        let content_rewritten = rewrite_id_links(&content, remove, keep);
+        if content_rewritten != content {
+            write(cache, &file, &content_rewritten)?;
+            rewritten.push(file);
+        }
+    }
+
+    // Nodes below the headlines of `remove` now live in `keep_file`. They
+    // have to leave the database before `keep_file` is indexed again.
+    trash_service::trash_file(state, &remove_file, user).await?;
+    for file in std::iter::once(&keep_file).chain(&rewritten) {
+        if let Err(err) = watcher::update_file(state, &cache.resolve(file)).await {
+            tracing::error!("Failed to index merged file {file}: {err}");
+        }
+    }
+
+    Ok(MergeResponse {
+        id: keep.into(),
+        file: keep_file,
+        rewritten,
+        trashed: remove_file,
+    })
+}
+
+async fn file_node(sqlite: &SqlitePool, id: &str) -> Result<String, MergeError> {
+    match trash::get_node_file(sqlite, id).await? {
+        Some((file, 0)) => Ok(file),
+        Some(_) => Err(MergeError::NotFileNode(id.to_string())),
+        None => Err(MergeError::NotFound(id.to_string())),
+    }
+}
+
+fn read(cache: &OrgCache, file: &str) -> anyhow::Result<String> {
+    Ok(fs::read_to_string(cache.resolve(file))?)
+}
+
+fn write(cache: &OrgCache, file: &str, content: &str) -> anyhow::Result<()> {
+    Ok(fs::write(cache.resolve(file), content)?)
+}
+
+/// Content of `keep` with the body of `remove` appended. The property drawer
+/// and keywords of `remove` are dropped, except for its tags and aliases.
+pub fn merge_content(keep: &str, remove: &str, keep_id: &str, remove_id: &str) -> String {
+    let (keep_front, keep_body) = keep.split_at(body_start(keep));
+    let (remove_front, remove_body) = remove.split_at(body_start(remove));
+    let tags = union(filetags(keep_front), filetags(remove_front));
+    let aliases = union(aliases(keep_front), aliases(remove_front));
+
+    let mut merged = merge_front_matter(keep_front, &tags, &aliases);
+    let keep_body = keep_body.trim_end();
+    merged.push_str(keep_body);
+    let remove_body = remove_body.trim_start_matches('\n').trim_end();
+    if !remove_body.is_empty() {
+        if !merged.ends_with('\n') {
+            merged.push('\n');
+        }
+        if !keep_body.is_empty() {
+            merged.push('\n');
+        }
+        merged.push_str(remove_body);
+    }
+    if !merged.ends_with('\n') {
+        merged.push('\n');
+    }
+    rewrite_id_links(&merged, remove_id, keep_id)
+}
+
+/// Byte offset after the property drawer and keywords at the top of a file.
+fn body_start(content: &str) -> usize {
+    let mut offset = 0;
+    let mut in_drawer = false;
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if in_drawer {
+            in_drawer = !trimmed.eq_ignore_ascii_case(":END:");
+        } else if trimmed.eq_ignore_ascii_case(":PROPERTIES:") {
+            in_drawer = true;
+        } else if !trimmed.is_empty() && !trimmed.starts_with("#+") {
+            break;
+        }
+        offset += line.len();
+    }
+    offset
+}
+
+fn keyword_value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let (k, v) = line.trim().strip_prefix("#+")?.split_once(':')?;
+    k.eq_ignore_ascii_case(key).then(|| v.trim())
+}
+
+fn property_value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let (k, v) = line.trim().strip_prefix(':')?.split_once(':')?;
+    k.eq_ignore_ascii_case(key).then(|| v.trim())
+}
+
+fn filetags(front: &str) -> Vec<String> {
+    front
+        .lines()
+        .filter_map(|line| keyword_value(line, "filetags"))
+        .flat_map(|tags| tags.split(':'))
+        .filter(|tag| !tag.trim().is_empty())
+        .map(ToString::to_string)
+        .collect()
+}
+
+fn aliases(front: &str) -> Vec<String> {
+    front
+        .lines()
+        .filter_map(|line| property_value(line, "ROAM_ALIASES"))
+        .flat_map(split_aliases)
+        .collect()
+}
+
+/// Split a `ROAM_ALIASES` value like org-roam: aliases are separated by
+/// whitespace, double quoted ones may contain whitespace and `\"`.
+fn split_aliases(value: &str) -> Vec<String> {
+    let mut aliases = vec![];
+    let mut chars = value.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(first) = chars.next() else {
+            return aliases;
+        };
+        let mut alias = String::new();
+        if first == '"' {
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => alias.extend(chars.next()),
+                    '"' => break,
+                    c => alias.push(c),
+                }
+            }
+        } else {
+            alias.push(first);
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                alias.push(c);
+            }
+        }
+        aliases.push(alias);
+    }
+}
+
+/// Inverse of [`split_aliases`], aliases are quoted where needed.
+fn join_aliases(aliases: &[String]) -> String {
+    let quote = |alias: &String| {
+        if !alias.is_empty() && !alias.contains(|c: char| c.is_whitespace() || c == '"') {
+            return alias.clone();
+        }
+        format!("\"{}\"", alias.replace('\\', "\\\\").replace('"', "\\\""))
+    };
+    aliases.iter().map(quote).collect::<Vec<_>>().join(" ")
+}
+
+fn union(mut a: Vec<String>, b: Vec<String>) -> Vec<String> {
+    for item in b {
+        if !a.contains(&item) {
+            a.push(item);
+        }
+    }
+    a
+}
+
+/// Replace the `filetags` keyword and `ROAM_ALIASES` property of `front`.
+/// Missing ones are added after the title and at the end of the drawer.
+fn merge_front_matter(front: &str, tags: &[String], aliases: &[String]) -> String {
+    let tags_line = (!tags.is_empty()).then(|| format!("#+filetags: :{}:\n", tags.join(":")));
+    let aliases_line =
+        (!aliases.is_empty()).then(|| format!(":ROAM_ALIASES: {}\n", join_aliases(aliases)));
+
+    let mut lines: Vec<String> = vec![];
+    let mut tags_at = None;
+    let mut aliases_at = None;
+    let mut title_at = None;
+    let mut drawer_end_at = None;
+    for line in front.split_inclusive('\n') {
+        let mut line = line.to_string();
+        if !line.ends_with('\n') {
+            line.push('\n');
+        }
+        if keyword_value(&line, "filetags").is_some() {
+            tags_at.get_or_insert(lines.len());
+            continue;
+        }
+        if property_value(&line, "ROAM_ALIASES").is_some() {
+            aliases_at.get_or_insert(lines.len());
+            continue;
+        }
+        if keyword_value(&line, "title").is_some() {
+            title_at.get_or_insert(lines.len() + 1);
+        }
+        if line.trim().eq_ignore_ascii_case(":END:") {
+            drawer_end_at.get_or_insert(lines.len());
+        }
+        lines.push(line);
+    }
+
+    // Insert from the back, so earlier positions stay valid.
+    let mut inserts = vec![];
+    if let Some(line) = tags_line {
+        let at = tags_at.or(title_at).or(drawer_end_at.map(|at| at + 1));
+        inserts.push((at.unwrap_or(lines.len()), line));
+    }
+    if let Some(line) = aliases_line {
+        inserts.push((aliases_at.or(drawer_end_at).unwrap_or(0), line));
+    }
+    inserts.sort_by_key(|(at, _)| std::cmp::Reverse(*at));
+    for (at, line) in inserts {
+        lines.insert(at, line);
+    }
+    lines.concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quoted_aliases() {
+        let aliases = split_aliases(r#"plain "two words" "say \"hi\"" last"#);
+        assert_eq!(aliases, vec!["plain", "two words", r#"say "hi""#, "last"]);
+        assert_eq!(
+            join_aliases(&aliases),
+            r#"plain "two words" "say \"hi\"" last"#
+        );
+
+        let keep = ":PROPERTIES:\n:ID: keep\n:ROAM_ALIASES: \"New York\"\n:END:\n";
+        let remove = ":PROPERTIES:\n:ID: remove\n:ROAM_ALIASES: \"New York\" NYC\n:END:\n";
+        assert_eq!(
+            merge_content(keep, remove, "keep", "remove"),
+            ":PROPERTIES:\n:ID: keep\n:ROAM_ALIASES: \"New York\" NYC\n:END:\n"
+        );
+    }
+
+    #[test]
+    fn test_merge_content() {
+        let keep = concat!(
+            ":PROPERTIES:\n",
+            ":ID: keep\n",
+            ":ROAM_ALIASES: a\n",
+            ":END:\n",
+            "#+title: Keep\n",
+            "Body of keep.\n",
+            "Self link [[id:remove][Remove]].\n",
+        );
+        let remove = concat!(
+            ":PROPERTIES:\n",
+            ":ID: remove\n",
+            ":ROAM_ALIASES: a b\n",
+            ":END:\n",
+            "#+title: Remove\n",
+            "#+filetags: :x:y:\n",
+            "\n",
+            "Body of remove.\n",
+            "* Heading\n",
+        );
+        assert_eq!(
+            merge_content(keep, remove, "keep", "remove"),
+            concat!(
+                ":PROPERTIES:\n",
+                ":ID: keep\n",
+                ":ROAM_ALIASES: a b\n",
+                ":END:\n",
+                "#+title: Keep\n",
+                "#+filetags: :x:y:\n",
+                "Body of keep.\n",
+                "Self link [[id:keep][Remove]].\n",
+                "\n",
+                "Body of remove.\n",
+                "* Heading\n",
+            )
+        );
+    }
+}
@@ -1,4 +1,31 @@
+pub mod agenda_service;
+pub mod archive_service;
 pub mod asset_service;
+pub mod audit_service;
+pub mod backlink_service;
+pub mod board_service;
+pub mod bundle_service;
+pub mod calendar_service;
+pub mod capture_service;
+pub mod citation_service;
+pub mod conflict_service;
+pub mod diagnostics_service;
+pub mod edit_service;
+pub mod emacs_service;
+pub mod export_service;
+pub mod extract_service;
+pub mod flashcard_service;
 pub mod graph_service;
 pub mod latex_service;
+pub mod lint_service;
+pub mod merge_service;
+pub mod move_service;
+pub mod node_service;
 pub mod org_service;
+pub mod query_service;
+pub mod render_service;
+pub mod review_service;
+pub mod similar_service;
+pub mod template_service;
+pub mod timeline_service;
+pub mod trash_service;
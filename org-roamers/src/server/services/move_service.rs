@@ -0,0 +1,245 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::cache::OrgCache;
+use crate::server::services::extract_service;
+use crate::server::types::MoveResponse;
+use crate::sqlite::trash;
+use crate::transform::html::link_target;
+use crate::transform::links::rewrite_links;
+use crate::{watcher, ServerState};
+
+#[derive(Debug, thiserror::Error)]
+pub enum MoveError {
+    #[error("Node {0} does not exist")]
+    NotFound(String),
+    #[error("Invalid file {0}")]
+    InvalidFile(String),
+    #[error("{0} already exists")]
+    Exists(String),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// Move the file of node `id` to `file`. Relative links in the moved file are
+/// adjusted to its new directory and links of other files to the old path
+/// are pointed at the new one.
+pub async fn move_file(
+    state: &ServerState,
+    id: &str,
+    file: &str,
+) -> Result<MoveResponse, MoveError> {
+    let (source, _) = trash::get_node_file(&state.sqlite, id)
+        .await?
+        .ok_or_else(|| MoveError::NotFound(id.to_string()))?;
+    let target = extract_service::valid_file(file)
+        .ok_or_else(|| MoveError::InvalidFile(file.to_string()))?;
+    let target = target.to_string_lossy().to_string();
+    let cache = &state.cache;
+    if cache.resolve(&target).exists() {
+        return Err(MoveError::Exists(target));
+    }
+
+    let content = read(cache, &source)?;
+    let moved = relocate_links(&content, &source, &target);
+    if let Some(parent) = cache.resolve(&target).parent() {
+        fs::create_dir_all(parent).map_err(anyhow::Error::from)?;
+    }
+    fs::write(cache.resolve(&target), moved).map_err(anyhow::Error::from)?;
+    fs::remove_file(cache.resolve(&source)).map_err(anyhow::Error::from)?;
+
+    let mut rewritten = vec![];
+    let name = Path::new(&source).file_name().unwrap_or_default();
+    for path in state.cache.org_files().map_err(anyhow::Error::from)? {
+        let path = path.map_err(anyhow::Error::from)?;
+        let Some(relative) = cache.relative(&path) else {
+            continue;
+        };
+        let other = relative.to_string_lossy().to_string();
+        if other == target {
+            continue;
+        }
+        let content = read(cache, &other)?;
+        // Cheap check before the links are parsed.
+        if !content.contains(&*name.to_string_lossy()) {
+            continue;
+        }
+        let content_rewritten = rewrite_links(&content, |link| {
+            linking_link(&other, &source, &target, link)
+        });
+        if content_rewritten != content {
+            fs::write(&path, content_rewritten).map_err(anyhow::Error::from)?;
+            rewritten.push(other);
+        }
+    }
+
+    if let Err(err) = watcher::remove_file(state, Path::new(&source)).await {
+        tracing::error!("Failed to remove {source} from the index: {err}");
+    }
+    for file in std::iter::once(&target).chain(&rewritten) {
+        if let Err(err) = watcher::update_file(state, &cache.resolve(file)).await {
+            tracing::error!("Failed to index {file}: {err}");
+        }
+    }
+
+    Ok(MoveResponse {
+        id: id.into(),
+        file: target,
+        source,
+        rewritten,
+    })
+}
+
+fn read(cache: &OrgCache, file: &str) -> anyhow::Result<String> {
+    Ok(fs::read_to_string(cache.resolve(file))?)
+}
+
+/// `content` of `source` with its relative file links adjusted to `target`.
+pub(crate) fn relocate_links(content: &str, source: &str, target: &str) -> String {
+    rewrite_links(content, |link| moved_link(source, target, link))
+}
+
+/// Relative file link of the moved file, adjusted from `source` to `target`.
+fn moved_link(source: &str, target: &str, link: &str) -> Option<String> {
+    let (prefix, path, search) = file_link(link)?;
+    let resolved = link_target(source, path);
+    let relative = relative_path(parent(target), &resolved);
+    Some(format_link(prefix, path, &relative, search))
+}
+
+/// Link in `file` to `source`, pointed at `target`.
+fn linking_link(file: &str, source: &str, target: &str, link: &str) -> Option<String> {
+    let (prefix, path, search) = file_link(link)?;
+    if link_target(file, path) != Path::new(source) {
+        return None;
+    }
+    let relative = relative_path(parent(file), Path::new(target));
+    Some(format_link(prefix, path, &relative, search))
+}
+
+/// Split a relative file link into its `file:` prefix, path and search
+/// option, e.g. `file:../a.org::*Heading`. Links without `file:` are only
+/// files if they start with `./` or `../`, like in Org mode.
+pub(crate) fn file_link(link: &str) -> Option<(&str, &str, &str)> {
+    let (prefix, rest) = match link.strip_prefix("file:") {
+        Some(rest) => ("file:", rest),
+        None if link.starts_with("./") || link.starts_with("../") => ("", link),
+        None => return None,
+    };
+    let (path, search) = match rest.find("::") {
+        Some(at) => rest.split_at(at),
+        None => (rest, ""),
+    };
+    let absolute = Path::new(path).is_absolute() || path.starts_with('~');
+    (!path.is_empty() && !absolute).then_some((prefix, path, search))
+}
+
+fn format_link(prefix: &str, old: &str, relative: &Path, search: &str) -> String {
+    let mut path = relative.to_string_lossy().to_string();
+    // Keep the `./` of links that need it to be recognized as file links.
+    let needs_dot = prefix.is_empty() || old.starts_with("./");
+    if needs_dot && !path.starts_with("../") {
+        path = format!("./{path}");
+    }
+    format!("{prefix}{path}{search}")
+}
+
+fn parent(file: &str) -> &Path {
+    Path::new(file).parent().unwrap_or(Path::new(""))
+}
+
+/// `target` relative to `dir`. Both are relative to the root.
+fn relative_path(dir: &Path, target: &Path) -> PathBuf {
+    let dir: Vec<Component> = dir.components().collect();
+    let target: Vec<Component> = target.components().collect();
+    let common = dir.iter().zip(&target).take_while(|(a, b)| a == b).count();
+    let mut path = PathBuf::new();
+    for _ in common..dir.len() {
+        path.push("..");
+    }
+    for component in &target[common..] {
+        path.push(component);
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_moved_links() {
+        let content = concat!(
+            "[[file:b.org::*Heading][B]] [[./img/a.png]]\n",
+            "[[id:x][X]] [[https://example.com]] [[file:/abs.org]]\n",
+        );
+        assert_eq!(
+            rewrite_links(content, |link| moved_link("a.org", "dir/a.org", link)),
+            concat!(
+                "[[file:../b.org::*Heading][B]] [[../img/a.png]]\n",
+                "[[id:x][X]] [[https://example.com]] [[file:/abs.org]]\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_linking_links() {
+        let content = "[[file:a.org][A]] [[file:c.org]] [[./a.org]]";
+        assert_eq!(
+            rewrite_links(content, |link| linking_link(
+                "b.org",
+                "a.org",
+                "dir/a.org",
+                link
+            )),
+            "[[file:dir/a.org][A]] [[file:c.org]] [[./dir/a.org]]"
+        );
+        assert_eq!(
+            rewrite_links("[[file:../a.org]]", |link| linking_link(
+                "dir/b.org",
+                "a.org",
+                "dir/a.org",
+                link
+            )),
+            "[[file:a.org]]"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_move_file_broadcasts_source_removal() {
+        use crate::client::message::WebSocketMessage;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path().join("notes");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join("a.org"),
+            ":PROPERTIES:\n:ID: a\n:END:\n#+title: A\n",
+        )
+        .unwrap();
+
+        let config = crate::config::Config {
+            org_roamers_root: root.clone(),
+            database: crate::config::DatabaseConfig {
+                path: Some(temp_dir.path().join("roam.db")),
+            },
+            ..Default::default()
+        };
+        let state = ServerState::new(config).await.unwrap();
+
+        move_file(&state, "a", "dir/a.org").await.unwrap();
+
+        let (file, _) = trash::get_node_file(&state.sqlite, "a")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(file, "dir/a.org");
+        let broadcasts = state.pending_broadcasts.lock().unwrap();
+        assert!(matches!(
+            broadcasts.as_slice(),
+            [WebSocketMessage::GraphUpdate(removed), WebSocketMessage::GraphUpdate(added)]
+                if removed.removed_nodes == vec!["a".into()]
+                    && added.new_nodes.iter().any(|node| node.id == "a".into())
+        ));
+    }
+}
@@ -0,0 +1,73 @@
+use sqlx::SqlitePool;
+
+use crate::cache::OrgCache;
+use crate::server::types::{Neighbor, NeighborsResponse, OutlineHeading, OutlineResponse, RoamID};
+use crate::sqlite::links;
+use crate::transform::outline::{self, Heading};
+use crate::transform::title::TitleSanitizer;
+
+/// Heading tree of the file containing `id`. `None` if the node is unknown.
+pub fn get_outline(cache: &OrgCache, id: &RoamID) -> Option<OutlineResponse> {
+    let entry = cache.retrieve(id)?;
+    let headings = outline::outline(entry.content())
+        .into_iter()
+        .map(to_response)
+        .collect();
+
+    Some(OutlineResponse {
+        file: entry.path().to_string_lossy().to_string(),
+        headings,
+    })
+}
+
+fn to_response(heading: Heading) -> OutlineHeading {
+    OutlineHeading {
+        title: TitleSanitizer::new().process(&heading.title).into(),
+        level: heading.level,
+        id: heading.id.map(RoamID::from),
+        children: heading.children.into_iter().map(to_response).collect(),
+    }
+}
+
+/// Nodes linking to and linked from `id`.
+pub async fn get_neighbors(sqlite: &SqlitePool, id: &RoamID) -> anyhow::Result<NeighborsResponse> {
+    let incoming = links::get_incoming(sqlite, id.id()).await?;
+    let outgoing = links::get_outgoing(sqlite, id.id()).await?;
+
+    Ok(NeighborsResponse {
+        incoming: incoming.into_iter().map(to_neighbor).collect(),
+        outgoing: outgoing.into_iter().map(to_neighbor).collect(),
+    })
+}
+
+fn to_neighbor((id, title, file, tags): (String, String, String, Option<String>)) -> Neighbor {
+    Neighbor {
+        id: id.into(),
+        title: TitleSanitizer::new().process(&title).into(),
+        tags: split_tags(tags.as_deref()),
+        file,
+    }
+}
+
+fn split_tags(tags: Option<&str>) -> Vec<String> {
+    let mut tags: Vec<String> = tags
+        .unwrap_or_default()
+        .split(',')
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect();
+    tags.sort();
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_tags() {
+        assert_eq!(split_tags(Some("rust,lang")), vec!["lang", "rust"]);
+        assert!(split_tags(Some("")).is_empty());
+        assert!(split_tags(None).is_empty());
+    }
+}
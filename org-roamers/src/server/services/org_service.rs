@@ -1,55 +1,28 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use orgize::Org;
 
+use crate::server::services::asset_service;
 use crate::server::types::{IncomingLink, OrgAsHTMLResponse, OutgoingLink, RoamID, RoamTitle};
+use crate::sqlite::{aliases, files, olp};
 use crate::transform::html::HtmlExport;
 use crate::transform::subtree::Subtree;
+use crate::transform::title::TitleSanitizer;
 use crate::ServerState;
 
-#[derive(Debug)]
-pub enum Query {
-    ByTitle(RoamTitle),
-    ById(RoamID),
-}
-
 pub async fn get_org_as_html(
     app_state: Arc<ServerState>,
-    query: Query,
+    id: RoamID,
     scope: String,
-) -> OrgAsHTMLResponse {
+) -> Option<OrgAsHTMLResponse> {
     let sqlite = &app_state.sqlite;
 
     // Get data from cache and extract needed values
-    let (id, content, path) = match &query {
-        Query::ByTitle(title) => {
-            let stmnt = r#"
-                SELECT id FROM nodes
-                WHERE title = ?;
-            "#;
-            let (id_str,): (String,) = sqlx::query_as(stmnt)
-                .bind(title.title())
-                .fetch_one(sqlite)
-                .await
-                .unwrap();
-
-            let id: RoamID = id_str.into();
-            let cache_entry = app_state.cache.retrieve(&id).unwrap();
-            (
-                id,
-                cache_entry.content().to_string(),
-                cache_entry.path().to_path_buf(),
-            )
-        }
-        Query::ById(id) => {
-            let cache_entry = app_state.cache.retrieve(&id).unwrap();
-            (
-                id.clone(),
-                cache_entry.content().to_string(),
-                cache_entry.path().to_path_buf(),
-            )
-        }
-    };
+    let cache_entry = app_state.cache.retrieve(&id)?;
+    let content = cache_entry.content().to_string();
+    let path = cache_entry.path().to_path_buf();
 
     let config = &app_state.config;
 
@@ -62,7 +35,29 @@ pub async fn get_org_as_html(
     // Convert absolute path to relative path from org-roam directory
     let relative_file = path.to_string_lossy().into_owned();
 
-    let mut handler = HtmlExport::new(&config.org_to_html, relative_file);
+    let file_nodes: HashMap<PathBuf, String> = files::get_file_nodes(sqlite)
+        .await
+        .unwrap_or_else(|err| {
+            tracing::error!("Failed to fetch file nodes: {err}");
+            vec![]
+        })
+        .into_iter()
+        .map(|(file, id)| (PathBuf::from(file), id))
+        .collect();
+
+    let names = match config.org_to_html.rewrites_titles() {
+        true => aliases::name_map(sqlite).await.unwrap_or_else(|err| {
+            tracing::error!("Failed to fetch node names: {err}");
+            HashMap::new()
+        }),
+        false => HashMap::new(),
+    };
+
+    let asset_version = |path: &Path| asset_service::asset_version(&app_state.cache.resolve(path));
+    let mut handler = HtmlExport::new(&config.org_to_html, relative_file)
+        .with_file_nodes(&file_nodes)
+        .with_names(&names)
+        .with_asset_versions(&asset_version);
     Org::parse(contents).traverse(&mut handler);
 
     let (org, org_outgoing_links, latex_blocks) = handler.finish();
@@ -94,18 +89,6 @@ pub async fn get_org_as_html(
         }
     }
 
-    let final_id: RoamID = match query {
-        Query::ByTitle(title) => {
-            let (id_str,): (String,) = sqlx::query_as("SELECT n.id FROM nodes n WHERE n.title = ?")
-                .bind(title.title())
-                .fetch_one(sqlite)
-                .await
-                .unwrap();
-            RoamID::from(id_str)
-        }
-        Query::ById(id) => id,
-    };
-
     const STMNT: &str = r#"
             SELECT n.id, n.title
             FROM links l
@@ -114,7 +97,7 @@ pub async fn get_org_as_html(
         "#;
 
     let incoming_links = sqlx::query_as::<_, (String, String)>(STMNT)
-        .bind(final_id.id())
+        .bind(id.id())
         .fetch_all(sqlite)
         .await
         .map(|list| {
@@ -133,11 +116,19 @@ pub async fn get_org_as_html(
         .await
         .unwrap();
 
-    OrgAsHTMLResponse {
+    let olp = olp::get_olp(sqlite, id.id())
+        .await
+        .unwrap_or_default()
+        .iter()
+        .map(|segment| TitleSanitizer::new().process(segment))
+        .collect();
+
+    Some(OrgAsHTMLResponse {
         org,
         tags,
         outgoing_links,
         incoming_links,
         latex_blocks,
-    }
+        olp,
+    })
 }
@@ -0,0 +1,256 @@
+//! Structured queries against the index, similar to `org-ql`. A query is a
+//! tree of predicates that is translated into a single SQL `WHERE` clause.
+
+use serde::Deserialize;
+use sqlx::SqlitePool;
+
+use crate::server::types::{QueryMatch, RoamID};
+use crate::transform::title::TitleSanitizer;
+
+/// Upper bound for the number of predicates in a single query.
+pub const MAX_PREDICATES: usize = 64;
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeQuery {
+    And(Vec<NodeQuery>),
+    Or(Vec<NodeQuery>),
+    Not(Box<NodeQuery>),
+    /// Node carries the tag (including inherited tags).
+    Tag(String),
+    /// TODO keyword of the headline, e.g. `"TODO"` or `"DONE"`.
+    Todo(String),
+    Priority(String),
+    /// Case insensitive substring of the title or an alias.
+    Title(String),
+    /// Substring of the file path relative to the root.
+    File(String),
+    Level(i64),
+    /// Property is set, optionally to a specific value.
+    Property {
+        key: String,
+        value: Option<String>,
+    },
+    /// Any active timestamp or datetree date in the range.
+    Date(DateRange),
+    Scheduled(DateRange),
+    Deadline(DateRange),
+}
+
+/// Inclusive range of `YYYY-MM-DD` dates. Missing bounds are open.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct DateRange {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Bind {
+    Text(String),
+    Int(i64),
+}
+
+impl NodeQuery {
+    pub fn num_predicates(&self) -> usize {
+        match self {
+            Self::And(queries) | Self::Or(queries) => {
+                1 + queries.iter().map(Self::num_predicates).sum::<usize>()
+            }
+            Self::Not(query) => 1 + query.num_predicates(),
+            _ => 1,
+        }
+    }
+
+    /// Translate the query into a condition on the `nodes` table aliased as
+    /// `n`. Values are returned as bindings in order of the placeholders.
+    pub fn to_sql(&self, binds: &mut Vec<Bind>) -> String {
+        match self {
+            Self::And(queries) => combine(queries, " AND ", "1", binds),
+            Self::Or(queries) => combine(queries, " OR ", "0", binds),
+            Self::Not(query) => format!("NOT ({})", query.to_sql(binds)),
+            Self::Tag(tag) => {
+                binds.push(Bind::Text(tag.clone()));
+                "n.id IN (SELECT node_id FROM tags WHERE tag = ?)".to_string()
+            }
+            Self::Todo(todo) => {
+                binds.push(Bind::Text(todo.clone()));
+                "n.todo = ?".to_string()
+            }
+            Self::Priority(priority) => {
+                binds.push(Bind::Text(priority.clone()));
+                "n.priority = ?".to_string()
+            }
+            Self::Title(title) => {
+                binds.push(Bind::Text(like_pattern(title)));
+                binds.push(Bind::Text(like_pattern(title)));
+                concat!(
+                    "(n.title LIKE ? ESCAPE '\\' OR EXISTS (SELECT 1 FROM aliases a ",
+                    "WHERE a.node_id = n.id AND a.alias LIKE ? ESCAPE '\\'))"
+                )
+                .to_string()
+            }
+            Self::File(file) => {
+                binds.push(Bind::Text(like_pattern(file)));
+                "n.file LIKE ? ESCAPE '\\'".to_string()
+            }
+            Self::Level(level) => {
+                binds.push(Bind::Int(*level));
+                "n.level = ?".to_string()
+            }
+            Self::Property { key, value } => {
+                binds.push(Bind::Text(key.to_uppercase()));
+                match value {
+                    Some(value) => {
+                        binds.push(Bind::Text(value.clone()));
+                        concat!(
+                            "EXISTS (SELECT 1 FROM json_each(n.properties) p ",
+                            "WHERE p.key = ? AND p.value = ?)"
+                        )
+                        .to_string()
+                    }
+                    None => "EXISTS (SELECT 1 FROM json_each(n.properties) p WHERE p.key = ?)"
+                        .to_string(),
+                }
+            }
+            Self::Date(range) => format!(
+                "n.id IN (SELECT node_id FROM dates WHERE {})",
+                range.to_sql("date", binds)
+            ),
+            Self::Scheduled(range) => range.to_sql("n.scheduled", binds),
+            Self::Deadline(range) => range.to_sql("n.deadline", binds),
+        }
+    }
+}
+
+impl DateRange {
+    fn to_sql(&self, column: &str, binds: &mut Vec<Bind>) -> String {
+        let mut conditions = vec![format!("{column} IS NOT NULL")];
+        if let Some(from) = &self.from {
+            binds.push(Bind::Text(from.clone()));
+            conditions.push(format!("{column} >= ?"));
+        }
+        if let Some(to) = &self.to {
+            binds.push(Bind::Text(to.clone()));
+            conditions.push(format!("{column} <= ?"));
+        }
+        format!("({})", conditions.join(" AND "))
+    }
+}
+
+fn combine(queries: &[NodeQuery], op: &str, empty: &str, binds: &mut Vec<Bind>) -> String {
+    if queries.is_empty() {
+        return empty.to_string();
+    }
+    let parts: Vec<String> = queries
+        .iter()
+        .map(|query| format!("({})", query.to_sql(binds)))
+        .collect();
+    parts.join(op)
+}
+
+fn like_pattern(s: &str) -> String {
+    let escaped = s
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{escaped}%")
+}
+
+pub async fn run_query(
+    sqlite: &SqlitePool,
+    query: &NodeQuery,
+    limit: u32,
+) -> anyhow::Result<Vec<QueryMatch>> {
+    let mut binds = vec![];
+    let condition = query.to_sql(&mut binds);
+    let stmnt = format!(
+        concat!(
+            "SELECT n.id, n.title, n.file, n.todo, n.scheduled, n.deadline\n",
+            "FROM nodes n WHERE {}\n",
+            "ORDER BY n.title, n.id LIMIT ?;"
+        ),
+        condition
+    );
+
+    type Row = (
+        String,
+        String,
+        String,
+        Option<String>,
+        Option<String>,
+        Option<String>,
+    );
+    let mut q = sqlx::query_as::<_, Row>(&stmnt);
+    for bind in binds {
+        q = match bind {
+            Bind::Text(text) => q.bind(text),
+            Bind::Int(int) => q.bind(int),
+        };
+    }
+    let rows = q.bind(limit).fetch_all(sqlite).await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(id, title, file, todo, scheduled, deadline)| QueryMatch {
+            id: RoamID::from(id),
+            title: TitleSanitizer::new().process(&title).into(),
+            file,
+            todo,
+            scheduled,
+            deadline,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_deserialization() {
+        let json = r#"{"and": [{"tag": "work"}, {"not": {"todo": "DONE"}},
+                      {"property": {"key": "type"}}]}"#;
+        let query: NodeQuery = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            query,
+            NodeQuery::And(vec![
+                NodeQuery::Tag("work".to_string()),
+                NodeQuery::Not(Box::new(NodeQuery::Todo("DONE".to_string()))),
+                NodeQuery::Property {
+                    key: "type".to_string(),
+                    value: None
+                },
+            ])
+        );
+        assert_eq!(query.num_predicates(), 5);
+    }
+
+    #[test]
+    fn test_query_to_sql() {
+        let query = NodeQuery::Or(vec![
+            NodeQuery::Title("50%".to_string()),
+            NodeQuery::Scheduled(DateRange {
+                from: Some("2024-01-01".to_string()),
+                to: None,
+            }),
+            NodeQuery::And(vec![]),
+        ]);
+        let mut binds = vec![];
+        assert_eq!(
+            query.to_sql(&mut binds),
+            concat!(
+                "((n.title LIKE ? ESCAPE '\\' OR EXISTS (SELECT 1 FROM aliases a ",
+                "WHERE a.node_id = n.id AND a.alias LIKE ? ESCAPE '\\'))) OR ",
+                "((n.scheduled IS NOT NULL AND n.scheduled >= ?)) OR (1)"
+            )
+        );
+        assert_eq!(
+            binds,
+            vec![
+                Bind::Text("%50\\%%".to_string()),
+                Bind::Text("%50\\%%".to_string()),
+                Bind::Text("2024-01-01".to_string())
+            ]
+        );
+    }
+}
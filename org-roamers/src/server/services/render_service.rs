@@ -0,0 +1,245 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+use crate::server::types::{GraphData, NodeKind, NodePosition, RoamID};
+
+const ITERATIONS: usize = 200;
+/// Preferred distance between linked nodes.
+const SPRING_LENGTH: f64 = 60.0;
+const MARGIN: f64 = 40.0;
+const LABEL_LEN: usize = 30;
+
+/// Deterministic force directed layout (Fruchterman-Reingold). Nodes with a
+/// stored position in `graph.layout` keep it, all others start on a circle
+/// in id order, so the same graph always yields the same picture.
+pub fn layout(graph: &GraphData) -> BTreeMap<RoamID, NodePosition> {
+    let mut ids: Vec<&RoamID> = graph.nodes.iter().map(|node| &node.id).collect();
+    ids.sort();
+    ids.dedup();
+    let n = ids.len();
+    let index: HashMap<&RoamID, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+
+    let radius = SPRING_LENGTH * (n as f64).sqrt();
+    let mut fixed = vec![false; n];
+    let mut pos: Vec<(f64, f64)> = ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| match graph.layout.get(*id) {
+            Some(position) => {
+                fixed[i] = true;
+                (position.x, position.y)
+            }
+            None => {
+                let angle = i as f64 / n as f64 * std::f64::consts::TAU;
+                (radius * angle.cos(), radius * angle.sin())
+            }
+        })
+        .collect();
+
+    let edges: Vec<(usize, usize)> = graph
+        .links
+        .iter()
+        .filter_map(|link| Some((*index.get(&link.from)?, *index.get(&link.to)?)))
+        .filter(|(from, to)| from != to)
+        .collect();
+
+    let k = SPRING_LENGTH;
+    let mut temperature = radius.max(k);
+    let cooling = temperature / ITERATIONS as f64;
+    for _ in 0..ITERATIONS {
+        let mut disp = vec![(0.0, 0.0); n];
+        for i in 0..n {
+            for j in (i + 1)..n {
+                let (dx, dy) = delta(pos[i], pos[j], i, j);
+                let dist = (dx * dx + dy * dy).sqrt();
+                let force = k * k / dist;
+                let (fx, fy) = (dx / dist * force, dy / dist * force);
+                disp[i].0 += fx;
+                disp[i].1 += fy;
+                disp[j].0 -= fx;
+                disp[j].1 -= fy;
+            }
+        }
+        for &(from, to) in &edges {
+            let (dx, dy) = delta(pos[from], pos[to], from, to);
+            let dist = (dx * dx + dy * dy).sqrt();
+            let force = dist * dist / k;
+            let (fx, fy) = (dx / dist * force, dy / dist * force);
+            disp[from].0 -= fx;
+            disp[from].1 -= fy;
+            disp[to].0 += fx;
+            disp[to].1 += fy;
+        }
+        for i in (0..n).filter(|i| !fixed[*i]) {
+            let (dx, dy) = disp[i];
+            let length = (dx * dx + dy * dy).sqrt();
+            if length > 0.0 {
+                let step = length.min(temperature);
+                pos[i].0 += dx / length * step;
+                pos[i].1 += dy / length * step;
+            }
+        }
+        temperature -= cooling;
+    }
+
+    ids.into_iter()
+        .zip(pos)
+        .map(|(id, (x, y))| (id.clone(), NodePosition { x, y }))
+        .collect()
+}
+
+/// Distance vector between two nodes. Nodes on top of each other are pushed
+/// apart in a direction derived from their indices.
+fn delta(a: (f64, f64), b: (f64, f64), i: usize, j: usize) -> (f64, f64) {
+    let (dx, dy) = (a.0 - b.0, a.1 - b.1);
+    if dx == 0.0 && dy == 0.0 {
+        let angle = (i * 31 + j * 17) as f64;
+        (angle.cos() * 0.01, angle.sin() * 0.01)
+    } else {
+        (dx, dy)
+    }
+}
+
+/// Render the graph as a standalone SVG document.
+pub fn render_svg(graph: &GraphData, positions: &BTreeMap<RoamID, NodePosition>) -> String {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (0.0f64, 0.0f64, 0.0f64, 0.0f64);
+    for (i, position) in positions.values().enumerate() {
+        if i == 0 {
+            (min_x, min_y, max_x, max_y) = (position.x, position.y, position.x, position.y);
+        }
+        min_x = min_x.min(position.x);
+        min_y = min_y.min(position.y);
+        max_x = max_x.max(position.x);
+        max_y = max_y.max(position.y);
+    }
+    let (x0, y0) = (min_x - MARGIN, min_y - MARGIN);
+    let (width, height) = (max_x - min_x + 2.0 * MARGIN, max_y - min_y + 2.0 * MARGIN);
+
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{x0:.1} {y0:.1} {width:.1} {height:.1}" width="{width:.0}" height="{height:.0}" font-family="sans-serif" font-size="8">"#
+    );
+    svg.push_str(r##"<g stroke="#999" stroke-opacity="0.6">"##);
+    for link in &graph.links {
+        let (Some(from), Some(to)) = (positions.get(&link.from), positions.get(&link.to)) else {
+            continue;
+        };
+        let _ = write!(
+            svg,
+            r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}"/>"#,
+            from.x, from.y, to.x, to.y
+        );
+    }
+    svg.push_str("</g>");
+
+    let mut nodes: Vec<_> = graph.nodes.iter().collect();
+    nodes.sort_by(|a, b| a.id.cmp(&b.id));
+    for node in nodes {
+        let Some(position) = positions.get(&node.id) else {
+            continue;
+        };
+        let radius = 3.0 + (node.num_links as f64).sqrt();
+        let fill = match node.kind {
+            NodeKind::Tag => "#e69f00",
+            NodeKind::Citation => "#009e73",
+            _ if node.pinned => "#d55e00",
+            _ => "#0072b2",
+        };
+        let _ = write!(
+            svg,
+            r#"<circle cx="{:.1}" cy="{:.1}" r="{radius:.1}" fill="{fill}"/><text x="{:.1}" y="{:.1}">{}</text>"#,
+            position.x,
+            position.y,
+            position.x + radius + 2.0,
+            position.y + 3.0,
+            escape(&label(node.title.title()))
+        );
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+fn label(title: &str) -> String {
+    match title.char_indices().nth(LABEL_LEN) {
+        Some((end, _)) => format!("{}…", &title[..end]),
+        None => title.to_string(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::types::{RoamLink, RoamNode};
+
+    fn node(id: &str) -> RoamNode {
+        RoamNode {
+            title: format!("<{id}>").as_str().into(),
+            id: id.into(),
+            parent: "".into(),
+            num_links: 1,
+            pinned: false,
+            kind: NodeKind::default(),
+            summary: None,
+            group: None,
+        }
+    }
+
+    fn graph() -> GraphData {
+        GraphData {
+            nodes: vec![node("b"), node("a"), node("c")],
+            links: vec![
+                RoamLink {
+                    from: "a".into(),
+                    to: "b".into(),
+                },
+                RoamLink {
+                    from: "b".into(),
+                    to: "c".into(),
+                },
+            ],
+            layout: BTreeMap::from([("c".into(), NodePosition { x: 5.0, y: 5.0 })]),
+            aggregated: false,
+            zoom: None,
+        }
+    }
+
+    #[test]
+    fn test_layout_is_deterministic() {
+        let graph = graph();
+        let positions = layout(&graph);
+        assert_eq!(positions, layout(&graph));
+        assert_eq!(positions.len(), 3);
+        assert_eq!(
+            positions[&RoamID::from("c")],
+            NodePosition { x: 5.0, y: 5.0 }
+        );
+        assert!(positions
+            .values()
+            .all(|p| p.x.is_finite() && p.y.is_finite()));
+    }
+
+    #[test]
+    fn test_render_svg() {
+        let graph = graph();
+        let svg = render_svg(&graph, &layout(&graph));
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>"));
+        assert_eq!(svg.matches("<line ").count(), 2);
+        assert_eq!(svg.matches("<circle ").count(), 3);
+        assert!(svg.contains("&lt;a&gt;"));
+    }
+
+    #[test]
+    fn test_label() {
+        assert_eq!(label("short"), "short");
+        assert_eq!(label(&"é".repeat(40)), format!("{}…", "é".repeat(30)));
+    }
+}
@@ -0,0 +1,177 @@
+use sqlx::SqlitePool;
+
+use crate::server::types::{ReviewItem, ReviewQueue};
+use crate::sqlite::review::{self, ReviewRow};
+
+/// Ease of a node that was never reviewed.
+const INITIAL_EASE: f64 = 2.5;
+/// SM-2 never lets the ease drop below this.
+const MIN_EASE: f64 = 1.3;
+const DAY: i64 = 24 * 60 * 60;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReviewError {
+    #[error("Grade {0} is not between 0 and 5")]
+    InvalidGrade(u8),
+    #[error("Node {0} does not exist")]
+    NotFound(String),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// Scheduling state of a node, see [`sm2`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Schedule {
+    /// Days until the next review
+    pub interval: i64,
+    pub ease: f64,
+    /// Successful reviews in a row
+    pub repetitions: i64,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self {
+            interval: 0,
+            ease: INITIAL_EASE,
+            repetitions: 0,
+        }
+    }
+}
+
+/// Schedule after a review with `grade` from 0 (blackout) to 5 (perfect),
+/// following SuperMemo 2. Grades below 3 start the repetitions over.
+pub fn sm2(schedule: Schedule, grade: u8) -> Schedule {
+    let q = f64::from(5 - grade.min(5));
+    let ease = (schedule.ease + 0.1 - q * (0.08 + q * 0.02)).max(MIN_EASE);
+    if grade < 3 {
+        return Schedule {
+            interval: 1,
+            ease,
+            repetitions: 0,
+        };
+    }
+    let interval = match schedule.repetitions {
+        0 => 1,
+        1 => 6,
+        _ => (schedule.interval as f64 * schedule.ease).round() as i64,
+    };
+    Schedule {
+        interval,
+        ease,
+        repetitions: schedule.repetitions + 1,
+    }
+}
+
+/// Due nodes carrying one of `tags`. Only the first `limit` are returned.
+pub async fn next(
+    sqlite: &SqlitePool,
+    user: &str,
+    tags: &[String],
+    now: i64,
+    limit: usize,
+) -> anyhow::Result<ReviewQueue> {
+    let due = review::get_due(sqlite, user, tags, now).await?;
+    Ok(ReviewQueue {
+        due: due.len(),
+        nodes: due
+            .into_iter()
+            .take(limit)
+            .map(|(id, title, row)| item(id, title, row))
+            .collect(),
+    })
+}
+
+/// Record a review of `id` by `user` and schedule the next one.
+pub async fn grade(
+    sqlite: &SqlitePool,
+    user: &str,
+    id: &str,
+    grade: u8,
+    now: i64,
+) -> Result<ReviewItem, ReviewError> {
+    if grade > 5 {
+        return Err(ReviewError::InvalidGrade(grade));
+    }
+    let title: Option<String> = sqlx::query_scalar("SELECT title FROM nodes WHERE id = ?;")
+        .bind(id)
+        .fetch_optional(sqlite)
+        .await
+        .map_err(anyhow::Error::from)?;
+    let title = title.ok_or_else(|| ReviewError::NotFound(id.to_string()))?;
+
+    let schedule = match review::get_review(sqlite, user, id).await? {
+        Some((interval, ease, repetitions, _, _)) => Schedule {
+            interval,
+            ease,
+            repetitions,
+        },
+        None => Schedule::default(),
+    };
+    let next = sm2(schedule, grade);
+    let row = (
+        next.interval,
+        next.ease,
+        next.repetitions,
+        now,
+        now + next.interval * DAY,
+    );
+    review::upsert_review(sqlite, user, id, row).await?;
+    Ok(item(id.to_string(), title, Some(row)))
+}
+
+fn item(id: String, title: String, row: Option<ReviewRow>) -> ReviewItem {
+    let (interval, ease, repetitions, last_review, due) = match row {
+        Some((interval, ease, repetitions, last_review, due)) => {
+            (interval, ease, repetitions, Some(last_review), Some(due))
+        }
+        None => (0, INITIAL_EASE, 0, None, None),
+    };
+    ReviewItem {
+        id: id.into(),
+        title: title.into(),
+        interval,
+        ease,
+        repetitions,
+        last_review,
+        due,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sm2() {
+        let first = sm2(Schedule::default(), 4);
+        assert_eq!(
+            first,
+            Schedule {
+                interval: 1,
+                ease: 2.5,
+                repetitions: 1
+            }
+        );
+        let second = sm2(first, 5);
+        assert_eq!((second.interval, second.repetitions), (6, 2));
+        assert!((second.ease - 2.6).abs() < 1e-9);
+        let third = sm2(second, 3);
+        assert_eq!((third.interval, third.repetitions), (16, 3));
+        assert!((third.ease - 2.46).abs() < 1e-9);
+
+        let failed = sm2(third, 1);
+        assert_eq!((failed.interval, failed.repetitions), (1, 0));
+        assert!(
+            sm2(
+                Schedule {
+                    ease: 1.3,
+                    ..failed
+                },
+                0
+            )
+            .ease
+                >= MIN_EASE
+        );
+    }
+}
@@ -0,0 +1,279 @@
+//! Nodes related to a node without necessarily linking to it. Candidates are
+//! scored by the tags they share with the node, the nodes both are linked
+//! with and the similarity of their text.
+
+use std::collections::{BTreeSet, HashMap};
+
+use rust_stemmers::{Algorithm, Stemmer};
+use sqlx::SqlitePool;
+
+use crate::cache::OrgCache;
+use crate::search::stemming::words;
+use crate::server::types::{RoamID, SimilarNode, SimilarResponse};
+use crate::transform::{subtree::Subtree, title::TitleSanitizer};
+
+const TAG_WEIGHT: f64 = 0.3;
+const LINK_WEIGHT: f64 = 0.3;
+const TEXT_WEIGHT: f64 = 0.4;
+
+/// Words shorter than this are ignored when comparing text.
+const MIN_WORD_LEN: usize = 3;
+
+/// Tags and links of every node, loaded by [`load`] and ranked by [`rank`].
+pub struct Relations {
+    id: RoamID,
+    file: String,
+    level: i64,
+    /// `(id, title, file)` of every node
+    nodes: Vec<(String, String, String)>,
+    tags: HashMap<String, BTreeSet<String>>,
+    /// Nodes linking to or linked from a node
+    neighbors: HashMap<String, BTreeSet<String>>,
+}
+
+/// Load what is needed to rank the nodes similar to `id`. Returns `None` if
+/// the node does not exist.
+pub async fn load(sqlite: &SqlitePool, id: &RoamID) -> anyhow::Result<Option<Relations>> {
+    const NODE: &str = "SELECT file, level FROM nodes WHERE id = ?;";
+    const NODES: &str = "SELECT id, title, file FROM nodes ORDER BY id;";
+    const TAGS: &str = "SELECT DISTINCT node_id, tag FROM tags;";
+    const LINKS: &str = concat!(
+        "SELECT DISTINCT source, dest FROM links\n",
+        "WHERE type = 'id' AND source != dest;"
+    );
+
+    let node: Option<(String, i64)> = sqlx::query_as(NODE)
+        .bind(id.id())
+        .fetch_optional(sqlite)
+        .await?;
+    let Some((file, level)) = node else {
+        return Ok(None);
+    };
+
+    let nodes: Vec<(String, String, String)> = sqlx::query_as(NODES).fetch_all(sqlite).await?;
+    let mut tags: HashMap<String, BTreeSet<String>> = HashMap::new();
+    for (node, tag) in sqlx::query_as::<_, (String, String)>(TAGS)
+        .fetch_all(sqlite)
+        .await?
+    {
+        tags.entry(node).or_default().insert(tag);
+    }
+    let mut neighbors: HashMap<String, BTreeSet<String>> = HashMap::new();
+    for (source, dest) in sqlx::query_as::<_, (String, String)>(LINKS)
+        .fetch_all(sqlite)
+        .await?
+    {
+        neighbors
+            .entry(source.clone())
+            .or_default()
+            .insert(dest.clone());
+        neighbors.entry(dest).or_default().insert(source);
+    }
+
+    Ok(Some(Relations {
+        id: id.clone(),
+        file,
+        level,
+        nodes,
+        tags,
+        neighbors,
+    }))
+}
+
+/// The `limit` nodes most similar to the node of `relations`, best first.
+/// Nodes of the same file are left out, they are related anyway. Headlines
+/// are compared by the text of their whole file.
+pub fn rank(
+    cache: &OrgCache,
+    relations: &Relations,
+    stemming: Option<Algorithm>,
+    limit: usize,
+) -> SimilarResponse {
+    let stemmer = stemming.map(Stemmer::create);
+    let empty = BTreeSet::new();
+    let id = relations.id.id();
+    let own_tags = relations.tags.get(id).unwrap_or(&empty);
+    let own_neighbors = relations.neighbors.get(id).unwrap_or(&empty);
+
+    // Term counts of every file, the first node of a file gives its content.
+    let mut counts: HashMap<&str, HashMap<String, usize>> = HashMap::new();
+    let mut own_text = None;
+    for (node, _, file) in &relations.nodes {
+        if counts.contains_key(file.as_str()) {
+            continue;
+        }
+        let Some(entry) = cache.retrieve(&node.as_str().into()) else {
+            continue;
+        };
+        if *file == relations.file {
+            own_text = Some(match relations.level {
+                0 => entry.content().to_string(),
+                _ => Subtree::get(relations.id.clone(), entry.content())
+                    .unwrap_or_else(|| entry.content().to_string()),
+            });
+        }
+        counts.insert(file, term_counts(entry.content(), stemmer.as_ref()));
+    }
+    let documents = counts.len();
+    let frequencies = document_frequencies(counts.values());
+    let own_vector = own_text
+        .map(|text| {
+            vector(
+                &term_counts(&text, stemmer.as_ref()),
+                &frequencies,
+                documents,
+            )
+        })
+        .unwrap_or_default();
+    let vectors: HashMap<&str, Vector> = counts
+        .iter()
+        .map(|(file, counts)| (*file, vector(counts, &frequencies, documents)))
+        .collect();
+
+    let mut nodes: Vec<SimilarNode> = relations
+        .nodes
+        .iter()
+        .filter(|(_, _, file)| *file != relations.file)
+        .filter_map(|(node, title, file)| {
+            let tags = relations.tags.get(node).unwrap_or(&empty);
+            let neighbors = relations.neighbors.get(node).unwrap_or(&empty);
+            let shared_tags: Vec<String> = own_tags.intersection(tags).cloned().collect();
+            let shared_links: Vec<RoamID> = own_neighbors
+                .intersection(neighbors)
+                .map(|shared| shared.as_str().into())
+                .collect();
+            let tag_score = jaccard(shared_tags.len(), own_tags.len(), tags.len());
+            // Do not count the direct link between both as shared.
+            let linked = own_neighbors.contains(node);
+            let link_score = jaccard(
+                shared_links.len(),
+                own_neighbors.len() - usize::from(linked),
+                neighbors.len() - usize::from(linked),
+            );
+            let text_score = vectors
+                .get(file.as_str())
+                .map_or(0.0, |vector| cosine(&own_vector, vector));
+            let score =
+                TAG_WEIGHT * tag_score + LINK_WEIGHT * link_score + TEXT_WEIGHT * text_score;
+            (score > 0.0).then(|| SimilarNode {
+                id: node.as_str().into(),
+                title: TitleSanitizer::new().process(title).into(),
+                score,
+                shared_tags,
+                shared_links,
+                text_score,
+                linked,
+            })
+        })
+        .collect();
+    nodes.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.id.cmp(&b.id)));
+    nodes.truncate(limit);
+
+    SimilarResponse {
+        id: relations.id.clone(),
+        nodes,
+    }
+}
+
+/// Term weights of a text, scaled to length 1.
+type Vector = HashMap<String, f64>;
+
+fn term_counts(text: &str, stemmer: Option<&Stemmer>) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for word in words(text).filter(|word| word.chars().count() >= MIN_WORD_LEN) {
+        let term = match stemmer {
+            Some(stemmer) => stemmer.stem(&word).to_string(),
+            None => word,
+        };
+        *counts.entry(term).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Number of documents every term appears in.
+fn document_frequencies<'a>(
+    documents: impl Iterator<Item = &'a HashMap<String, usize>>,
+) -> HashMap<&'a str, usize> {
+    let mut frequencies = HashMap::new();
+    for counts in documents {
+        for term in counts.keys() {
+            *frequencies.entry(term.as_str()).or_insert(0) += 1;
+        }
+    }
+    frequencies
+}
+
+/// TF-IDF weights of `counts`. Terms that appear in most documents, like
+/// the `PROPERTIES` drawer, get a weight close to zero.
+fn vector(
+    counts: &HashMap<String, usize>,
+    frequencies: &HashMap<&str, usize>,
+    documents: usize,
+) -> Vector {
+    let mut vector: Vector = counts
+        .iter()
+        .map(|(term, count)| {
+            let frequency = frequencies.get(term.as_str()).copied().unwrap_or(0);
+            let idf = ((documents + 1) as f64 / (frequency + 1) as f64).ln();
+            (term.clone(), (1.0 + (*count as f64).ln()) * idf)
+        })
+        .filter(|(_, weight)| *weight > 0.0)
+        .collect();
+    let norm = vector
+        .values()
+        .map(|weight| weight * weight)
+        .sum::<f64>()
+        .sqrt();
+    if norm > 0.0 {
+        vector.values_mut().for_each(|weight| *weight /= norm);
+    }
+    vector
+}
+
+fn cosine(a: &Vector, b: &Vector) -> f64 {
+    let (small, large) = if a.len() < b.len() { (a, b) } else { (b, a) };
+    small
+        .iter()
+        .filter_map(|(term, weight)| Some(weight * large.get(term)?))
+        .sum()
+}
+
+fn jaccard(shared: usize, a: usize, b: usize) -> f64 {
+    match a + b - shared {
+        0 => 0.0,
+        union => shared as f64 / union as f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_similarity() {
+        let documents = [
+            "The borrow checker of Rust rejects dangling references.",
+            "References in Rust are checked by the borrow checker.",
+            "Cook the pasta in salted water.",
+        ]
+        .map(|text| term_counts(text, Some(&Stemmer::create(Algorithm::English))));
+        let frequencies = document_frequencies(documents.iter());
+        let vectors: Vec<Vector> = documents
+            .iter()
+            .map(|counts| vector(counts, &frequencies, documents.len()))
+            .collect();
+
+        let related = cosine(&vectors[0], &vectors[1]);
+        let unrelated = cosine(&vectors[0], &vectors[2]);
+        assert!(related > 0.2, "{related}");
+        assert_eq!(unrelated, 0.0);
+        assert!((cosine(&vectors[0], &vectors[0]) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_jaccard() {
+        assert_eq!(jaccard(0, 0, 0), 0.0);
+        assert_eq!(jaccard(1, 2, 2), 1.0 / 3.0);
+        assert_eq!(jaccard(2, 2, 2), 1.0);
+    }
+}
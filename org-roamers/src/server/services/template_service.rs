@@ -0,0 +1,134 @@
+use std::fs;
+use std::path::Path;
+
+/// Directory below the root holding the templates of new files. It mirrors
+/// the directories of the vault.
+pub const TEMPLATE_DIR: &str = ".templates";
+/// Template of a directory within [`TEMPLATE_DIR`], e.g.
+/// `.templates/daily/template.org` for files in `daily/`.
+const TEMPLATE_FILE: &str = "template.org";
+
+/// Front matter and body that are added to files created by the server.
+#[derive(Debug, Default, PartialEq)]
+pub struct FileTemplate {
+    /// Properties of the file level property drawer, without the `ID`.
+    pub properties: Vec<(String, String)>,
+    /// Keywords except for the title and tags, e.g. `("startup", "overview")`.
+    pub keywords: Vec<(String, String)>,
+    /// Tags of `#+filetags`.
+    pub tags: Vec<String>,
+    pub body: String,
+}
+
+impl FileTemplate {
+    /// Template for a new file at `file` relative to `root`: the template of
+    /// its directory or of the closest parent directory that has one.
+    pub fn find(root: &Path, file: &Path) -> Option<FileTemplate> {
+        let templates = root.join(TEMPLATE_DIR);
+        let mut dir = file.parent();
+        while let Some(current) = dir {
+            if let Ok(content) = fs::read_to_string(templates.join(current).join(TEMPLATE_FILE)) {
+                return Some(FileTemplate::parse(&content));
+            }
+            dir = current.parent();
+        }
+        None
+    }
+
+    pub fn parse(content: &str) -> FileTemplate {
+        let mut template = FileTemplate::default();
+        let mut in_drawer = false;
+        let mut lines = content.lines().peekable();
+        while let Some(line) = lines.peek() {
+            let trimmed = line.trim();
+            if in_drawer {
+                if trimmed.eq_ignore_ascii_case(":END:") {
+                    in_drawer = false;
+                } else if let Some((key, value)) = property(trimmed) {
+                    if !key.eq_ignore_ascii_case("ID") {
+                        template
+                            .properties
+                            .push((key.to_uppercase(), value.to_string()));
+                    }
+                }
+            } else if trimmed.eq_ignore_ascii_case(":PROPERTIES:") {
+                in_drawer = true;
+            } else if let Some((key, value)) = keyword(trimmed) {
+                match key.to_lowercase().as_str() {
+                    "title" => {}
+                    "filetags" => template.tags.extend(
+                        value
+                            .split(':')
+                            .filter(|tag| !tag.trim().is_empty())
+                            .map(ToString::to_string),
+                    ),
+                    key => template.keywords.push((key.to_string(), value.to_string())),
+                }
+            } else if !trimmed.is_empty() {
+                break;
+            }
+            lines.next();
+        }
+        template.body = lines.collect::<Vec<_>>().join("\n").trim_end().to_string();
+        template
+    }
+}
+
+fn keyword(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.strip_prefix("#+")?.split_once(':')?;
+    Some((key, value.trim()))
+}
+
+fn property(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.strip_prefix(':')?.split_once(':')?;
+    (!key.is_empty()).then(|| (key, value.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse() {
+        let content = concat!(
+            ":PROPERTIES:\n",
+            ":ID: ignored\n",
+            ":category: journal\n",
+            ":END:\n",
+            "#+title: ignored\n",
+            "#+filetags: :daily:\n",
+            "#+startup: overview\n",
+            "\n",
+            "* Tasks\n",
+            "* Notes\n\n",
+        );
+        assert_eq!(
+            FileTemplate::parse(content),
+            FileTemplate {
+                properties: vec![("CATEGORY".into(), "journal".into())],
+                keywords: vec![("startup".into(), "overview".into())],
+                tags: vec!["daily".into()],
+                body: "* Tasks\n* Notes".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_find() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join(".templates/daily")).unwrap();
+        fs::write(root.join(".templates/template.org"), "#+filetags: :root:").unwrap();
+        fs::write(
+            root.join(".templates/daily/template.org"),
+            "#+filetags: :daily:",
+        )
+        .unwrap();
+
+        let tags = |file: &str| FileTemplate::find(root, Path::new(file)).unwrap().tags;
+        assert_eq!(tags("daily/2024/a.org"), vec!["daily"]);
+        assert_eq!(tags("work/a.org"), vec!["root"]);
+        assert_eq!(tags("a.org"), vec!["root"]);
+    }
+}
@@ -0,0 +1,161 @@
+use std::path::Path;
+
+use sqlx::SqlitePool;
+use time::OffsetDateTime;
+use tokio::process::Command;
+
+use crate::cache::OrgCache;
+use crate::server::types::{
+    OutgoingLink, RoamID, TimelineEvent, TimelineEventKind, TimelineResponse,
+};
+use crate::transform::title::TitleSanitizer;
+
+/// Assemble the timeline of node `id`. Returns `None` if the node does not
+/// exist. The root of the node's file in `cache` is used to ask git for its
+/// commits.
+pub async fn get_timeline(
+    sqlite: &SqlitePool,
+    cache: &OrgCache,
+    id: &RoamID,
+) -> anyhow::Result<Option<TimelineResponse>> {
+    const NODE: &str = concat!(
+        "SELECT n.title, n.file, f.created, f.mtime FROM nodes n\n",
+        "LEFT JOIN files f ON f.file = n.file\n",
+        "WHERE n.id = ?;"
+    );
+    const DATES: &str = "SELECT DISTINCT date FROM dates WHERE node_id = ?;";
+    const BACKLINKS: &str = concat!(
+        "SELECT DISTINCT n.id, n.title, f.created FROM links l\n",
+        "JOIN nodes n ON n.id = l.source\n",
+        "LEFT JOIN files f ON f.file = n.file\n",
+        "WHERE l.type = 'id' AND l.dest = ? AND l.source != l.dest;"
+    );
+
+    let node: Option<(String, String, Option<i64>, Option<i64>)> = sqlx::query_as(NODE)
+        .bind(id.id())
+        .fetch_optional(sqlite)
+        .await?;
+    let Some((title, file, created, mtime)) = node else {
+        return Ok(None);
+    };
+    let dates: Vec<(String,)> = sqlx::query_as(DATES)
+        .bind(id.id())
+        .fetch_all(sqlite)
+        .await?;
+    let backlinks: Vec<(String, String, Option<i64>)> = sqlx::query_as(BACKLINKS)
+        .bind(id.id())
+        .fetch_all(sqlite)
+        .await?;
+
+    let mut events = vec![];
+    events.extend(created.and_then(|time| event(time, TimelineEventKind::Created, None)));
+    let (root, path) = cache.locate(Path::new(&file));
+    let modifications = match git_commits(root, path).await {
+        Some(commits) => commits,
+        None => mtime.into_iter().collect(),
+    };
+    events.extend(
+        modifications
+            .into_iter()
+            .filter_map(|time| event(time, TimelineEventKind::Modified, None)),
+    );
+    events.extend(dates.into_iter().map(|(date,)| TimelineEvent {
+        date,
+        time: None,
+        kind: TimelineEventKind::Timestamp,
+        source: None,
+    }));
+    events.extend(backlinks.into_iter().filter_map(|(id, title, created)| {
+        let source = OutgoingLink {
+            display: TitleSanitizer::new().process(&title).into(),
+            id: id.into(),
+        };
+        event(created?, TimelineEventKind::Backlink, Some(source))
+    }));
+    sort_events(&mut events);
+
+    Ok(Some(TimelineResponse {
+        id: id.clone(),
+        title: TitleSanitizer::new().process(&title).into(),
+        events,
+    }))
+}
+
+fn event(
+    time: i64,
+    kind: TimelineEventKind,
+    source: Option<OutgoingLink>,
+) -> Option<TimelineEvent> {
+    let date = OffsetDateTime::from_unix_timestamp(time).ok()?.date();
+    Some(TimelineEvent {
+        date: format!(
+            "{:04}-{:02}-{:02}",
+            date.year(),
+            date.month() as u8,
+            date.day()
+        ),
+        time: Some(time),
+        kind,
+        source,
+    })
+}
+
+/// Order by date. Events of the same day are ordered by time, events
+/// without a time go first.
+fn sort_events(events: &mut [TimelineEvent]) {
+    events.sort_by(|a, b| (&a.date, a.time, a.kind).cmp(&(&b.date, b.time, b.kind)));
+}
+
+/// Commit times of `file`, newest first. `None` if `root` is not a git
+/// repository or git is not installed.
+async fn git_commits(root: &Path, file: &Path) -> Option<Vec<i64>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(["log", "--follow", "--format=%ct", "--"])
+        .arg(file)
+        .output()
+        .await
+        .inspect_err(|err| tracing::debug!("Failed to run git: {err}"))
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let commits: Vec<i64> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.trim().parse().ok())
+        .collect();
+    // Files that were never committed fall back to the mtime.
+    (!commits.is_empty()).then_some(commits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort_events() {
+        let mut events = vec![
+            event(86400 + 60, TimelineEventKind::Modified, None).unwrap(),
+            TimelineEvent {
+                date: "1970-01-02".to_string(),
+                time: None,
+                kind: TimelineEventKind::Timestamp,
+                source: None,
+            },
+            event(0, TimelineEventKind::Created, None).unwrap(),
+        ];
+        sort_events(&mut events);
+
+        let kinds: Vec<_> = events.iter().map(|event| event.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TimelineEventKind::Created,
+                TimelineEventKind::Timestamp,
+                TimelineEventKind::Modified
+            ]
+        );
+        assert_eq!(events[0].date, "1970-01-01");
+    }
+}
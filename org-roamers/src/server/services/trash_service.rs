@@ -0,0 +1,130 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use time::OffsetDateTime;
+
+use crate::{cache::OrgCache, sqlite::trash, watcher, ServerState};
+
+/// Location of a deleted file in the trash, relative to the root. The
+/// deletion time is appended so the same file can be deleted repeatedly, and
+/// so the trashed file is no longer picked up as an org file.
+pub fn trash_path(trash_dir: &Path, file: &Path, deleted_at: i64) -> PathBuf {
+    let mut name = file.as_os_str().to_os_string();
+    name.push(format!(".{deleted_at}"));
+    trash_dir.join(name)
+}
+
+/// Move `file` to `trash_path`. Both are relative to the root of `cache`.
+pub fn move_to_trash(cache: &OrgCache, file: &Path, trash_path: &Path) -> io::Result<()> {
+    move_file(&cache.resolve(file), &cache.resolve(trash_path))
+}
+
+/// Move a trashed file back to `file`. Fails if `file` was recreated in the
+/// meantime.
+pub fn restore(cache: &OrgCache, trash_path: &Path, file: &Path) -> io::Result<()> {
+    let target = cache.resolve(file);
+    if target.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", file.display()),
+        ));
+    }
+    move_file(&cache.resolve(trash_path), &target)
+}
+
+/// Move `file` to the trash and drop it from the database and cache, which
+/// broadcasts the removed nodes. Its nodes are recorded first, so it is
+/// never lost without a trace.
+pub async fn trash_file(state: &ServerState, file: &str, user: Option<&str>) -> anyhow::Result<()> {
+    let sqlite = &state.sqlite;
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    let trash_path = trash_path(&state.config.trash.dir, Path::new(file), now);
+    let target = trash_path.to_string_lossy();
+
+    trash::insert_deleted_file(sqlite, file, &target, now, user).await?;
+    if let Err(err) = move_to_trash(&state.cache, Path::new(file), &trash_path) {
+        if let Err(err) = trash::delete_trash_entry(sqlite, &target).await {
+            tracing::error!("Failed to remove trash entry {target}: {err}");
+        }
+        return Err(err.into());
+    }
+
+    if let Err(err) = watcher::remove_file(state, Path::new(file)).await {
+        tracing::error!("Failed to remove {file} from the index: {err}");
+    }
+    Ok(())
+}
+
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(from, to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_trash_path() {
+        assert_eq!(
+            trash_path(Path::new(".trash"), Path::new("daily/a.org"), 1700000000),
+            PathBuf::from(".trash/daily/a.org.1700000000")
+        );
+    }
+
+    #[test]
+    fn test_trash_and_restore() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("dir")).unwrap();
+        fs::write(root.join("dir/a.org"), "* a").unwrap();
+        let cache = OrgCache::new(root.to_path_buf());
+
+        let trashed = trash_path(Path::new(".trash"), Path::new("dir/a.org"), 1);
+        move_to_trash(&cache, Path::new("dir/a.org"), &trashed).unwrap();
+        assert!(!root.join("dir/a.org").exists());
+        assert!(root.join(&trashed).exists());
+
+        restore(&cache, &trashed, Path::new("dir/a.org")).unwrap();
+        assert_eq!(fs::read_to_string(root.join("dir/a.org")).unwrap(), "* a");
+        assert!(restore(&cache, &trashed, Path::new("dir/a.org")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_trash_file_broadcasts_removed_nodes() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("notes");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join("a.org"),
+            ":PROPERTIES:\n:ID: a\n:END:\n#+title: A\n",
+        )
+        .unwrap();
+
+        let config = crate::config::Config {
+            org_roamers_root: root.clone(),
+            database: crate::config::DatabaseConfig {
+                path: Some(temp_dir.path().join("roam.db")),
+            },
+            ..Default::default()
+        };
+        let state = ServerState::new(config).await.unwrap();
+        let id = crate::types::RoamID::from("a");
+
+        trash_file(&state, "a.org", None).await.unwrap();
+
+        assert!(!root.join("a.org").exists());
+        assert!(state.cache.retrieve(&id).is_none());
+        let broadcasts = state.pending_broadcasts.lock().unwrap();
+        assert!(matches!(
+            broadcasts.as_slice(),
+            [crate::client::message::WebSocketMessage::GraphUpdate(update)]
+                if update.removed_nodes == vec![id.clone()]
+        ));
+    }
+}
@@ -1,9 +1,21 @@
-use axum::response::{IntoResponse, Json, Response};
+use std::collections::BTreeMap;
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
 use serde::{Deserialize, Serialize};
 
+use crate::client::{topic::Topic, ConnectionKind};
 use crate::transform::node_builder::OrgNode;
+use crate::watcher::WatcherStatus;
 
+/// Id of a node. Ids are normalized on construction: surrounding
+/// whitespace and quotes are removed and UUIDs are lowercased, so ids
+/// spelled differently in files and requests find the same node.
+/// Deserializing rejects ids that [`RoamID::parse`] rejects.
 #[derive(PartialEq, Clone, Debug, Serialize, Deserialize, Hash, Eq, PartialOrd, Ord)]
+#[serde(try_from = "String")]
 pub struct RoamID(String);
 
 impl RoamID {
@@ -15,19 +27,71 @@ impl RoamID {
         let quotes = "\"".repeat(num);
         format!("{}{}{}", quotes, self.0, quotes)
     }
+
+    /// Normalized form of `value`, see [`RoamID`].
+    pub fn normalize(value: &str) -> String {
+        let value = value.trim();
+        let value = value.strip_prefix('"').unwrap_or(value);
+        let value = value.split('"').next().unwrap_or_default().trim();
+        match is_uuid(value) {
+            true => value.to_ascii_lowercase(),
+            false => value.to_string(),
+        }
+    }
+
+    /// Normalize `value` and reject ids that cannot appear in an org
+    /// property or an `id:` link: empty ids and ids containing whitespace,
+    /// quotes, brackets or control characters.
+    pub fn parse(value: &str) -> Result<Self, InvalidRoamID> {
+        let trimmed = value.trim();
+        let inner = trimmed
+            .strip_prefix('"')
+            .and_then(|inner| inner.strip_suffix('"'))
+            .unwrap_or(trimmed)
+            .trim();
+        let malformed = inner
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || matches!(c, '"' | '[' | ']'));
+        match inner.is_empty() || malformed {
+            true => Err(InvalidRoamID(value.to_string())),
+            false => Ok(Self::from(inner)),
+        }
+    }
+}
+
+fn is_uuid(value: &str) -> bool {
+    value.len() == 36
+        && value.char_indices().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
+/// Error of [`RoamID::parse`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidRoamID(String);
+
+impl std::fmt::Display for InvalidRoamID {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Malformed node id: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidRoamID {}
+
+impl TryFrom<String> for RoamID {
+    type Error = InvalidRoamID;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::parse(&value)
+    }
 }
 
+/// For ids read from the index or from files. Ids of requests are checked
+/// with [`RoamID::parse`] instead.
 impl From<&str> for RoamID {
     fn from(value: &str) -> Self {
-        let mut iter = value.chars();
-        let mut id = String::new();
-        if let Some(c) = iter.next() {
-            if c != '"' {
-                id.push(c);
-            }
-        }
-        id.push_str(iter.take_while(|c| *c != '"').collect::<String>().as_str());
-        Self(id)
+        Self(Self::normalize(value))
     }
 }
 
@@ -74,6 +138,40 @@ pub struct RoamNode {
     pub id: RoamID,
     pub parent: RoamID,
     pub num_links: usize,
+    /// Pinned nodes should always be kept visible by the client.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    #[serde(default, skip_serializing_if = "NodeKind::is_node")]
+    pub kind: NodeKind,
+    /// First sentence of the node, only sent if requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    /// Top level directory of the file relative to the vault root, only sent
+    /// if requested. Files in the root have no group.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+}
+
+/// What a graph node represents. Tag and citation nodes can not be opened.
+#[derive(PartialEq, Clone, Copy, Debug, Default, Serialize, Deserialize, PartialOrd, Ord, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeKind {
+    /// An org-roam node.
+    #[default]
+    Node,
+    /// A tag that is linked to all nodes carrying it.
+    Tag,
+    /// Several nodes of a file merged into one. It carries the id of the top
+    /// most node of the file and can be expanded with `/graph/cluster/{id}`.
+    Cluster,
+    /// A cited work with the id `cite:<key>`, linked to all nodes citing it.
+    Citation,
+}
+
+impl NodeKind {
+    pub fn is_node(&self) -> bool {
+        *self == NodeKind::Node
+    }
 }
 
 impl From<OrgNode> for RoamNode {
@@ -86,6 +184,10 @@ impl From<OrgNode> for RoamNode {
                 .map(Into::into)
                 .unwrap_or(RoamID("".to_string())),
             num_links: value.links.len(),
+            pinned: false,
+            kind: NodeKind::Node,
+            summary: None,
+            group: None,
         }
     }
 }
@@ -119,14 +221,83 @@ impl From<OrgNode> for RoamNode {
 pub struct GraphData {
     pub nodes: Vec<RoamNode>,
     pub links: Vec<RoamLink>,
+    /// Manually arranged node positions. Nodes without an entry are placed by
+    /// the client.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub layout: BTreeMap<RoamID, NodePosition>,
+    /// Set if nodes were merged into clusters, either on request or because
+    /// the graph exceeded `graph_lod_threshold`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub aggregated: bool,
+    /// Zoom of the saved view the graph was requested with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zoom: Option<f64>,
 }
 
-impl IntoResponse for GraphData {
+impl GraphData {
+    /// Order nodes by id and links by their ends, so equal graphs serialize
+    /// to the same JSON.
+    pub fn sort(&mut self) {
+        self.nodes.sort_by(|a, b| a.id.cmp(&b.id));
+        self.links.sort();
+    }
+}
+
+/// Node of [`LiteGraph`].
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct LiteNode {
+    pub id: RoamID,
+    /// Title truncated to [`LITE_TITLE_LEN`] characters.
+    pub title: String,
+    /// Number of links from and to the node.
+    pub degree: usize,
+}
+
+/// Maximum title length in [`LiteGraph`].
+pub const LITE_TITLE_LEN: usize = 40;
+
+/// Compact graph for mobile clients and widgets. Links are `[from, to]`
+/// pairs.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize, Default)]
+pub struct LiteGraph {
+    pub nodes: Vec<LiteNode>,
+    pub links: Vec<(RoamID, RoamID)>,
+}
+
+impl IntoResponse for LiteGraph {
     fn into_response(self) -> Response {
         Json(self).into_response()
     }
 }
 
+/// Communities of the graph, see [`crate::graph::community::louvain`].
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct GraphClusters {
+    /// Number of communities
+    pub count: usize,
+    /// Community of every node, numbered from `0` by decreasing size
+    pub clusters: BTreeMap<RoamID, usize>,
+}
+
+impl IntoResponse for GraphClusters {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct NodePosition {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl IntoResponse for GraphData {
+    fn into_response(mut self) -> Response {
+        self.sort();
+        Json(self).into_response()
+    }
+}
+
 #[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct OutgoingLink {
     pub display: RoamTitle,
@@ -146,6 +317,9 @@ pub struct OrgAsHTMLResponse {
     pub outgoing_links: Vec<OutgoingLink>,
     pub incoming_links: Vec<IncomingLink>,
     pub latex_blocks: Vec<String>,
+    /// Titles of the ancestors of the node, outermost first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub olp: Vec<String>,
 }
 
 impl IntoResponse for OrgAsHTMLResponse {
@@ -154,6 +328,688 @@ impl IntoResponse for OrgAsHTMLResponse {
     }
 }
 
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct PinnedNode {
+    pub id: RoamID,
+    /// `None` if the pinned node no longer exists.
+    pub title: Option<RoamTitle>,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: RoamID,
+    /// `None` if the visited node no longer exists.
+    pub title: Option<RoamTitle>,
+    /// Unix timestamp in seconds.
+    pub visited_at: i64,
+}
+
+impl From<(i64, String, Option<String>, i64)> for HistoryEntry {
+    fn from((_, id, title, visited_at): (i64, String, Option<String>, i64)) -> Self {
+        Self {
+            id: id.into(),
+            title: title.map(Into::into),
+            visited_at,
+        }
+    }
+}
+
+/// Visited nodes in chronological order. `position` is the index of the
+/// current node, entries after it can be reached by going forward.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct HistoryResponse {
+    pub entries: Vec<HistoryEntry>,
+    pub position: Option<usize>,
+}
+
+impl IntoResponse for HistoryResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub id: i64,
+    /// Unix timestamp in seconds.
+    pub time: i64,
+    /// `None` if authentication is disabled or the user is unknown.
+    pub user: Option<String>,
+    pub action: String,
+    pub target: Option<String>,
+}
+
+impl From<(i64, i64, Option<String>, String, Option<String>)> for AuditEvent {
+    fn from(
+        (id, time, user, action, target): (i64, i64, Option<String>, String, Option<String>),
+    ) -> Self {
+        Self {
+            id,
+            time,
+            user,
+            action,
+            target,
+        }
+    }
+}
+
+/// A node whose file was moved to the trash.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub id: RoamID,
+    pub title: Option<RoamTitle>,
+    pub file: String,
+    /// Unix timestamp in seconds.
+    pub deleted_at: i64,
+    pub deleted_by: Option<String>,
+}
+
+impl From<(String, Option<String>, String, String, i64, Option<String>)> for TrashEntry {
+    fn from(
+        (id, title, file, _, deleted_at, deleted_by): (
+            String,
+            Option<String>,
+            String,
+            String,
+            i64,
+            Option<String>,
+        ),
+    ) -> Self {
+        Self {
+            id: id.into(),
+            title: title.map(Into::into),
+            file,
+            deleted_at,
+            deleted_by,
+        }
+    }
+}
+
+/// Trashed nodes, most recently deleted first.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct TrashResponse {
+    pub entries: Vec<TrashEntry>,
+}
+
+impl IntoResponse for TrashResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+/// A websocket or server-sent events client.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct ConnectionInfo {
+    pub id: u64,
+    pub kind: ConnectionKind,
+    pub user: Option<String>,
+    pub user_agent: Option<String>,
+    /// Unix timestamp in seconds.
+    pub connected_at: i64,
+    /// Node the client currently visits.
+    pub working_id: Option<RoamID>,
+    pub messages_sent: u64,
+    pub messages_dropped: u64,
+    /// Messages held back because the client is slow.
+    pub queued: usize,
+    /// Subscribed topics, `None` if the client receives every broadcast.
+    pub topics: Option<Vec<Topic>>,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct ConnectionsResponse {
+    pub connections: Vec<ConnectionInfo>,
+}
+
+impl IntoResponse for ConnectionsResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+/// Audit events, newest first.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct AuditResponse {
+    pub events: Vec<AuditEvent>,
+}
+
+impl IntoResponse for AuditResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct CalendarEntry {
+    pub id: RoamID,
+    pub title: RoamTitle,
+}
+
+/// Nodes per day (`YYYY-MM-DD`) of a month. Days without nodes are omitted.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct CalendarResponse {
+    pub month: String,
+    pub days: BTreeMap<String, Vec<CalendarEntry>>,
+}
+
+impl IntoResponse for CalendarResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+/// A node whose title or alias is shared with other nodes.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct DuplicateNode {
+    pub id: RoamID,
+    pub title: RoamTitle,
+    pub file: String,
+    /// Set if the name matched an alias and not the title.
+    pub alias: bool,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct DuplicateTitle {
+    pub name: String,
+    pub nodes: Vec<DuplicateNode>,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct DuplicateTitlesResponse {
+    pub duplicates: Vec<DuplicateTitle>,
+}
+
+impl IntoResponse for DuplicateTitlesResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+/// Note quality rules, see [`crate::config::LintConfig`].
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintRule {
+    MissingTags,
+    TitleTooLong,
+    NoBacklinks,
+    TodoWithoutDeadline,
+}
+
+/// A node violating at least one lint rule.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct LintedNode {
+    pub id: RoamID,
+    pub title: RoamTitle,
+    pub file: String,
+    pub rules: Vec<LintRule>,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct LintResponse {
+    pub nodes: Vec<LintedNode>,
+}
+
+impl IntoResponse for LintResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticSeverity {
+    /// Parts of the file, or all of it, were not indexed.
+    Error,
+    Warning,
+}
+
+/// A problem found while reading or indexing a file. Positions are 1-based.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct FileDiagnostic {
+    pub severity: DiagnosticSeverity,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    /// Node the problem belongs to, e.g. one that could not be stored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node: Option<String>,
+    pub message: String,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct FileDiagnostics {
+    pub file: String,
+    pub diagnostics: Vec<FileDiagnostic>,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct FileDiagnosticsResponse {
+    pub files: Vec<FileDiagnostics>,
+}
+
+impl IntoResponse for FileDiagnosticsResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+/// A link whose target is missing or could not be fetched.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct BrokenLink {
+    pub source: RoamID,
+    pub source_title: RoamTitle,
+    /// `id` or the scheme of an external link.
+    #[serde(rename = "type")]
+    pub link_type: String,
+    pub dest: String,
+    /// Why an external link failed, e.g. the status code.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Result of the last run of the link checker.
+#[derive(PartialEq, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LinkReport {
+    /// Unix timestamp of the check, `None` if no check has run yet.
+    pub checked_at: Option<i64>,
+    pub links: Vec<BrokenLink>,
+}
+
+impl IntoResponse for LinkReport {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+/// A file that was moved into the archive as a whole.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct ArchivedFile {
+    pub file: String,
+    /// Archive file the content was appended to
+    pub archive: String,
+    /// Number of nodes in the file
+    pub nodes: usize,
+}
+
+/// A headline node that was moved into the archive with its subtree.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct ArchivedSubtree {
+    pub id: RoamID,
+    pub title: RoamTitle,
+    pub file: String,
+    pub archive: String,
+}
+
+/// Nodes archived by `/admin/archive`. Paths are relative to the
+/// org-roamers root.
+#[derive(PartialEq, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ArchiveReport {
+    /// Set if nothing was moved and the report only lists candidates.
+    pub dry_run: bool,
+    pub files: Vec<ArchivedFile>,
+    pub subtrees: Vec<ArchivedSubtree>,
+}
+
+impl IntoResponse for ArchiveReport {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+/// Differences between the indexed state and the files on disk found by the
+/// last reindex. Paths are relative to the org-roamers root.
+#[derive(PartialEq, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ReindexReport {
+    /// Unix timestamp of the reindex, `None` if none has run yet.
+    pub finished_at: Option<i64>,
+    /// Number of files that were indexed.
+    pub files: usize,
+    /// Files that were missing in the index.
+    pub added: Vec<String>,
+    /// Files whose indexed content was outdated.
+    pub changed: Vec<String>,
+    /// Indexed files that no longer exist.
+    pub removed: Vec<String>,
+}
+
+impl ReindexReport {
+    pub fn discrepancies(&self) -> usize {
+        self.added.len() + self.changed.len() + self.removed.len()
+    }
+}
+
+impl IntoResponse for ReindexReport {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+/// Full content of the file containing a node, or only the subtree of the
+/// node. `hash` is always the one of the file and has to be sent back when
+/// editing it.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct NodeSource {
+    pub id: RoamID,
+    pub file: String,
+    pub content: String,
+    pub hash: String,
+}
+
+impl IntoResponse for NodeSource {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffOp {
+    Equal,
+    Insert,
+    Delete,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct DiffHunk {
+    pub op: DiffOp,
+    pub lines: Vec<String>,
+}
+
+/// The file changed since the client read it. `diff` turns the current
+/// content into the rejected content.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct EditConflict {
+    pub hash: String,
+    pub content: String,
+    pub diff: Vec<DiffHunk>,
+}
+
+impl IntoResponse for EditConflict {
+    fn into_response(self) -> Response {
+        (StatusCode::CONFLICT, Json(self)).into_response()
+    }
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct ReindexJob {
+    pub job_id: u64,
+}
+
+impl IntoResponse for ReindexJob {
+    fn into_response(self) -> Response {
+        (StatusCode::ACCEPTED, Json(self)).into_response()
+    }
+}
+
+/// A heading in the outline of a file. `id` is only set for headings that
+/// are nodes.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct OutlineHeading {
+    pub title: RoamTitle,
+    pub level: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<RoamID>,
+    pub children: Vec<OutlineHeading>,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct OutlineResponse {
+    /// File relative to the org-roamers root.
+    pub file: String,
+    pub headings: Vec<OutlineHeading>,
+}
+
+impl IntoResponse for OutlineResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+/// The other endpoint of a link.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct Neighbor {
+    pub id: RoamID,
+    pub title: RoamTitle,
+    pub tags: Vec<String>,
+    pub file: String,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct NeighborsResponse {
+    pub incoming: Vec<Neighbor>,
+    pub outgoing: Vec<Neighbor>,
+}
+
+impl IntoResponse for NeighborsResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct CaptureResponse {
+    pub id: RoamID,
+    /// File of the new node relative to the org-roamers root.
+    pub file: String,
+}
+
+impl IntoResponse for CaptureResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct QueryMatch {
+    pub id: RoamID,
+    pub title: RoamTitle,
+    pub file: String,
+    pub todo: Option<String>,
+    pub scheduled: Option<String>,
+    pub deadline: Option<String>,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize)]
+pub struct HealthResponse {
+    /// `ok` or `degraded`.
+    pub status: &'static str,
+    pub watcher: WatcherStatus,
+}
+
+impl IntoResponse for HealthResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+/// Current change sequence number of the vault.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct StatusResponse {
+    pub seq: u64,
+}
+
+impl IntoResponse for StatusResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct UploadedAsset {
+    /// Path of the stored file relative to the org-roamers root.
+    pub path: String,
+    /// Org link that can be inserted to reference the file.
+    pub link: String,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct UploadResponse {
+    pub files: Vec<UploadedAsset>,
+}
+
+impl IntoResponse for UploadResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+/// A node linking to another node, with the line the link is in.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct Backlink {
+    pub source: OutgoingLink,
+    pub file: String,
+    /// Line of the link in `file`, starting at 1. Not set if the link could
+    /// not be found in the content, e.g. because the file is not cached.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    /// Column in characters where the link starts, starting at 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    /// The line containing the link.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct BacklinksResponse {
+    pub id: RoamID,
+    pub backlinks: Vec<Backlink>,
+}
+
+impl IntoResponse for BacklinksResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+/// A node related to another one, see `/similar`. The scores are between 0
+/// and 1.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct SimilarNode {
+    pub id: RoamID,
+    pub title: RoamTitle,
+    /// Weighted sum of the overlap of tags and links and of `text_score`
+    pub score: f64,
+    pub shared_tags: Vec<String>,
+    /// Nodes both nodes are linked with
+    pub shared_links: Vec<RoamID>,
+    /// Cosine similarity of the TF-IDF weighted words
+    pub text_score: f64,
+    /// Whether both nodes already link to each other
+    pub linked: bool,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct SimilarResponse {
+    pub id: RoamID,
+    pub nodes: Vec<SimilarNode>,
+}
+
+impl IntoResponse for SimilarResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+/// Entry of `/emacs/candidates`, serialized as the array
+/// `[name, id, tags, file]`. `name` is the title or one of the aliases of
+/// the node, `tags` are written like in Org, e.g. `:a:b:`, or empty.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct EmacsCandidate(pub String, pub RoamID, pub String, pub String);
+
+/// Every title and alias of the vault, for completing node names in Emacs.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct EmacsCandidates {
+    pub candidates: Vec<EmacsCandidate>,
+}
+
+/// Result of merging a node into another one
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct MergeResponse {
+    /// Node that was kept
+    pub id: RoamID,
+    /// File of the kept node
+    pub file: String,
+    /// Files whose links to the merged node were rewritten
+    pub rewritten: Vec<String>,
+    /// File of the merged node that was moved to the trash
+    pub trashed: String,
+}
+
+impl IntoResponse for MergeResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+/// Result of extracting a headline node into its own file
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct ExtractResponse {
+    pub id: RoamID,
+    /// New file of the node relative to the org-roamers root
+    pub file: String,
+    /// File the node was extracted from
+    pub source: String,
+}
+
+impl IntoResponse for ExtractResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+/// Result of moving the file of a node
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct MoveResponse {
+    pub id: RoamID,
+    /// New file relative to the org-roamers root
+    pub file: String,
+    /// Previous file
+    pub source: String,
+    /// Files whose links to `source` were rewritten
+    pub rewritten: Vec<String>,
+}
+
+impl IntoResponse for MoveResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+/// Spaced repetition state of a node
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct ReviewItem {
+    pub id: RoamID,
+    pub title: RoamTitle,
+    /// Days between the last and the next review
+    pub interval: i64,
+    pub ease: f64,
+    /// Successful reviews in a row
+    pub repetitions: i64,
+    /// Unix timestamps, `None` if the node was never reviewed
+    pub last_review: Option<i64>,
+    pub due: Option<i64>,
+}
+
+impl IntoResponse for ReviewItem {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct ReviewQueue {
+    /// Number of nodes that are due
+    pub due: usize,
+    /// Next nodes to review, most overdue first
+    pub nodes: Vec<ReviewItem>,
+}
+
+impl IntoResponse for ReviewQueue {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -166,18 +1022,29 @@ mod tests {
                     id: RoamID("a64477aa-d900-476d-b500-b8ab0b03c17d".to_string()),
                     parent: RoamID("".to_string()),
                     num_links: 1,
+                    pinned: false,
+                    kind: NodeKind::Node,
+                    summary: None,
+                    group: None,
                 },
                 RoamNode {
                     title: RoamTitle("Vec<T>".to_string()),
                     id: RoamID("bcb77e31-b4c6-4cf9-a05d-47b766349e57".to_string()),
                     parent: RoamID("".to_string()),
                     num_links: 1,
+                    pinned: false,
+                    kind: NodeKind::Node,
+                    summary: None,
+                    group: None,
                 },
             ],
             links: vec![RoamLink {
                 from: RoamID("bcb77e31-b4c6-4cf9-a05d-47b766349e57".to_string()),
                 to: RoamID("a64477aa-d900-476d-b500-b8ab0b03c17d".to_string()),
             }],
+            layout: BTreeMap::new(),
+            aggregated: false,
+            zoom: None,
         };
 
         let serialized = concat!(
@@ -190,6 +1057,38 @@ mod tests {
         assert_eq!(serde_json::to_string(&data).unwrap(), serialized);
     }
 
+    #[test]
+    fn test_graph_data_sort() {
+        let node = |id: &str| RoamNode {
+            title: RoamTitle(id.to_uppercase()),
+            id: RoamID(id.to_string()),
+            parent: RoamID("".to_string()),
+            num_links: 0,
+            pinned: false,
+            kind: NodeKind::Node,
+            summary: None,
+            group: None,
+        };
+        let link = |from: &str, to: &str| RoamLink {
+            from: RoamID(from.to_string()),
+            to: RoamID(to.to_string()),
+        };
+        let mut data = GraphData {
+            nodes: vec![node("c"), node("a"), node("b")],
+            links: vec![link("c", "a"), link("a", "c"), link("a", "b")],
+            layout: BTreeMap::new(),
+            aggregated: false,
+            zoom: None,
+        };
+        data.sort();
+        let ids: Vec<&str> = data.nodes.iter().map(|node| node.id.id()).collect();
+        assert_eq!(ids, vec!["a", "b", "c"]);
+        assert_eq!(
+            data.links,
+            vec![link("a", "b"), link("a", "c"), link("c", "a")]
+        );
+    }
+
     #[test]
     fn test_id_from() {
         let s = "\"a64477aa-d900-476d-b500-b8ab0b03c17d\"";
@@ -204,6 +1103,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_id_normalize() {
+        let id = RoamID("a64477aa-d900-476d-b500-b8ab0b03c17d".to_string());
+        assert_eq!(RoamID::from(" A64477AA-D900-476D-B500-B8AB0B03C17D "), id);
+        assert_eq!(
+            RoamID::parse("\"A64477aa-d900-476d-b500-b8ab0b03c17D\"").unwrap(),
+            id
+        );
+        // Only UUIDs are case insensitive.
+        assert_eq!(RoamID::from("Custom-Id").id(), "Custom-Id");
+
+        assert!(RoamID::parse("").is_err());
+        assert!(RoamID::parse("\"\"").is_err());
+        assert!(RoamID::parse("a b").is_err());
+        assert!(RoamID::parse("a]]").is_err());
+        assert!(RoamID::parse("\"a\"b\"").is_err());
+        assert!(RoamID::parse("a\"b").is_err());
+        assert!(serde_json::from_str::<RoamID>("\"a\\nb\"").is_err());
+        assert_eq!(
+            serde_json::from_str::<RoamID>("\"ABCDEF01-2345-6789-ABCD-EF0123456789\"").unwrap(),
+            RoamID("abcdef01-2345-6789-abcd-ef0123456789".to_string())
+        );
+    }
+
     #[test]
     fn test_title_from() {
         let s = "\"Vec<T> in Rust\"";
@@ -226,6 +1149,7 @@ mod tests {
             tags: vec![],
             incoming_links: vec![],
             latex_blocks: vec![],
+            olp: vec![],
         };
         let expected = concat!(
             "{\"org\":\"<h1>title</h1>\",\"tags\":[],",
@@ -234,4 +1158,279 @@ mod tests {
         );
         assert_eq!(serde_json::to_string(&resp).unwrap(), expected);
     }
+
+    #[test]
+    fn test_graph_layout_serialization() {
+        let data = GraphData {
+            nodes: vec![],
+            links: vec![],
+            layout: BTreeMap::from([("id".into(), NodePosition { x: 1.5, y: -2.0 })]),
+            aggregated: false,
+            zoom: None,
+        };
+        let expected = "{\"nodes\":[],\"links\":[],\"layout\":{\"id\":{\"x\":1.5,\"y\":-2.0}}}";
+        assert_eq!(serde_json::to_string(&data).unwrap(), expected);
+    }
+}
+
+/// Version of the [`VaultIndex`] schema. Incremented on incompatible
+/// changes, fields may be added without a bump.
+pub const VAULT_INDEX_VERSION: u32 = 1;
+
+/// The whole index as served by `/export/index.json` and
+/// `org-roamers-cli --export-index`. Nodes are sorted by file and id, links
+/// by source and destination, so exports of the same vault are identical.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct VaultIndex {
+    /// See [`VAULT_INDEX_VERSION`].
+    pub version: u32,
+    pub nodes: Vec<IndexNode>,
+    pub links: Vec<IndexLink>,
+}
+
+impl IntoResponse for VaultIndex {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct IndexNode {
+    pub id: RoamID,
+    /// Title without org markup.
+    pub title: String,
+    /// Path relative to the vault root.
+    pub file: String,
+    /// `0` for file nodes, the headline level otherwise.
+    pub level: u64,
+    /// Id of the closest enclosing node.
+    pub parent: Option<RoamID>,
+    pub todo: Option<String>,
+    pub priority: Option<String>,
+    /// `YYYY-MM-DD`
+    pub scheduled: Option<String>,
+    /// `YYYY-MM-DD`
+    pub deadline: Option<String>,
+    /// Sorted, including inherited tags.
+    pub tags: Vec<String>,
+    /// `ROAM_ALIASES`
+    pub aliases: Vec<String>,
+    /// `ROAM_REFS`
+    pub refs: Vec<String>,
+    /// Property drawer without the `ID`. Keys are upper case.
+    pub properties: BTreeMap<String, String>,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct IndexLink {
+    pub source: RoamID,
+    /// Node id for `id` links, the url otherwise.
+    pub dest: String,
+    /// `id` for links to nodes, the url scheme otherwise.
+    #[serde(rename = "type")]
+    pub link_type: String,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct BoardTask {
+    pub id: RoamID,
+    pub title: RoamTitle,
+    pub file: String,
+    pub priority: Option<String>,
+    /// `YYYY-MM-DD`
+    pub scheduled: Option<String>,
+    /// `YYYY-MM-DD`
+    pub deadline: Option<String>,
+}
+
+/// Tasks with the same TODO keyword. Tasks are ordered by priority, then
+/// deadline and title.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct BoardColumn {
+    pub keyword: String,
+    pub tasks: Vec<BoardTask>,
+}
+
+/// Kanban board of all nodes with a TODO keyword. Configured columns are
+/// always present, even if empty.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct BoardResponse {
+    pub columns: Vec<BoardColumn>,
+}
+
+impl IntoResponse for BoardResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+/// Whether an agenda item is shown for the scheduled date or the deadline.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AgendaKind {
+    Scheduled,
+    Deadline,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct AgendaItem {
+    /// `YYYY-MM-DD`
+    pub date: String,
+    pub kind: AgendaKind,
+    pub title: RoamTitle,
+    pub file: String,
+    /// Closest node containing the headline, if any.
+    pub node: Option<RoamID>,
+    pub todo: Option<String>,
+    pub priority: Option<String>,
+}
+
+/// Tasks scheduled or due within `from` and `to`, ordered by date.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct AgendaResponse {
+    pub from: String,
+    pub to: String,
+    pub items: Vec<AgendaItem>,
+}
+
+impl IntoResponse for AgendaResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineEventKind {
+    /// The file of the node was created.
+    Created,
+    /// A commit touching the file, or its last modification if the vault
+    /// is not a git repository.
+    Modified,
+    /// An active timestamp in the node or its datetree date.
+    Timestamp,
+    /// A node linking here. Link creation is not tracked, so the creation
+    /// of the linking file is used.
+    Backlink,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct TimelineEvent {
+    /// `YYYY-MM-DD`
+    pub date: String,
+    /// Unix timestamp in seconds. Missing for timestamps in the content,
+    /// which only carry a date.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<i64>,
+    pub kind: TimelineEventKind,
+    /// Linking node of [`TimelineEventKind::Backlink`] events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<OutgoingLink>,
+}
+
+/// Events related to a node in chronological order.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct TimelineResponse {
+    pub id: RoamID,
+    pub title: RoamTitle,
+    pub events: Vec<TimelineEvent>,
+}
+
+impl IntoResponse for TimelineResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+/// A cited work with the nodes citing it and its literature notes, i.e.
+/// nodes naming the key in `ROAM_REFS`.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct CitationResponse {
+    pub key: String,
+    pub citing: Vec<OutgoingLink>,
+    pub literature: Vec<OutgoingLink>,
+}
+
+impl IntoResponse for CitationResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+/// A `ROAM_REFS` entry of a node.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct RefEntry {
+    /// Cite key for cited works, the url otherwise
+    #[serde(rename = "ref")]
+    pub reference: String,
+    /// `cite` or the scheme of the url
+    #[serde(rename = "type")]
+    pub ref_type: String,
+    pub node: OutgoingLink,
+    /// Number of nodes citing the key. Always `0` for urls.
+    pub cited_by: usize,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct RefsResponse {
+    pub refs: Vec<RefEntry>,
+}
+
+impl IntoResponse for RefsResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+/// Filters of a [`GraphView`], with the same meaning as the query
+/// parameters of `/graph`.
+#[derive(PartialEq, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct GraphViewFilter {
+    /// Comma separated tags of which a node must carry one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<String>,
+    /// Comma separated tags that exclude a node.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exclude: Option<String>,
+    /// `YYYY-MM-DD`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_after: Option<String>,
+    /// `YYYY-MM-DD`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_after: Option<String>,
+}
+
+/// A named graph view of a user, applied with `/graph?view=<name>`.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct GraphView {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub filter: GraphViewFilter,
+    /// Node positions that take precedence over the stored layout.
+    #[serde(default)]
+    pub layout: BTreeMap<RoamID, NodePosition>,
+    #[serde(default = "default_zoom")]
+    pub zoom: f64,
+}
+
+fn default_zoom() -> f64 {
+    1.0
+}
+
+impl IntoResponse for GraphView {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct GraphViewsResponse {
+    pub views: Vec<GraphView>,
+}
+
+impl IntoResponse for GraphViewsResponse {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
 }
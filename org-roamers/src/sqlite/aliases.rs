@@ -0,0 +1,36 @@
+use std::collections::HashMap;
+
+use sqlx::SqlitePool;
+
+/// Resolve a node by title or `ROAM_ALIASES`. Titles take precedence over
+/// aliases, then file nodes over headlines.
+pub async fn resolve_name(con: &SqlitePool, name: &str) -> anyhow::Result<Option<String>> {
+    const STMNT: &str = concat!(
+        "SELECT id FROM (\n",
+        "    SELECT n.id, 0 AS rank, n.level FROM nodes n WHERE n.title = ?1\n",
+        "    UNION ALL\n",
+        "    SELECT n.id, 1 AS rank, n.level FROM aliases a\n",
+        "    JOIN nodes n ON n.id = a.node_id WHERE a.alias = ?1\n",
+        ") ORDER BY rank, level, id LIMIT 1;"
+    );
+    let id: Option<(String,)> = sqlx::query_as(STMNT).bind(name).fetch_optional(con).await?;
+    Ok(id.map(|(id,)| id))
+}
+
+/// Every title and alias mapped to the node [`resolve_name`] resolves it to.
+pub async fn name_map(con: &SqlitePool) -> anyhow::Result<HashMap<String, String>> {
+    const STMNT: &str = concat!(
+        "SELECT name, id FROM (\n",
+        "    SELECT n.title AS name, n.id, 0 AS rank, n.level FROM nodes n\n",
+        "    UNION ALL\n",
+        "    SELECT a.alias AS name, n.id, 1 AS rank, n.level FROM aliases a\n",
+        "    JOIN nodes n ON n.id = a.node_id\n",
+        ") ORDER BY rank, level, id;"
+    );
+    let rows: Vec<(String, String)> = sqlx::query_as(STMNT).fetch_all(con).await?;
+    let mut names = HashMap::with_capacity(rows.len());
+    for (name, id) in rows {
+        names.entry(name).or_insert(id);
+    }
+    Ok(names)
+}
@@ -0,0 +1,69 @@
+use sqlx::{Executor, SqlitePool};
+
+/// The audit log is append-only: updating or deleting rows is aborted by
+/// triggers.
+pub async fn init_audit_table(con: &SqlitePool) -> anyhow::Result<()> {
+    const AUDIT: &str = concat!(
+        "CREATE TABLE IF NOT EXISTS audit_log (id INTEGER PRIMARY KEY AUTOINCREMENT, ",
+        "time INTEGER NOT NULL, user TEXT, action TEXT NOT NULL, target TEXT);"
+    );
+    const NO_UPDATE: &str = concat!(
+        "CREATE TRIGGER IF NOT EXISTS audit_log_no_update BEFORE UPDATE ON audit_log\n",
+        "BEGIN SELECT RAISE(ABORT, 'audit log is append-only'); END;"
+    );
+    const NO_DELETE: &str = concat!(
+        "CREATE TRIGGER IF NOT EXISTS audit_log_no_delete BEFORE DELETE ON audit_log\n",
+        "BEGIN SELECT RAISE(ABORT, 'audit log is append-only'); END;"
+    );
+    con.execute(AUDIT).await?;
+    con.execute(NO_UPDATE).await?;
+    con.execute(NO_DELETE).await?;
+    Ok(())
+}
+
+/// A row of the audit log: `(id, time, user, action, target)`.
+pub type AuditRow = (i64, i64, Option<String>, String, Option<String>);
+
+pub async fn insert_event(
+    con: &SqlitePool,
+    time: i64,
+    user: Option<&str>,
+    action: &str,
+    target: Option<&str>,
+) -> anyhow::Result<()> {
+    const STMNT: &str = "INSERT INTO audit_log (time, user, action, target) VALUES (?, ?, ?, ?);";
+    sqlx::query(STMNT)
+        .bind(time)
+        .bind(user)
+        .bind(action)
+        .bind(target)
+        .execute(con)
+        .await?;
+    Ok(())
+}
+
+/// Get the newest `limit` events, optionally only of `user`, of `action` or
+/// at or after `since`.
+pub async fn get_events(
+    con: &SqlitePool,
+    user: Option<&str>,
+    action: Option<&str>,
+    since: Option<i64>,
+    limit: i64,
+) -> anyhow::Result<Vec<AuditRow>> {
+    const STMNT: &str = concat!(
+        "SELECT id, time, user, action, target FROM audit_log\n",
+        "WHERE (?1 IS NULL OR user = ?1)\n",
+        "AND (?2 IS NULL OR action = ?2)\n",
+        "AND (?3 IS NULL OR time >= ?3)\n",
+        "ORDER BY id DESC LIMIT ?4;"
+    );
+    let rows = sqlx::query_as(STMNT)
+        .bind(user)
+        .bind(action)
+        .bind(since)
+        .bind(limit)
+        .fetch_all(con)
+        .await?;
+    Ok(rows)
+}
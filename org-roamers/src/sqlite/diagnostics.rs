@@ -0,0 +1,89 @@
+use std::io;
+
+use sqlx::{Executor, SqlitePool};
+
+use crate::server::types::{DiagnosticSeverity, FileDiagnostic};
+
+/// Problems found while indexing a file. Files that could not be read at all
+/// are not in `files`, so rows are not tied to it and replaced whenever the
+/// file is indexed again.
+pub async fn init_diagnostics_table(con: &SqlitePool) -> anyhow::Result<()> {
+    const STMNT: &str = concat!(
+        "CREATE TABLE file_diagnostics (file TEXT NOT NULL, severity TEXT NOT NULL, ",
+        "line INTEGER, col INTEGER, node_id TEXT, message TEXT NOT NULL);"
+    );
+    const STMNT_INDEX: &str = "CREATE INDEX file_diagnostics_file ON file_diagnostics (file);";
+    con.execute(STMNT).await?;
+    con.execute(STMNT_INDEX).await?;
+    Ok(())
+}
+
+/// `(file, severity, line, col, node_id, message)`
+pub type DiagnosticRow = (
+    String,
+    String,
+    Option<i64>,
+    Option<i64>,
+    Option<String>,
+    String,
+);
+
+/// Replace the diagnostics of `file`. An empty slice clears them.
+pub async fn replace_file_diagnostics(
+    con: &SqlitePool,
+    file: &str,
+    diagnostics: &[FileDiagnostic],
+) -> anyhow::Result<()> {
+    sqlx::query("DELETE FROM file_diagnostics WHERE file = ?;")
+        .bind(file)
+        .execute(con)
+        .await?;
+    const STMNT: &str = concat!(
+        "INSERT INTO file_diagnostics (file, severity, line, col, node_id, message)\n",
+        "VALUES (?, ?, ?, ?, ?, ?);"
+    );
+    for diagnostic in diagnostics {
+        let severity = match diagnostic.severity {
+            DiagnosticSeverity::Error => "error",
+            DiagnosticSeverity::Warning => "warning",
+        };
+        sqlx::query(STMNT)
+            .bind(file)
+            .bind(severity)
+            .bind(diagnostic.line.map(|line| line as i64))
+            .bind(diagnostic.column.map(|column| column as i64))
+            .bind(diagnostic.node.as_deref())
+            .bind(&diagnostic.message)
+            .execute(con)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Replace the diagnostics of `file` with the error that kept it from being
+/// read. Files skipped because of their size are only a warning.
+pub async fn record_file_error(con: &SqlitePool, file: &str, err: &io::Error) {
+    let severity = match err.kind() {
+        io::ErrorKind::FileTooLarge => DiagnosticSeverity::Warning,
+        _ => DiagnosticSeverity::Error,
+    };
+    let diagnostic = FileDiagnostic {
+        severity,
+        line: None,
+        column: None,
+        node: None,
+        message: err.to_string(),
+    };
+    if let Err(err) = replace_file_diagnostics(con, file, &[diagnostic]).await {
+        tracing::error!("Failed to store diagnostics of {file}: {err}");
+    }
+}
+
+/// All diagnostics, ordered by file and position.
+pub async fn get_diagnostics(con: &SqlitePool) -> anyhow::Result<Vec<DiagnosticRow>> {
+    const STMNT: &str = concat!(
+        "SELECT file, severity, line, col, node_id, message FROM file_diagnostics\n",
+        "ORDER BY file, line, col;"
+    );
+    Ok(sqlx::query_as(STMNT).fetch_all(con).await?)
+}
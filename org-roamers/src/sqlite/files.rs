@@ -5,25 +5,100 @@ use sqlx::{Executor, SqlitePool};
 pub async fn init_files_table(con: &SqlitePool) -> anyhow::Result<()> {
     const STMNT: &str = concat!(
         "CREATE TABLE files (id INTEGER PRIMARY KEY AUTOINCREMENT, ",
-        "file TEXT NOT NULL UNIQUE, hash INTEGER NOT NULL);"
+        "file TEXT NOT NULL UNIQUE, hash INTEGER NOT NULL, ",
+        "mtime INTEGER, created INTEGER);"
     );
     con.execute(STMNT).await?;
     Ok(())
 }
 
+/// All indexed files with the hash of their content.
+pub async fn get_files(con: &SqlitePool) -> anyhow::Result<Vec<(String, u64)>> {
+    let files: Vec<(String, i64)> = sqlx::query_as("SELECT file, hash FROM files;")
+        .fetch_all(con)
+        .await?;
+    Ok(files
+        .into_iter()
+        .map(|(file, hash)| (file, hash as u64))
+        .collect())
+}
+
+/// Files that have a file level node, as `(file, id of that node)`.
+pub async fn get_file_nodes(con: &SqlitePool) -> anyhow::Result<Vec<(String, String)>> {
+    let nodes = sqlx::query_as("SELECT file, id FROM nodes WHERE level = 0;")
+        .fetch_all(con)
+        .await?;
+    Ok(nodes)
+}
+
+/// Ids of all nodes of `file`.
+pub async fn get_node_ids(con: &SqlitePool, file: &str) -> anyhow::Result<Vec<String>> {
+    let ids = sqlx::query_scalar("SELECT id FROM nodes WHERE file = ?;")
+        .bind(file)
+        .fetch_all(con)
+        .await?;
+    Ok(ids)
+}
+
+/// Remove a file. Its nodes are removed by the foreign key constraint.
+pub async fn delete_file(con: &SqlitePool, filename: &str) -> anyhow::Result<()> {
+    sqlx::query("DELETE FROM files WHERE file = ?;")
+        .bind(filename)
+        .execute(con)
+        .await?;
+    sqlx::query("DELETE FROM file_diagnostics WHERE file = ?;")
+        .bind(filename)
+        .execute(con)
+        .await?;
+    Ok(())
+}
+
+/// Insert or update a file. `mtime` and `created` are unix timestamps in
+/// seconds.
 pub async fn insert_file<P: AsRef<Path>>(
     con: &SqlitePool,
     filename: P,
     hash: u64,
+    mtime: Option<i64>,
+    created: Option<i64>,
 ) -> anyhow::Result<()> {
     let filename = filename.as_ref().to_string_lossy();
-    let hash = hash as u32;
+    // SQLite integers are signed, the hash is stored with the same bits.
+    let hash = hash as i64;
 
-    let _ = sqlx::query("INSERT OR REPLACE INTO files (file, hash) VALUES (?, ?);")
+    const STMNT: &str =
+        "INSERT OR REPLACE INTO files (file, hash, mtime, created) VALUES (?, ?, ?, ?);";
+    let _ = sqlx::query(STMNT)
         .bind(filename)
         .bind(hash)
+        .bind(mtime)
+        .bind(created)
         .execute(con)
         .await?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::sqlite::init_db;
+
+    #[tokio::test]
+    async fn test_hash_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let pool = init_db(Some(&temp_dir.path().join("roam.db")))
+            .await
+            .unwrap();
+
+        // Hashes above `i64::MAX` are kept in full
+        let hash = u64::MAX - 1;
+        insert_file(&pool, "a.org", hash, None, None).await.unwrap();
+        assert_eq!(
+            get_files(&pool).await.unwrap(),
+            vec![("a.org".to_string(), hash)]
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! Full text index of the content of all files, backed by SQLite FTS5.
+
+use sqlx::{Executor, SqlitePool};
+
+/// Marks the start of a match in snippets.
+const MATCH_START: char = '\u{2}';
+/// Marks the end of a match in snippets.
+const MATCH_END: char = '\u{3}';
+
+/// Rows are dropped together with their file. Prefixes of two and three
+/// characters are indexed, so prefix searches of short words stay fast.
+pub async fn init_fts_table(con: &SqlitePool) -> anyhow::Result<()> {
+    const STMNT: &str = concat!(
+        "CREATE VIRTUAL TABLE files_fts\n",
+        "USING fts5(file UNINDEXED, content, prefix = '2 3');"
+    );
+    const TRIGGER: &str = concat!(
+        "CREATE TRIGGER files_fts_delete AFTER DELETE ON files BEGIN\n",
+        "    DELETE FROM files_fts WHERE file = old.file;\n",
+        "END;"
+    );
+    con.execute(STMNT).await?;
+    con.execute(TRIGGER).await?;
+    Ok(())
+}
+
+/// Replace the indexed content of `file`.
+pub async fn index_file(con: &SqlitePool, file: &str, content: &str) -> anyhow::Result<()> {
+    let mut tx = con.begin().await?;
+    sqlx::query("DELETE FROM files_fts WHERE file = ?;")
+        .bind(file)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("INSERT INTO files_fts (file, content) VALUES (?, ?);")
+        .bind(file)
+        .bind(content)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Files containing every word of `query`, best match first. Every file
+/// comes with a preview of a match, see [`preview`]. With `prefix` the words
+/// only have to start with the words of `query`.
+pub async fn search(
+    con: &SqlitePool,
+    query: &str,
+    prefix: bool,
+) -> anyhow::Result<Vec<(String, (String, usize, usize))>> {
+    const STMNT: &str = concat!(
+        "SELECT file, snippet(files_fts, 1, ?, ?, '…', 12) FROM files_fts\n",
+        "WHERE files_fts MATCH ?\n",
+        "ORDER BY rank;"
+    );
+    let Some(fts_query) = match_query(query, prefix) else {
+        return Ok(vec![]);
+    };
+    let rows: Vec<(String, String)> = sqlx::query_as(STMNT)
+        .bind(MATCH_START.to_string())
+        .bind(MATCH_END.to_string())
+        .bind(fts_query)
+        .fetch_all(con)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(file, snippet)| (file, preview(&snippet)))
+        .collect())
+}
+
+/// FTS5 query matching documents that contain every word of `query`. Words
+/// are quoted, so FTS5 operators in `query` are matched literally.
+fn match_query(query: &str, prefix: bool) -> Option<String> {
+    let words: Vec<String> = query
+        .split_whitespace()
+        .map(|word| {
+            let quoted = format!("\"{}\"", word.replace('"', "\"\""));
+            match prefix {
+                true => quoted + "*",
+                false => quoted,
+            }
+        })
+        .collect();
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
+/// Turn a snippet with marked matches into the line of the first match and
+/// its range in characters. The snippet is shortened to one line.
+fn preview(snippet: &str) -> (String, usize, usize) {
+    let line = snippet
+        .lines()
+        .find(|line| line.contains(MATCH_START))
+        .unwrap_or(snippet);
+    let mut text = String::with_capacity(line.len());
+    let (mut start, mut end) = (None, None);
+    for c in line.chars() {
+        match c {
+            MATCH_START if start.is_none() => start = Some(text.chars().count()),
+            MATCH_END if end.is_none() => end = Some(text.chars().count()),
+            MATCH_START | MATCH_END => {}
+            c => text.push(c),
+        }
+    }
+    let text = text.trim_end().to_string();
+    let start = start.unwrap_or(0);
+    let end = end.unwrap_or(start);
+    (text, start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_query() {
+        assert_eq!(
+            match_query("rust \"ffi\"", false),
+            Some("\"rust\" \"\"\"ffi\"\"\"".to_string())
+        );
+        assert_eq!(
+            match_query("ru f", true),
+            Some("\"ru\"* \"f\"*".to_string())
+        );
+        assert_eq!(match_query("  ", true), None);
+    }
+
+    #[test]
+    fn test_preview() {
+        let snippet = "…first line\nsee \u{2}Rüst\u{3} and \u{2}more\u{3}\nlast…";
+        assert_eq!(preview(snippet), ("see Rüst and more".to_string(), 4, 8));
+    }
+}
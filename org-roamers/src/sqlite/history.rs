@@ -0,0 +1,234 @@
+use sqlx::{Executor, SqlitePool};
+
+/// Number of visits that are kept per user.
+pub const MAX_HISTORY: i64 = 1000;
+
+/// `history_cursor` points at the entry of `history` that is currently
+/// visited. Entries after the cursor form the forward history.
+pub async fn init_history_tables(con: &SqlitePool) -> anyhow::Result<()> {
+    const HISTORY: &str = concat!(
+        "CREATE TABLE IF NOT EXISTS history (id INTEGER PRIMARY KEY AUTOINCREMENT, ",
+        "user TEXT NOT NULL, node_id TEXT NOT NULL, visited_at INTEGER NOT NULL);"
+    );
+    const INDEX: &str = "CREATE INDEX IF NOT EXISTS history_user ON history (user, id);";
+    const CURSOR: &str = concat!(
+        "CREATE TABLE IF NOT EXISTS history_cursor (user TEXT NOT NULL PRIMARY KEY, ",
+        "entry INTEGER NOT NULL);"
+    );
+    con.execute(HISTORY).await?;
+    con.execute(INDEX).await?;
+    con.execute(CURSOR).await?;
+    Ok(())
+}
+
+/// A row of the history: `(entry, node_id, title, visited_at)`.
+pub type HistoryRow = (i64, String, Option<String>, i64);
+
+/// Get the last `limit` entries of the history of `user` in chronological
+/// order, including forward entries.
+pub async fn get_history(
+    con: &SqlitePool,
+    user: &str,
+    limit: i64,
+) -> anyhow::Result<Vec<HistoryRow>> {
+    const STMNT: &str = concat!(
+        "SELECT * FROM (SELECT h.id, h.node_id, n.title, h.visited_at FROM history h\n",
+        "LEFT JOIN nodes n ON n.id = h.node_id\n",
+        "WHERE h.user = ? ORDER BY h.id DESC LIMIT ?)\n",
+        "ORDER BY id ASC;"
+    );
+    let rows = sqlx::query_as(STMNT)
+        .bind(user)
+        .bind(limit)
+        .fetch_all(con)
+        .await?;
+    Ok(rows)
+}
+
+/// Get the most recently visited distinct nodes of `user`.
+pub async fn get_recent(
+    con: &SqlitePool,
+    user: &str,
+    limit: i64,
+) -> anyhow::Result<Vec<HistoryRow>> {
+    const STMNT: &str = concat!(
+        "SELECT MAX(h.id), h.node_id, n.title, MAX(h.visited_at) FROM history h\n",
+        "LEFT JOIN nodes n ON n.id = h.node_id\n",
+        "WHERE h.user = ?\n",
+        "GROUP BY h.node_id\n",
+        "ORDER BY MAX(h.id) DESC LIMIT ?;"
+    );
+    let rows = sqlx::query_as(STMNT)
+        .bind(user)
+        .bind(limit)
+        .fetch_all(con)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn get_cursor(con: &SqlitePool, user: &str) -> anyhow::Result<Option<i64>> {
+    const STMNT: &str = "SELECT entry FROM history_cursor WHERE user = ?;";
+    let cursor = sqlx::query_scalar(STMNT)
+        .bind(user)
+        .fetch_optional(con)
+        .await?;
+    Ok(cursor)
+}
+
+/// Record a visit of `id`. The forward history is discarded, like in a
+/// browser. Visiting the current node again does nothing.
+pub async fn push_visit(
+    con: &SqlitePool,
+    user: &str,
+    id: &str,
+    visited_at: i64,
+) -> anyhow::Result<()> {
+    let mut tx = con.begin().await?;
+
+    let current: Option<(i64, String)> = sqlx::query_as(concat!(
+        "SELECT h.id, h.node_id FROM history_cursor c\n",
+        "JOIN history h ON h.id = c.entry WHERE c.user = ?;"
+    ))
+    .bind(user)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if let Some((entry, node_id)) = &current {
+        if node_id == id {
+            return Ok(());
+        }
+        sqlx::query("DELETE FROM history WHERE user = ? AND id > ?;")
+            .bind(user)
+            .bind(entry)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    let entry = sqlx::query("INSERT INTO history (user, node_id, visited_at) VALUES (?, ?, ?);")
+        .bind(user)
+        .bind(id)
+        .bind(visited_at)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+
+    sqlx::query("INSERT OR REPLACE INTO history_cursor (user, entry) VALUES (?, ?);")
+        .bind(user)
+        .bind(entry)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(concat!(
+        "DELETE FROM history WHERE user = ? AND id NOT IN\n",
+        "(SELECT id FROM history WHERE user = ? ORDER BY id DESC LIMIT ?);"
+    ))
+    .bind(user)
+    .bind(user)
+    .bind(MAX_HISTORY)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Move the cursor of `user` one entry back (`forward == false`) or forward.
+/// Returns the node that is now current, or `None` if there is no entry in
+/// that direction.
+pub async fn step(con: &SqlitePool, user: &str, forward: bool) -> anyhow::Result<Option<String>> {
+    const BACK: &str = concat!(
+        "SELECT id, node_id FROM history WHERE user = ? AND id < ?\n",
+        "ORDER BY id DESC LIMIT 1;"
+    );
+    const FORWARD: &str = concat!(
+        "SELECT id, node_id FROM history WHERE user = ? AND id > ?\n",
+        "ORDER BY id ASC LIMIT 1;"
+    );
+
+    let Some(cursor) = get_cursor(con, user).await? else {
+        return Ok(None);
+    };
+
+    let next: Option<(i64, String)> = sqlx::query_as(if forward { FORWARD } else { BACK })
+        .bind(user)
+        .bind(cursor)
+        .fetch_optional(con)
+        .await?;
+
+    let Some((entry, node_id)) = next else {
+        return Ok(None);
+    };
+
+    sqlx::query("UPDATE history_cursor SET entry = ? WHERE user = ?;")
+        .bind(entry)
+        .bind(user)
+        .execute(con)
+        .await?;
+
+    Ok(Some(node_id))
+}
+
+pub async fn clear_history(con: &SqlitePool, user: &str) -> anyhow::Result<()> {
+    sqlx::query("DELETE FROM history WHERE user = ?;")
+        .bind(user)
+        .execute(con)
+        .await?;
+    sqlx::query("DELETE FROM history_cursor WHERE user = ?;")
+        .bind(user)
+        .execute(con)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::sqlite::init_db;
+
+    async fn nodes(con: &SqlitePool, user: &str) -> Vec<String> {
+        let history = get_history(con, user, MAX_HISTORY).await.unwrap();
+        history.into_iter().map(|(_, id, _, _)| id).collect()
+    }
+
+    #[tokio::test]
+    async fn test_push_after_back_truncates_forward() {
+        let temp_dir = TempDir::new().unwrap();
+        let pool = init_db(Some(&temp_dir.path().join("roam.db")))
+            .await
+            .unwrap();
+
+        for (i, id) in ["a", "b", "c"].into_iter().enumerate() {
+            push_visit(&pool, "me", id, i as i64).await.unwrap();
+        }
+        assert_eq!(
+            step(&pool, "me", false).await.unwrap().as_deref(),
+            Some("b")
+        );
+
+        push_visit(&pool, "me", "d", 3).await.unwrap();
+        assert_eq!(nodes(&pool, "me").await, ["a", "b", "d"]);
+        assert_eq!(step(&pool, "me", true).await.unwrap(), None);
+        assert_eq!(
+            step(&pool, "me", false).await.unwrap().as_deref(),
+            Some("b")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_back_at_start_is_noop() {
+        let temp_dir = TempDir::new().unwrap();
+        let pool = init_db(Some(&temp_dir.path().join("roam.db")))
+            .await
+            .unwrap();
+
+        assert_eq!(step(&pool, "me", false).await.unwrap(), None);
+
+        push_visit(&pool, "me", "a", 0).await.unwrap();
+        let cursor = get_cursor(&pool, "me").await.unwrap();
+        assert_eq!(step(&pool, "me", false).await.unwrap(), None);
+        assert_eq!(get_cursor(&pool, "me").await.unwrap(), cursor);
+        assert_eq!(nodes(&pool, "me").await, ["a"]);
+    }
+}
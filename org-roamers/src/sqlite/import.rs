@@ -0,0 +1,323 @@
+//! Import of an existing org-roam database (schema version 20). Values are
+//! stored as printed elisp, e.g. strings keep their quotes. Files are only
+//! imported if they did not change since org-roam indexed them, all other
+//! files are parsed as usual.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use sqlx::{sqlite::SqliteConnectOptions, SqlitePool};
+
+use crate::{
+    cache::content_hash,
+    sqlite::{files::insert_file, fts, rebuild},
+    transform::citations,
+};
+
+/// Properties org adds to every entry. They are not part of the property
+/// drawer and therefore not stored by org-roamers.
+const SPECIAL_PROPERTIES: &[&str] = &[
+    "ALLTAGS",
+    "BLOCKED",
+    "CATEGORY",
+    "CLOCKSUM",
+    "CLOCKSUM_T",
+    "CLOSED",
+    "DEADLINE",
+    "FILE",
+    "ID",
+    "ITEM",
+    "PRIORITY",
+    "SCHEDULED",
+    "TAGS",
+    "TIMESTAMP",
+    "TIMESTAMP_IA",
+    "TODO",
+];
+
+/// `(id, file, level, pos, todo, priority, scheduled, deadline, title,
+/// properties, olp)`
+type NodeRow = (
+    String,
+    String,
+    i64,
+    i64,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+);
+
+/// Read the org-roam database at `path` into `con`. Returns the number of
+/// imported files.
+pub async fn import_org_roam_db(
+    con: &SqlitePool,
+    path: &Path,
+    root: &Path,
+) -> anyhow::Result<usize> {
+    let options = SqliteConnectOptions::new().filename(path).read_only(true);
+    let org_roam = SqlitePool::connect_with(options).await?;
+
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let files: Vec<(String, Option<String>)> =
+        sqlx::query_as("SELECT file, CAST(mtime AS TEXT) FROM files;")
+            .fetch_all(&org_roam)
+            .await?;
+
+    // Stored path in org-roam -> path relative to the root
+    let mut imported: HashMap<String, String> = HashMap::new();
+    for (file, mtime) in files {
+        let absolute = PathBuf::from(elisp_string(&file));
+        let Ok(relative) = absolute.strip_prefix(&root) else {
+            continue;
+        };
+        // Files changed after org-roam saw them are parsed instead.
+        let Ok(content) = std::fs::read_to_string(&absolute) else {
+            continue;
+        };
+        let modified = std::fs::metadata(&absolute)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|since| since.as_secs() as i64);
+        let indexed = mtime.as_deref().and_then(elisp_time);
+        if modified.is_none() || modified > indexed {
+            continue;
+        }
+
+        insert_file(con, relative, content_hash(&content), modified, modified).await?;
+        fts::index_file(con, &relative.to_string_lossy(), &content).await?;
+        imported.insert(file, relative.to_string_lossy().to_string());
+    }
+
+    const NODES: &str = concat!(
+        "SELECT id, file, CAST(level AS INTEGER), CAST(pos AS INTEGER), todo, ",
+        "CAST(priority AS TEXT), scheduled, deadline, title, properties, olp ",
+        "FROM nodes ORDER BY file, pos;"
+    );
+    let nodes: Vec<NodeRow> = sqlx::query_as(NODES).fetch_all(&org_roam).await?;
+    let refs = refs(&org_roam).await?;
+
+    let mut node_ids = HashSet::new();
+    let mut file_title = None;
+    let mut ancestors: Vec<(i64, String)> = vec![];
+    let mut current_file = None;
+    for (id, file, level, _, todo, priority, scheduled, deadline, title, properties, olp) in nodes {
+        let Some(relative) = imported.get(&file) else {
+            continue;
+        };
+        let id = elisp_string(&id);
+        let title = title.as_deref().map(elisp_string).unwrap_or_default();
+        if current_file.as_ref() != Some(&file) {
+            current_file = Some(file.clone());
+            ancestors.clear();
+            file_title = None;
+        }
+        if level == 0 {
+            file_title = Some(title.clone());
+        }
+
+        // The parent is the closest node above with a lower level.
+        while ancestors
+            .last()
+            .is_some_and(|(parent_level, _)| *parent_level >= level)
+        {
+            ancestors.pop();
+        }
+        let parent = ancestors.last().map(|(_, parent)| parent.clone());
+        ancestors.push((level, id.clone()));
+
+        let mut properties: HashMap<String, String> =
+            elisp_strings(properties.as_deref().unwrap_or(""))
+                .chunks_exact(2)
+                .filter(|pair| !SPECIAL_PROPERTIES.contains(&pair[0].as_str()))
+                .map(|pair| (pair[0].clone(), pair[1].clone()))
+                .collect();
+        if let Some(node_refs) = refs.get(&id) {
+            properties
+                .entry("ROAM_REFS".to_string())
+                .or_insert_with(|| node_refs.join(" "));
+        }
+
+        let actual_olp: Vec<String> = if level == 0 {
+            vec![title.clone()]
+        } else {
+            let olp = elisp_strings(olp.as_deref().unwrap_or(""));
+            file_title.iter().cloned().chain(olp).collect()
+        };
+
+        rebuild::insert_node(
+            con,
+            &id,
+            relative,
+            level as u64,
+            parent.as_deref(),
+            todo.as_deref().map(elisp_string).as_deref(),
+            priority.as_deref().map(elisp_priority).as_deref(),
+            scheduled.as_deref().map(elisp_date).as_deref(),
+            deadline.as_deref().map(elisp_date).as_deref(),
+            &title,
+            &serde_json::to_string(&properties)?,
+            "",
+            &actual_olp,
+        )
+        .await?;
+        if let Some(node_refs) = properties.get("ROAM_REFS") {
+            for reference in citations::parse_refs(node_refs) {
+                rebuild::insert_ref(con, &id, &reference).await?;
+            }
+        }
+        node_ids.insert(id);
+    }
+
+    for (node_id, tag) in node_values(&org_roam, "SELECT node_id, tag FROM tags;").await? {
+        if node_ids.contains(&node_id) {
+            rebuild::insert_tag(con, &node_id, &tag).await?;
+        }
+    }
+    for (node_id, alias) in node_values(&org_roam, "SELECT node_id, alias FROM aliases;").await? {
+        if node_ids.contains(&node_id) {
+            rebuild::insert_alias(con, &node_id, &alias).await?;
+        }
+    }
+    for (node_id, key) in node_values(&org_roam, "SELECT node_id, cite_key FROM citations;").await?
+    {
+        if node_ids.contains(&node_id) {
+            rebuild::insert_citation(con, &node_id, &key).await?;
+        }
+    }
+
+    let links: Vec<(String, String, String)> =
+        sqlx::query_as("SELECT source, dest, type FROM links;")
+            .fetch_all(&org_roam)
+            .await?;
+    for (source, dest, link_type) in links {
+        let (source, dest, link_type) = (
+            elisp_string(&source),
+            elisp_string(&dest),
+            elisp_string(&link_type),
+        );
+        if !node_ids.contains(&source) {
+            continue;
+        }
+        match link_type.as_str() {
+            "id" => rebuild::insert_link(con, &source, &dest, "id").await?,
+            "http" | "https" => {
+                let url = format!("{link_type}:{dest}");
+                rebuild::insert_link(con, &source, &url, &link_type).await?
+            }
+            _ => {}
+        }
+    }
+
+    org_roam.close().await;
+    Ok(imported.len())
+}
+
+/// Pairs of node id and a string value, e.g. tags.
+async fn node_values(org_roam: &SqlitePool, stmnt: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let rows: Vec<(String, String)> = sqlx::query_as(stmnt).fetch_all(org_roam).await?;
+    Ok(rows
+        .into_iter()
+        .map(|(id, value)| (elisp_string(&id), elisp_string(&value)))
+        .collect())
+}
+
+/// Refs of every node in `ROAM_REFS` notation.
+async fn refs(org_roam: &SqlitePool) -> anyhow::Result<HashMap<String, Vec<String>>> {
+    let rows: Vec<(String, String, String)> =
+        sqlx::query_as("SELECT node_id, ref, type FROM refs;")
+            .fetch_all(org_roam)
+            .await?;
+    let mut refs: HashMap<String, Vec<String>> = HashMap::new();
+    for (node_id, reference, ref_type) in rows {
+        let (reference, ref_type) = (elisp_string(&reference), elisp_string(&ref_type));
+        let reference = match ref_type.as_str() {
+            "cite" => format!("@{reference}"),
+            _ => format!("{ref_type}:{reference}"),
+        };
+        refs.entry(elisp_string(&node_id))
+            .or_default()
+            .push(reference);
+    }
+    Ok(refs)
+}
+
+/// Unquote a printed elisp string. Other values are returned as they are.
+fn elisp_string(value: &str) -> String {
+    elisp_strings(value)
+        .into_iter()
+        .next()
+        .filter(|_| value.starts_with('"'))
+        .unwrap_or_else(|| value.to_string())
+}
+
+/// All strings of a printed elisp value, e.g. the segments of an olp
+/// `("a" "b")` or keys and values of an alist `(("K" . "V"))`.
+fn elisp_strings(value: &str) -> Vec<String> {
+    let mut strings = vec![];
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '"' {
+            continue;
+        }
+        let mut string = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => string.extend(chars.next()),
+                '"' => break,
+                c => string.push(c),
+            }
+        }
+        strings.push(string);
+    }
+    strings
+}
+
+/// Unix timestamp of an elisp time `(HIGH LOW USEC PSEC)`.
+fn elisp_time(value: &str) -> Option<i64> {
+    let mut parts = value
+        .trim_matches(|c| c == '(' || c == ')')
+        .split_whitespace()
+        .map(str::parse::<i64>);
+    let high = parts.next()?.ok()?;
+    let low = parts.next()?.ok()?;
+    Some(high * 65536 + low)
+}
+
+/// org-roam stores priorities as characters, e.g. `65` for `A`.
+fn elisp_priority(value: &str) -> String {
+    match value.parse::<u32>().ok().and_then(char::from_u32) {
+        Some(priority) => priority.to_string(),
+        None => elisp_string(value),
+    }
+}
+
+/// `YYYY-MM-DD` of an ISO 8601 timestamp.
+fn elisp_date(value: &str) -> String {
+    elisp_string(value).chars().take(10).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elisp_values() {
+        assert_eq!(elisp_string("\"a \\\"b\\\"\""), "a \"b\"");
+        assert_eq!(elisp_string("TODO"), "TODO");
+        assert_eq!(
+            elisp_strings("((\"ID\" . \"x\") (\"TOPIC\" . \"y\"))"),
+            vec!["ID", "x", "TOPIC", "y"]
+        );
+        assert_eq!(elisp_time("(26321 1024 0 0)"), Some(26321 * 65536 + 1024));
+        assert_eq!(elisp_priority("65"), "A");
+        assert_eq!(elisp_date("\"2024-03-01T00:00:00+0100\""), "2024-03-01");
+    }
+}
@@ -16,8 +16,8 @@ pub use super::files::init_files_table;
 pub async fn init_nodes_table(con: &SqlitePool) -> anyhow::Result<()> {
     const STMNT: &str = concat!(
         "CREATE TABLE nodes (id NOT NULL PRIMARY KEY, file NOT NULL, ",
-        "level NOT NULL, todo, priority, scheduled text, ",
-        "deadline text, title, properties, ",
+        "level NOT NULL, parent text, todo, priority, scheduled text, ",
+        "deadline text, title, properties, summary text, ",
         "FOREIGN KEY (file) REFERENCES files (file) ON DELETE CASCADE);"
     );
     con.execute(STMNT).await?;
@@ -59,6 +59,44 @@ pub async fn init_tags(con: &SqlitePool) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Dates a node is anchored to, either by active timestamps in its content or
+/// by being part of a datetree.
+pub async fn init_dates_table(con: &SqlitePool) -> anyhow::Result<()> {
+    const STMNT: &str = concat!(
+        "CREATE TABLE dates (node_id NOT NULL, date TEXT NOT NULL, ",
+        "FOREIGN KEY (node_id) REFERENCES nodes (id) ON DELETE CASCADE);"
+    );
+    const STMNT_INDEX: &str = "CREATE INDEX dates_date ON dates (date);";
+    con.execute(STMNT).await?;
+    con.execute(STMNT_INDEX).await?;
+    Ok(())
+}
+
+/// Citation keys cited with org-cite in the content of a node.
+pub async fn init_citations_table(con: &SqlitePool) -> anyhow::Result<()> {
+    const STMNT: &str = concat!(
+        "CREATE TABLE citations (node_id NOT NULL, cite_key TEXT NOT NULL, ",
+        "FOREIGN KEY (node_id) REFERENCES nodes (id) ON DELETE CASCADE);"
+    );
+    const STMNT_INDEX: &str = "CREATE INDEX citations_key ON citations (cite_key);";
+    con.execute(STMNT).await?;
+    con.execute(STMNT_INDEX).await?;
+    Ok(())
+}
+
+/// Refs of a node from its `ROAM_REFS` property, see
+/// [`classify_ref`](crate::transform::citations::classify_ref).
+pub async fn init_refs_table(con: &SqlitePool) -> anyhow::Result<()> {
+    const STMNT: &str = concat!(
+        "CREATE TABLE refs (node_id NOT NULL, ref TEXT NOT NULL, type TEXT NOT NULL, ",
+        "FOREIGN KEY (node_id) REFERENCES nodes (id) ON DELETE CASCADE);"
+    );
+    const STMNT_INDEX: &str = "CREATE INDEX refs_ref ON refs (ref);";
+    con.execute(STMNT).await?;
+    con.execute(STMNT_INDEX).await?;
+    Ok(())
+}
+
 pub async fn init_olp_table(con: &SqlitePool) -> anyhow::Result<()> {
     const OLP: &str = concat!(
         "CREATE TABLE olp (\n",
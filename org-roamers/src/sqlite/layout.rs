@@ -0,0 +1,50 @@
+use sqlx::{Executor, SqlitePool};
+
+pub async fn init_layout_table(con: &SqlitePool) -> anyhow::Result<()> {
+    const STMNT: &str = concat!(
+        "CREATE TABLE IF NOT EXISTS layout (user TEXT NOT NULL, node_id TEXT NOT NULL, ",
+        "x REAL NOT NULL, y REAL NOT NULL, PRIMARY KEY (user, node_id));"
+    );
+    con.execute(STMNT).await?;
+    Ok(())
+}
+
+/// Get the stored `(id, x, y)` positions of `user`.
+pub async fn get_layout(con: &SqlitePool, user: &str) -> anyhow::Result<Vec<(String, f64, f64)>> {
+    const STMNT: &str = "SELECT node_id, x, y FROM layout WHERE user = ?;";
+    let layout = sqlx::query_as(STMNT).bind(user).fetch_all(con).await?;
+    Ok(layout)
+}
+
+pub async fn insert_positions(
+    con: &SqlitePool,
+    user: &str,
+    positions: &[(String, f64, f64)],
+) -> anyhow::Result<()> {
+    const STMNT: &str = concat!(
+        "INSERT OR REPLACE INTO layout (user, node_id, x, y)\n",
+        "VALUES (?, ?, ?, ?);"
+    );
+
+    let mut tx = con.begin().await?;
+    for (id, x, y) in positions {
+        sqlx::query(STMNT)
+            .bind(user)
+            .bind(id)
+            .bind(x)
+            .bind(y)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn clear_layout(con: &SqlitePool, user: &str) -> anyhow::Result<()> {
+    sqlx::query("DELETE FROM layout WHERE user = ?;")
+        .bind(user)
+        .execute(con)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use sqlx::SqlitePool;
+
+/// `id:` links whose destination is not a known node, as
+/// `(source, source title, dest)`.
+pub async fn get_broken_id_links(
+    con: &SqlitePool,
+) -> anyhow::Result<Vec<(String, String, String)>> {
+    const STMNT: &str = concat!(
+        "SELECT l.source, n.title, l.dest FROM links l\n",
+        "JOIN nodes n ON n.id = l.source\n",
+        "WHERE l.type = 'id' AND l.dest NOT IN (SELECT id FROM nodes)\n",
+        "ORDER BY n.title, l.source, l.dest;"
+    );
+    let links = sqlx::query_as(STMNT).fetch_all(con).await?;
+    Ok(links)
+}
+
+/// All `http` and `https` links as `(source, source title, type, url)`.
+pub async fn get_external_links(
+    con: &SqlitePool,
+) -> anyhow::Result<Vec<(String, String, String, String)>> {
+    const STMNT: &str = concat!(
+        "SELECT DISTINCT l.source, n.title, l.type, l.dest FROM links l\n",
+        "JOIN nodes n ON n.id = l.source\n",
+        "WHERE l.type IN ('http', 'https')\n",
+        "ORDER BY n.title, l.source, l.dest;"
+    );
+    let links = sqlx::query_as(STMNT).fetch_all(con).await?;
+    Ok(links)
+}
+
+/// `id:` links pointing to `id` as `(source, title, file, tags)`. Tags are
+/// comma separated.
+pub async fn get_incoming(
+    con: &SqlitePool,
+    id: &str,
+) -> anyhow::Result<Vec<(String, String, String, Option<String>)>> {
+    const STMNT: &str = concat!(
+        "SELECT n.id, n.title, n.file, GROUP_CONCAT(DISTINCT t.tag) FROM links l\n",
+        "JOIN nodes n ON n.id = l.source\n",
+        "LEFT JOIN tags t ON t.node_id = n.id\n",
+        "WHERE l.type = 'id' AND l.dest = ?\n",
+        "GROUP BY n.id ORDER BY n.title, n.id;"
+    );
+    let links = sqlx::query_as(STMNT).bind(id).fetch_all(con).await?;
+    Ok(links)
+}
+
+/// Known nodes linked from `id` as `(dest, title, file, tags)`. Tags are
+/// comma separated.
+pub async fn get_outgoing(
+    con: &SqlitePool,
+    id: &str,
+) -> anyhow::Result<Vec<(String, String, String, Option<String>)>> {
+    const STMNT: &str = concat!(
+        "SELECT n.id, n.title, n.file, GROUP_CONCAT(DISTINCT t.tag) FROM links l\n",
+        "JOIN nodes n ON n.id = l.dest\n",
+        "LEFT JOIN tags t ON t.node_id = n.id\n",
+        "WHERE l.type = 'id' AND l.source = ?\n",
+        "GROUP BY n.id ORDER BY n.title, n.id;"
+    );
+    let links = sqlx::query_as(STMNT).bind(id).fetch_all(con).await?;
+    Ok(links)
+}
@@ -1,24 +1,173 @@
-use sqlx::SqlitePool;
+use std::path::Path;
 
+use sqlx::{sqlite::SqliteConnectOptions, SqlitePool};
+
+pub mod aliases;
+pub mod audit;
+pub mod diagnostics;
 pub mod files;
+pub mod fts;
+pub mod history;
+pub mod import;
 pub mod init;
+pub mod layout;
+pub mod links;
 pub mod olp;
+pub mod pins;
+pub mod preferences;
 pub mod rebuild;
+pub mod review;
+pub mod tasks;
+pub mod trash;
+pub mod views;
+
+/// Version of the index tables. Bump it on every schema change of a table
+/// in [`INDEX_TABLES`], databases with another version rebuild them on
+/// startup. Tables of user state are kept, they are created if missing and
+/// must stay compatible.
+const SCHEMA_VERSION: i64 = 9;
+
+/// Tables derived from the org files, which are filled again by indexing.
+/// Dropping `files_fts` also drops its shadow tables.
+const INDEX_TABLES: &[&str] = &[
+    "files",
+    "nodes",
+    "links",
+    "aliases",
+    "tags",
+    "olp",
+    "dates",
+    "citations",
+    "refs",
+    "tasks",
+    "file_diagnostics",
+    "files_fts",
+];
 
-pub async fn init_db() -> anyhow::Result<SqlitePool> {
-    // Use a named in-memory database that's shared across all connections in the pool
-    let pool = SqlitePool::connect("sqlite:file:org-roamers-db?mode=memory&cache=shared").await?;
+/// Open the database at `path`, creating it if missing. Without a path the
+/// database is kept in memory and the user state is lost on restart.
+pub async fn init_db(path: Option<&Path>) -> anyhow::Result<SqlitePool> {
+    if path.is_none() {
+        tracing::warn!(
+            "No database.path configured, preferences, pins and history are lost on restart"
+        );
+    }
+
+    let pool = match path {
+        Some(path) => {
+            let options = SqliteConnectOptions::new()
+                .filename(path)
+                .create_if_missing(true);
+            SqlitePool::connect_with(options).await?
+        }
+        // Use a named in-memory database that's shared across all connections in the pool
+        None => SqlitePool::connect("sqlite:file:org-roamers-db?mode=memory&cache=shared").await?,
+    };
 
     sqlx::query("PRAGMA foreign_keys = ON;")
         .execute(&pool)
         .await?;
 
-    init::init_files_table(&pool).await?;
-    init::init_nodes_table(&pool).await?;
-    init::init_links_table(&pool).await?;
-    init::init_aliases(&pool).await?;
-    init::init_tags(&pool).await?;
-    init::init_olp_table(&pool).await?;
+    let version: i64 = sqlx::query_scalar("PRAGMA user_version;")
+        .fetch_one(&pool)
+        .await?;
+    if version != SCHEMA_VERSION {
+        if version != 0 {
+            tracing::warn!("Database has schema version {version}, rebuilding the index");
+            drop_index_tables(&pool).await?;
+        }
+        init_index_tables(&pool).await?;
+    }
+
+    preferences::init_preferences_table(&pool).await?;
+    pins::init_pins_table(&pool).await?;
+    review::init_review_table(&pool).await?;
+    layout::init_layout_table(&pool).await?;
+    history::init_history_tables(&pool).await?;
+    audit::init_audit_table(&pool).await?;
+    trash::init_trash_table(&pool).await?;
+    views::init_views_table(&pool).await?;
+
+    sqlx::query(&format!("PRAGMA user_version = {SCHEMA_VERSION};"))
+        .execute(&pool)
+        .await?;
 
     Ok(pool)
 }
+
+async fn init_index_tables(pool: &SqlitePool) -> anyhow::Result<()> {
+    init::init_files_table(pool).await?;
+    init::init_nodes_table(pool).await?;
+    init::init_links_table(pool).await?;
+    init::init_aliases(pool).await?;
+    init::init_tags(pool).await?;
+    init::init_olp_table(pool).await?;
+    init::init_dates_table(pool).await?;
+    init::init_citations_table(pool).await?;
+    init::init_refs_table(pool).await?;
+    tasks::init_tasks_table(pool).await?;
+    diagnostics::init_diagnostics_table(pool).await?;
+    fts::init_fts_table(pool).await?;
+    Ok(())
+}
+
+/// Drop the [`INDEX_TABLES`] of an outdated database.
+async fn drop_index_tables(pool: &SqlitePool) -> anyhow::Result<()> {
+    // Foreign keys are only disabled on this connection.
+    let mut con = pool.acquire().await?;
+    sqlx::query("PRAGMA foreign_keys = OFF;")
+        .execute(&mut *con)
+        .await?;
+    for table in INDEX_TABLES {
+        sqlx::query(&format!("DROP TABLE IF EXISTS {table};"))
+            .execute(&mut *con)
+            .await?;
+    }
+    sqlx::query("PRAGMA foreign_keys = ON;")
+        .execute(&mut *con)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_upgrade_keeps_user_state() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("roam.db");
+
+        let pool = init_db(Some(&path)).await.unwrap();
+        fts::index_file(&pool, "a.org", "some content")
+            .await
+            .unwrap();
+        pins::insert_pin(&pool, "me", "node").await.unwrap();
+        preferences::set_preference(&pool, "me", "theme", &serde_json::json!("dark"))
+            .await
+            .unwrap();
+        sqlx::query("PRAGMA user_version = 6;")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+
+        let pool = init_db(Some(&path)).await.unwrap();
+        let version: i64 = sqlx::query_scalar("PRAGMA user_version;")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+        let indexed: i64 = sqlx::query_scalar("SELECT count(*) FROM files_fts;")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(indexed, 0);
+        let pins = pins::get_pins(&pool, "me").await.unwrap();
+        assert_eq!(pins, vec![("node".to_string(), None)]);
+        let preferences = preferences::get_preferences(&pool, "me").await.unwrap();
+        assert_eq!(preferences["theme"], "dark");
+    }
+}
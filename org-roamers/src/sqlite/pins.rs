@@ -0,0 +1,73 @@
+use sqlx::{Executor, SqlitePool};
+
+/// Pins intentionally do not reference `nodes`, because nodes are deleted and
+/// reinserted whenever their file changes.
+pub async fn init_pins_table(con: &SqlitePool) -> anyhow::Result<()> {
+    const STMNT: &str = concat!(
+        "CREATE TABLE IF NOT EXISTS pins (user TEXT NOT NULL, node_id TEXT NOT NULL, ",
+        "PRIMARY KEY (user, node_id));"
+    );
+    con.execute(STMNT).await?;
+    Ok(())
+}
+
+/// Get all pinned nodes of `user` together with their title. The title is
+/// `None` if the node no longer exists.
+pub async fn get_pins(
+    con: &SqlitePool,
+    user: &str,
+) -> anyhow::Result<Vec<(String, Option<String>)>> {
+    const STMNT: &str = concat!(
+        "SELECT p.node_id, n.title FROM pins p\n",
+        "LEFT JOIN nodes n ON n.id = p.node_id\n",
+        "WHERE p.user = ?\n",
+        "ORDER BY p.rowid;"
+    );
+    let pins = sqlx::query_as(STMNT).bind(user).fetch_all(con).await?;
+    Ok(pins)
+}
+
+pub async fn insert_pin(con: &SqlitePool, user: &str, id: &str) -> anyhow::Result<()> {
+    const STMNT: &str = "INSERT OR IGNORE INTO pins (user, node_id) VALUES (?, ?);";
+    sqlx::query(STMNT).bind(user).bind(id).execute(con).await?;
+    Ok(())
+}
+
+/// Returns `true` if a pin was removed.
+pub async fn delete_pin(con: &SqlitePool, user: &str, id: &str) -> anyhow::Result<bool> {
+    const STMNT: &str = "DELETE FROM pins WHERE user = ? AND node_id = ?;";
+    let result = sqlx::query(STMNT).bind(user).bind(id).execute(con).await?;
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::sqlite::init_db;
+
+    #[tokio::test]
+    async fn test_pin_unpin_list() {
+        let temp_dir = TempDir::new().unwrap();
+        let pool = init_db(Some(&temp_dir.path().join("roam.db")))
+            .await
+            .unwrap();
+
+        insert_pin(&pool, "me", "b").await.unwrap();
+        insert_pin(&pool, "me", "a").await.unwrap();
+        insert_pin(&pool, "me", "b").await.unwrap();
+        insert_pin(&pool, "other", "c").await.unwrap();
+
+        let pins = get_pins(&pool, "me").await.unwrap();
+        assert_eq!(pins, vec![("b".to_string(), None), ("a".to_string(), None)]);
+
+        assert!(delete_pin(&pool, "me", "b").await.unwrap());
+        assert!(!delete_pin(&pool, "me", "b").await.unwrap());
+        assert_eq!(
+            get_pins(&pool, "me").await.unwrap(),
+            vec![("a".to_string(), None)]
+        );
+        assert_eq!(get_pins(&pool, "other").await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use sqlx::{Executor, SqlitePool};
+
+/// Owner used for preferences if authentication is disabled.
+pub const GLOBAL_USER: &str = "";
+
+pub async fn init_preferences_table(con: &SqlitePool) -> anyhow::Result<()> {
+    const STMNT: &str = concat!(
+        "CREATE TABLE IF NOT EXISTS preferences (user TEXT NOT NULL, key TEXT NOT NULL, ",
+        "value TEXT NOT NULL, PRIMARY KEY (user, key));"
+    );
+    con.execute(STMNT).await?;
+    Ok(())
+}
+
+/// Get all preferences of `user`. Values are stored as JSON.
+pub async fn get_preferences(
+    con: &SqlitePool,
+    user: &str,
+) -> anyhow::Result<HashMap<String, serde_json::Value>> {
+    const STMNT: &str = "SELECT key, value FROM preferences WHERE user = ?;";
+
+    let rows: Vec<(String, String)> = sqlx::query_as(STMNT).bind(user).fetch_all(con).await?;
+
+    let mut preferences = HashMap::with_capacity(rows.len());
+    for (key, value) in rows {
+        match serde_json::from_str(&value) {
+            Ok(value) => {
+                preferences.insert(key, value);
+            }
+            Err(err) => tracing::warn!("Ignoring malformed preference {key}: {err}"),
+        }
+    }
+
+    Ok(preferences)
+}
+
+pub async fn set_preference(
+    con: &SqlitePool,
+    user: &str,
+    key: &str,
+    value: &serde_json::Value,
+) -> anyhow::Result<()> {
+    const STMNT: &str = concat!(
+        "INSERT OR REPLACE INTO preferences (user, key, value)\n",
+        "VALUES (?, ?, ?);"
+    );
+    sqlx::query(STMNT)
+        .bind(user)
+        .bind(key)
+        .bind(value.to_string())
+        .execute(con)
+        .await?;
+    Ok(())
+}
+
+/// Returns `true` if a preference was removed.
+pub async fn delete_preference(con: &SqlitePool, user: &str, key: &str) -> anyhow::Result<bool> {
+    const STMNT: &str = "DELETE FROM preferences WHERE user = ? AND key = ?;";
+    let result = sqlx::query(STMNT).bind(user).bind(key).execute(con).await?;
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::sqlite::init_db;
+
+    #[tokio::test]
+    async fn test_preferences_are_per_user() {
+        let temp_dir = TempDir::new().unwrap();
+        let pool = init_db(Some(&temp_dir.path().join("roam.db")))
+            .await
+            .unwrap();
+
+        set_preference(&pool, "me", "theme", &json!("dark"))
+            .await
+            .unwrap();
+        set_preference(&pool, "me", "theme", &json!({ "name": "light" }))
+            .await
+            .unwrap();
+        set_preference(&pool, "other", "zoom", &json!(2))
+            .await
+            .unwrap();
+
+        let preferences = get_preferences(&pool, "me").await.unwrap();
+        assert_eq!(preferences.len(), 1);
+        assert_eq!(preferences["theme"], json!({ "name": "light" }));
+
+        assert!(delete_preference(&pool, "me", "theme").await.unwrap());
+        assert!(!delete_preference(&pool, "me", "theme").await.unwrap());
+        assert!(get_preferences(&pool, "me").await.unwrap().is_empty());
+        assert_eq!(get_preferences(&pool, "other").await.unwrap()["zoom"], 2);
+    }
+}
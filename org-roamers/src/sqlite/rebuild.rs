@@ -1,6 +1,67 @@
+use std::path::Path;
+
 use sqlx::SqlitePool;
 
 use crate::sqlite::olp;
+use crate::transform::citations;
+
+/// Insert a file or update its metadata. Unlike
+/// [`insert_file`](crate::sqlite::files::insert_file) this keeps the nodes of
+/// the file.
+pub async fn upsert_file<P: AsRef<Path>>(
+    con: &SqlitePool,
+    filename: P,
+    hash: u64,
+    mtime: Option<i64>,
+    created: Option<i64>,
+) -> anyhow::Result<()> {
+    const STMNT: &str = concat!(
+        "INSERT INTO files (file, hash, mtime, created) VALUES (?, ?, ?, ?)\n",
+        "ON CONFLICT (file) DO UPDATE SET hash = excluded.hash, ",
+        "mtime = excluded.mtime, created = excluded.created;"
+    );
+    sqlx::query(STMNT)
+        .bind(filename.as_ref().to_string_lossy())
+        .bind(hash as i64)
+        .bind(mtime)
+        .bind(created)
+        .execute(con)
+        .await?;
+    Ok(())
+}
+
+/// Remove a node. Its tags, aliases, links, etc. are removed by the foreign
+/// key constraints.
+pub async fn delete_node(con: &SqlitePool, id: &str) -> anyhow::Result<()> {
+    sqlx::query("DELETE FROM nodes WHERE id = ?;")
+        .bind(id)
+        .execute(con)
+        .await?;
+    Ok(())
+}
+
+/// Stored nodes of `file` as `(id, title, parent)`.
+pub async fn get_file_nodes(
+    con: &SqlitePool,
+    file: &str,
+) -> anyhow::Result<Vec<(String, String, Option<String>)>> {
+    let nodes = sqlx::query_as("SELECT id, title, parent FROM nodes WHERE file = ?;")
+        .bind(file)
+        .fetch_all(con)
+        .await?;
+    Ok(nodes)
+}
+
+/// Stored `id` links starting in `file` as `(source, dest)`.
+pub async fn get_file_links(con: &SqlitePool, file: &str) -> anyhow::Result<Vec<(String, String)>> {
+    const STMNT: &str = concat!(
+        "SELECT l.source, l.dest FROM links l\n",
+        "JOIN nodes n ON n.id = l.source\n",
+        "WHERE n.file = ? AND l.type = 'id';"
+    );
+    let links = sqlx::query_as(STMNT).bind(file).fetch_all(con).await?;
+    Ok(links)
+}
 
 // TODO: remove file. This also requires updating the table def.
 #[allow(clippy::too_many_arguments)]
@@ -9,28 +70,33 @@ pub async fn insert_node(
     id: &str,
     file: &str,
     level: u64,
-    todo: bool,
-    priority: usize,
-    scheduled: &str,
-    deadline: &str,
+    parent: Option<&str>,
+    todo: Option<&str>,
+    priority: Option<&str>,
+    scheduled: Option<&str>,
+    deadline: Option<&str>,
     title: &str,
+    properties: &str,
+    summary: &str,
     olp: &[String],
 ) -> anyhow::Result<()> {
     const STMNT: &str = concat!(
-        "INSERT OR REPLACE INTO nodes (id, file, level, todo, priority, scheduled, deadline, title, properties)\n",
-        "VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?);"
+        "INSERT OR REPLACE INTO nodes (id, file, level, parent, todo, priority, scheduled, deadline, title, properties, summary)\n",
+        "VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?);"
     );
 
     sqlx::query(STMNT)
         .bind(id)
         .bind(file)
         .bind(level as u32)
+        .bind(parent)
         .bind(todo)
-        .bind(priority as u32)
+        .bind(priority)
         .bind(scheduled)
         .bind(deadline)
         .bind(title)
-        .bind(Option::<String>::None) // properties - not currently used
+        .bind(properties)
+        .bind(summary)
         .execute(con)
         .await?;
 
@@ -57,8 +123,42 @@ pub async fn insert_alias(con: &SqlitePool, id: &str, alias: &str) -> anyhow::Re
     Ok(())
 }
 
-pub async fn insert_link(con: &SqlitePool, source: &str, dest: &str) -> anyhow::Result<()> {
-    const TYPE: &str = "id";
+pub async fn insert_date(con: &SqlitePool, id: &str, date: &str) -> anyhow::Result<()> {
+    const STMNT: &str = "INSERT INTO dates (node_id, date) VALUES (?, ?);";
+    sqlx::query(STMNT).bind(id).bind(date).execute(con).await?;
+    Ok(())
+}
+
+pub async fn insert_citation(con: &SqlitePool, id: &str, key: &str) -> anyhow::Result<()> {
+    const STMNT: &str = "INSERT INTO citations (node_id, cite_key) VALUES (?, ?);";
+    sqlx::query(STMNT).bind(id).bind(key).execute(con).await?;
+    Ok(())
+}
+
+/// Insert a `ROAM_REFS` entry. Unrecognized entries are skipped.
+pub async fn insert_ref(con: &SqlitePool, id: &str, reference: &str) -> anyhow::Result<()> {
+    const STMNT: &str = "INSERT INTO refs (node_id, ref, type) VALUES (?, ?, ?);";
+    let Some((reference, ref_type)) = citations::classify_ref(reference) else {
+        tracing::warn!("Unrecognized ref {reference} of node {id}");
+        return Ok(());
+    };
+    sqlx::query(STMNT)
+        .bind(id)
+        .bind(reference)
+        .bind(ref_type)
+        .execute(con)
+        .await?;
+    Ok(())
+}
+
+/// Insert a link. `link_type` is `id` for links to nodes and the scheme (e.g.
+/// `https`) for external links.
+pub async fn insert_link(
+    con: &SqlitePool,
+    source: &str,
+    dest: &str,
+    link_type: &str,
+) -> anyhow::Result<()> {
     const PROPERTIES: &str = "";
     const POS: u32 = 0;
     const STMNT: &str = concat!(
@@ -69,7 +169,7 @@ pub async fn insert_link(con: &SqlitePool, source: &str, dest: &str) -> anyhow::
         .bind(POS)
         .bind(source)
         .bind(dest)
-        .bind(TYPE)
+        .bind(link_type)
         .bind(PROPERTIES)
         .execute(con)
         .await?;
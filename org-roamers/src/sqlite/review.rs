@@ -0,0 +1,110 @@
+use sqlx::{Executor, SqlitePool};
+
+/// Like pins, reviews do not reference `nodes`, so they survive changes of
+/// the file of a node.
+pub async fn init_review_table(con: &SqlitePool) -> anyhow::Result<()> {
+    const STMNT: &str = concat!(
+        "CREATE TABLE IF NOT EXISTS reviews (user TEXT NOT NULL, node_id TEXT NOT NULL, ",
+        "interval INTEGER NOT NULL, ease REAL NOT NULL, repetitions INTEGER NOT NULL, ",
+        "last_review INTEGER NOT NULL, due INTEGER NOT NULL, ",
+        "PRIMARY KEY (user, node_id));"
+    );
+    con.execute(STMNT).await?;
+    Ok(())
+}
+
+/// `(interval, ease, repetitions, last_review, due)` of a reviewed node.
+pub type ReviewRow = (i64, f64, i64, i64, i64);
+
+pub async fn get_review(
+    con: &SqlitePool,
+    user: &str,
+    id: &str,
+) -> anyhow::Result<Option<ReviewRow>> {
+    const STMNT: &str = concat!(
+        "SELECT interval, ease, repetitions, last_review, due FROM reviews\n",
+        "WHERE user = ? AND node_id = ?;"
+    );
+    let review = sqlx::query_as(STMNT)
+        .bind(user)
+        .bind(id)
+        .fetch_optional(con)
+        .await?;
+    Ok(review)
+}
+
+pub async fn upsert_review(
+    con: &SqlitePool,
+    user: &str,
+    id: &str,
+    (interval, ease, repetitions, last_review, due): ReviewRow,
+) -> anyhow::Result<()> {
+    const STMNT: &str = concat!(
+        "INSERT OR REPLACE INTO reviews\n",
+        "(user, node_id, interval, ease, repetitions, last_review, due)\n",
+        "VALUES (?, ?, ?, ?, ?, ?, ?);"
+    );
+    sqlx::query(STMNT)
+        .bind(user)
+        .bind(id)
+        .bind(interval)
+        .bind(ease)
+        .bind(repetitions)
+        .bind(last_review)
+        .bind(due)
+        .execute(con)
+        .await?;
+    Ok(())
+}
+
+/// Nodes carrying one of `tags` that are due at `now` as `(id, title,
+/// review)`, most overdue first. Nodes that were never reviewed come first
+/// and have no review.
+pub async fn get_due(
+    con: &SqlitePool,
+    user: &str,
+    tags: &[String],
+    now: i64,
+) -> anyhow::Result<Vec<(String, String, Option<ReviewRow>)>> {
+    if tags.is_empty() {
+        return Ok(vec![]);
+    }
+    let placeholders = vec!["?"; tags.len()].join(", ");
+    let stmnt = format!(
+        concat!(
+            "SELECT n.id, n.title, r.interval, r.ease, r.repetitions, r.last_review, r.due\n",
+            "FROM nodes n\n",
+            "LEFT JOIN reviews r ON r.node_id = n.id AND r.user = ?\n",
+            "WHERE n.id IN (SELECT node_id FROM tags WHERE tag IN ({}))\n",
+            "AND (r.due IS NULL OR r.due <= ?)\n",
+            "ORDER BY r.due IS NOT NULL, r.due, n.title, n.id;"
+        ),
+        placeholders
+    );
+    type Row = (
+        String,
+        String,
+        Option<i64>,
+        Option<f64>,
+        Option<i64>,
+        Option<i64>,
+        Option<i64>,
+    );
+    let mut query = sqlx::query_as::<_, Row>(&stmnt).bind(user);
+    for tag in tags {
+        query = query.bind(tag);
+    }
+    let rows = query.bind(now).fetch_all(con).await?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(id, title, interval, ease, repetitions, last_review, due)| {
+                let review = match (interval, ease, repetitions, last_review, due) {
+                    (Some(i), Some(e), Some(r), Some(l), Some(d)) => Some((i, e, r, l, d)),
+                    _ => None,
+                };
+                (id, title, review)
+            },
+        )
+        .collect())
+}
@@ -0,0 +1,85 @@
+use sqlx::{Executor, SqlitePool};
+
+/// Headlines with a TODO keyword or planning, whether or not they have an
+/// id. Rows are dropped together with their file.
+pub async fn init_tasks_table(con: &SqlitePool) -> anyhow::Result<()> {
+    const STMNT: &str = concat!(
+        "CREATE TABLE tasks (file TEXT NOT NULL, node_id TEXT, title TEXT NOT NULL, ",
+        "level INTEGER NOT NULL, todo TEXT, priority TEXT, scheduled TEXT, deadline TEXT, ",
+        "FOREIGN KEY (file) REFERENCES files (file) ON DELETE CASCADE);"
+    );
+    const STMNT_SCHEDULED: &str = "CREATE INDEX tasks_scheduled ON tasks (scheduled);";
+    const STMNT_DEADLINE: &str = "CREATE INDEX tasks_deadline ON tasks (deadline);";
+    con.execute(STMNT).await?;
+    con.execute(STMNT_SCHEDULED).await?;
+    con.execute(STMNT_DEADLINE).await?;
+    Ok(())
+}
+
+/// `(title, file, node_id, todo, priority, scheduled, deadline)`
+pub type TaskRow = (
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+);
+
+pub async fn delete_file_tasks(con: &SqlitePool, file: &str) -> anyhow::Result<()> {
+    sqlx::query("DELETE FROM tasks WHERE file = ?;")
+        .bind(file)
+        .execute(con)
+        .await?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn insert_task(
+    con: &SqlitePool,
+    file: &str,
+    node_id: Option<&str>,
+    title: &str,
+    level: u64,
+    todo: Option<&str>,
+    priority: Option<&str>,
+    scheduled: Option<&str>,
+    deadline: Option<&str>,
+) -> anyhow::Result<()> {
+    const STMNT: &str = concat!(
+        "INSERT INTO tasks (file, node_id, title, level, todo, priority, scheduled, deadline)\n",
+        "VALUES (?, ?, ?, ?, ?, ?, ?, ?);"
+    );
+    sqlx::query(STMNT)
+        .bind(file)
+        .bind(node_id)
+        .bind(title)
+        .bind(level as i64)
+        .bind(todo)
+        .bind(priority)
+        .bind(scheduled)
+        .bind(deadline)
+        .execute(con)
+        .await?;
+    Ok(())
+}
+
+/// Tasks scheduled or due between the `YYYY-MM-DD` dates `from` and `to`,
+/// both inclusive.
+pub async fn get_tasks_between(
+    con: &SqlitePool,
+    from: &str,
+    to: &str,
+) -> anyhow::Result<Vec<TaskRow>> {
+    const STMNT: &str = concat!(
+        "SELECT title, file, node_id, todo, priority, scheduled, deadline FROM tasks\n",
+        "WHERE scheduled BETWEEN ?1 AND ?2 OR deadline BETWEEN ?1 AND ?2;"
+    );
+    let rows = sqlx::query_as(STMNT)
+        .bind(from)
+        .bind(to)
+        .fetch_all(con)
+        .await?;
+    Ok(rows)
+}
@@ -0,0 +1,85 @@
+use sqlx::{Executor, SqlitePool};
+
+/// Nodes of files that were moved to the trash. Like pins, this does not
+/// reference `nodes`, because the nodes are removed together with the file.
+pub async fn init_trash_table(con: &SqlitePool) -> anyhow::Result<()> {
+    const STMNT: &str = concat!(
+        "CREATE TABLE IF NOT EXISTS deleted_nodes (node_id TEXT NOT NULL, title TEXT, ",
+        "file TEXT NOT NULL, trash_path TEXT NOT NULL, ",
+        "deleted_at INTEGER NOT NULL, deleted_by TEXT);"
+    );
+    con.execute(STMNT).await?;
+    Ok(())
+}
+
+/// A row of the trash: `(node_id, title, file, trash_path, deleted_at, deleted_by)`.
+pub type TrashRow = (String, Option<String>, String, String, i64, Option<String>);
+
+/// Get the file and level of a node.
+pub async fn get_node_file(con: &SqlitePool, id: &str) -> anyhow::Result<Option<(String, i64)>> {
+    const STMNT: &str = "SELECT file, level FROM nodes WHERE id = ?;";
+    let row = sqlx::query_as(STMNT).bind(id).fetch_optional(con).await?;
+    Ok(row)
+}
+
+/// Copy all nodes of `file` into the trash. Must be called before the file
+/// is removed from `files`.
+pub async fn insert_deleted_file(
+    con: &SqlitePool,
+    file: &str,
+    trash_path: &str,
+    deleted_at: i64,
+    deleted_by: Option<&str>,
+) -> anyhow::Result<()> {
+    const STMNT: &str = concat!(
+        "INSERT INTO deleted_nodes ",
+        "(node_id, title, file, trash_path, deleted_at, deleted_by)\n",
+        "SELECT id, title, file, ?, ?, ? FROM nodes WHERE file = ?;"
+    );
+    sqlx::query(STMNT)
+        .bind(trash_path)
+        .bind(deleted_at)
+        .bind(deleted_by)
+        .bind(file)
+        .execute(con)
+        .await?;
+    Ok(())
+}
+
+/// Get the trash, most recently deleted first.
+pub async fn get_deleted(con: &SqlitePool) -> anyhow::Result<Vec<TrashRow>> {
+    const STMNT: &str = concat!(
+        "SELECT node_id, title, file, trash_path, deleted_at, deleted_by\n",
+        "FROM deleted_nodes ORDER BY deleted_at DESC, rowid;"
+    );
+    let rows = sqlx::query_as(STMNT).fetch_all(con).await?;
+    Ok(rows)
+}
+
+/// Get the most recent deletion of a node.
+pub async fn get_deleted_node(con: &SqlitePool, id: &str) -> anyhow::Result<Option<TrashRow>> {
+    const STMNT: &str = concat!(
+        "SELECT node_id, title, file, trash_path, deleted_at, deleted_by\n",
+        "FROM deleted_nodes WHERE node_id = ?\n",
+        "ORDER BY deleted_at DESC LIMIT 1;"
+    );
+    let row = sqlx::query_as(STMNT).bind(id).fetch_optional(con).await?;
+    Ok(row)
+}
+
+/// Remove all nodes that were deleted together in `trash_path`.
+pub async fn delete_trash_entry(con: &SqlitePool, trash_path: &str) -> anyhow::Result<()> {
+    const STMNT: &str = "DELETE FROM deleted_nodes WHERE trash_path = ?;";
+    sqlx::query(STMNT).bind(trash_path).execute(con).await?;
+    Ok(())
+}
+
+/// Get the distinct trashed files that were deleted before `before`.
+pub async fn get_expired(con: &SqlitePool, before: i64) -> anyhow::Result<Vec<String>> {
+    const STMNT: &str = "SELECT DISTINCT trash_path FROM deleted_nodes WHERE deleted_at < ?;";
+    let paths = sqlx::query_scalar(STMNT)
+        .bind(before)
+        .fetch_all(con)
+        .await?;
+    Ok(paths)
+}
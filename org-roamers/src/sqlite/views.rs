@@ -0,0 +1,57 @@
+use sqlx::{Executor, SqlitePool};
+
+/// Saved graph views. `view` holds the serialized view, so its fields can
+/// change without touching the table.
+pub async fn init_views_table(con: &SqlitePool) -> anyhow::Result<()> {
+    const STMNT: &str = concat!(
+        "CREATE TABLE IF NOT EXISTS graph_views (user TEXT NOT NULL, name TEXT NOT NULL, ",
+        "view TEXT NOT NULL, PRIMARY KEY (user, name));"
+    );
+    con.execute(STMNT).await?;
+    Ok(())
+}
+
+/// All `(name, view)` pairs of `user`, ordered by name.
+pub async fn get_views(con: &SqlitePool, user: &str) -> anyhow::Result<Vec<(String, String)>> {
+    const STMNT: &str = "SELECT name, view FROM graph_views WHERE user = ? ORDER BY name;";
+    let views = sqlx::query_as(STMNT).bind(user).fetch_all(con).await?;
+    Ok(views)
+}
+
+pub async fn get_view(con: &SqlitePool, user: &str, name: &str) -> anyhow::Result<Option<String>> {
+    const STMNT: &str = "SELECT view FROM graph_views WHERE user = ? AND name = ?;";
+    let view: Option<(String,)> = sqlx::query_as(STMNT)
+        .bind(user)
+        .bind(name)
+        .fetch_optional(con)
+        .await?;
+    Ok(view.map(|(view,)| view))
+}
+
+/// Create or replace a view.
+pub async fn insert_view(
+    con: &SqlitePool,
+    user: &str,
+    name: &str,
+    view: &str,
+) -> anyhow::Result<()> {
+    const STMNT: &str = "INSERT OR REPLACE INTO graph_views (user, name, view) VALUES (?, ?, ?);";
+    sqlx::query(STMNT)
+        .bind(user)
+        .bind(name)
+        .bind(view)
+        .execute(con)
+        .await?;
+    Ok(())
+}
+
+/// Returns `true` if a view was removed.
+pub async fn delete_view(con: &SqlitePool, user: &str, name: &str) -> anyhow::Result<bool> {
+    const STMNT: &str = "DELETE FROM graph_views WHERE user = ? AND name = ?;";
+    let result = sqlx::query(STMNT)
+        .bind(user)
+        .bind(name)
+        .execute(con)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
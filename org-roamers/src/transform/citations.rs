@@ -0,0 +1,123 @@
+//! Find citation keys in org text.
+
+/// Keys cited with org-cite, e.g. `[cite/t:see @doe2020 p. 4;@roe2021]`.
+pub fn cite_keys(text: &str) -> Vec<String> {
+    let mut keys = vec![];
+    let mut rest = text;
+    while let Some(start) = rest.find("[cite") {
+        rest = &rest[start + "[cite".len()..];
+        let Some(end) = rest.find(']') else {
+            break;
+        };
+        let (citation, after) = rest.split_at(end);
+        rest = after;
+        // Skip the style, e.g. `/t`, up to the colon.
+        let Some((_, references)) = citation.split_once(':') else {
+            continue;
+        };
+        for reference in references.split(';') {
+            if let Some(key) = reference
+                .split_once('@')
+                .and_then(|(_, key)| key_prefix(key))
+            {
+                if !keys.contains(&key) {
+                    keys.push(key);
+                }
+            }
+        }
+    }
+    keys
+}
+
+/// Key of a `ROAM_REFS` entry that names a cited work, i.e. `@key` or the
+/// org-ref forms `cite:key` and `cite:&key`.
+pub fn ref_key(reference: &str) -> Option<String> {
+    let key = reference
+        .strip_prefix('@')
+        .or_else(|| reference.strip_prefix("cite:&"))
+        .or_else(|| reference.strip_prefix("cite:"))?;
+    key_prefix(key)
+}
+
+/// Stored form of a `ROAM_REFS` entry as `(ref, type)`. Cited works are
+/// stored by key with the type `cite`, urls with their scheme as type. Other
+/// entries are not recognized, like in org-roam.
+pub fn classify_ref(reference: &str) -> Option<(String, String)> {
+    if let Some(key) = ref_key(reference) {
+        return Some((key, "cite".to_string()));
+    }
+    let (scheme, rest) = reference.split_once("://")?;
+    let valid = !scheme.is_empty()
+        && !rest.is_empty()
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '+');
+    valid.then(|| (reference.to_string(), scheme.to_lowercase()))
+}
+
+/// Split a `ROAM_REFS` value. Refs are separated by whitespace and may be
+/// wrapped in an org link or quotes.
+pub fn parse_refs(refs: &str) -> Vec<String> {
+    refs.split_whitespace()
+        .map(|r| {
+            let r = r.strip_prefix("[[").unwrap_or(r);
+            let r = r.strip_suffix("]]").unwrap_or(r);
+            r.trim_matches('"').to_string()
+        })
+        .filter(|r| !r.is_empty())
+        .collect()
+}
+
+/// The leading characters of `text` that may form a key.
+fn key_prefix(text: &str) -> Option<String> {
+    let end = text
+        .find(|c: char| c.is_whitespace() || matches!(c, ';' | ']' | ',' | '"'))
+        .unwrap_or(text.len());
+    let key = text[..end].trim_end_matches(['.', ':']);
+    (!key.is_empty()).then(|| key.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cite_keys() {
+        let text = concat!(
+            "As shown [cite:@doe2020] and [cite/t:see @roe2021 p. 4;@doe2020;@smith-jones_2019].\n",
+            "Mail me@example.com, [cite: no key] [cite:@unterminated"
+        );
+        assert_eq!(
+            cite_keys(text),
+            vec!["doe2020", "roe2021", "smith-jones_2019"]
+        );
+    }
+
+    #[test]
+    fn test_ref_key() {
+        assert_eq!(ref_key("@doe2020"), Some("doe2020".to_string()));
+        assert_eq!(ref_key("cite:doe2020"), Some("doe2020".to_string()));
+        assert_eq!(ref_key("cite:&doe2020"), Some("doe2020".to_string()));
+        assert_eq!(ref_key("https://example.com"), None);
+    }
+
+    #[test]
+    fn test_classify_ref() {
+        let stored = |r: &str, t: &str| Some((r.to_string(), t.to_string()));
+        assert_eq!(classify_ref("@doe2020"), stored("doe2020", "cite"));
+        assert_eq!(
+            classify_ref("https://example.com"),
+            stored("https://example.com", "https")
+        );
+        assert_eq!(classify_ref("example.com"), None);
+    }
+
+    #[test]
+    fn test_parse_refs() {
+        assert_eq!(
+            parse_refs("https://example.com  [[https://rust-lang.org]] \"@doe2020\""),
+            vec!["https://example.com", "https://rust-lang.org", "@doe2020"]
+        );
+        assert!(parse_refs("  ").is_empty());
+    }
+}
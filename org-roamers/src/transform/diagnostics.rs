@@ -0,0 +1,143 @@
+use crate::server::types::{DiagnosticSeverity, FileDiagnostic, RoamID};
+
+/// Syntax problems that make orgize silently drop parts of a file, e.g. a
+/// property drawer without `:END:`, whose `ID` is then never seen.
+pub fn check(content: &str) -> Vec<FileDiagnostic> {
+    let mut diagnostics = vec![];
+    // Line of the open property drawer and block, with the block name
+    let mut drawer: Option<usize> = None;
+    let mut block: Option<(usize, String)> = None;
+
+    for (index, line) in content.lines().enumerate() {
+        let number = index + 1;
+        let trimmed = line.trim();
+        let lower = trimmed.to_ascii_lowercase();
+
+        if let Some((_, name)) = &block {
+            if lower.strip_prefix("#+end_") == Some(name.as_str()) {
+                block = None;
+            }
+            continue;
+        }
+
+        if line.starts_with('*') && line.trim_start_matches('*').starts_with(' ') {
+            if let Some(start) = drawer.take() {
+                diagnostics.push(unterminated(start, "Property drawer"));
+            }
+            continue;
+        }
+
+        if let Some(name) = lower.strip_prefix("#+begin_") {
+            let name = name.split_whitespace().next().unwrap_or_default();
+            block = Some((number, name.to_string()));
+        } else if lower == ":properties:" {
+            if let Some(start) = drawer.replace(number) {
+                diagnostics.push(unterminated(start, "Property drawer"));
+            }
+        } else if lower == ":end:" {
+            drawer = None;
+        } else if drawer.is_some() && lower.starts_with(":id:") && trimmed[4..].trim().is_empty() {
+            diagnostics.push(FileDiagnostic {
+                severity: DiagnosticSeverity::Warning,
+                line: Some(number),
+                column: Some(line.len() - line.trim_start().len() + 1),
+                node: None,
+                message: "Empty ID property".to_string(),
+            });
+        }
+    }
+
+    if let Some(start) = drawer {
+        diagnostics.push(unterminated(start, "Property drawer"));
+    }
+    if let Some((start, name)) = block {
+        diagnostics.push(unterminated(start, &format!("Block {name}")));
+    }
+    diagnostics
+}
+
+fn unterminated(line: usize, what: &str) -> FileDiagnostic {
+    FileDiagnostic {
+        severity: DiagnosticSeverity::Error,
+        line: Some(line),
+        column: Some(1),
+        node: None,
+        message: format!("{what} is never closed"),
+    }
+}
+
+/// Fill in the position of diagnostics that only name their node, from the
+/// line of its `ID` property.
+pub fn locate_nodes(content: &str, diagnostics: &mut [FileDiagnostic]) {
+    for diagnostic in diagnostics.iter_mut().filter(|d| d.line.is_none()) {
+        let Some(node) = &diagnostic.node else {
+            continue;
+        };
+        let position = content.lines().enumerate().find_map(|(index, line)| {
+            let trimmed = line.trim_start();
+            let value = trimmed
+                .get(..4)?
+                .eq_ignore_ascii_case(":id:")
+                .then(|| &trimmed[4..])?;
+            (RoamID::normalize(value) == *node).then(|| (index + 1, line.len() - trimmed.len() + 1))
+        });
+        if let Some((line, column)) = position {
+            diagnostic.line = Some(line);
+            diagnostic.column = Some(column);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let content = concat!(
+            "* A\n",
+            ":PROPERTIES:\n",
+            ":ID: a\n",
+            "* B\n",
+            "  :PROPERTIES:\n",
+            "  :ID:\n",
+            "  :END:\n",
+            "#+begin_src org\n",
+            ":PROPERTIES:\n",
+            "#+end_src\n",
+            "#+BEGIN_QUOTE\n",
+            "text\n",
+        );
+        let diagnostics = check(content);
+        let found: Vec<(Option<usize>, Option<usize>, &str)> = diagnostics
+            .iter()
+            .map(|d| (d.line, d.column, d.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Some(2), Some(1), "Property drawer is never closed"),
+                (Some(6), Some(3), "Empty ID property"),
+                (Some(11), Some(1), "Block quote is never closed"),
+            ]
+        );
+        assert!(check("* A\n:PROPERTIES:\n:ID: a\n:END:\n").is_empty());
+    }
+
+    #[test]
+    fn test_locate_nodes() {
+        let mut diagnostics = vec![FileDiagnostic {
+            severity: DiagnosticSeverity::Error,
+            line: None,
+            column: None,
+            node: Some("b".into()),
+            message: "Duplicate".into(),
+        }];
+        locate_nodes(
+            "* A\n:PROPERTIES:\n:ID: a\n:END:\n* B\n  :properties:\n  :id:   b\n",
+            &mut diagnostics,
+        );
+        assert_eq!(diagnostics[0].line, Some(7));
+        assert_eq!(diagnostics[0].column, Some(3));
+    }
+}
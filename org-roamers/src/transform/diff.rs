@@ -0,0 +1,108 @@
+//! Graph of a single file, as stored in the database and as freshly parsed.
+//! [`crate::graph::diff`] of the two is broadcast after a file was indexed,
+//! instead of letting clients refetch `/graph`.
+
+use std::collections::HashMap;
+
+use crate::{
+    graph::{GraphData, NodeKind, RoamLink, RoamNode},
+    transform::node_builder::OrgNode,
+};
+
+/// Graph of the stored nodes of a file. `nodes` are `(id, title, parent)`,
+/// `links` are the `id` links starting in the file as `(source, dest)`.
+pub fn stored_graph(
+    nodes: Vec<(String, String, Option<String>)>,
+    links: Vec<(String, String)>,
+) -> GraphData {
+    file_graph(nodes, links)
+}
+
+/// Graph of the parsed nodes of a file.
+pub fn parsed_graph(nodes: &[OrgNode]) -> GraphData {
+    let links = nodes
+        .iter()
+        .flat_map(|node| {
+            node.links
+                .iter()
+                .map(|(dest, _)| (node.uuid.clone(), dest.clone()))
+        })
+        .collect();
+    let nodes = nodes
+        .iter()
+        .map(|node| (node.uuid.clone(), node.title.clone(), node.parent.clone()))
+        .collect();
+    file_graph(nodes, links)
+}
+
+fn file_graph(
+    nodes: Vec<(String, String, Option<String>)>,
+    links: Vec<(String, String)>,
+) -> GraphData {
+    let mut num_links: HashMap<&str, usize> = HashMap::new();
+    for (source, _) in &links {
+        *num_links.entry(source).or_default() += 1;
+    }
+
+    GraphData {
+        nodes: nodes
+            .iter()
+            .map(|(id, title, parent)| RoamNode {
+                title: title.as_str().into(),
+                id: id.as_str().into(),
+                parent: parent.as_deref().unwrap_or_default().into(),
+                num_links: num_links.get(id.as_str()).copied().unwrap_or_default(),
+                pinned: false,
+                kind: NodeKind::Node,
+                summary: None,
+                group: None,
+            })
+            .collect(),
+        links: links
+            .iter()
+            .map(|(source, dest)| RoamLink {
+                from: source.as_str().into(),
+                to: dest.as_str().into(),
+            })
+            .collect(),
+        layout: Default::default(),
+        aggregated: false,
+        zoom: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{diff, RoamID};
+
+    fn node(id: &str, title: &str, links: &[&str]) -> OrgNode {
+        OrgNode {
+            uuid: id.to_string(),
+            title: title.to_string(),
+            links: links
+                .iter()
+                .map(|dest| (dest.to_string(), String::new()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_file_diff() {
+        let stored = stored_graph(
+            vec![
+                ("a".into(), "A".into(), None),
+                ("b".into(), "B".into(), Some("a".into())),
+            ],
+            vec![("a".into(), "x".into())],
+        );
+        let nodes = vec![node("a", "A", &["x"]), node("c", "C", &[])];
+
+        let update = diff(&stored, &parsed_graph(&nodes));
+        assert!(update.updated_nodes.is_empty());
+        assert_eq!(update.new_nodes.len(), 1);
+        assert_eq!(update.removed_nodes, vec![RoamID::from("b")]);
+        assert!(update.new_links.is_empty() && update.removed_links.is_empty());
+    }
+}
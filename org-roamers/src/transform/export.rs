@@ -0,0 +1,229 @@
+//! Export of the vault to a static HTML site, so it can be published without
+//! running the server. Every node gets a page named by [`page_name`],
+//! `index.html` lists all nodes by title and the files the pages link to are
+//! copied below `assets/`.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use orgize::{export::HtmlEscape, Org};
+use sqlx::SqlitePool;
+
+use crate::cache::OrgCache;
+use crate::config::HtmlExportSettings;
+use crate::server::services::{asset_service::resolve_asset, bundle_service::attachments};
+use crate::sqlite::{aliases, files};
+use crate::transform::{html::HtmlExport, subtree::Subtree, title::TitleSanitizer};
+
+const STYLE: &str = concat!(
+    "body{max-width:48rem;margin:2rem auto;padding:0 1rem;",
+    "font-family:sans-serif;line-height:1.5}",
+    "nav{margin-bottom:1rem}",
+    "pre{overflow-x:auto}",
+    "table{border-collapse:collapse}td{border:1px solid #ccc;padding:0 .3rem}",
+    ".tags{color:#666}",
+);
+
+/// File name of the page of the node `id`. Characters other than ASCII
+/// letters, digits and `-` are written as `_<hex>_`, so every id gets its own
+/// name that is safe in paths and urls.
+pub fn page_name(id: &str) -> String {
+    let mut name = String::with_capacity(id.len() + 5);
+    for c in id.chars() {
+        match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' => name.push(c),
+            c => {
+                let _ = write!(name, "_{:x}_", c as u32);
+            }
+        }
+    }
+    name.push_str(".html");
+    name
+}
+
+/// A node as it is rendered to its page.
+struct Page {
+    id: String,
+    title: String,
+    tags: Vec<String>,
+    body: String,
+    backlinks: Vec<(String, String)>,
+}
+
+/// Render every node into `out`, which is created if missing. Files that
+/// cannot be read are skipped. Returns the number of pages written.
+pub async fn export_site(
+    sqlite: &SqlitePool,
+    cache: &OrgCache,
+    settings: &HtmlExportSettings,
+    out: &Path,
+) -> anyhow::Result<usize> {
+    const NODES: &str = "SELECT id, title, file, level FROM nodes ORDER BY file, level, id;";
+    const TAGS: &str = "SELECT DISTINCT node_id, tag FROM tags ORDER BY tag;";
+    const BACKLINKS: &str = concat!(
+        "SELECT DISTINCT l.dest, n.id, n.title FROM links l\n",
+        "JOIN nodes n ON n.id = l.source\n",
+        "WHERE l.type = 'id' AND l.source != l.dest\n",
+        "ORDER BY n.title, n.id;"
+    );
+
+    let nodes: Vec<(String, String, String, i64)> = sqlx::query_as(NODES).fetch_all(sqlite).await?;
+    let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+    for (id, tag) in sqlx::query_as::<_, (String, String)>(TAGS)
+        .fetch_all(sqlite)
+        .await?
+    {
+        tags.entry(id).or_default().push(tag);
+    }
+    let mut backlinks: HashMap<String, Vec<(String, String)>> = HashMap::new();
+    for (dest, id, title) in sqlx::query_as::<_, (String, String, String)>(BACKLINKS)
+        .fetch_all(sqlite)
+        .await?
+    {
+        let title = TitleSanitizer::new().process(&title);
+        backlinks.entry(dest).or_default().push((id, title));
+    }
+    let file_nodes: HashMap<PathBuf, String> = files::get_file_nodes(sqlite)
+        .await?
+        .into_iter()
+        .map(|(file, id)| (PathBuf::from(file), id))
+        .collect();
+
+    let names = match settings.rewrites_titles() {
+        true => aliases::name_map(sqlite).await?,
+        false => HashMap::new(),
+    };
+
+    fs::create_dir_all(out)?;
+    let mut assets = BTreeSet::new();
+    let mut index = vec![];
+    // Nodes are ordered by file, so every file is read once.
+    let mut current: Option<(String, Option<String>)> = None;
+    for (id, title, file, level) in nodes {
+        if current.as_ref().is_none_or(|(read, _)| *read != file) {
+            let content = match cache.entry(&file) {
+                Ok(entry) => {
+                    assets.extend(attachments(&file, entry.content()));
+                    Some(entry.content().to_string())
+                }
+                Err(err) => {
+                    tracing::error!("Skipping {file}: {err}");
+                    None
+                }
+            };
+            current = Some((file.clone(), content));
+        }
+        let Some((_, Some(content))) = &current else {
+            continue;
+        };
+
+        let org = match level {
+            0 => content.clone(),
+            _ => Subtree::get(id.clone().into(), content).unwrap_or_else(|| content.clone()),
+        };
+        let mut handler = HtmlExport::new(settings, file)
+            .with_file_nodes(&file_nodes)
+            .with_names(&names)
+            .with_static_links();
+        Org::parse(org).traverse(&mut handler);
+        let (body, _, _) = handler.finish();
+
+        let page = Page {
+            title: TitleSanitizer::new().process(&title),
+            tags: tags.remove(&id).unwrap_or_default(),
+            backlinks: backlinks.remove(&id).unwrap_or_default(),
+            body,
+            id,
+        };
+        fs::write(out.join(page_name(&page.id)), render_page(&page))?;
+        index.push((page.title, page.id));
+    }
+
+    for asset in assets {
+        let (root, rest) = cache.locate(&asset);
+        let Ok(source) = resolve_asset(root, rest, cache.follows_symlinks()) else {
+            tracing::warn!("Not copying {asset:?}, it is missing or outside of the root");
+            continue;
+        };
+        let dest = out.join("assets").join(&asset);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(source, dest)?;
+    }
+
+    index.sort();
+    fs::write(out.join("index.html"), render_index(&index))?;
+    Ok(index.len())
+}
+
+fn document(title: &str, body: &str) -> String {
+    format!(
+        concat!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n",
+            "<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n",
+            "<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n"
+        ),
+        HtmlEscape(title),
+        STYLE,
+        body
+    )
+}
+
+fn render_page(page: &Page) -> String {
+    let mut body = String::from("<nav><a href=\"index.html\">Index</a></nav>\n<main>\n");
+    let _ = writeln!(body, "<h1>{}</h1>", HtmlEscape(&page.title));
+    if !page.tags.is_empty() {
+        let _ = writeln!(
+            body,
+            "<p class=\"tags\">{}</p>",
+            HtmlEscape(page.tags.join(" "))
+        );
+    }
+    body.push_str(&page.body);
+    if !page.backlinks.is_empty() {
+        body.push_str("\n<section class=\"backlinks\">\n<h2>Backlinks</h2>\n<ul>\n");
+        for (id, title) in &page.backlinks {
+            let _ = writeln!(
+                body,
+                "<li><a href=\"{}\">{}</a></li>",
+                HtmlEscape(page_name(id)),
+                HtmlEscape(title)
+            );
+        }
+        body.push_str("</ul>\n</section>");
+    }
+    body.push_str("\n</main>\n");
+    document(&page.title, &body)
+}
+
+/// Index of `(title, id)` pairs.
+fn render_index(nodes: &[(String, String)]) -> String {
+    let mut body = String::from("<main>\n<h1>Index</h1>\n<ul>\n");
+    for (title, id) in nodes {
+        let _ = writeln!(
+            body,
+            "<li><a href=\"{}\">{}</a></li>",
+            HtmlEscape(page_name(id)),
+            HtmlEscape(title)
+        );
+    }
+    body.push_str("</ul>\n</main>\n");
+    document("Index", &body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_name() {
+        assert_eq!(
+            page_name("a64477aa-d900-476d-b500-b8ab0b03c17d"),
+            "a64477aa-d900-476d-b500-b8ab0b03c17d.html"
+        );
+        assert_eq!(page_name("../x_y"), "_2e__2e__2f_x_5f_y.html");
+    }
+}
@@ -0,0 +1,156 @@
+//! Extract flashcards from headlines tagged `:drill:` or `:anki:`.
+//!
+//! The front and back of a card are taken from, in this order:
+//! - the `FRONT` and `BACK` properties of the headline,
+//! - the sections of the subheadings `Front` and `Back` (or `Answer`, as
+//!   used by org-drill),
+//! - the headline title for the front and its section for the back.
+//!
+//! If the back comes from a subheading, the section of the card headline
+//! is appended to the front, so org-drill cards keep their question.
+
+use orgize::{
+    ast::Headline,
+    export::{Container, Event, TraversalContext, Traverser},
+    Org,
+};
+
+use crate::transform::title::TitleSanitizer;
+
+/// Tags that mark a headline as a flashcard. Compared case insensitively.
+pub const CARD_TAGS: [&str; 2] = ["drill", "anki"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flashcard {
+    /// Raw org of the front.
+    pub front: String,
+    /// Raw org of the back.
+    pub back: String,
+    /// Tags of the headline without the [`CARD_TAGS`].
+    pub tags: Vec<String>,
+}
+
+/// Collect all flashcards of `org`. Cards without a back are skipped.
+pub fn flashcards(org: &str) -> Vec<Flashcard> {
+    let mut traverser = Flashcards { cards: vec![] };
+    Org::parse(org).traverse(&mut traverser);
+    traverser.cards
+}
+
+struct Flashcards {
+    cards: Vec<Flashcard>,
+}
+
+impl Traverser for Flashcards {
+    fn event(&mut self, event: Event, _: &mut TraversalContext) {
+        if let Event::Enter(Container::Headline(headline)) = event {
+            let tags: Vec<String> = headline
+                .tags()
+                .map(|tag| tag.trim().to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect();
+            if !tags.iter().any(|tag| is_card_tag(tag)) {
+                return;
+            }
+            if let Some(card) = card(&headline, tags) {
+                self.cards.push(card);
+            }
+        }
+    }
+}
+
+fn is_card_tag(tag: &str) -> bool {
+    CARD_TAGS.iter().any(|card| tag.eq_ignore_ascii_case(card))
+}
+
+fn card(headline: &Headline, tags: Vec<String>) -> Option<Flashcard> {
+    let property = |key: &str| {
+        let properties = headline.properties()?;
+        let value = properties.get(key)?.trim().to_string();
+        (!value.is_empty()).then_some(value)
+    };
+    let subheading = |titles: &[&str]| {
+        headline
+            .headlines()
+            .find(|sub| {
+                let title = sub.title_raw();
+                titles.iter().any(|t| title.trim().eq_ignore_ascii_case(t))
+            })
+            .and_then(|sub| sub.section())
+            .map(|section| section.raw().trim().to_string())
+    };
+    let section = headline
+        .section()
+        .map(|section| section.raw().trim().to_string())
+        .unwrap_or_default();
+    let title = TitleSanitizer::new().process(headline.title_raw().trim());
+
+    let (front, back) = match (property("FRONT"), property("BACK")) {
+        (Some(front), Some(back)) => (front, back),
+        (front, back) => {
+            let front = front.or_else(|| subheading(&["Front"]));
+            match back.or_else(|| subheading(&["Back", "Answer"])) {
+                Some(back) => (front.unwrap_or_else(|| join(&title, &section)), back),
+                None => (front.unwrap_or(title), section),
+            }
+        }
+    };
+    if front.is_empty() || back.is_empty() {
+        return None;
+    }
+
+    Some(Flashcard {
+        front,
+        back,
+        tags: tags.into_iter().filter(|tag| !is_card_tag(tag)).collect(),
+    })
+}
+
+fn join(title: &str, section: &str) -> String {
+    match section.is_empty() {
+        true => title.to_string(),
+        false => format!("{title}\n{section}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flashcards() {
+        let org = concat!(
+            "* Capital of France :drill:geo:\n",
+            "Paris\n",
+            "* Drill card :drill:\n",
+            "What is 2 + 2?\n",
+            "** Answer\n",
+            "4\n",
+            "* Properties :anki:\n",
+            ":PROPERTIES:\n",
+            ":FRONT:    ownership\n",
+            ":BACK:     each value has one owner\n",
+            ":END:\n",
+            "* Subheadings :ANKI:\n",
+            "** Front\n",
+            "borrow\n",
+            "** Back\n",
+            "reference\n",
+            "* Not a card\n",
+            "text\n",
+            "* Empty :drill:\n",
+        );
+        let cards = flashcards(org);
+        assert_eq!(cards.len(), 4);
+        assert_eq!(cards[0].front, "Capital of France");
+        assert_eq!(cards[0].back, "Paris");
+        assert_eq!(cards[0].tags, vec!["geo"]);
+        assert_eq!(cards[1].front, "Drill card\nWhat is 2 + 2?");
+        assert_eq!(cards[1].back, "4");
+        assert_eq!(cards[2].front, "ownership");
+        assert_eq!(cards[2].back, "each value has one owner");
+        assert_eq!(cards[3].front, "borrow");
+        assert_eq!(cards[3].back, "reference");
+        assert!(cards[3].tags.is_empty());
+    }
+}
@@ -1,13 +1,17 @@
 use std::cmp::min;
+use std::collections::HashMap;
 use std::fmt::Write;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 
-use crate::config::HtmlExportSettings;
+use crate::config::{HtmlExportSettings, LinkRewriteKind};
+use crate::server::services::move_service::file_link;
+use crate::server::types::RoamID;
+use crate::transform::export::page_name;
 use orgize::rowan::ast::AstNode;
 use orgize::{
     export::{Container, Event, HtmlEscape, TraversalContext, Traverser},
     rowan::NodeOrToken,
-    SyntaxKind,
+    SyntaxKind, SyntaxNode,
 };
 
 /// This is needed because if we have the table
@@ -27,18 +31,45 @@ struct OrgTableHints {
     next_is_first: bool,
 }
 
+/// Resolves the version of an asset, given its path relative to the
+/// org-roamers root.
+pub type AssetVersion<'a> = dyn Fn(&Path) -> Option<String> + Sync + 'a;
+
 pub struct HtmlExport<'a> {
     settings: &'a HtmlExportSettings,
     output: String,
     table_row: TableRow,
     in_descriptive_list: Vec<bool>,
-    in_special_block: bool,
     outgoing_id_links: Vec<String>,
     file: String,
     latex_blocks: Vec<String>,
     latex_counter: usize,
     table_hints: OrgTableHints,
     footnote_open: bool,
+    /// Depth of code, verbatim and literal blocks, whose text is never
+    /// changed by smart punctuation.
+    in_literal: usize,
+    /// Set while inside an exported inline task.
+    in_inlinetask: bool,
+    /// Set between a source block and the end of its results.
+    in_babel_group: bool,
+    /// Files with a file level node, mapped to the id of that node. `file:`
+    /// links to these files are exported as id links.
+    file_nodes: Option<&'a HashMap<PathBuf, String>>,
+    /// Versions of images, appended to their url for cache busting.
+    asset_version: Option<&'a AssetVersion<'a>>,
+    /// Link to the pages and assets of a static export instead of the
+    /// routes of the web client.
+    static_links: bool,
+    /// Titles and aliases mapped to the node they name, for link rewrites
+    /// by title.
+    names: Option<&'a HashMap<String, String>>,
+}
+
+/// Link after applying [`HtmlExportSettings::link_rewrites`].
+enum Rewritten {
+    Id(String),
+    Url(String),
 }
 
 impl<'a> HtmlExport<'a> {
@@ -48,16 +79,78 @@ impl<'a> HtmlExport<'a> {
             output: String::with_capacity(1000),
             table_row: TableRow::default(),
             in_descriptive_list: vec![],
-            in_special_block: false,
             outgoing_id_links: vec![],
             file,
             latex_blocks: vec![],
             latex_counter: 0,
             table_hints: OrgTableHints::default(),
             footnote_open: false,
+            in_literal: 0,
+            in_inlinetask: false,
+            in_babel_group: false,
+            file_nodes: None,
+            asset_version: None,
+            static_links: false,
+            names: None,
+        }
+    }
+
+    /// Resolve `file:` links to the given files to their file level node.
+    /// Paths are relative to the org-roamers root, like the file passed to
+    /// [`HtmlExport::new`].
+    pub fn with_file_nodes(mut self, file_nodes: &'a HashMap<PathBuf, String>) -> Self {
+        self.file_nodes = Some(file_nodes);
+        self
+    }
+
+    /// Append `&v=<version>` to image urls. `asset_version` gets the image
+    /// path relative to the org-roamers root.
+    pub fn with_asset_versions(mut self, asset_version: &'a AssetVersion) -> Self {
+        self.asset_version = Some(asset_version);
+        self
+    }
+
+    /// Export for [`crate::transform::export`]: id links point to the page
+    /// of their node, files to their copy below `assets/` and LaTeX is kept
+    /// as source.
+    pub fn with_static_links(mut self) -> Self {
+        self.static_links = true;
+        self
+    }
+
+    /// Resolve link rewrites of kind title with `names`, which maps titles
+    /// and aliases to the id of their node.
+    pub fn with_names(mut self, names: &'a HashMap<String, String>) -> Self {
+        self.names = Some(names);
+        self
+    }
+
+    /// Apply the first rewrite rule for the type of `path`. Titles that name
+    /// no node leave the link as it is.
+    fn rewrite_link(&self, path: &str) -> Option<Rewritten> {
+        let (rewrite, target) = self
+            .settings
+            .link_rewrites
+            .iter()
+            .find_map(|rewrite| Some((rewrite, rewrite.apply(path)?)))?;
+        match rewrite.kind {
+            LinkRewriteKind::Url => Some(Rewritten::Url(target)),
+            LinkRewriteKind::Id => Some(Rewritten::Id(RoamID::normalize(&target))),
+            LinkRewriteKind::Title => self
+                .names?
+                .get(target.as_str())
+                .map(|id| Rewritten::Id(id.clone())),
         }
     }
 
+    /// Id of the file level node a `file:` link points to, if the target is
+    /// an indexed file.
+    fn resolve_file_link(&self, path: &str) -> Option<String> {
+        let target = path.strip_prefix("file:")?;
+        let target = link_target(&self.file, target);
+        self.file_nodes?.get(&target).cloned()
+    }
+
     /// Extract label from footnote syntax like "[fn:1]" or "[fn:label]"
     fn extract_footnote_label(raw: &str) -> String {
         if let Some(start) = raw.find("[fn:") {
@@ -97,6 +190,99 @@ impl<'a> HtmlExport<'a> {
     }
 }
 
+/// Path of the `file:` link `target` relative to the root, given the `file`
+/// the link is in. Search options like `::*Heading` are dropped.
+pub(crate) fn link_target(file: &str, target: &str) -> PathBuf {
+    let target = target.split_once("::").map_or(target, |(path, _)| path);
+    if Path::new(target).is_absolute() {
+        return PathBuf::from(target);
+    }
+    let mut path = Path::new(file)
+        .parent()
+        .unwrap_or(Path::new(""))
+        .to_path_buf();
+    for component in Path::new(target).components() {
+        match component {
+            Component::ParentDir => {
+                path.pop();
+            }
+            Component::Normal(part) => path.push(part),
+            _ => {}
+        }
+    }
+    path
+}
+
+/// Headlines with at least this many stars are inline tasks, like the default
+/// of `org-inlinetask-min-level`.
+const INLINETASK_MIN_LEVEL: usize = 15;
+
+/// The headline closing an inline task.
+fn is_inlinetask_end(title: &str) -> bool {
+    title.trim() == "END"
+}
+
+/// Containers whose text is exported verbatim.
+fn is_literal(container: &Container) -> bool {
+    matches!(
+        container,
+        Container::Verbatim(_)
+            | Container::Code(_)
+            | Container::SourceBlock(_)
+            | Container::ExampleBlock(_)
+            | Container::FixedWidth(_)
+    )
+}
+
+/// Containers that can hold the output of a source block.
+fn is_results(container: &Container) -> bool {
+    let node = match container {
+        Container::FixedWidth(e) => e.syntax(),
+        Container::ExampleBlock(e) => e.syntax(),
+        Container::OrgTable(e) => e.syntax(),
+        Container::Paragraph(e) => e.syntax(),
+        _ => return false,
+    };
+    has_results(node)
+}
+
+/// Whether `node` is annotated with `#+RESULTS:`.
+fn has_results(node: &SyntaxNode) -> bool {
+    node.children().any(|child| {
+        child.kind() == SyntaxKind::AFFILIATED_KEYWORD
+            && child
+                .to_string()
+                .trim_start()
+                .to_ascii_uppercase()
+                .starts_with("#+RESULTS")
+    })
+}
+
+/// Replace `---` with an em dash, `--` with an en dash, `...` with an
+/// ellipsis and straight quotes with curly quotes. A quote opens if it is at
+/// the start or follows whitespace or an opening bracket.
+fn smart_punctuation(text: &str) -> String {
+    let text = text
+        .replace("---", "\u{2014}")
+        .replace("--", "\u{2013}")
+        .replace("...", "\u{2026}");
+
+    let mut result = String::with_capacity(text.len());
+    let mut prev: Option<char> = None;
+    for c in text.chars() {
+        let opens = prev.is_none_or(|p| p.is_whitespace() || "([{".contains(p));
+        match c {
+            '"' if opens => result.push('\u{201C}'),
+            '"' => result.push('\u{201D}'),
+            '\'' if opens => result.push('\u{2018}'),
+            '\'' => result.push('\u{2019}'),
+            c => result.push(c),
+        }
+        prev = Some(c);
+    }
+    result
+}
+
 #[derive(Default, PartialEq, Eq)]
 enum TableRow {
     #[default]
@@ -117,9 +303,48 @@ impl HtmlExport<'_> {
 
 impl Traverser for HtmlExport<'_> {
     fn event(&mut self, event: Event, ctx: &mut TraversalContext) {
+        match &event {
+            Event::Enter(container) if is_literal(container) => self.in_literal += 1,
+            Event::Leave(container) if is_literal(container) => {
+                self.in_literal = self.in_literal.saturating_sub(1)
+            }
+            _ => {}
+        }
+
+        let results = match &event {
+            Event::Enter(container) | Event::Leave(container) => is_results(container),
+            _ => false,
+        };
+        match &event {
+            Event::Enter(Container::SourceBlock(block))
+                if block
+                    .syntax()
+                    .next_sibling()
+                    .is_some_and(|n| has_results(&n)) =>
+            {
+                let _ = write!(
+                    self.output,
+                    r#"<div class="{}">"#,
+                    self.settings.class("babel")
+                );
+                self.in_babel_group = true;
+            }
+            Event::Enter(_) if results => {
+                let _ = write!(
+                    self.output,
+                    r#"<div class="{}">"#,
+                    self.settings.class("results")
+                );
+            }
+            _ => {}
+        }
+        let leaving_results = results && matches!(event, Event::Leave(_));
+
         match event {
             Event::Enter(Container::Document(document)) => {
-                self.output += "<div>";
+                if self.settings.wrappers {
+                    self.output += "<div>";
+                }
                 if let Some(title) = document.title() {
                     let _ = write!(
                         &mut self.output,
@@ -128,14 +353,35 @@ impl Traverser for HtmlExport<'_> {
                     );
                 }
             }
-            Event::Leave(Container::Document(_)) => self.output += "</div>",
+            Event::Leave(Container::Document(_)) if self.settings.wrappers => {
+                self.output += "</div>"
+            }
 
             Event::Enter(Container::Headline(headline)) => {
-                if self.settings.respect_noexport && headline.tags().any(|t| t.contains("noexport"))
+                if headline.is_commented()
+                    || (self.settings.respect_noexport
+                        && headline.tags().any(|t| t.contains("noexport")))
                 {
                     ctx.skip();
                     return;
                 }
+                if headline.level() >= INLINETASK_MIN_LEVEL {
+                    if !self.settings.inlinetasks || is_inlinetask_end(&headline.title_raw()) {
+                        ctx.skip();
+                        return;
+                    }
+                    let _ = write!(
+                        &mut self.output,
+                        r#"<div class="{}"><b>"#,
+                        self.settings.class("inlinetask")
+                    );
+                    for elem in headline.title() {
+                        self.element(elem, ctx);
+                    }
+                    self.output += "</b>";
+                    self.in_inlinetask = true;
+                    return;
+                }
                 let level = min(headline.level(), 6);
                 let _ = write!(&mut self.output, "<h{level}>");
                 for elem in headline.title() {
@@ -143,7 +389,10 @@ impl Traverser for HtmlExport<'_> {
                 }
                 let _ = write!(&mut self.output, "</h{level}>");
             }
-            Event::Leave(Container::Headline(_)) => {}
+            Event::Leave(Container::Headline(_)) if self.in_inlinetask => {
+                self.in_inlinetask = false;
+                self.output += "</div>";
+            }
 
             Event::Enter(Container::SpecialBlock(specialblock)) => {
                 let mut iter = specialblock
@@ -162,42 +411,38 @@ impl Traverser for HtmlExport<'_> {
                             Some(advice) => {
                                 let _ = write!(
                                     self.output,
-                                    "<div class=\"{}\" style=\"{}\">{}<p style=\"{}\">",
-                                    advice.on, advice.css_style, advice.header, advice.text_styling
+                                    "<div class=\"{}\" style=\"{}\">{}<div style=\"{}\">",
+                                    self.settings.class(&advice.on),
+                                    advice.css_style,
+                                    advice.header,
+                                    advice.text_styling
                                 );
                             }
                             None => {
-                                let _ = write!(self.output, "<div class=\"{}\"><p>", block_type);
+                                let class = self.settings.class(&block_type);
+                                let _ = write!(self.output, "<div class=\"{}\"><div>", class);
                             }
                         }
                     }
                     None => {
                         tracing::warn!("Block type not found.");
-                        self.output += "<div><p>";
+                        self.output += "<div><div>";
                     }
                 }
-                self.in_special_block = true;
-            }
-            Event::Leave(Container::SpecialBlock(_)) => {
-                self.in_special_block = false;
-                self.output += "</p></div>";
             }
+            Event::Leave(Container::SpecialBlock(_)) => self.output += "</div></div>",
 
-            Event::Enter(Container::Paragraph(_)) => {
-                if !self.in_special_block && !self.footnote_open {
-                    self.output += "<p>"
-                }
-            }
-            Event::Leave(Container::Paragraph(_)) => {
-                if !self.in_special_block && !self.footnote_open {
-                    self.output += "</p>";
-                }
-            }
+            Event::Enter(Container::Paragraph(_)) if !self.footnote_open => self.output += "<p>",
+            Event::Leave(Container::Paragraph(_)) if !self.footnote_open => self.output += "</p>",
 
-            Event::Enter(Container::Section(_)) => self.output += "<section>",
+            Event::Enter(Container::Section(_)) if self.settings.wrappers => {
+                self.output += "<section>"
+            }
             Event::Leave(Container::Section(_)) => {
                 self.close_footnote_if_needed();
-                self.output += "</section>";
+                if self.settings.wrappers {
+                    self.output += "</section>";
+                }
             }
 
             Event::Enter(Container::Italic(_)) => self.output += "<i>",
@@ -222,7 +467,8 @@ impl Traverser for HtmlExport<'_> {
                 if let Some(language) = block.language() {
                     let _ = write!(
                         &mut self.output,
-                        r#"<pre><code class="language-{}">"#,
+                        r#"<pre><code class="{}{}">"#,
+                        self.settings.class("language-"),
                         HtmlEscape(&language)
                     );
                 } else {
@@ -231,21 +477,34 @@ impl Traverser for HtmlExport<'_> {
             }
             Event::Leave(Container::SourceBlock(_)) => self.output += "</code></pre>",
 
-            Event::Enter(Container::QuoteBlock(_)) => self.output += "<blockquote class=\"quote\">",
+            Event::Enter(Container::QuoteBlock(_)) => {
+                let class = self.settings.class("quote");
+                let _ = write!(self.output, "<blockquote class=\"{class}\">");
+            }
             Event::Leave(Container::QuoteBlock(_)) => self.output += "</blockquote>",
 
-            Event::Enter(Container::VerseBlock(_)) => self.output += "<p class=\"verse\">",
+            Event::Enter(Container::VerseBlock(_)) => {
+                let class = self.settings.class("verse");
+                let _ = write!(self.output, "<p class=\"{class}\">");
+            }
             Event::Leave(Container::VerseBlock(_)) => self.output += "</p>",
 
-            Event::Enter(Container::ExampleBlock(_)) => self.output += "<pre class=\"example\">",
+            Event::Enter(Container::ExampleBlock(_)) => {
+                let class = self.settings.class("example");
+                let _ = write!(self.output, "<pre class=\"{class}\">");
+            }
             Event::Leave(Container::ExampleBlock(_)) => self.output += "</pre>",
 
             Event::Enter(Container::FixedWidth(_)) => {
-                self.output += "<pre class=\"program-output\">"
+                let class = self.settings.class("program-output");
+                let _ = write!(self.output, "<pre class=\"{class}\">");
             }
             Event::Leave(Container::FixedWidth(_)) => self.output += "</pre>",
 
-            Event::Enter(Container::CenterBlock(_)) => self.output += "<div class=\"center\">",
+            Event::Enter(Container::CenterBlock(_)) => {
+                let class = self.settings.class("center");
+                let _ = write!(self.output, "<div class=\"{class}\">");
+            }
             Event::Leave(Container::CenterBlock(_)) => self.output += "</div>",
 
             Event::Enter(Container::CommentBlock(_)) => self.output += "<!--",
@@ -386,26 +645,66 @@ impl Traverser for HtmlExport<'_> {
                 let path = link.path();
                 let path = path.trim_start_matches("file:");
 
-                if link.path().starts_with("id:") {
-                    let id = link.path().trim_start_matches("id:").to_string();
+                let (id, url) = match self.rewrite_link(&link.path()) {
+                    Some(Rewritten::Id(id)) => (Some(id), None),
+                    Some(Rewritten::Url(url)) => (None, Some(url)),
+                    None => match link.path().strip_prefix("id:") {
+                        Some(id) => (Some(RoamID::normalize(id)), None),
+                        None => (self.resolve_file_link(&link.path()), None),
+                    },
+                };
+
+                let file_link = file_link(&link.path()).map(|(_, target, _)| target.to_string());
+                if let (Some(id), true) = (&id, self.static_links) {
                     let _ = write!(
                         &mut self.output,
-                        r#"<a id="{}" class="org-preview-id-link">"#,
+                        r#"<a href="{}" class="{}">"#,
+                        HtmlEscape(page_name(id)),
+                        self.settings.class("org-preview-id-link"),
+                    );
+                    self.outgoing_id_links.push(id.clone());
+                } else if let Some(id) = id {
+                    let _ = write!(
+                        &mut self.output,
+                        r#"<a id="{}" class="{}">"#,
                         HtmlEscape(&id),
+                        self.settings.class("org-preview-id-link"),
                     );
                     self.outgoing_id_links.push(id);
+                } else if let Some(url) = url {
+                    let _ = write!(&mut self.output, r#"<a href="{}">"#, HtmlEscape(&url));
+                } else if let (Some(target), true) = (&file_link, self.static_links) {
+                    let target = link_target(&self.file, target);
+                    let _ = write!(
+                        &mut self.output,
+                        r#"<a href="assets/{}">"#,
+                        HtmlEscape(target.to_string_lossy())
+                    );
                 } else {
                     let _ = write!(&mut self.output, r#"<a href="{}">"#, HtmlEscape(&path));
                 }
 
-                if link.is_image() {
+                if link.is_image() && self.static_links {
+                    let target = link_target(&self.file, path);
+                    let _ = write!(
+                        &mut self.output,
+                        r#"<img style="width: 80%; margin: auto; display: block;" src="assets/{}">"#,
+                        HtmlEscape(target.to_string_lossy())
+                    );
+                } else if link.is_image() {
                     let mut path = PathBuf::from(self.file.clone());
                     path.pop();
                     path.push(link.path().as_ref());
+                    let version = self
+                        .asset_version
+                        .and_then(|asset_version| asset_version(&path))
+                        .map(|version| format!("&v={version}"))
+                        .unwrap_or_default();
                     let _ = write!(
                         &mut self.output,
-                        r#"<img style="width: 80%; margin: auto; display: block;" src="assets?file={}">"#,
-                        HtmlEscape(&path.to_str().unwrap())
+                        r#"<img style="width: 80%; margin: auto; display: block;" src="assets?file={}{}">"#,
+                        HtmlEscape(&path.to_str().unwrap()),
+                        HtmlEscape(&version)
                     );
                     // return ctx.skip();
                 }
@@ -418,7 +717,12 @@ impl Traverser for HtmlExport<'_> {
             Event::Leave(Container::Link(_)) => self.output += "</a>",
 
             Event::Text(text) => {
-                let _ = write!(&mut self.output, "{}", HtmlEscape(text));
+                if self.settings.smart_punctuation && self.in_literal == 0 {
+                    let text = smart_punctuation(&text);
+                    let _ = write!(&mut self.output, "{}", HtmlEscape(text));
+                } else {
+                    let _ = write!(&mut self.output, "{}", HtmlEscape(text));
+                }
             }
 
             Event::LineBreak(_) => self.output += "<br/>",
@@ -432,7 +736,12 @@ impl Traverser for HtmlExport<'_> {
             Event::Rule(_) => self.output += "<hr/>",
 
             Event::Timestamp(timestamp) => {
-                self.output += r#"<span class="timestamp-wrapper"><span class="timestamp">"#;
+                let _ = write!(
+                    self.output,
+                    r#"<span class="{}"><span class="{}">"#,
+                    self.settings.class("timestamp-wrapper"),
+                    self.settings.class("timestamp")
+                );
                 for e in timestamp.syntax().children_with_tokens() {
                     match e {
                         NodeOrToken::Token(t) if t.kind() == SyntaxKind::MINUS2 => {
@@ -447,13 +756,31 @@ impl Traverser for HtmlExport<'_> {
                 self.output += r#"</span></span>"#;
             }
 
+            Event::LatexFragment(latex) if self.static_links => {
+                let _ = write!(
+                    &mut self.output,
+                    r#"<span class="{}">{}</span>"#,
+                    self.settings.class("org-latex"),
+                    HtmlEscape(latex.raw())
+                );
+            }
+            Event::LatexEnvironment(latex) if self.static_links => {
+                let _ = write!(
+                    &mut self.output,
+                    r#"<pre class="{}">{}</pre>"#,
+                    self.settings.class("org-latex-block"),
+                    HtmlEscape(latex.raw())
+                );
+            }
             Event::LatexFragment(latex) => {
                 let latex_content = latex.raw().to_string();
                 self.latex_blocks.push(latex_content);
                 let _ = write!(
                     &mut self.output,
-                    r#"<span class="org-latex-placeholder" data-latex-index="{}">[LaTeX Block {}]</span>"#,
-                    self.latex_counter, self.latex_counter
+                    r#"<span class="{}" data-latex-index="{}">[LaTeX Block {}]</span>"#,
+                    self.settings.class("org-latex-placeholder"),
+                    self.latex_counter,
+                    self.latex_counter
                 );
                 self.latex_counter += 1;
             }
@@ -462,23 +789,33 @@ impl Traverser for HtmlExport<'_> {
                 self.latex_blocks.push(latex_content);
                 let _ = write!(
                     &mut self.output,
-                    r#"<div class="org-latex-block-placeholder" data-latex-index="{}">[LaTeX Environment {}]</div>"#,
-                    self.latex_counter, self.latex_counter
+                    r#"<div class="{}" data-latex-index="{}">[LaTeX Environment {}]</div>"#,
+                    self.settings.class("org-latex-block-placeholder"),
+                    self.latex_counter,
+                    self.latex_counter
                 );
                 self.latex_counter += 1;
             }
 
             Event::Enter(Container::Keyword(_)) => ctx.skip(),
 
-            Event::Entity(entity) => self.output += entity.html(),
+            Event::Entity(entity) => match entity.name() {
+                "nbsp" | " " if !self.settings.non_breaking_spaces => self.output += " ",
+                _ if self.settings.entities => self.output += entity.html(),
+                _ => {
+                    let _ = write!(&mut self.output, "{}", HtmlEscape(entity.raw()));
+                }
+            },
 
             Event::InlineSrc(src) => {
                 let code = src.value();
                 let lang = src.language();
                 let _ = write!(
                     self.output,
-                    "<code class=\"language-{}\">{}</code>",
-                    lang, code
+                    "<code class=\"{}{}\">{}</code>",
+                    self.settings.class("language-"),
+                    lang,
+                    code
                 );
             }
 
@@ -488,8 +825,9 @@ impl Traverser for HtmlExport<'_> {
                 let label = raw.trim_start_matches("[fn:").trim_end_matches(']');
                 let _ = write!(
                     &mut self.output,
-                    "<sup><a id=\"fnr.{}\" class=\"footref\" href=\"#fn.{}\">{}</a></sup>",
+                    "<sup><a id=\"fnr.{}\" class=\"{}\" href=\"#fn.{}\">{}</a></sup>",
                     HtmlEscape(label),
+                    self.settings.class("footref"),
                     HtmlEscape(label),
                     HtmlEscape(label)
                 );
@@ -515,10 +853,13 @@ impl Traverser for HtmlExport<'_> {
                 // Write footnote header
                 let _ = write!(
                     &mut self.output,
-                    "<div class=\"footdef\"><sup><a id=\"fn.{}\" class=\"footnum\" href=\"#fnr.{}\">{}</a></sup> <div class=\"footpara\">",
+                    "<div class=\"{}\"><sup><a id=\"fn.{}\" class=\"{}\" href=\"#fnr.{}\">{}</a></sup> <div class=\"{}\">",
+                    self.settings.class("footdef"),
+                    HtmlEscape(&label),
+                    self.settings.class("footnum"),
                     HtmlEscape(&label),
                     HtmlEscape(&label),
-                    HtmlEscape(&label)
+                    self.settings.class("footpara")
                 );
 
                 // Parse and render the footnote content with inline markup support
@@ -537,6 +878,14 @@ impl Traverser for HtmlExport<'_> {
 
             _ => {}
         }
+
+        if leaving_results {
+            self.output += "</div>";
+            if self.in_babel_group {
+                self.output += "</div>";
+                self.in_babel_group = false;
+            }
+        }
     }
 }
 
@@ -546,6 +895,195 @@ mod tests {
     use orgize::Org;
 
     use super::*;
+    /// Tags that define the structure compared against ox-html. Wrappers,
+    /// paragraphs and inline markup differ between the exporters and are
+    /// ignored.
+    const STRUCTURAL_TAGS: &[&str] = &[
+        "ul",
+        "ol",
+        "li",
+        "dl",
+        "dt",
+        "dd",
+        "blockquote",
+        "pre",
+        "table",
+        "thead",
+        "tbody",
+        "tr",
+        "td",
+    ];
+
+    /// Elements that may not appear inside a `<p>`.
+    const BLOCK_TAGS: &[&str] = &[
+        "ul",
+        "ol",
+        "dl",
+        "div",
+        "p",
+        "blockquote",
+        "pre",
+        "table",
+        "section",
+    ];
+
+    const VOID_TAGS: &[&str] = &["br", "hr", "img", "col"];
+
+    /// Tags of `html` as `(name, is_closing)`, skipping comments and void
+    /// elements.
+    fn tags(html: &str) -> Vec<(String, bool)> {
+        let mut tags = vec![];
+        let mut rest = html;
+        while let Some(start) = rest.find('<') {
+            rest = &rest[start + 1..];
+            if rest.starts_with("!--") {
+                rest = rest.find("-->").map_or("", |end| &rest[end + 3..]);
+                continue;
+            }
+            let Some(end) = rest.find('>') else {
+                break;
+            };
+            let tag = &rest[..end];
+            rest = &rest[end + 1..];
+            let closing = tag.starts_with('/');
+            let name: String = tag
+                .trim_start_matches('/')
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric())
+                .collect::<String>()
+                .to_ascii_lowercase();
+            if tag.ends_with('/') || VOID_TAGS.contains(&name.as_str()) {
+                continue;
+            }
+            tags.push((name, closing));
+        }
+        tags
+    }
+
+    fn structure(html: &str) -> Vec<(String, bool)> {
+        tags(html)
+            .into_iter()
+            .map(|(name, closing)| match name.as_str() {
+                "th" => ("td".to_string(), closing),
+                _ => (name, closing),
+            })
+            .filter(|(name, _)| STRUCTURAL_TAGS.contains(&name.as_str()))
+            .collect()
+    }
+
+    /// Check that all tags are balanced and no block element is nested in a
+    /// paragraph.
+    fn check_well_formed(html: &str) -> Result<(), String> {
+        let mut stack: Vec<String> = vec![];
+        for (name, closing) in tags(html) {
+            if closing {
+                match stack.pop() {
+                    Some(open) if open == name => {}
+                    open => return Err(format!("</{name}> closes {open:?}")),
+                }
+            } else {
+                if stack.iter().any(|open| open == "p") && BLOCK_TAGS.contains(&name.as_str()) {
+                    return Err(format!("<{name}> inside <p>"));
+                }
+                stack.push(name);
+            }
+        }
+        match stack.is_empty() {
+            true => Ok(()),
+            false => Err(format!("unclosed {stack:?}")),
+        }
+    }
+
+    #[test]
+    fn test_check_well_formed() {
+        assert!(check_well_formed("<div><p>a<br/><img src=\"x\"></p></div>").is_ok());
+        assert!(check_well_formed("<p><ul><li>a</li></ul></p>").is_err());
+        assert!(check_well_formed("<ul><li>a</ul>").is_err());
+        assert_eq!(
+            structure(
+                "<ul class=\"org-ul\"><li><p>a</p></li></ul><table><tr><th>b</th></tr></table>"
+            ),
+            structure("<div><ul><li>a</li></ul></div><table><tr><td>b</td></tr></table>")
+        );
+    }
+
+    /// Export every `.org` file in `tests/fixtures/export` and compare it to
+    /// the ox-html output stored next to it.
+    #[test]
+    fn test_export_fixtures() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/export");
+        let settings = HtmlExportSettings::default();
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|ext| ext != "org") {
+                continue;
+            }
+            let org = std::fs::read_to_string(&path).unwrap();
+            let expected = std::fs::read_to_string(path.with_extension("html")).unwrap();
+
+            let mut handler = HtmlExport::new(&settings, "".into());
+            Org::parse(&org).traverse(&mut handler);
+            let result = handler.finish().0;
+
+            if let Err(err) = check_well_formed(&result) {
+                panic!("{}: {err} in {result}", path.display());
+            }
+            assert_eq!(
+                structure(&result),
+                structure(&expected),
+                "{}: {result}",
+                path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn test_smart_punctuation() {
+        assert_eq!(
+            smart_punctuation("wait... it's \"done\" -- 1--2 --- end"),
+            "wait\u{2026} it\u{2019}s \u{201C}done\u{201D} \u{2013} 1\u{2013}2 \u{2014} end"
+        );
+        assert_eq!(smart_punctuation("('quoted')"), "(\u{2018}quoted\u{2019})");
+    }
+
+    #[test]
+    fn test_link_rewrites() {
+        let settings = HtmlExportSettings {
+            link_rewrites: serde_json::from_str(concat!(
+                r#"[{"type": "roam", "kind": "Title"},"#,
+                r#"{"type": "denote", "kind": "Id", "target": "denote-{path}"},"#,
+                r#"{"type": "jira", "target": "https://jira.example.com/browse/{path}"}]"#
+            ))
+            .unwrap(),
+            ..Default::default()
+        };
+        let names = HashMap::from([("Rust".to_string(), "rust-id".to_string())]);
+        let org =
+            "[[roam:Rust][a]] [[roam:Missing][b]] [[denote:20240101T1200][c]] [[jira:ABC-1][d]]\n";
+        let mut handler = HtmlExport::new(&settings, "".into()).with_names(&names);
+        Org::parse(org).traverse(&mut handler);
+        let (html, outgoing, _) = handler.finish();
+
+        assert!(html.contains(r#"<a id="rust-id" class="org-preview-id-link">a</a>"#));
+        assert!(html.contains(r#"<a href="roam:Missing">b</a>"#));
+        assert!(html.contains(r#"<a id="denote-20240101T1200" class="org-preview-id-link">c</a>"#));
+        assert!(html.contains("https://jira.example.com/browse/ABC-1"));
+        assert_eq!(outgoing, vec!["denote-20240101T1200", "rust-id"]);
+    }
+
+    #[test]
+    fn test_link_target() {
+        assert_eq!(link_target("a.org", "b.org"), PathBuf::from("b.org"));
+        assert_eq!(
+            link_target("dir/a.org", "b.org::*Heading"),
+            PathBuf::from("dir/b.org")
+        );
+        assert_eq!(
+            link_target("dir/a.org", "../other/./b.org"),
+            PathBuf::from("other/b.org")
+        );
+    }
+
     #[test]
     fn test_org_table_export_advice_header() {
         let org = concat!(
@@ -653,6 +1191,26 @@ mod tests {
         assert_eq!(handler.finish().0, exp);
     }
 
+    #[test]
+    fn test_class_prefix_without_wrappers() {
+        let org = concat!(
+            "* Heading\n",
+            "#+begin_quote\n",
+            "Quoted.\n",
+            "#+end_quote\n"
+        );
+        let exp = concat!(
+            "<h1>Heading</h1>",
+            "<blockquote class=\"roam-quote\"><p>Quoted.\n</p></blockquote>"
+        );
+        let mut settings = HtmlExportSettings::default();
+        settings.class_prefix = "roam-".into();
+        settings.wrappers = false;
+        let mut handler = HtmlExport::new(&settings, "".into());
+        Org::parse(org).traverse(&mut handler);
+        assert_eq!(handler.finish().0, exp);
+    }
+
     #[test]
     fn test_noexport_with_subtree() {
         let org = concat!(
@@ -701,6 +1259,70 @@ mod tests {
         assert_eq!(handler.finish().0, exp);
     }
 
+    #[test]
+    fn test_comment_heading_skipped() {
+        let org = concat!(
+            "* COMMENT Draft\n",
+            "Not exported.\n",
+            "** Child\n",
+            "Not exported either.\n",
+            "* Public\n",
+            "Exported.\n"
+        );
+        let exp = concat!(
+            "<div>",
+            "<h1>Public</h1>",
+            "<section><p>Exported.\n</p></section></div>"
+        );
+        let settings = HtmlExportSettings::default();
+        let mut handler = HtmlExport::new(&settings, "".into());
+        Org::parse(org).traverse(&mut handler);
+        assert_eq!(handler.finish().0, exp);
+    }
+
+    #[test]
+    fn test_inlinetask() {
+        let org = concat!(
+            "* Heading\n",
+            "*************** TODO Task\n",
+            "Task body.\n",
+            "*************** END\n",
+        );
+        let mut settings = HtmlExportSettings::default();
+        let mut handler = HtmlExport::new(&settings, "".into());
+        Org::parse(org).traverse(&mut handler);
+        let result = handler.finish().0;
+        assert!(result.contains("<div class=\"inlinetask\"><b>"));
+        assert!(result.contains("Task body."));
+        assert!(!result.contains("END"));
+
+        settings.inlinetasks = false;
+        let mut handler = HtmlExport::new(&settings, "".into());
+        Org::parse(org).traverse(&mut handler);
+        assert!(!handler.finish().0.contains("Task body."));
+    }
+
+    #[test]
+    fn test_babel_results() {
+        let org = concat!(
+            "#+begin_src python\n",
+            "print(1)\n",
+            "#+end_src\n",
+            "\n",
+            "#+RESULTS:\n",
+            ": 1\n"
+        );
+        let settings = HtmlExportSettings::default();
+        let mut handler = HtmlExport::new(&settings, "".into());
+        Org::parse(org).traverse(&mut handler);
+        let result = handler.finish().0;
+        assert!(result.contains(concat!(
+            "<div class=\"babel\"><pre><code class=\"language-python\">",
+            "print(1)\n</code></pre>",
+            "<div class=\"results\"><pre class=\"program-output\">1\n</pre></div></div>"
+        )));
+    }
+
     #[test]
     fn test_noexport_disabled() {
         let org = concat!(
@@ -0,0 +1,41 @@
+//! Rewrite the targets of the bracket links of an org document.
+
+/// Replace the targets of all bracket links in `content` for which `rewrite`
+/// returns a new target. Descriptions are kept.
+pub fn rewrite_links(content: &str, rewrite: impl Fn(&str) -> Option<String>) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("[[") {
+        let (before, link) = rest.split_at(start + 2);
+        result.push_str(before);
+        let end = link.find(']').unwrap_or(link.len());
+        let target = &link[..end];
+        match rewrite(target) {
+            Some(new) => result.push_str(&new),
+            None => result.push_str(target),
+        }
+        rest = &link[end..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// `content` with its `id:` links to `from` pointed at `to`.
+pub fn rewrite_id_links(content: &str, from: &str, to: &str) -> String {
+    rewrite_links(content, |target| {
+        (target.strip_prefix("id:") == Some(from)).then(|| format!("id:{to}"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rewrite_id_links() {
+        assert_eq!(
+            rewrite_id_links("[[id:a]] [[id:a][A]] [[id:ab]] [[file:a]]", "a", "b"),
+            "[[id:b]] [[id:b][A]] [[id:ab]] [[file:a]]"
+        );
+    }
+}
@@ -1,17 +1,37 @@
 //! # transform module
 //! This module contains all tranformation and filtering the library supports
 //! on org. Each operation is it's own module:
-//! - [`export`]: Export an org string/file to html.
+//! - [`html`]: Export an org string/file to html.
+//! - [`export`]: Export all nodes to a static html site.
 //! - [`org`]: Transform an org string into a
 //!   [`OrgNode`](crate::transform::node_builder::OrgNode).
 //! - [`subtree`]: Get a subtree of an org file.
+//! - [`outline`]: Get the heading tree of an org file.
 //! - [`title`]: Strip all syntax from the org input and return a string that
 //!   can be displayed in contexts without org support.
 //! - [`keywords`]: Collect all keywords from a given org document.
+//! - [`links`]: Rewrite the targets of links, e.g. after a file moved.
+//! - [`timestamps`]: Collect the dates a node is anchored to.
+//! - [`summary`]: Extract the first sentence of a node.
+//! - [`citations`]: Find citation keys in org text.
+//! - [`flashcards`]: Extract flashcards from `:drill:` and `:anki:` headlines.
+//! - [`diff`]: Build the graph of a single file, to diff it against the
+//!   stored one.
+//! - [`diagnostics`]: Find syntax problems that keep parts of a file from
+//!   being indexed.
 //!
 //! All of these parsers use the [`orgize`] parsers.
+pub mod citations;
+pub mod diagnostics;
+pub mod diff;
+pub mod export;
+pub mod flashcards;
 pub mod html;
 pub mod keywords;
+pub mod links;
 pub mod node_builder;
+pub mod outline;
 pub mod subtree;
+pub mod summary;
+pub mod timestamps;
 pub mod title;
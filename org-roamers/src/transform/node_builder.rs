@@ -1,13 +1,15 @@
 use std::collections::HashSet;
 
 use orgize::{
-    ast::{Keyword, Link},
+    ast::{Keyword, Link, PropertyDrawer, Timestamp},
     export::{Container, Event, Traverser},
     Org, SyntaxElement,
 };
 use sqlx::SqlitePool;
 
-use crate::sqlite::rebuild;
+use crate::server::types::{DiagnosticSeverity, FileDiagnostic, RoamID};
+use crate::sqlite::{rebuild, tasks};
+use crate::transform::{citations, summary, timestamps};
 
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct OrgNode {
@@ -21,18 +23,51 @@ pub struct OrgNode {
     pub(crate) tags: Vec<String>,
     pub(crate) aliases: Vec<String>,
     pub(crate) links: Vec<(String, String)>,
+    /// Targets of `http` and `https` links.
+    pub(crate) external_links: Vec<String>,
     pub(crate) refs: Vec<String>,
     pub(crate) cites: Vec<String>,
     pub(crate) file: String,
+    pub(crate) todo: Option<String>,
+    pub(crate) priority: Option<String>,
+    /// Date (`YYYY-MM-DD`) the node is scheduled for.
+    pub(crate) scheduled: Option<String>,
+    /// Date (`YYYY-MM-DD`) of the deadline of the node.
+    pub(crate) deadline: Option<String>,
+    /// Properties of the node except for the `ID`.
+    pub(crate) properties: Vec<(String, String)>,
+}
+
+/// A headline with a TODO keyword, a scheduled date or a deadline. Unlike
+/// nodes, tasks do not need an id.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct OrgTask {
+    pub(crate) title: String,
+    /// Closest node containing the headline, the headline itself if it is a
+    /// node.
+    pub(crate) node: Option<String>,
+    pub(crate) level: u64,
+    pub(crate) todo: Option<String>,
+    pub(crate) priority: Option<String>,
+    /// Date (`YYYY-MM-DD`) the headline is scheduled for.
+    pub(crate) scheduled: Option<String>,
+    /// Date (`YYYY-MM-DD`) of the deadline of the headline.
+    pub(crate) deadline: Option<String>,
 }
 
 impl OrgNode {
     #[rustfmt::skip]
     pub async fn insert_node(&self, con: &SqlitePool) -> anyhow::Result<()> {
         // this does not insert olp, tags, etc. -- why?
+        let properties = serde_json::to_string(
+            &self.properties.iter().cloned().collect::<std::collections::HashMap<_, _>>(),
+        )?;
         rebuild::insert_node(
-            con, &self.uuid, &self.file, self.level,
-            false, 0, "", "", self.title.as_str(), &self.actual_olp
+            con, &self.uuid, &self.file, self.level, self.parent.as_deref(),
+            self.todo.as_deref(), self.priority.as_deref(),
+            self.scheduled.as_deref(), self.deadline.as_deref(),
+            self.title.as_str(), &properties,
+            &summary::summary(&self.content), &self.actual_olp
         ).await
     }
 
@@ -50,51 +85,128 @@ impl OrgNode {
         Ok(())
     }
 
+    pub async fn insert_dates(&self, con: &SqlitePool) -> anyhow::Result<()> {
+        let mut dates = timestamps::active_dates(&self.content);
+        if let Some(date) = timestamps::datetree_date(&self.olp) {
+            if !dates.contains(&date) {
+                dates.push(date);
+            }
+        }
+        for date in &dates {
+            rebuild::insert_date(con, &self.uuid, date).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn insert_citations(&self, con: &SqlitePool) -> anyhow::Result<()> {
+        for key in &self.cites {
+            rebuild::insert_citation(con, &self.uuid, key).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn insert_refs(&self, con: &SqlitePool) -> anyhow::Result<()> {
+        for reference in &self.refs {
+            rebuild::insert_ref(con, &self.uuid, reference).await?;
+        }
+        Ok(())
+    }
+
     pub async fn insert_links(&self, con: &SqlitePool) -> anyhow::Result<()> {
         for link in &self.links {
-            rebuild::insert_link(con, &self.uuid, &link.0).await?;
+            rebuild::insert_link(con, &self.uuid, &link.0, "id").await?;
+        }
+        for url in &self.external_links {
+            let scheme = url.split_once(':').map_or("https", |(scheme, _)| scheme);
+            rebuild::insert_link(con, &self.uuid, url, scheme).await?;
         }
         Ok(())
     }
 }
 
-pub async fn insert_nodes(con: &SqlitePool, nodes: Vec<OrgNode>) {
+/// Store `nodes`. Nodes that could not be stored, e.g. because their id is
+/// already used by another file, are returned as diagnostics.
+pub async fn insert_nodes(con: &SqlitePool, nodes: Vec<OrgNode>) -> Vec<FileDiagnostic> {
+    let mut diagnostics = vec![];
+    let mut report = |node: &OrgNode, severity, message: String| {
+        tracing::error!("{message}");
+        diagnostics.push(FileDiagnostic {
+            severity,
+            line: None,
+            column: None,
+            node: Some(node.uuid.clone()),
+            message,
+        });
+    };
     for node in nodes.iter() {
         // Only insert tags, aliases, and links if the node was successfully inserted
         match node.insert_node(con).await {
             Ok(_) => {
-                if let Err(err) = node.insert_tags(con).await {
-                    tracing::error!("Failed to insert tags for node {}: {}", node.uuid, err);
-                }
-                if let Err(err) = node.insert_aliases(con).await {
-                    tracing::error!("Failed to insert aliases for node {}: {}", node.uuid, err);
-                }
-                if let Err(err) = node.insert_links(con).await {
-                    tracing::error!("Failed to insert links for node {}: {}", node.uuid, err);
+                let results = [
+                    ("tags", node.insert_tags(con).await),
+                    ("aliases", node.insert_aliases(con).await),
+                    ("links", node.insert_links(con).await),
+                    ("dates", node.insert_dates(con).await),
+                    ("citations", node.insert_citations(con).await),
+                    ("refs", node.insert_refs(con).await),
+                ];
+                for (what, result) in results {
+                    if let Err(err) = result {
+                        let message =
+                            format!("Failed to insert {what} for node {}: {err}", node.uuid);
+                        report(node, DiagnosticSeverity::Warning, message);
+                    }
                 }
             }
             Err(err) => {
-                tracing::error!(
+                let message = format!(
                     "Failed to insert node {}: {} - skipping tags, aliases, and links",
-                    node.uuid,
-                    err
+                    node.uuid, err
                 );
+                report(node, DiagnosticSeverity::Error, message);
             }
         }
     }
+    diagnostics
+}
+
+/// Replace the tasks of `file`.
+pub async fn insert_tasks(con: &SqlitePool, file: &str, tasks: &[OrgTask]) -> anyhow::Result<()> {
+    tasks::delete_file_tasks(con, file).await?;
+    for task in tasks {
+        tasks::insert_task(
+            con,
+            file,
+            task.node.as_deref(),
+            &task.title,
+            task.level,
+            task.todo.as_deref(),
+            task.priority.as_deref(),
+            task.scheduled.as_deref(),
+            task.deadline.as_deref(),
+        )
+        .await?;
+    }
+    Ok(())
 }
 
 pub fn get_nodes(content: &str, file: &str) -> Vec<OrgNode> {
+    get_nodes_and_tasks(content, file).0
+}
+
+/// Nodes and tasks of `content`, collected in one pass.
+pub fn get_nodes_and_tasks(content: &str, file: &str) -> (Vec<OrgNode>, Vec<OrgTask>) {
     let org = Org::parse(content);
 
     let mut traverser = NodesBuilder::new(file);
     org.traverse(&mut traverser);
-    traverser.nodes
+    (traverser.nodes, traverser.tasks)
 }
 
 #[derive(Default)]
 pub struct NodesBuilder {
     nodes: Vec<OrgNode>,
+    tasks: Vec<OrgTask>,
     id_stack: Vec<(String, String)>,
     tags_stack: Vec<Vec<String>>,
     olp: Vec<String>,
@@ -140,12 +252,16 @@ impl Traverser for NodesBuilder {
                     if let Some(id) = properties.get("ID") {
                         let title = document.title().unwrap_or_else(String::new);
                         let tags = get_tags_from_keywords(document.keywords());
-                        let id = id.to_string();
+                        let id = RoamID::normalize(&id);
                         let content = document.raw();
                         let aliases = properties
                             .get("ROAM_ALIASES")
                             .map(parse_aliases)
                             .unwrap_or_default();
+                        let refs = properties
+                            .get("ROAM_REFS")
+                            .map(|refs| citations::parse_refs(&refs))
+                            .unwrap_or_default();
 
                         let node = OrgNode {
                             title: title.clone(),
@@ -154,10 +270,12 @@ impl Traverser for NodesBuilder {
                             level: 0,
                             tags: tags.clone(),
                             aliases,
+                            refs,
                             parent: None,
                             olp: vec![],
                             actual_olp: vec![],
                             file: self.file.clone(),
+                            properties: collect_properties(&properties),
                             ..Default::default()
                         };
 
@@ -186,6 +304,10 @@ impl Traverser for NodesBuilder {
                             .get("ROAM_ALIASES")
                             .map(parse_aliases)
                             .unwrap_or_default();
+                        let refs = properties
+                            .get("ROAM_REFS")
+                            .map(|refs| citations::parse_refs(&refs))
+                            .unwrap_or_default();
 
                         let tags: Vec<String> = headline
                             .tags()
@@ -193,7 +315,7 @@ impl Traverser for NodesBuilder {
                             .filter(|t| !t.trim().is_empty())
                             .collect();
 
-                        let id = id.to_string();
+                        let id = RoamID::normalize(&id);
                         // TODO: this is wrong.
                         let title = headline.title_raw().trim().to_string();
                         let level = headline.level() as u64;
@@ -228,13 +350,32 @@ impl Traverser for NodesBuilder {
                             olp,
                             actual_olp,
                             aliases,
+                            refs,
                             file: self.file.clone(),
+                            todo: headline.todo_keyword().map(|todo| todo.to_string()),
+                            priority: headline.priority().map(|priority| priority.to_string()),
+                            scheduled: headline.scheduled().and_then(timestamp_date),
+                            deadline: headline.deadline().and_then(timestamp_date),
+                            properties: collect_properties(&properties),
                             ..Default::default()
                         };
 
                         self.nodes.push(node);
                     }
                 }
+                let task = OrgTask {
+                    title: headline.title_raw().trim().to_string(),
+                    // Headline nodes were pushed onto the stack above.
+                    node: self.id_stack.last().map(|(_, id)| id.clone()),
+                    level: headline.level() as u64,
+                    todo: headline.todo_keyword().map(|todo| todo.to_string()),
+                    priority: headline.priority().map(|priority| priority.to_string()),
+                    scheduled: headline.scheduled().and_then(timestamp_date),
+                    deadline: headline.deadline().and_then(timestamp_date),
+                };
+                if task.todo.is_some() || task.scheduled.is_some() || task.deadline.is_some() {
+                    self.tasks.push(task);
+                }
                 self.olp.push(headline.title_raw());
                 self.actual_olp.push(headline.title_raw());
             }
@@ -244,7 +385,7 @@ impl Traverser for NodesBuilder {
                 if let Some(properties) = headline.properties() {
                     if let Some(id) = properties.get("ID") {
                         if let Some((_, id_from_stack)) = self.id_stack.last() {
-                            if id == *id_from_stack {
+                            if RoamID::normalize(&id) == *id_from_stack {
                                 let _ = self.id_stack.pop();
                                 let _ = self.tags_stack.pop();
                             }
@@ -253,7 +394,17 @@ impl Traverser for NodesBuilder {
                 }
             }
             Event::Enter(Container::Link(link)) => {
-                if let Some((id, description)) = parse_link(link) {
+                if let Some(url) = parse_external_link(&link) {
+                    let node = self.id_stack.last().and_then(|parent| {
+                        self.nodes
+                            .iter_mut()
+                            .rev()
+                            .find(|n| n.title == parent.0.trim())
+                    });
+                    if let Some(node) = node {
+                        node.external_links.push(url);
+                    }
+                } else if let Some((id, description)) = parse_link(link) {
                     let id_parent = match self.id_stack.last() {
                         Some(parent) => parent,
                         None => return,
@@ -270,11 +421,44 @@ impl Traverser for NodesBuilder {
                     }
                 }
             }
+            Event::Text(text) => {
+                let keys = citations::cite_keys(&text);
+                if keys.is_empty() {
+                    return;
+                }
+                let node = self.id_stack.last().and_then(|parent| {
+                    self.nodes
+                        .iter_mut()
+                        .rev()
+                        .find(|n| n.title == parent.0.trim())
+                });
+                if let Some(node) = node {
+                    for key in keys {
+                        if !node.cites.contains(&key) {
+                            node.cites.push(key);
+                        }
+                    }
+                }
+            }
             _ => {}
         }
     }
 }
 
+fn collect_properties(properties: &PropertyDrawer) -> Vec<(String, String)> {
+    properties
+        .iter()
+        .filter(|(key, _)| !key.eq_ignore_ascii_case("ID"))
+        .map(|(key, value)| (key.to_uppercase(), value.trim().to_string()))
+        .collect()
+}
+
+fn timestamp_date(timestamp: Timestamp) -> Option<String> {
+    timestamps::active_dates(&timestamp.raw())
+        .into_iter()
+        .next()
+}
+
 fn parse_aliases(aliases: orgize::ast::Token) -> Vec<String> {
     aliases
         .split(' ')
@@ -283,6 +467,13 @@ fn parse_aliases(aliases: orgize::ast::Token) -> Vec<String> {
         .collect()
 }
 
+fn parse_external_link(link: &Link) -> Option<String> {
+    let path = link.path();
+    let path = path.trim();
+    let (scheme, _) = path.split_once("://")?;
+    matches!(scheme.to_lowercase().as_str(), "http" | "https").then(|| path.to_string())
+}
+
 fn parse_link(link: Link) -> Option<(String, String)> {
     let path = link.path();
 
@@ -296,7 +487,7 @@ fn parse_link(link: Link) -> Option<(String, String)> {
                 })
                 .collect::<String>();
 
-            return Some((id.to_string(), desc));
+            return Some((RoamID::normalize(id), desc));
         }
     }
 
@@ -607,6 +798,24 @@ Linking to [[id:e655725f-97db-4eec-925a-b80d66ad97e8][Test]]";
         );
     }
 
+    #[test]
+    fn test_normalized_ids() {
+        const ORG: &str = ":PROPERTIES:
+:ID:       E655725F-97DB-4EEC-925A-B80D66AD97E8
+:END:
+#+title: Test
+* other
+:PROPERTIES:
+:ID:       e655725f-97db-4eec-925a-b80d66ad97E9
+:END:
+Linking to [[id:E655725F-97db-4eec-925a-b80d66ad97e8][Test]]";
+        let res = get_nodes(ORG, "test.org");
+        assert_eq!(res[0].uuid, "e655725f-97db-4eec-925a-b80d66ad97e8");
+        assert_eq!(res[1].uuid, "e655725f-97db-4eec-925a-b80d66ad97e9");
+        assert_eq!(res[1].parent.as_deref(), Some(res[0].uuid.as_str()));
+        assert_eq!(res[1].links[0].0, "e655725f-97db-4eec-925a-b80d66ad97e8");
+    }
+
     #[test]
     fn test_aliases() {
         const ORG: &str = ":PROPERTIES:
@@ -629,4 +838,43 @@ Linking to [[id:e655725f-97db-4eec-925a-b80d66ad97e8][Test]]";
             vec!["test3".to_string(), "test4".to_string()]
         );
     }
+
+    #[test]
+    fn test_tasks() {
+        const ORG: &str = ":PROPERTIES:
+:ID:       e655725f-97db-4eec-925a-b80d66ad97e8
+:END:
+#+title: Test
+* TODO [#A] Write report
+DEADLINE: <2024-05-03 Fri> SCHEDULED: <2024-05-01 Wed>
+* Notes
+* Meeting
+:PROPERTIES:
+:ID:       e655725f-97db-4eec-925a-b80d66ad97e9
+:END:
+** TODO Follow up
+";
+        let (_, tasks) = get_nodes_and_tasks(ORG, "test.org");
+        assert_eq!(
+            tasks,
+            vec![
+                OrgTask {
+                    title: "Write report".to_string(),
+                    node: Some("e655725f-97db-4eec-925a-b80d66ad97e8".to_string()),
+                    level: 1,
+                    todo: Some("TODO".to_string()),
+                    priority: Some("A".to_string()),
+                    scheduled: Some("2024-05-01".to_string()),
+                    deadline: Some("2024-05-03".to_string()),
+                },
+                OrgTask {
+                    title: "Follow up".to_string(),
+                    node: Some("e655725f-97db-4eec-925a-b80d66ad97e9".to_string()),
+                    level: 2,
+                    todo: Some("TODO".to_string()),
+                    ..Default::default()
+                },
+            ]
+        );
+    }
 }
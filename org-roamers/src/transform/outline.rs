@@ -0,0 +1,125 @@
+//! Build the heading tree of an org document.
+
+use orgize::{
+    export::{Container, Event, TraversalContext, Traverser},
+    Org,
+};
+
+use crate::server::types::RoamID;
+
+/// A heading of an org document. `id` is set if the heading is a node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heading {
+    pub title: String,
+    pub level: usize,
+    pub id: Option<String>,
+    pub children: Vec<Heading>,
+}
+
+/// Collect all headings of `org` as a tree.
+pub fn outline(org: &str) -> Vec<Heading> {
+    let mut traverser = Outline { headings: vec![] };
+    Org::parse(org).traverse(&mut traverser);
+    build_tree(traverser.headings)
+}
+
+struct Outline {
+    headings: Vec<Heading>,
+}
+
+impl Traverser for Outline {
+    fn event(&mut self, event: Event, _: &mut TraversalContext) {
+        if let Event::Enter(Container::Headline(headline)) = event {
+            let id = headline
+                .properties()
+                .and_then(|properties| properties.get("ID"))
+                .map(|id| RoamID::normalize(&id));
+            self.headings.push(Heading {
+                title: headline.title_raw().trim().to_string(),
+                level: headline.level(),
+                id,
+                children: vec![],
+            });
+        }
+    }
+}
+
+/// Nest headings in document order below the closest preceding heading with
+/// a lower level.
+fn build_tree(headings: Vec<Heading>) -> Vec<Heading> {
+    let mut roots: Vec<Heading> = vec![];
+    let mut stack: Vec<Heading> = vec![];
+
+    let close = |stack: &mut Vec<Heading>, roots: &mut Vec<Heading>| {
+        let heading = stack.pop().unwrap();
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(heading),
+            None => roots.push(heading),
+        }
+    };
+
+    for heading in headings {
+        while stack.last().is_some_and(|last| last.level >= heading.level) {
+            close(&mut stack, &mut roots);
+        }
+        stack.push(heading);
+    }
+    while !stack.is_empty() {
+        close(&mut stack, &mut roots);
+    }
+
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heading(title: &str, level: usize) -> Heading {
+        Heading {
+            title: title.to_string(),
+            level,
+            id: None,
+            children: vec![],
+        }
+    }
+
+    #[test]
+    fn test_build_tree() {
+        let headings = vec![
+            heading("a", 1),
+            heading("b", 2),
+            heading("c", 3),
+            heading("d", 2),
+            heading("e", 1),
+        ];
+        let tree = build_tree(headings);
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree[0].title, "a");
+        assert_eq!(tree[0].children.len(), 2);
+        assert_eq!(tree[0].children[0].children[0].title, "c");
+        assert_eq!(tree[0].children[1].title, "d");
+        assert!(tree[1].children.is_empty());
+    }
+
+    #[test]
+    fn test_outline() {
+        let org = concat!(
+            "#+title: File\n",
+            "* Hello\n",
+            ":PROPERTIES:\n",
+            ":ID:       e655725f-97db-4eec-925a-b80d66ad97e8\n",
+            ":END:\n",
+            "** World\n",
+            "* Again\n",
+        );
+        let tree = outline(org);
+        assert_eq!(tree.len(), 2);
+        assert_eq!(
+            tree[0].id.as_deref(),
+            Some("e655725f-97db-4eec-925a-b80d66ad97e8")
+        );
+        assert_eq!(tree[0].children[0].title, "World");
+        assert_eq!(tree[1].title, "Again");
+    }
+}
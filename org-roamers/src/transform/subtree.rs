@@ -1,8 +1,10 @@
 //! Org utils to extract subtrees.
 
+use std::ops::Range;
+
 use orgize::{
     export::{Container, Event, TraversalContext, Traverser},
-    rowan::ast::AstNode,
+    rowan::{ast::AstNode, TextRange},
     Org,
 };
 
@@ -10,30 +12,39 @@ use crate::server::types::RoamID;
 
 pub struct Subtree {
     on: RoamID,
-    subtree: Option<String>,
+    range: Option<Range<usize>>,
 }
 
 impl Subtree {
     /// Construct and get a subtree.
     pub fn get(on: RoamID, org: &str) -> Option<String> {
-        let org = Org::parse(org);
-        let mut traverser = Subtree { on, subtree: None };
-        org.traverse(&mut traverser);
-        traverser.subtree
+        Self::range(on, org).map(|range| org[range].to_string())
+    }
+
+    /// Byte range of the subtree in `org`.
+    pub fn range(on: RoamID, org: &str) -> Option<Range<usize>> {
+        let parsed = Org::parse(org);
+        let mut traverser = Subtree { on, range: None };
+        parsed.traverse(&mut traverser);
+        traverser.range
+    }
+
+    fn found(&mut self, range: TextRange) {
+        self.range = Some(range.start().into()..range.end().into());
     }
 }
 
 impl Traverser for Subtree {
     fn event(&mut self, event: Event, _: &mut TraversalContext) {
-        if self.subtree.is_some() {
+        if self.range.is_some() {
             return;
         }
         match event {
             Event::Enter(Container::Document(document)) => {
                 if let Some(properties) = document.properties() {
                     if let Some(id) = properties.get("ID") {
-                        if id == self.on.id() {
-                            self.subtree = Some(document.syntax().clone_subtree().to_string());
+                        if RoamID::normalize(&id) == self.on.id() {
+                            self.found(document.syntax().text_range());
                         }
                     }
                 }
@@ -41,8 +52,8 @@ impl Traverser for Subtree {
             Event::Enter(Container::Headline(headline)) => {
                 if let Some(properties) = headline.properties() {
                     if let Some(id) = properties.get("ID") {
-                        if id == self.on.id() {
-                            self.subtree = Some(headline.syntax().clone_subtree().to_string());
+                        if RoamID::normalize(&id) == self.on.id() {
+                            self.found(headline.syntax().text_range());
                         }
                     }
                 }
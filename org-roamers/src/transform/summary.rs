@@ -0,0 +1,186 @@
+//! Extract a one line summary of a node. The summary is the first sentence of
+//! the first paragraph, with drawers, keywords, blocks and planning lines
+//! skipped and links replaced by their description.
+
+/// Summaries longer than this are truncated.
+pub const MAX_SUMMARY_LEN: usize = 200;
+
+/// First sentence of the first paragraph in `content`. Returns an empty string
+/// if the node has no text before its first child headline.
+pub fn summary(content: &str) -> String {
+    let paragraph = first_paragraph(content);
+    let text = strip_links(&paragraph);
+    let sentence = first_sentence(&text);
+    truncate(sentence.trim())
+}
+
+fn first_paragraph(content: &str) -> String {
+    let mut lines = vec![];
+    let mut in_drawer = false;
+    let mut in_block = false;
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+        let lower = trimmed.to_ascii_lowercase();
+        if in_drawer {
+            in_drawer = !trimmed.eq_ignore_ascii_case(":END:");
+            continue;
+        }
+        if in_block {
+            in_block = !lower.starts_with("#+end_");
+            continue;
+        }
+        if is_headline(line) {
+            break;
+        }
+        if trimmed.is_empty() {
+            if lines.is_empty() {
+                continue;
+            }
+            break;
+        }
+        if is_drawer_start(trimmed) {
+            in_drawer = true;
+            continue;
+        }
+        if lower.starts_with("#+begin_") {
+            in_block = true;
+            continue;
+        }
+        if trimmed.starts_with('#') || trimmed.starts_with('|') || is_planning(trimmed) {
+            continue;
+        }
+        let text = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("+ "))
+            .unwrap_or(trimmed);
+        lines.push(text);
+    }
+
+    lines.join(" ")
+}
+
+fn is_headline(line: &str) -> bool {
+    let stars = line.chars().take_while(|c| *c == '*').count();
+    stars > 0 && line[stars..].starts_with(' ')
+}
+
+fn is_drawer_start(line: &str) -> bool {
+    line.len() > 2
+        && line.starts_with(':')
+        && line.ends_with(':')
+        && line[1..line.len() - 1]
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn is_planning(line: &str) -> bool {
+    ["SCHEDULED:", "DEADLINE:", "CLOSED:"]
+        .iter()
+        .any(|keyword| line.starts_with(keyword))
+}
+
+/// Replace `[[target][description]]` with `description` and `[[target]]` with
+/// `target`.
+fn strip_links(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        let Some(end) = rest[start..].find("]]") else {
+            break;
+        };
+        result.push_str(&rest[..start]);
+        let link = &rest[start + 2..start + end];
+        let shown = match link.split_once("][") {
+            Some((_, description)) => description,
+            None => link,
+        };
+        result.push_str(shown);
+        rest = &rest[start + end + 2..];
+    }
+    result.push_str(rest);
+    result
+}
+
+fn first_sentence(text: &str) -> &str {
+    let end = text
+        .char_indices()
+        .zip(text.chars().skip(1))
+        .find(|((_, c), next)| matches!(c, '.' | '!' | '?') && next.is_whitespace())
+        .map(|((i, c), _)| i + c.len_utf8());
+    match end {
+        Some(end) => &text[..end],
+        None => text,
+    }
+}
+
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(MAX_SUMMARY_LEN) {
+        Some((end, _)) => format!("{}…", text[..end].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_skips_metadata() {
+        let content = concat!(
+            ":PROPERTIES:\n",
+            ":ID:       e655725d-97db-4eec-925a-b80d66ad97e8\n",
+            ":END:\n",
+            "#+title: Rust\n",
+            "#+filetags: :lang:\n",
+            "\n",
+            "Rust is a [[id:abc][systems]] language. It is fast.\n",
+            "* Ownership\n",
+        );
+        assert_eq!(summary(content), "Rust is a systems language.");
+    }
+
+    #[test]
+    fn test_summary_joins_paragraph_lines() {
+        let content = concat!(
+            "SCHEDULED: <2024-03-15 Fri>\n",
+            ":LOGBOOK:\n",
+            "- Note taken\n",
+            ":END:\n",
+            "#+begin_src rust\n",
+            "fn main() {}\n",
+            "#+end_src\n",
+            "A sentence spanning\n",
+            "two lines without end\n",
+            "\n",
+            "Second paragraph.\n",
+        );
+        assert_eq!(
+            summary(content),
+            "A sentence spanning two lines without end"
+        );
+    }
+
+    #[test]
+    fn test_summary_empty() {
+        assert_eq!(summary("\n* Child\nText.\n"), "");
+        assert_eq!(summary(""), "");
+    }
+
+    #[test]
+    fn test_summary_truncates() {
+        let content = "word ".repeat(100);
+        let summary = summary(&content);
+        assert!(summary.ends_with('…'));
+        assert!(summary.chars().count() <= MAX_SUMMARY_LEN + 1);
+    }
+
+    #[test]
+    fn test_strip_links() {
+        assert_eq!(
+            strip_links("see [[https://example.com]] and [[id:1][Rust]]"),
+            "see https://example.com and Rust"
+        );
+        assert_eq!(strip_links("unclosed [[link"), "unclosed [[link");
+    }
+}
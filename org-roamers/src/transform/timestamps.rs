@@ -0,0 +1,98 @@
+//! Extract the dates a node is anchored to. Dates are returned as
+//! `YYYY-MM-DD` strings, which compare in chronological order.
+
+use time::{Date, Month};
+
+/// Dates of all active timestamps (`<2024-03-15 Fri>`) in `content`. Both
+/// ends of ranges are returned. The result is sorted and deduplicated.
+pub fn active_dates(content: &str) -> Vec<String> {
+    let mut dates: Vec<String> = content
+        .match_indices('<')
+        .filter_map(|(pos, _)| parse_date(&content[pos + 1..]))
+        .collect();
+    dates.sort();
+    dates.dedup();
+    dates
+}
+
+/// Date of the day heading of a datetree (`2024-03-15 Friday`) in an outline
+/// path.
+pub fn datetree_date(olp: &[String]) -> Option<String> {
+    olp.iter().rev().find_map(|segment| {
+        let segment = segment.trim();
+        let date = parse_date(segment)?;
+        // Only the date followed by the name of the day is a datetree heading.
+        let rest = &segment[date.len()..];
+        rest.starts_with(' ').then_some(date)
+    })
+}
+
+/// Check whether `s` is exactly a `YYYY-MM-DD` date.
+pub fn is_date(s: &str) -> bool {
+    s.len() == 10 && parse_date(s).is_some()
+}
+
+/// Parse a `YYYY-MM-DD` date, rejecting days that do not exist.
+pub fn calendar_date(date: &str) -> Option<Date> {
+    let mut parts = date.splitn(3, '-');
+    let year = parts.next()?.parse().ok()?;
+    let month = Month::try_from(parts.next()?.parse::<u8>().ok()?).ok()?;
+    let day = parts.next()?.parse().ok()?;
+    Date::from_calendar_date(year, month, day).ok()
+}
+
+/// Parse a `YYYY-MM-DD` date at the start of `s`.
+fn parse_date(s: &str) -> Option<String> {
+    let date = s.get(..10)?;
+    let bytes = date.as_bytes();
+    let digits = [0, 1, 2, 3, 5, 6, 8, 9];
+    if bytes[4] != b'-' || bytes[7] != b'-' || !digits.iter().all(|i| bytes[*i].is_ascii_digit()) {
+        return None;
+    }
+
+    let month: u8 = date[5..7].parse().ok()?;
+    let day: u8 = date[8..10].parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    Some(date.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_date() {
+        assert!(is_date("2024-03-15"));
+        assert!(!is_date("2024-03-15 Fri"));
+        assert!(!is_date("2024-3-15"));
+        assert!(!is_date("2024-13-01"));
+    }
+
+    #[test]
+    fn test_active_dates() {
+        let content = concat!(
+            "* Meeting\n",
+            "SCHEDULED: <2024-03-15 Fri 10:00>\n",
+            "Again <2024-03-01 Fri>--<2024-03-02 Sat> and <2024-03-15 Fri>.\n",
+            "Inactive [2024-04-01 Mon], no date <2024-13-01>, <b>html</b>.\n",
+        );
+        assert_eq!(
+            active_dates(content),
+            vec!["2024-03-01", "2024-03-02", "2024-03-15"]
+        );
+    }
+
+    #[test]
+    fn test_datetree_date() {
+        let olp = vec![
+            "2024".to_string(),
+            "2024-03 March".to_string(),
+            "2024-03-15 Friday".to_string(),
+        ];
+        assert_eq!(datetree_date(&olp), Some("2024-03-15".to_string()));
+        assert_eq!(datetree_date(&olp[..2]), None);
+    }
+}
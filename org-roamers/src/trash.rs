@@ -0,0 +1,55 @@
+//! Background job that removes files from the trash once they are older than
+//! [`TrashConfig::retention_days`](crate::config::TrashConfig).
+
+use std::{sync::Arc, time::Duration};
+
+use time::OffsetDateTime;
+use tokio_util::sync::CancellationToken;
+
+use crate::{sqlite::trash, ServerState};
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub fn purger(state: Arc<ServerState>, cancellation_token: CancellationToken) {
+    if state.config.trash.retention_days == 0 {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            tokio::select! {
+                _ = cancellation_token.cancelled() => {
+                    tracing::info!("Trash purger cancelled");
+                    break;
+                }
+                _ = interval.tick() => {
+                    if let Err(err) = purge(&state).await {
+                        tracing::error!("Failed to purge trash: {err}");
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Remove all expired files from the trash.
+pub async fn purge(state: &ServerState) -> anyhow::Result<()> {
+    let retention = i64::from(state.config.trash.retention_days) * 24 * 60 * 60;
+    let before = OffsetDateTime::now_utc().unix_timestamp() - retention;
+
+    for path in trash::get_expired(&state.sqlite, before).await? {
+        match std::fs::remove_file(state.cache.resolve(&path)) {
+            Ok(()) => tracing::info!("Purged {path} from the trash"),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => {
+                tracing::error!("Failed to purge {path}: {err}");
+                continue;
+            }
+        }
+        trash::delete_trash_entry(&state.sqlite, &path).await?;
+    }
+    Ok(())
+}
@@ -1,126 +1,479 @@
 use notify::event::{CreateKind, ModifyKind, RemoveKind};
-use notify_debouncer_full::{new_debouncer, notify::*, DebounceEventResult};
-use std::{path::PathBuf, sync::Arc, time::Duration};
+use notify_debouncer_full::{
+    new_debouncer_opt, notify::*, DebounceEventHandler, DebounceEventResult, DebouncedEvent,
+    Debouncer, RecommendedCache,
+};
+use serde::Serialize;
+use std::{
+    any::Any,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::runtime::Handle;
 use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 
 use crate::{
-    cache::OrgCacheEntry, client::message::WebSocketMessage, server::types::RoamID,
-    sqlite::files::insert_file, transform::node_builder, ServerState,
+    cache::{is_hidden, OrgCacheEntry},
+    client::message::WebSocketMessage,
+    collab,
+    graph::{self, GraphData, GraphUpdate},
+    reindex,
+    server::types::RoamID,
+    sqlite::{diagnostics as stored_diagnostics, files::delete_file, fts, rebuild},
+    transform::{
+        diagnostics,
+        diff::{parsed_graph, stored_graph},
+        node_builder,
+        title::TitleSanitizer,
+    },
+    ServerState,
 };
 
-pub async fn watcher(
-    state: Arc<ServerState>,
-    cancellation_token: CancellationToken,
+const DEBOUNCE_TIMEOUT: Duration = Duration::from_secs(2);
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+/// Failures of the native watcher before falling back to polling.
+const POLL_FALLBACK_AFTER: u32 = 3;
+/// A watcher that ran this long before it failed starts over with the
+/// native watcher and the initial backoff.
+const STABLE_PERIOD: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatcherStatus {
+    Disabled,
+    /// Native file system events are used.
+    Running,
+    /// The native watcher failed repeatedly, the vault is polled instead.
+    Polling,
+    /// The watcher failed and is restarted after a backoff.
+    Restarting,
+}
+
+impl WatcherStatus {
+    pub fn is_degraded(self) -> bool {
+        matches!(self, Self::Polling | Self::Restarting)
+    }
+}
+
+/// Watch the vault in the background. If the watcher fails, it is restarted
+/// with exponential backoff and eventually replaced by a polling watcher.
+pub fn watcher(state: Arc<ServerState>, cancellation_token: CancellationToken) {
+    tokio::spawn(async move {
+        let mut recovery = Recovery::default();
+
+        loop {
+            let started = Instant::now();
+            match run(&state, &cancellation_token, recovery.polling()).await {
+                Ok(()) => break,
+                Err(err) => tracing::error!("File watcher failed: {err}"),
+            }
+
+            let backoff = recovery.failed(started.elapsed());
+            set_status(&state, WatcherStatus::Restarting);
+            tracing::info!("Restarting file watcher in {backoff:?}");
+
+            tokio::select! {
+                _ = cancellation_token.cancelled() => break,
+                _ = tokio::time::sleep(backoff) => {}
+            }
+        }
+
+        tracing::info!("Watcher shutdown complete");
+    });
+}
+
+/// Failures of the watcher, deciding on the backoff and when to fall back
+/// to polling.
+#[derive(Debug)]
+struct Recovery {
+    failures: u32,
+    backoff: Duration,
+}
+
+impl Default for Recovery {
+    fn default() -> Self {
+        Self {
+            failures: 0,
+            backoff: INITIAL_BACKOFF,
+        }
+    }
+}
+
+impl Recovery {
+    fn polling(&self) -> bool {
+        self.failures >= POLL_FALLBACK_AFTER
+    }
+
+    /// Record a failure of a watcher that ran for `ran_for`, whether it
+    /// failed to start or while running. Returns how long to wait before it
+    /// is restarted.
+    fn failed(&mut self, ran_for: Duration) -> Duration {
+        // Only a watcher that ran for a while starts over
+        if ran_for >= STABLE_PERIOD {
+            *self = Self::default();
+        }
+        self.failures += 1;
+        let backoff = self.backoff;
+        self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+        backoff
+    }
+}
+
+/// Run a watcher until it is cancelled (`Ok`) or reports errors (`Err`).
+async fn run(
+    state: &ServerState,
+    cancellation_token: &CancellationToken,
+    polling: bool,
 ) -> anyhow::Result<()> {
-    let path = state.cache.path().to_path_buf();
     let (tx, mut rx) = mpsc::channel(100);
     let rt = Handle::current();
 
-    let mut debouncer = new_debouncer(
-        Duration::from_secs(2),
-        None,
-        move |result: DebounceEventResult| {
-            let tx = tx.clone();
-            let rt = rt.clone();
+    let handler = move |result: DebounceEventResult| {
+        let tx = tx.clone();
+        let rt = rt.clone();
+
+        rt.spawn(async move {
+            if let Err(e) = tx.send(result).await {
+                tracing::debug!("Failed to send watcher event: {}", e);
+            }
+        });
+    };
 
-            rt.spawn(async move {
-                if let Err(e) = tx.send(result).await {
-                    tracing::debug!("Failed to send watcher event: {}", e);
+    let _debouncer: Box<dyn Any + Send> = if polling {
+        let config = Config::default()
+            .with_poll_interval(POLL_INTERVAL)
+            .with_follow_symlinks(state.config.follow_symlinks);
+        let debouncer = start::<PollWatcher, _>(state, handler, config)?;
+        tracing::warn!("Falling back to polling the vault every {POLL_INTERVAL:?}");
+        set_status(state, WatcherStatus::Polling);
+        Box::new(debouncer)
+    } else {
+        let config = Config::default().with_follow_symlinks(state.config.follow_symlinks);
+        let debouncer = start::<RecommendedWatcher, _>(state, handler, config)?;
+        set_status(state, WatcherStatus::Running);
+        Box::new(debouncer)
+    };
+
+    loop {
+        tokio::select! {
+            _ = cancellation_token.cancelled() => {
+                tracing::info!("Watcher cancelled");
+                return Ok(());
+            }
+            Some(result) = rx.recv() => match result {
+                Ok(events) => handle_watcher_event(events, state).await,
+                Err(errors) => {
+                    if errors.iter().any(is_watch_limit) {
+                        report_watch_budget(state.cache.path());
+                    }
+                    let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+                    anyhow::bail!("{}", errors.join(", "));
                 }
-            });
-        },
+            }
+        }
+    }
+}
+
+fn start<T: Watcher, F: DebounceEventHandler>(
+    state: &ServerState,
+    handler: F,
+    config: Config,
+) -> anyhow::Result<Debouncer<T, RecommendedCache>> {
+    let root = state.cache.path();
+    let mut debouncer = new_debouncer_opt::<_, T, _>(
+        DEBOUNCE_TIMEOUT,
+        None,
+        handler,
+        RecommendedCache::new(),
+        config,
     )?;
+    let follow_symlinks = state.config.follow_symlinks;
+    let mut targets = watch_targets(root, state.config.watcher.top_level_only, follow_symlinks)?;
+    for extra in state
+        .config
+        .extra_roots
+        .iter()
+        .filter(|root| root.is_valid())
+    {
+        match watch_targets(&extra.path, extra.top_level_only, follow_symlinks) {
+            Ok(extra_targets) => targets.extend(extra_targets),
+            Err(err) => tracing::error!("Failed to watch {}: {err}", extra.path.display()),
+        }
+    }
+    for (path, mode) in targets {
+        if let Err(err) = debouncer.watch(&path, mode) {
+            if is_watch_limit(&err) {
+                report_watch_budget(root);
+            }
+            return Err(err.into());
+        }
+    }
+    Ok(debouncer)
+}
 
-    debouncer.watch(&path, RecursiveMode::Recursive)?;
+/// Directories to watch. In `top_level_only` mode these are the root and
+/// its direct subdirectories, which are watched without recursion.
+/// Symlinked subdirectories count if symlinks are followed.
+fn watch_targets(
+    root: &Path,
+    top_level_only: bool,
+    follow_symlinks: bool,
+) -> io::Result<Vec<(PathBuf, RecursiveMode)>> {
+    if !top_level_only {
+        return Ok(vec![(root.to_path_buf(), RecursiveMode::Recursive)]);
+    }
+
+    let mut targets = vec![(root.to_path_buf(), RecursiveMode::NonRecursive)];
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let is_dir = match file_type.is_symlink() {
+            true if follow_symlinks => entry.path().is_dir(),
+            _ => file_type.is_dir(),
+        };
+        if is_dir {
+            targets.push((entry.path(), RecursiveMode::NonRecursive));
+        }
+    }
+    Ok(targets)
+}
+
+/// Periodically reindex the vault, because changes below the top level
+/// directories are not watched in `top_level_only` mode.
+pub fn rescanner(state: Arc<ServerState>, cancellation_token: CancellationToken) {
+    let minutes = state.config.watcher.rescan_minutes;
+    let top_level_only = state.config.watcher.top_level_only
+        || state
+            .config
+            .extra_roots
+            .iter()
+            .any(|root| root.top_level_only);
+    if !top_level_only || minutes == 0 {
+        return;
+    }
 
     tokio::spawn(async move {
-        let _debouncer = debouncer;
+        let mut interval = tokio::time::interval(Duration::from_secs(minutes * 60));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        // The first tick completes immediately, the vault was just indexed.
+        interval.tick().await;
 
         loop {
             tokio::select! {
-                _ = cancellation_token.cancelled() => {
-                    tracing::info!("Watcher cancelled");
-                    break;
-                }
-                Some(result) = rx.recv() => {
-                    handle_watcher_event(result, &state).await;
+                _ = cancellation_token.cancelled() => break,
+                _ = interval.tick() => {
+                    let job_id = reindex::next_job_id(&state);
+                    reindex::run(&state, job_id, None).await;
                 }
             }
         }
-
-        tracing::info!("Watcher shutdown complete");
     });
+}
 
-    Ok(())
+fn is_watch_limit(err: &Error) -> bool {
+    matches!(err.kind, ErrorKind::MaxFilesWatch)
 }
 
-async fn handle_watcher_event(result: DebounceEventResult, state: &ServerState) {
-    match result {
-        Ok(events) => {
-            let paths: Vec<PathBuf> = events
-                .iter()
-                .filter(|event| is_write_event(&event.kind))
-                .flat_map(|e| e.paths.clone())
-                .collect();
+/// Every watched directory needs an inotify watch. Log how many are needed,
+/// so the limit can be raised.
+fn report_watch_budget(root: &Path) {
+    let dirs = count_dirs(root);
+    match max_user_watches() {
+        Some(limit) => tracing::error!(
+            "Out of inotify watches: the vault has {dirs} directories, but \
+             fs.inotify.max_user_watches is {limit}. Raise it to at least {} \
+             (sysctl fs.inotify.max_user_watches=N) or set watcher.top_level_only",
+            limit + dirs
+        ),
+        None => tracing::error!(
+            "Out of file watches: the vault has {dirs} directories. Raise the \
+             limit of the OS or set watcher.top_level_only"
+        ),
+    }
+}
 
-            let filtered = filter_org_files(paths);
-            let mut files_updated = 0;
+#[cfg(target_os = "linux")]
+fn max_user_watches() -> Option<usize> {
+    fs::read_to_string("/proc/sys/fs/inotify/max_user_watches")
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
 
-            for path in filtered {
-                tracing::info!("File changed: {:?}", path);
+#[cfg(not(target_os = "linux"))]
+fn max_user_watches() -> Option<usize> {
+    None
+}
 
-                // Update both cache and database
-                if let Err(e) = update_file(state, &path).await {
-                    tracing::error!("Failed to update file {:?}: {}", path, e);
-                } else {
-                    files_updated += 1;
-                }
-            }
+/// Number of directories below and including `root`.
+fn count_dirs(root: &Path) -> usize {
+    let Ok(entries) = fs::read_dir(root) else {
+        return 0;
+    };
+    1 + entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+        .map(|entry| count_dirs(&entry.path()))
+        .sum::<usize>()
+}
 
-            // Notify all WebSocket clients about the changes
-            if files_updated > 0 {
-                let message = WebSocketMessage::StatusUpdate {
-                    files_changed: files_updated,
-                };
-                state.broadcast_to_websockets(message);
-                tracing::info!(
-                    "Notified WebSocket clients: {} files changed",
-                    files_updated
-                );
-            }
+fn set_status(state: &ServerState, status: WatcherStatus) {
+    *state.watcher_status.write().unwrap() = status;
+}
+
+async fn handle_watcher_event(events: Vec<DebouncedEvent>, state: &ServerState) {
+    let paths: Vec<PathBuf> = events
+        .iter()
+        .filter(|event| is_write_event(&event.kind))
+        .flat_map(|e| e.paths.clone())
+        .collect();
+
+    // Templates and other hidden files are not indexed
+    let filtered = filter_org_files(paths).into_iter().filter(|path| {
+        !state
+            .cache
+            .relative(path)
+            .is_some_and(|file| is_hidden(&file))
+    });
+    let mut files_updated = 0;
+
+    for path in filtered {
+        tracing::info!("File changed: {:?}", path);
+
+        // Update both cache and database
+        if let Err(e) = update_file(state, &path).await {
+            tracing::error!("Failed to update file {:?}: {}", path, e);
+        } else {
+            files_updated += 1;
         }
-        Err(errors) => {
-            for error in errors {
-                tracing::error!("Watcher error: {error}");
+    }
+
+    // Notify all WebSocket clients about the changes
+    if files_updated > 0 {
+        state.notify_vault_changed();
+
+        let message = WebSocketMessage::StatusUpdate {
+            files_changed: files_updated,
+        };
+        state.broadcast_to_websockets(message);
+        tracing::info!(
+            "Notified WebSocket clients: {} files changed",
+            files_updated
+        );
+    }
+}
+
+/// Index a changed file and broadcast the changes of the graph.
+pub(crate) async fn update_file(state: &ServerState, path: &PathBuf) -> anyhow::Result<()> {
+    // Create new cache entry by reading the file
+    let cache_entry = match state.cache.entry(path) {
+        Ok(entry) => entry,
+        Err(err) => {
+            if let Some(relative) = state.cache.relative(path) {
+                let file = relative.to_string_lossy();
+                match err.kind() {
+                    io::ErrorKind::NotFound => return remove_file(state, &relative).await,
+                    // Skipped files are dropped from the index
+                    io::ErrorKind::FileTooLarge => {
+                        remove_file(state, &relative).await?;
+                        stored_diagnostics::record_file_error(&state.sqlite, &file, &err).await
+                    }
+                    _ => stored_diagnostics::record_file_error(&state.sqlite, &file, &err).await,
+                }
             }
+            return Err(err.into());
+        }
+    };
+    collab::file_changed(state, cache_entry.path(), cache_entry.content());
+    let mut update = index_entry(state, cache_entry).await?;
+
+    if !update.is_empty() {
+        for node in update
+            .new_nodes
+            .iter_mut()
+            .chain(update.updated_nodes.iter_mut())
+        {
+            node.title = TitleSanitizer::new().process(node.title.title()).into();
         }
+        state.broadcast_to_websockets(WebSocketMessage::GraphUpdate(update));
     }
+    Ok(())
 }
 
-async fn update_file(state: &ServerState, path: &PathBuf) -> anyhow::Result<()> {
-    // Create new cache entry by reading the file
-    let cache_entry = OrgCacheEntry::new(state.cache.path(), path)?;
+/// Drop a file from the cache and database, e.g. because it was deleted, and
+/// broadcast the nodes and links that went away with it. `file` is relative
+/// to the root.
+pub(crate) async fn remove_file(state: &ServerState, file: &Path) -> anyhow::Result<()> {
+    let file_path_str = file.to_string_lossy().to_string();
+    let stored = stored_graph(
+        rebuild::get_file_nodes(&state.sqlite, &file_path_str).await?,
+        rebuild::get_file_links(&state.sqlite, &file_path_str).await?,
+    );
+
+    // Diagnostics and the full text index are dropped with the file
+    delete_file(&state.sqlite, &file_path_str).await?;
+    state.cache.remove_file(file);
+
+    let update = graph::diff(&stored, &GraphData::default());
+    if !update.is_empty() {
+        state.broadcast_to_websockets(WebSocketMessage::GraphUpdate(update));
+    }
+    Ok(())
+}
+
+/// Store an already read file in the cache and database. Returns how the
+/// graph changed.
+pub(crate) async fn index_entry(
+    state: &ServerState,
+    cache_entry: OrgCacheEntry,
+) -> anyhow::Result<GraphUpdate> {
+    let file_path_str = cache_entry.path().to_string_lossy().to_string();
+    let stored = stored_graph(
+        rebuild::get_file_nodes(&state.sqlite, &file_path_str).await?,
+        rebuild::get_file_links(&state.sqlite, &file_path_str).await?,
+    );
 
     // Update database with file metadata
-    insert_file(&state.sqlite, cache_entry.path(), cache_entry.get_hash()).await?;
+    rebuild::upsert_file(
+        &state.sqlite,
+        cache_entry.path(),
+        cache_entry.get_hash(),
+        cache_entry.mtime(),
+        cache_entry.created(),
+    )
+    .await?;
+    fts::index_file(&state.sqlite, &file_path_str, cache_entry.content()).await?;
 
     // Parse org content to extract nodes
-    let file_path_str = cache_entry.path().to_string_lossy().to_string();
-    let nodes = node_builder::get_nodes(cache_entry.content(), &file_path_str);
+    let (nodes, tasks) = node_builder::get_nodes_and_tasks(cache_entry.content(), &file_path_str);
+    node_builder::insert_tasks(&state.sqlite, &file_path_str, &tasks).await?;
+    let update = graph::diff(&stored, &parsed_graph(&nodes));
 
     // Collect node IDs
     let node_ids: Vec<RoamID> = nodes.iter().map(|n| n.uuid.clone().into()).collect();
 
-    // Update cache with all nodes from this file
-    state.cache.insert_many(&node_ids, cache_entry);
+    // Update nodes in database. Kept nodes are replaced, which also clears
+    // their tags, links, etc. before they are inserted again.
+    for id in &update.removed_nodes {
+        rebuild::delete_node(&state.sqlite, id.id()).await?;
+    }
+    let mut found = diagnostics::check(cache_entry.content());
+    found.extend(node_builder::insert_nodes(&state.sqlite, nodes).await);
+    diagnostics::locate_nodes(cache_entry.content(), &mut found);
+    stored_diagnostics::replace_file_diagnostics(&state.sqlite, &file_path_str, &found).await?;
 
-    // Update nodes in database
-    node_builder::insert_nodes(&state.sqlite, nodes).await;
+    // Update cache with all nodes from this file, dropping removed ones
+    state.cache.remove_file(cache_entry.path());
+    state.cache.insert_many(&node_ids, cache_entry);
 
     tracing::info!("Updated file {:?} in cache and database", file_path_str);
-    Ok(())
+    Ok(update)
 }
 
 fn is_write_event(kind: &EventKind) -> bool {
@@ -156,4 +509,74 @@ mod tests {
         let res = filter_org_files(paths);
         assert_eq!(res, vec![PathBuf::from("/org/test.org")]);
     }
+
+    #[tokio::test]
+    async fn test_deleted_file_is_removed() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path().join("notes");
+        fs::create_dir_all(&root).unwrap();
+        let path = root.join("a.org");
+        fs::write(&path, ":PROPERTIES:\n:ID: a\n:END:\n#+title: A\n").unwrap();
+
+        let config = crate::config::Config {
+            org_roamers_root: root.clone(),
+            database: crate::config::DatabaseConfig {
+                path: Some(temp_dir.path().join("roam.db")),
+            },
+            ..Default::default()
+        };
+        let state = ServerState::new(config).await.unwrap();
+        let id = RoamID::from("a");
+        assert!(state.cache.retrieve(&id).is_some());
+
+        fs::remove_file(&path).unwrap();
+        update_file(&state, &path).await.unwrap();
+
+        assert!(state.cache.retrieve(&id).is_none());
+        let nodes = rebuild::get_file_nodes(&state.sqlite, "a.org")
+            .await
+            .unwrap();
+        assert!(nodes.is_empty());
+        let broadcasts = state.pending_broadcasts.lock().unwrap();
+        assert!(matches!(
+            broadcasts.as_slice(),
+            [WebSocketMessage::GraphUpdate(update)] if update.removed_nodes == vec![id.clone()]
+        ));
+    }
+
+    #[test]
+    fn test_recovery_falls_back_to_polling() {
+        let mut recovery = Recovery::default();
+        let quickly = Duration::from_secs(1);
+        for _ in 0..POLL_FALLBACK_AFTER {
+            assert!(!recovery.polling());
+            recovery.failed(quickly);
+        }
+        assert!(recovery.polling());
+        assert_eq!(recovery.failed(quickly), INITIAL_BACKOFF * 8);
+
+        // Failing after a stable period starts over
+        assert_eq!(recovery.failed(STABLE_PERIOD), INITIAL_BACKOFF);
+        assert_eq!(recovery.failures, 1);
+        assert!(!recovery.polling());
+    }
+
+    #[test]
+    fn test_watch_targets() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("a/deep")).unwrap();
+        fs::write(root.join("note.org"), "").unwrap();
+
+        assert_eq!(count_dirs(root), 3);
+        assert_eq!(watch_targets(root, false, false).unwrap().len(), 1);
+        let targets = watch_targets(root, true, false).unwrap();
+        assert_eq!(
+            targets,
+            vec![
+                (root.to_path_buf(), RecursiveMode::NonRecursive),
+                (root.join("a"), RecursiveMode::NonRecursive),
+            ]
+        );
+    }
 }
@@ -1,6 +1,10 @@
 import { ref, type Ref } from "vue";
 import { getScope } from "../settings";
-import { type OrgAsHTMLResponse } from "../types";
+import {
+  type LintResponse,
+  type LintRule,
+  type OrgAsHTMLResponse,
+} from "../types";
 import { History } from "../history";
 import { getRouter } from "../router";
 
@@ -12,6 +16,7 @@ export function usePreviewContent() {
   const links: Ref<{ display: string; id: string }[]> = ref([]);
   const incomingLinks: Ref<{ display: string; id: string }[]> = ref([]);
   const tags: Ref<string[]> = ref([]);
+  const lints: Ref<LintRule[]> = ref([]);
   const rendered = ref("");
 
   let current_id: string = "";
@@ -20,6 +25,21 @@ export function usePreviewContent() {
   const history = new History<string>();
   const router = getRouter();
 
+  // Lint failures are not worth an error message, the badges just stay empty.
+  const loadLints = (id: string) => {
+    lints.value = [];
+    fetch(`/diagnostics/lint?id=${encodeURIComponent(id)}`, {
+      credentials: "include",
+    })
+      .then((response) => (response.ok ? response.json() : { nodes: [] }))
+      .then((resp: LintResponse) => {
+        if (current_id === id) {
+          lints.value = resp.nodes.flatMap((node) => node.rules);
+        }
+      })
+      .catch((error) => console.error("Failed to load lints:", error));
+  };
+
   const preview = (
     id: string,
     onError?: (message: string) => void,
@@ -50,6 +70,7 @@ export function usePreviewContent() {
         links.value = resp.outgoing_links;
         incomingLinks.value = resp.incoming_links || [];
         tags.value = resp.tags || [];
+        loadLints(id);
         current_latex_blocks = resp.latex_blocks || [];
         console.log(
           `Loaded content with ${current_latex_blocks.length} LaTeX blocks`,
@@ -106,6 +127,7 @@ export function usePreviewContent() {
     links,
     incomingLinks,
     tags,
+    lints,
     rendered,
     history,
 
@@ -40,6 +40,21 @@ export interface OrgAsHTMLResponse {
   latex_blocks: string[];
 }
 
+export type LintRule =
+  | "missing_tags"
+  | "title_too_long"
+  | "no_backlinks"
+  | "todo_without_deadline";
+
+export interface LintResponse {
+  nodes: {
+    id: string;
+    title: string;
+    file: string;
+    rules: LintRule[];
+  }[];
+}
+
 export interface WebSocketMessage {
   type: string;
 }
@@ -66,6 +81,11 @@ export interface GraphUpdateMessage extends WebSocketMessage {
   removed_links: RoamLink[];
 }
 
+export interface SubscribeMessage extends WebSocketMessage {
+  type: "subscribe" | "unsubscribe";
+  topics: string[];
+}
+
 export interface PingMessage extends WebSocketMessage {
   type: "ping";
 }
@@ -78,6 +98,8 @@ export interface SearchRequestMessage extends WebSocketMessage {
   type: "search_request";
   query: string;
   request_id: string;
+  group_by_file?: boolean;
+  prefix?: boolean;
 }
 
 export interface SearchResultEntry {
@@ -86,6 +108,8 @@ export interface SearchResultEntry {
   id: string;
   tags: string[];
   preview: [string, number, number] | null;
+  file?: string;
+  matches?: number;
 }
 
 export interface SearchResponseMessage extends WebSocketMessage {